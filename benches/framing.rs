@@ -0,0 +1,70 @@
+//! How fast messages are read and dispatched, run with
+//! `cargo bench --features bench_support`.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use lsp_client::lsp::bench_support;
+use lsp_client::lsp::parsing::{read_message, MessageReader, ParseMode};
+use tokio::runtime::Runtime;
+
+/// The notifications of a busy indexing run.
+const MESSAGES: usize = 1000;
+
+fn framing(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let bodies: Vec<String> = (0..MESSAGES)
+        .map(bench_support::progress_notification)
+        .collect();
+    let stream = bench_support::framed(&bodies);
+
+    let mut group = c.benchmark_group("framing");
+    group.throughput(Throughput::Bytes(stream.len() as u64));
+    group.bench_function("read_message", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let mut reader = stream.as_slice();
+                for _ in 0..MESSAGES {
+                    read_message(&mut reader).await.unwrap();
+                }
+            })
+        })
+    });
+    group.bench_function("message_reader", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let mut messages = MessageReader::new(stream.as_slice(), ParseMode::Strict);
+                for _ in 0..MESSAGES {
+                    messages.read().await.unwrap();
+                }
+            })
+        })
+    });
+    group.finish();
+}
+
+fn dispatch(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let _runtime = runtime.enter();
+    let server = bench_support::client();
+    let messages = [
+        ("progress", bench_support::progress_notification(1)),
+        ("diagnostics", bench_support::diagnostics_notification(100)),
+        (
+            "semantic_tokens",
+            bench_support::semantic_tokens_response(1, 10_000),
+        ),
+        (
+            "workspace_symbols",
+            bench_support::workspace_symbols_response(2, 1_000),
+        ),
+    ];
+
+    let mut group = c.benchmark_group("dispatch");
+    for (name, body) in &messages {
+        group.throughput(Throughput::Bytes(body.len() as u64));
+        group.bench_function(*name, |b| b.iter(|| bench_support::dispatch(&server, body)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, framing, dispatch);
+criterion_main!(benches);
@@ -0,0 +1,196 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use lsp_types::{
+    CodeActionContext, CodeActionOrCommand, CodeActionTriggerKind, Range, Url, WorkspaceEdit,
+};
+use serde_json::{json, Value};
+
+use super::args::{ActionId, LineColumnRange};
+use super::output::{Outcome, OutputFormat};
+use super::session::Session;
+use super::CliError;
+use crate::lsp::edits;
+
+/// The kind of a code action as `actions` shows it, `command` for bare commands.
+pub fn action_kind(action: &CodeActionOrCommand) -> &str {
+    match action {
+        CodeActionOrCommand::Command(_) => "command",
+        CodeActionOrCommand::CodeAction(action) => {
+            action.kind.as_ref().map_or("", |kind| kind.as_str())
+        }
+    }
+}
+
+pub fn action_title(action: &CodeActionOrCommand) -> &str {
+    match action {
+        CodeActionOrCommand::Command(command) => &command.title,
+        CodeActionOrCommand::CodeAction(action) => &action.title,
+    }
+}
+
+/// Returns true if `kind` is `wanted` or one of its sub-kinds, e.g. `refactor.extract`
+/// for `refactor`.
+fn kind_matches(kind: &str, wanted: &str) -> bool {
+    kind == wanted
+        || kind
+            .strip_prefix(wanted)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// Picks the action `id` names among `actions`, in the order the server listed them.
+pub fn pick<'a>(
+    actions: &'a [CodeActionOrCommand],
+    id: &ActionId,
+) -> Result<&'a CodeActionOrCommand, CliError> {
+    match id {
+        ActionId::Index(index) => actions.get(index - 1).ok_or_else(|| {
+            CliError::Failed(format!(
+                "there is no code action {}, the range has {}",
+                index,
+                actions.len()
+            ))
+        }),
+        ActionId::Kind(kind) => {
+            let found: Vec<_> = actions
+                .iter()
+                .filter(|action| kind_matches(action_kind(action), kind))
+                .collect();
+            match found.as_slice() {
+                [action] => Ok(action),
+                [] => Err(CliError::Failed(format!(
+                    "there is no code action of kind {}",
+                    kind
+                ))),
+                found => Err(CliError::Failed(format!(
+                    "{} code actions are of kind {}, pick one by its number",
+                    found.len(),
+                    kind
+                ))),
+            }
+        }
+    }
+}
+
+/// Converts a user given range into a range within the open document `uri`.
+pub fn range(session: &Session, uri: &Url, range: LineColumnRange) -> Range {
+    Range::new(
+        session.position(uri, range.start),
+        session.position(uri, range.end),
+    )
+}
+
+/// Lists the code actions for `range`, passing the server the diagnostics overlapping it
+/// so it can offer quick fixes.
+pub async fn list(
+    session: &Session,
+    uri: &Url,
+    range: Range,
+) -> Result<Vec<CodeActionOrCommand>, CliError> {
+    let overlaps = |other: &Range| {
+        (other.start.line, other.start.character) <= (range.end.line, range.end.character)
+            && (range.start.line, range.start.character) <= (other.end.line, other.end.character)
+    };
+    let diagnostics = session
+        .current_diagnostics(uri, 0)
+        .await?
+        .into_iter()
+        .filter(|diagnostic| overlaps(&diagnostic.range))
+        .collect();
+    let context = CodeActionContext {
+        diagnostics,
+        only: None,
+        trigger_kind: Some(CodeActionTriggerKind::INVOKED),
+    };
+    session
+        .timed(session.server.code_actions(uri.clone(), range, context))
+        .await
+}
+
+/// Performs the code action `id` picks among the actions for `range` of `file`: applies
+/// its edit to disk, then executes its command and applies the edits the server sends
+/// back. Each edit is printed in `format` before it is applied, text showing a diff.
+pub async fn apply(
+    session: &Session,
+    file: &Path,
+    range: LineColumnRange,
+    id: &ActionId,
+    format: OutputFormat,
+) -> Result<(), CliError> {
+    let uri = session.open(file).await?;
+    let range = self::range(session, &uri, range);
+    let actions = list(session, &uri, range).await?;
+    let (edit, command) = match pick(&actions, id)?.clone() {
+        CodeActionOrCommand::Command(command) => (None, Some(command)),
+        CodeActionOrCommand::CodeAction(action) => {
+            if let Some(disabled) = &action.disabled {
+                return Err(CliError::Failed(format!(
+                    "the code action {:?} is disabled: {}",
+                    action.title, disabled.reason
+                )));
+            }
+            let action = if action.edit.is_none() && action.data.is_some() {
+                session
+                    .timed(session.server.resolve_code_action(action))
+                    .await?
+            } else {
+                action
+            };
+            (action.edit, action.command)
+        }
+    };
+
+    let mut json_edits = Vec::new();
+    if let Some(edit) = edit {
+        json_edits.extend(write(session, format, &edit).await?);
+    }
+    if let Some(command) = command {
+        // each edit the command sends is written before the server is told it was applied
+        let command_edits = Mutex::new(Vec::new());
+        let apply = |edit: WorkspaceEdit| {
+            let command_edits = &command_edits;
+            async move {
+                let value = write(session, format, &edit)
+                    .await
+                    .map_err(|err| err.to_string())?;
+                command_edits.lock().unwrap().extend(value);
+                Ok(())
+            }
+        };
+        session
+            .timed(session.server.run_command_with(&command, apply))
+            .await?;
+        json_edits.extend(command_edits.into_inner().unwrap());
+    }
+    if format == OutputFormat::Json {
+        println!("{:#}", json!({ "edits": json_edits }));
+    }
+    Ok(())
+}
+
+/// Prints `edit`, then applies it to disk and syncs the open documents it changed, so
+/// that a command running next sees them. In JSON the edit is returned instead, all edits
+/// are printed as one document.
+async fn write(
+    session: &Session,
+    format: OutputFormat,
+    edit: &WorkspaceEdit,
+) -> Result<Option<Value>, CliError> {
+    // a new printer, the one of an earlier edit remembers the files as they were
+    let printer = session.printer();
+    let outcome = Outcome::Edit(edit.clone());
+    let value = match format {
+        OutputFormat::Json => Some(printer.json(&outcome)["edit"].take()),
+        format => {
+            print!("{}", printer.render(format, &outcome)?);
+            None
+        }
+    };
+    edits::apply_workspace_edit_to_disk(edit).await?;
+    for uri in edits::workspace_edit_text_edits(edit).keys() {
+        if let (true, Ok(path)) = (session.documents.is_open(uri), uri.to_file_path()) {
+            session.sync(&path).await?;
+        }
+    }
+    Ok(value)
+}
@@ -0,0 +1,928 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use lsp_types::DiagnosticSeverity;
+use serde::{Deserialize, Serialize};
+
+use super::bench::BenchMethod;
+use super::bridge::PathMapping;
+use super::completions::Shell;
+use super::output::{ColorChoice, OutputFormat};
+use super::proxy::ProxyOptions;
+use super::record::Record;
+use super::session::{Timeouts, Trace};
+use super::CliError;
+use crate::lsp::call_hierarchy::CallDirection;
+
+pub const USAGE: &str = "\
+usage: lsp-client [options] <command> [arguments]
+
+commands:
+    definition <file> <line>:<column>     where the symbol at the position is defined
+    references <file> <line>:<column>     where the symbol at the position is used
+    hover <file> <line>:<column>          the hover of the symbol at the position
+    explain <file> <line>:<column>        everything about the symbol at the position: its
+                                          hover, definition, type definition, how often it
+                                          is used and the symbol around it
+    symbols <file>                        the symbols of a file
+    diagnostics <file>...                 the diagnostics of the files
+    rename <file> <line>:<column> <name>  renames the symbol at the position, printing the
+                                          changes as a unified diff
+    callgraph <file> <line>:<column>      the calls made by the function at the position, and
+                                          by the functions it calls
+    complete <file> <line>:<column>       the completions at the position, best first
+    actions <file> <range>                the code actions available for the range, given as
+                                          <line>:<column>[-<line>:<column>], numbered
+    apply-action <file> <range> <id>      performs the code action with the number or kind
+                                          among the actions for the range, printing the
+                                          changes as a unified diff and writing them
+    repl [<file>]                         answers commands read from stdin with one server,
+                                          see `help` within
+    run <script.jsonl>                    runs the commands of a script with one server
+    replay <session.json>                 runs the command saved by --record again, the
+                                          recorded server answering it
+    dump-symbols [<file>...]              the symbols of the files, or of every file below
+                                          the root the server handles
+    dead-code [<file>...]                 the public functions, types and constants of the
+                                          files, or of the workspace, that are not used
+    report [<file>...]                    the diagnostics, symbols and unused public symbols
+                                          of the files, or of the workspace, as a page to
+                                          share with --output html
+    format <file>...                      formats the files with the server, in place
+    lsif [<file>...]                      an LSIF index of the files, or of the workspace,
+                                          one element per line, for code navigation
+                                          without the server
+    index [<file>...]                     caches the symbols of the files, or of the
+                                          workspace, for --symbol to find them without
+                                          searching the workspace
+    bench <file> <line>:<column>...       sends hovers, definitions and completions at the
+                                          positions over and over, printing how long the
+                                          server took per method
+    capabilities [<file>]                 what the server supports, the server picked by
+                                          the file's extension unless --server is given
+    doctor [<file>]                       checks that the server is installed, starts and
+                                          answers, with hints on what to fix
+    proxy                                 relays LSP messages between stdin/stdout and the
+                                          server, for debugging an editor's traffic
+    bridge                                relays like proxy, filtering diagnostics, mapping
+                                          paths and adding the initializationOptions of
+                                          the config to the editor's
+    highlight <file>                      the file as an HTML page highlighted by the
+                                          server's semantic tokens, blocks collapsible
+    completions <shell>                   the completion script for bash, zsh or fish
+    config-schema                         the JSON Schema of config files, for editors to
+                                          check lsp.json with
+    daemon                                keeps the servers it starts running, answering
+                                          the queries of later commands on --socket until
+                                          interrupted, so they skip starting the server
+
+options:
+    --config <file>[,...]  the config file, the nearest lsp.json by default. With several,
+                           one server is started for each and every file is routed to
+                           the one handling it
+    --server <command>     the command starting the language server, picked from the
+                           file's extension by default
+    --root <dir>           the workspace root, the current directory by default
+    --output <format>      text (the default), plain, json, sarif for diagnostics, csv
+                           for dump-symbols, dot for callgraph, vim-quickfix or emacs
+                           for definition, references and diagnostics, or html for
+                           report and highlight
+    --startup-timeout <s>  how many seconds the server may take to initialize, 30 by
+                           default
+    --wait-for-index <s>   how many seconds to wait for the server to load the project
+                           before querying anyway, 60 by default, 0 does not wait
+    --timeout <s>          how many seconds the server may take to answer a query, 60 by
+                           default
+    --trace-lsp[=<file>]   writes every message exchanged with the server to stderr, or
+                           appends it to the file
+    --log-frames <file>    appends every message exchanged with the server to the file as
+                           a line of JSON with its direction, time and size, which
+                           serve-recording replays
+    --record <file>        saves the command line and every message exchanged with the
+                           server to the file, for replay
+    --index <file>         definition, references, hover: answers from an LSIF dump or a
+                           SCIP index instead of starting the server
+    --context <n>          definition, references, hover, diagnostics: shows the source
+                           with n lines around each position, the range underlined
+    --color <when>         auto (the default) colors the text output on a terminal unless
+                           NO_COLOR is set, always or never
+    --man                  prints this help as a man page
+    --socket <file>        where the daemon listens and queries look for it,
+                           lsp-client.sock in $XDG_RUNTIME_DIR by default
+    --no-daemon            starts a server for the query even when a daemon is listening,
+                           as --trace-lsp, --log-frames and --record do
+    --quiet                draws no progress bars on stderr while the server starts and
+                           loads the project, which it only does on a terminal
+    --no-cache             asks the server for workspace symbols and semantic tokens even
+                           when the files are unchanged since the results were cached in
+                           .lsp-client/results
+    --symbol <path>        definition, references, hover, explain, rename, callgraph: the
+                           symbol to ask about, e.g. MyClass.myMethod, instead of
+                           <line>:<column>
+    --dry-run              rename: only prints the changes, the default
+    --write                rename: also writes the changes to disk
+    --no-declaration       references: leave out the declaration
+    --resolve              complete: asks the server for the documentation and details
+                           of every completion
+    --depth <n>            callgraph: how many calls away to follow, 3 by default
+    --incoming             callgraph: follows the callers instead of the callees
+    --threshold <n>        dead-code, report: reports symbols used at most n times, 0
+                           by default
+    --iterations <n>       bench: how many times to send each request, 10 by default
+    --methods <list>       bench: the requests to send, comma separated among hover,
+                           definition and completion, all three by default
+    --check                format: prints a diff instead of writing the files, and exits
+                           with 1 when one is not formatted
+    --watch                diagnostics: prints them again whenever the file changes
+    --fail-on <severity>   diagnostics: exits with 1 when there are diagnostics of the
+                           severity or worse, one of error, warning, info, hint
+    --changed-only[=<ref>] diagnostics: only those on lines changed since the git ref,
+                           HEAD by default, untracked files counting as all changed
+    --log <file>           proxy, bridge: appends every message to the file as a JSON
+                           line
+    --drop <method>        proxy, bridge: does not forward messages with the method,
+                           repeatable
+    --inject <file>        proxy, bridge: sends the messages of the JSONL file to the
+                           server after the client's initialized notification
+    --min-severity <sev>   bridge: drops published diagnostics less severe than this
+    --hide-code <code>     bridge: drops published diagnostics with the code, repeatable
+    --map-path <a>=<b>     bridge: rewrites the editor's folder a to the server's folder
+                           b in every message, and back, repeatable
+
+environment:
+    LSP_CLIENT_<OPTION>    stands in for --server, --root, --socket, --output,
+                           --startup-timeout, --wait-for-index and --timeout, e.g.
+                           LSP_CLIENT_WAIT_FOR_INDEX for --wait-for-index, overriding the
+                           config file and overridden by the command line
+
+lines and columns count from 1, columns in characters";
+
+/// A position as users give it, `line:column` counted from 1 with the column in characters.
+/// Deserializing it checks it as `parse` does, e.g. for the queries sent to the daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedLineColumn")]
+pub struct LineColumn {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl LineColumn {
+    pub fn parse(arg: &str) -> Result<LineColumn, CliError> {
+        let invalid =
+            || CliError::Usage(format!("invalid position {:?}, expected line:column", arg));
+        let (line, column) = arg.split_once(':').ok_or_else(invalid)?;
+        let line = line.parse().map_err(|_| invalid())?;
+        let column = column.parse().map_err(|_| invalid())?;
+        LineColumn::try_from(UncheckedLineColumn { line, column }).map_err(|_| invalid())
+    }
+}
+
+/// A `LineColumn` whose line and column may be 0.
+#[derive(Deserialize)]
+struct UncheckedLineColumn {
+    line: u32,
+    column: u32,
+}
+
+impl TryFrom<UncheckedLineColumn> for LineColumn {
+    type Error = String;
+
+    fn try_from(at: UncheckedLineColumn) -> Result<LineColumn, String> {
+        if at.line == 0 || at.column == 0 {
+            return Err(format!(
+                "invalid position {}:{}, lines and columns count from 1",
+                at.line, at.column
+            ));
+        }
+        Ok(LineColumn {
+            line: at.line,
+            column: at.column,
+        })
+    }
+}
+
+/// A range as users give it, `line:column-line:column`, or a single `line:column` for an
+/// empty range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineColumnRange {
+    pub start: LineColumn,
+    pub end: LineColumn,
+}
+
+impl LineColumnRange {
+    pub fn parse(arg: &str) -> Result<LineColumnRange, CliError> {
+        let (start, end) = match arg.split_once('-') {
+            Some((start, end)) => (LineColumn::parse(start)?, LineColumn::parse(end)?),
+            None => {
+                let start = LineColumn::parse(arg)?;
+                (start, start)
+            }
+        };
+        if (end.line, end.column) < (start.line, start.column) {
+            return Err(CliError::Usage(format!(
+                "invalid range {:?}, the end lies before the start",
+                arg
+            )));
+        }
+        Ok(LineColumnRange { start, end })
+    }
+}
+
+/// Picks one of the code actions `actions` lists: by its number, or by its kind when no
+/// other action is of that kind, e.g. `source.organizeImports`. Kinds are hierarchical,
+/// `refactor` also picks a `refactor.extract`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionId {
+    Index(usize),
+    Kind(String),
+}
+
+impl ActionId {
+    pub fn parse(arg: &str) -> Result<ActionId, CliError> {
+        match arg.parse::<usize>() {
+            Ok(0) => Err(CliError::Usage(
+                "code actions are numbered from 1".to_owned(),
+            )),
+            Ok(index) => Ok(ActionId::Index(index)),
+            Err(_) => Ok(ActionId::Kind(arg.to_owned())),
+        }
+    }
+}
+
+/// What a query is about: a position, or a symbol named by its path.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Target {
+    At(LineColumn),
+    /// The names of a symbol and its containers joined by dots, e.g. `MyClass.myMethod`.
+    /// Looked up in the symbols of the file first, then in the whole workspace.
+    Symbol(String),
+}
+
+/// A query about a single file, serialized for `daemon` to answer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Query {
+    Definition {
+        file: PathBuf,
+        target: Target,
+    },
+    References {
+        file: PathBuf,
+        target: Target,
+        include_declaration: bool,
+    },
+    Hover {
+        file: PathBuf,
+        target: Target,
+    },
+    /// Hover, definitions, references and more about one symbol, see `explain`.
+    Explain {
+        file: PathBuf,
+        target: Target,
+    },
+    Symbols {
+        file: PathBuf,
+    },
+    Diagnostics {
+        file: PathBuf,
+        /// Prints the diagnostics again whenever the file changes, until interrupted.
+        watch: bool,
+        /// Fails when there are diagnostics of this severity or worse, e.g. to fail a CI job.
+        fail_on: Option<DiagnosticSeverity>,
+        /// Only reports the diagnostics on lines changed since this git ref, see `changes`.
+        changed_since: Option<String>,
+    },
+    Rename {
+        file: PathBuf,
+        target: Target,
+        new_name: String,
+        apply: bool,
+    },
+    /// The completions at a position, resolved one by one when `resolve` is set.
+    Complete {
+        file: PathBuf,
+        at: LineColumn,
+        resolve: bool,
+    },
+    /// The code actions available for a range, numbered for `apply-action`.
+    Actions {
+        file: PathBuf,
+        range: LineColumnRange,
+    },
+    CallGraph {
+        file: PathBuf,
+        target: Target,
+        direction: CallDirection,
+        /// How many calls away from the target to follow.
+        depth: usize,
+    },
+}
+
+impl Query {
+    /// The file the query is about.
+    pub fn file(&self) -> &Path {
+        match self {
+            Query::Definition { file, .. }
+            | Query::References { file, .. }
+            | Query::Hover { file, .. }
+            | Query::Explain { file, .. }
+            | Query::Symbols { file }
+            | Query::Diagnostics { file, .. }
+            | Query::Rename { file, .. }
+            | Query::Complete { file, .. }
+            | Query::Actions { file, .. }
+            | Query::CallGraph { file, .. } => file,
+        }
+    }
+
+    /// The query with its file resolved against `dir`, for answering it elsewhere.
+    pub fn rebased(mut self, dir: &Path) -> Query {
+        let (Query::Definition { file, .. }
+        | Query::References { file, .. }
+        | Query::Hover { file, .. }
+        | Query::Explain { file, .. }
+        | Query::Symbols { file }
+        | Query::Diagnostics { file, .. }
+        | Query::Rename { file, .. }
+        | Query::Complete { file, .. }
+        | Query::Actions { file, .. }
+        | Query::CallGraph { file, .. }) = &mut self;
+        *file = dir.join(&*file);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Answers a single query, then shuts the server down.
+    Query(Query),
+    /// Answers queries read from stdin, see `repl`.
+    Repl { file: Option<PathBuf> },
+    /// Runs the commands of a script, see `script`.
+    Run { script: PathBuf },
+    /// Relays messages between an editor and the server, see `proxy`.
+    Proxy(ProxyOptions),
+    /// Relays messages between an editor and the server like `Proxy`, rewriting them as
+    /// the transforms of the options say, see `bridge`.
+    Bridge(ProxyOptions),
+    /// Prints what the server announced in its `InitializeResult`. The file only picks the
+    /// server.
+    Capabilities { file: Option<PathBuf> },
+    /// Checks that the server starts and answers, see `doctor`. The file only picks the
+    /// server.
+    Doctor { file: Option<PathBuf> },
+    /// Runs the command line of a recording against the recording, see `record`.
+    Replay { recording: PathBuf },
+    /// Acts as the server of a recording on stdin and stdout, started by `replay`.
+    ServeRecording { recording: PathBuf },
+    /// Prints the completion script for a shell, see `completions`.
+    Completions { shell: Shell },
+    /// Prints the usage as a man page, see `completions::man_page`.
+    Man,
+    /// Prints the JSON Schema of config files, see `schema`.
+    ConfigSchema,
+    /// Answers the queries of later invocations with servers kept running, see `daemon`.
+    Daemon,
+    /// Lists the symbols of many files, see `inventory`. Without files the workspace is
+    /// crawled.
+    DumpSymbols { files: Vec<PathBuf> },
+    /// Reports the public symbols of many files used at most `threshold` times, see
+    /// `dead_code`. Without files the workspace is crawled.
+    DeadCode {
+        files: Vec<PathBuf>,
+        threshold: usize,
+    },
+    /// The diagnostics, symbols and unused public symbols of many files, see
+    /// `report::analyze`. Without files the workspace is crawled.
+    Report {
+        files: Vec<PathBuf>,
+        threshold: usize,
+    },
+    /// The diagnostics of several files in one report, see `report`.
+    DiagnosticsReport {
+        files: Vec<PathBuf>,
+        fail_on: Option<DiagnosticSeverity>,
+        changed_since: Option<String>,
+    },
+    /// Dumps an LSIF index of many files, see `lsif`. Without files the workspace is
+    /// crawled.
+    Lsif { files: Vec<PathBuf> },
+    /// Formats files with the server, see `format`. With `check` nothing is written and
+    /// the command fails when a file is not formatted.
+    Format { files: Vec<PathBuf>, check: bool },
+    /// Caches the symbols of many files for `--symbol`, see `cache`. Without files the
+    /// workspace is crawled.
+    Index { files: Vec<PathBuf> },
+    /// Times the answers to `methods` at `positions` of a file, see `bench`.
+    Bench {
+        file: PathBuf,
+        positions: Vec<LineColumn>,
+        methods: Vec<BenchMethod>,
+        iterations: usize,
+    },
+    /// Renders a file highlighted by its semantic tokens, see `highlight`.
+    Highlight { file: PathBuf },
+    /// Performs one of the code actions `actions` lists for the same range, see `actions`.
+    ApplyAction {
+        file: PathBuf,
+        range: LineColumnRange,
+        id: ActionId,
+    },
+}
+
+impl Command {
+    /// The file the server is started for, which picks the server unless `--server` is
+    /// given.
+    pub fn file(&self) -> Option<&Path> {
+        match self {
+            Command::Query(query) => Some(query.file()),
+            Command::ApplyAction { file, .. }
+            | Command::Bench { file, .. }
+            | Command::Highlight { file } => Some(file),
+            Command::Repl { file } | Command::Capabilities { file } | Command::Doctor { file } => {
+                file.as_deref()
+            }
+            Command::DumpSymbols { files }
+            | Command::DeadCode { files, .. }
+            | Command::Report { files, .. }
+            | Command::DiagnosticsReport { files, .. }
+            | Command::Lsif { files }
+            | Command::Index { files }
+            | Command::Format { files, .. } => files.first().map(PathBuf::as_path),
+            Command::Run { .. }
+            | Command::Proxy(_)
+            | Command::Bridge(_)
+            | Command::Replay { .. }
+            | Command::ServeRecording { .. }
+            | Command::Completions { .. }
+            | Command::Man
+            | Command::ConfigSchema
+            | Command::Daemon => None,
+        }
+    }
+}
+
+/// The options shared by all commands. Options left out are taken from the config file.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Options {
+    /// The config files, the one looked up from the current directory when empty. Each
+    /// names a server, several route every file to the server handling it.
+    pub config: Vec<PathBuf>,
+    pub server: Option<Vec<String>>,
+    pub root: Option<PathBuf>,
+    pub output: Option<OutputFormat>,
+    pub timeouts: Timeouts,
+    pub trace: Option<Trace>,
+    pub record: Option<Record>,
+    /// An LSIF or SCIP index answering queries instead of a server, see `index`.
+    pub index: Option<PathBuf>,
+    pub color: ColorChoice,
+    /// How many lines of source to show around positions, none when `None`.
+    pub context: Option<usize>,
+    /// Draws no progress bars, see `progress`.
+    pub quiet: bool,
+    /// The socket of the daemon, see `daemon::default_socket`.
+    pub socket: Option<PathBuf>,
+    /// Starts a server for the query even when a daemon is listening.
+    pub no_daemon: bool,
+    /// Neither reads nor writes the results cached on disk, see `results`.
+    pub no_cache: bool,
+}
+
+/// The prefix of the environment variables standing in for options, see
+/// `Options::from_env`.
+const ENV_PREFIX: &str = "LSP_CLIENT_";
+
+impl Options {
+    /// The options the `LSP_CLIENT_*` variables among `vars` set, e.g. `LSP_CLIENT_TIMEOUT`
+    /// for `--timeout`, for CI jobs to adjust without editing the config file. They
+    /// override the config file, the command line overrides them. Unknown variables with
+    /// the prefix are rejected, as misspelled options are.
+    pub fn from_env(vars: impl IntoIterator<Item = (String, String)>) -> Result<Options, CliError> {
+        let mut options = Options::default();
+        for (name, value) in vars {
+            let Some(option) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            match option {
+                "SERVER" => {
+                    let command: Vec<String> =
+                        value.split_whitespace().map(str::to_owned).collect();
+                    if command.is_empty() {
+                        return Err(CliError::Usage(format!("{} expects a command", name)));
+                    }
+                    options.server = Some(command);
+                }
+                "ROOT" => options.root = Some(PathBuf::from(value)),
+                "SOCKET" => options.socket = Some(PathBuf::from(value)),
+                "OUTPUT" => {
+                    let output = OutputFormat::parse(&value)
+                        .map_err(|err| CliError::Usage(format!("{}: {}", name, err.message())))?;
+                    options.output = Some(output);
+                }
+                "STARTUP_TIMEOUT" => options.timeouts.startup = parse_seconds(&name, &value)?,
+                "WAIT_FOR_INDEX" => options.timeouts.index = parse_seconds(&name, &value)?,
+                "TIMEOUT" => options.timeouts.request = parse_seconds(&name, &value)?,
+                _ => {
+                    return Err(CliError::Usage(format!(
+                        "unknown environment variable {}",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation {
+    pub options: Options,
+    pub command: Command,
+}
+
+/// Parses the value of the option `name`, a number of seconds such as `30` or `0.5`.
+fn parse_seconds(name: &str, arg: &str) -> Result<Duration, CliError> {
+    arg.parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| {
+            CliError::Usage(format!(
+                "{} expects a number of seconds, not {:?}",
+                name, arg
+            ))
+        })
+}
+
+/// The severities `parse_severity` takes.
+pub const SEVERITIES: &[&str] = &["error", "warning", "info", "hint"];
+
+/// Parses a severity as `--fail-on` takes it.
+pub fn parse_severity(arg: &str) -> Result<DiagnosticSeverity, CliError> {
+    match arg {
+        "error" => Ok(DiagnosticSeverity::ERROR),
+        "warning" => Ok(DiagnosticSeverity::WARNING),
+        "info" | "information" => Ok(DiagnosticSeverity::INFORMATION),
+        "hint" => Ok(DiagnosticSeverity::HINT),
+        _ => Err(CliError::Usage(format!(
+            "unknown severity {:?}, expected error, warning, info or hint",
+            arg
+        ))),
+    }
+}
+
+fn wrong_arguments(command: &str) -> CliError {
+    CliError::Usage(format!("unknown command or wrong arguments: {}", command))
+}
+
+/// Splits the arguments of a query following its file into the target and the rest: the
+/// `--symbol` when given, the leading `line:column` otherwise.
+fn split_target<'a>(
+    args: &'a [&'a str],
+    symbol: Option<String>,
+) -> Result<(Target, &'a [&'a str]), CliError> {
+    match (symbol, args) {
+        (Some(symbol), args) => Ok((Target::Symbol(symbol), args)),
+        (None, [position, rest @ ..]) => Ok((Target::At(LineColumn::parse(position)?), rest)),
+        (None, []) => Err(CliError::Usage(
+            "expected <line>:<column> or --symbol".to_owned(),
+        )),
+    }
+}
+
+/// Parses the arguments following the program name. Options may appear anywhere.
+pub fn parse(args: &[String]) -> Result<Invocation, CliError> {
+    parse_over(Options::default(), args)
+}
+
+/// Parses the arguments following the program name, the options they give overriding
+/// `defaults`, e.g. those of `Options::from_env`.
+pub fn parse_over(defaults: Options, args: &[String]) -> Result<Invocation, CliError> {
+    let mut options = defaults;
+    let mut apply = false;
+    let mut include_declaration = true;
+    let mut watch = false;
+    let mut fail_on = None;
+    let mut changed_since = None;
+    let mut symbol = None;
+    let mut depth = 3;
+    let mut direction = CallDirection::Outgoing;
+    let mut threshold = 0;
+    let mut check = false;
+    let mut resolve = false;
+    let mut iterations = 10;
+    let mut methods = BenchMethod::all();
+    let mut proxy = ProxyOptions::default();
+    let mut positional = Vec::new();
+
+    let mut record = None;
+    let mut man = false;
+    let all = args;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| CliError::Usage(format!("{} expects a value", name)))
+        };
+        match arg.as_str() {
+            "--server" => {
+                let command: Vec<String> =
+                    value(arg)?.split_whitespace().map(str::to_owned).collect();
+                if command.is_empty() {
+                    return Err(CliError::Usage("--server expects a command".to_owned()));
+                }
+                options.server = Some(command);
+            }
+            "--config" => options
+                .config
+                .extend(value(arg)?.split(',').map(PathBuf::from)),
+            "--root" => options.root = Some(PathBuf::from(value(arg)?)),
+            "--socket" => options.socket = Some(PathBuf::from(value(arg)?)),
+            "--no-daemon" => options.no_daemon = true,
+            "--no-cache" => options.no_cache = true,
+            "--output" => options.output = Some(OutputFormat::parse(&value(arg)?)?),
+            "--startup-timeout" => options.timeouts.startup = parse_seconds(arg, &value(arg)?)?,
+            "--wait-for-index" => options.timeouts.index = parse_seconds(arg, &value(arg)?)?,
+            "--timeout" => options.timeouts.request = parse_seconds(arg, &value(arg)?)?,
+            "--dry-run" => apply = false,
+            "--write" => apply = true,
+            "--no-declaration" => include_declaration = false,
+            "--watch" => watch = true,
+            "--fail-on" => fail_on = Some(parse_severity(&value(arg)?)?),
+            "--symbol" => symbol = Some(value(arg)?),
+            "--depth" => {
+                let arg = value(arg)?;
+                depth = arg.parse().map_err(|_| {
+                    CliError::Usage(format!("--depth expects a number, not {:?}", arg))
+                })?;
+            }
+            "--incoming" => direction = CallDirection::Incoming,
+            "--check" => check = true,
+            "--resolve" => resolve = true,
+            "--threshold" => {
+                let arg = value(arg)?;
+                threshold = arg.parse().map_err(|_| {
+                    CliError::Usage(format!("--threshold expects a number, not {:?}", arg))
+                })?;
+            }
+            "--iterations" => {
+                let arg = value(arg)?;
+                iterations = arg.parse().map_err(|_| {
+                    CliError::Usage(format!("--iterations expects a number, not {:?}", arg))
+                })?;
+            }
+            "--methods" => methods = BenchMethod::parse_list(&value(arg)?)?,
+            "--log" => proxy.log = Some(PathBuf::from(value(arg)?)),
+            "--drop" => proxy.drop.push(value(arg)?),
+            "--inject" => proxy.inject = Some(PathBuf::from(value(arg)?)),
+            "--min-severity" => proxy.transforms.min_severity = Some(parse_severity(&value(arg)?)?),
+            "--hide-code" => proxy.transforms.hidden_codes.push(value(arg)?),
+            "--map-path" => proxy
+                .transforms
+                .paths
+                .push(PathMapping::parse(&value(arg)?)?),
+            "--record" => record = Some(PathBuf::from(value(arg)?)),
+            "--log-frames" => options.trace = Some(Trace::Frames(PathBuf::from(value(arg)?))),
+            "--index" => options.index = Some(PathBuf::from(value(arg)?)),
+            "--color" => options.color = ColorChoice::parse(&value(arg)?)?,
+            "--man" => man = true,
+            "--quiet" => options.quiet = true,
+            "--context" => {
+                let arg = value(arg)?;
+                options.context = Some(arg.parse().map_err(|_| {
+                    CliError::Usage(format!("--context expects a number, not {:?}", arg))
+                })?);
+            }
+            "--changed-only" => changed_since = Some("HEAD".to_owned()),
+            flag if flag.starts_with("--changed-only=") => {
+                let base = &flag["--changed-only=".len()..];
+                if base.is_empty() {
+                    return Err(CliError::Usage(
+                        "--changed-only= expects a git ref".to_owned(),
+                    ));
+                }
+                changed_since = Some(base.to_owned());
+            }
+            "--trace-lsp" => options.trace = Some(Trace::Stderr),
+            flag if flag.starts_with("--trace-lsp=") => {
+                let path = &flag["--trace-lsp=".len()..];
+                if path.is_empty() {
+                    return Err(CliError::Usage("--trace-lsp= expects a file".to_owned()));
+                }
+                options.trace = Some(Trace::File(PathBuf::from(path)));
+            }
+            flag if flag.starts_with("--") => {
+                return Err(CliError::Usage(format!("unknown option {}", flag)))
+            }
+            _ => positional.push(arg.as_str()),
+        }
+    }
+
+    if let Some(path) = record {
+        let mut args = Vec::new();
+        let mut all = all.iter();
+        while let Some(arg) = all.next() {
+            match arg.as_str() {
+                "--record" => {
+                    all.next();
+                }
+                _ => args.push(arg.clone()),
+            }
+        }
+        options.record = Some(Record { path, args });
+    }
+    if man {
+        // like --help elsewhere, whatever else is given
+        let command = Command::Man;
+        return Ok(Invocation { options, command });
+    }
+
+    let query = match positional.as_slice() {
+        [command @ ("definition" | "references" | "hover" | "explain" | "rename" | "callgraph"), file, rest @ ..] =>
+        {
+            let file = PathBuf::from(file);
+            let (target, rest) = split_target(rest, symbol)?;
+            match (*command, rest) {
+                ("definition", []) => Query::Definition { file, target },
+                ("references", []) => Query::References {
+                    file,
+                    target,
+                    include_declaration,
+                },
+                ("hover", []) => Query::Hover { file, target },
+                ("explain", []) => Query::Explain { file, target },
+                ("rename", [new_name]) => Query::Rename {
+                    file,
+                    target,
+                    new_name: new_name.to_string(),
+                    apply,
+                },
+                ("callgraph", []) => Query::CallGraph {
+                    file,
+                    target,
+                    direction,
+                    depth,
+                },
+                _ => return Err(wrong_arguments(command)),
+            }
+        }
+        ["complete", file, at] => Query::Complete {
+            file: PathBuf::from(file),
+            at: LineColumn::parse(at)?,
+            resolve,
+        },
+        ["actions", file, range] => Query::Actions {
+            file: PathBuf::from(file),
+            range: LineColumnRange::parse(range)?,
+        },
+        ["apply-action", file, range, id] => {
+            let command = Command::ApplyAction {
+                file: PathBuf::from(file),
+                range: LineColumnRange::parse(range)?,
+                id: ActionId::parse(id)?,
+            };
+            return Ok(Invocation { options, command });
+        }
+        ["symbols", file] => Query::Symbols {
+            file: PathBuf::from(file),
+        },
+        ["diagnostics", file] => {
+            if watch && fail_on.is_some() {
+                return Err(CliError::Usage(
+                    "--fail-on cannot be combined with --watch".to_owned(),
+                ));
+            }
+            if watch && changed_since.is_some() {
+                return Err(CliError::Usage(
+                    "--changed-only cannot be combined with --watch".to_owned(),
+                ));
+            }
+            Query::Diagnostics {
+                file: PathBuf::from(file),
+                watch,
+                fail_on,
+                changed_since,
+            }
+        }
+        ["diagnostics", files @ ..] if files.len() > 1 => {
+            if watch {
+                return Err(CliError::Usage("--watch takes a single file".to_owned()));
+            }
+            let command = Command::DiagnosticsReport {
+                files: files.iter().map(PathBuf::from).collect(),
+                fail_on,
+                changed_since,
+            };
+            return Ok(Invocation { options, command });
+        }
+        ["repl", file @ ..] if file.len() <= 1 => {
+            let file = file.first().map(PathBuf::from);
+            let command = Command::Repl { file };
+            return Ok(Invocation { options, command });
+        }
+        ["capabilities", file @ ..] if file.len() <= 1 => {
+            let file = file.first().map(PathBuf::from);
+            let command = Command::Capabilities { file };
+            return Ok(Invocation { options, command });
+        }
+        ["doctor", file @ ..] if file.len() <= 1 => {
+            let file = file.first().map(PathBuf::from);
+            let command = Command::Doctor { file };
+            return Ok(Invocation { options, command });
+        }
+        ["replay", recording] => {
+            let command = Command::Replay {
+                recording: PathBuf::from(recording),
+            };
+            return Ok(Invocation { options, command });
+        }
+        ["completions", shell] => {
+            let command = Command::Completions {
+                shell: Shell::parse(shell)?,
+            };
+            return Ok(Invocation { options, command });
+        }
+        ["config-schema"] => {
+            let command = Command::ConfigSchema;
+            return Ok(Invocation { options, command });
+        }
+        ["daemon"] => {
+            let command = Command::Daemon;
+            return Ok(Invocation { options, command });
+        }
+        ["serve-recording", recording] => {
+            let command = Command::ServeRecording {
+                recording: PathBuf::from(recording),
+            };
+            return Ok(Invocation { options, command });
+        }
+        ["run", script] => {
+            let command = Command::Run {
+                script: PathBuf::from(script),
+            };
+            return Ok(Invocation { options, command });
+        }
+        ["dump-symbols", files @ ..] => {
+            let files = files.iter().map(PathBuf::from).collect();
+            let command = Command::DumpSymbols { files };
+            return Ok(Invocation { options, command });
+        }
+        ["dead-code", files @ ..] => {
+            let files = files.iter().map(PathBuf::from).collect();
+            let command = Command::DeadCode { files, threshold };
+            return Ok(Invocation { options, command });
+        }
+        ["report", files @ ..] => {
+            let files = files.iter().map(PathBuf::from).collect();
+            let command = Command::Report { files, threshold };
+            return Ok(Invocation { options, command });
+        }
+        ["lsif", files @ ..] => {
+            let files = files.iter().map(PathBuf::from).collect();
+            let command = Command::Lsif { files };
+            return Ok(Invocation { options, command });
+        }
+        ["format", files @ ..] if !files.is_empty() => {
+            let files = files.iter().map(PathBuf::from).collect();
+            let command = Command::Format { files, check };
+            return Ok(Invocation { options, command });
+        }
+        ["index", files @ ..] => {
+            let files = files.iter().map(PathBuf::from).collect();
+            let command = Command::Index { files };
+            return Ok(Invocation { options, command });
+        }
+        ["bench", file, positions @ ..] if !positions.is_empty() => {
+            let command = Command::Bench {
+                file: PathBuf::from(file),
+                positions: positions
+                    .iter()
+                    .map(|at| LineColumn::parse(at))
+                    .collect::<Result<_, _>>()?,
+                methods,
+                iterations,
+            };
+            return Ok(Invocation { options, command });
+        }
+        ["highlight", file] => {
+            let command = Command::Highlight {
+                file: PathBuf::from(file),
+            };
+            return Ok(Invocation { options, command });
+        }
+        ["proxy"] => {
+            let command = Command::Proxy(proxy);
+            return Ok(Invocation { options, command });
+        }
+        ["bridge"] => {
+            let command = Command::Bridge(proxy);
+            return Ok(Invocation { options, command });
+        }
+        [] => return Err(CliError::Usage("missing command".to_owned())),
+        [command, ..] => return Err(wrong_arguments(command)),
+    };
+    Ok(Invocation {
+        options,
+        command: Command::Query(query),
+    })
+}
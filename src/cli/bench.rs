@@ -0,0 +1,160 @@
+//! Measures how long the server takes to answer, to compare servers or configurations:
+//! `bench` sends a mix of requests at positions of a file, over and over, and reports
+//! the latencies per method.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request};
+
+use super::args::LineColumn;
+use super::output::Outcome;
+use super::session::Session;
+use super::CliError;
+use crate::lsp::client::ClientError;
+use crate::lsp::navigation::GotoKind;
+
+/// A request `bench` sends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BenchMethod {
+    Hover,
+    Definition,
+    Completion,
+}
+
+impl BenchMethod {
+    /// The names `parse_list` takes, leaving out aliases.
+    pub const NAMES: &'static [&'static str] = &["hover", "definition", "completion"];
+
+    /// Parses `--methods`, a comma separated list. A method listed twice is sent twice as
+    /// often.
+    pub fn parse_list(arg: &str) -> Result<Vec<BenchMethod>, CliError> {
+        arg.split(',')
+            .map(|method| match method.trim() {
+                "hover" => Ok(BenchMethod::Hover),
+                "definition" => Ok(BenchMethod::Definition),
+                "completion" | "complete" => Ok(BenchMethod::Completion),
+                method => Err(CliError::Usage(format!(
+                    "unknown method {:?}, expected hover, definition or completion",
+                    method
+                ))),
+            })
+            .collect()
+    }
+
+    /// The LSP method of the request.
+    pub fn method(self) -> &'static str {
+        match self {
+            BenchMethod::Hover => HoverRequest::METHOD,
+            BenchMethod::Definition => GotoDefinition::METHOD,
+            BenchMethod::Completion => Completion::METHOD,
+        }
+    }
+
+    /// Every method, the mix sent by default.
+    pub fn all() -> Vec<BenchMethod> {
+        vec![
+            BenchMethod::Hover,
+            BenchMethod::Definition,
+            BenchMethod::Completion,
+        ]
+    }
+}
+
+/// How long the server took to answer the requests of one method, fastest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Latencies {
+    pub method: &'static str,
+    pub samples: Vec<Duration>,
+    /// Requests that failed or timed out, not counted in `samples`.
+    pub failed: usize,
+}
+
+impl Latencies {
+    /// The latency `percent` of the requests took at most, by the nearest rank.
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        let rank = (percent / 100.0 * self.samples.len() as f64).ceil() as usize;
+        self.samples
+            .get(rank.clamp(1, self.samples.len().max(1)) - 1)
+            .copied()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.samples.len())
+            .ok()
+            .filter(|&count| count > 0)?;
+        Some(self.samples.iter().sum::<Duration>() / count)
+    }
+}
+
+/// Sends `methods` at every position of `file`, `iterations` times over, timing each
+/// answer. The file is opened first and the project loaded, so the first requests do not
+/// pay for the loading.
+///
+/// Requests that fail or time out are counted and the benchmark goes on; a server not
+/// supporting one of the methods fails it.
+pub async fn run(
+    session: &Session,
+    file: &Path,
+    positions: &[LineColumn],
+    methods: &[BenchMethod],
+    iterations: usize,
+) -> Result<Outcome, CliError> {
+    let uri = session.open(file).await?;
+    let positions: Vec<_> = positions
+        .iter()
+        .map(|at| session.position(&uri, *at))
+        .collect();
+    let mut latencies: Vec<Latencies> = Vec::new();
+    for method in methods {
+        if !latencies
+            .iter()
+            .any(|known| known.method == method.method())
+        {
+            latencies.push(Latencies {
+                method: method.method(),
+                samples: Vec::new(),
+                failed: 0,
+            });
+        }
+    }
+    for _ in 0..iterations {
+        for &position in &positions {
+            for method in methods {
+                let start = Instant::now();
+                let answered = match method {
+                    BenchMethod::Hover => session
+                        .timed(session.server.hover(uri.clone(), position))
+                        .await
+                        .map(drop),
+                    BenchMethod::Definition => session
+                        .timed(
+                            session
+                                .server
+                                .goto(GotoKind::Definition, uri.clone(), position),
+                        )
+                        .await
+                        .map(drop),
+                    BenchMethod::Completion => session
+                        .timed(session.server.completions(uri.clone(), position, None))
+                        .await
+                        .map(drop),
+                };
+                let elapsed = start.elapsed();
+                let latency = latencies
+                    .iter_mut()
+                    .find(|latency| latency.method == method.method())
+                    .expect("every method has its latencies");
+                match answered {
+                    Ok(()) => latency.samples.push(elapsed),
+                    Err(err @ CliError::Client(ClientError::Unsupported(_))) => return Err(err),
+                    Err(_) => latency.failed += 1,
+                }
+            }
+        }
+    }
+    for latency in &mut latencies {
+        latency.samples.sort();
+    }
+    Ok(Outcome::Bench(latencies))
+}
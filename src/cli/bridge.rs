@@ -0,0 +1,155 @@
+//! Rewrites the messages `bridge` relays between an editor and the server, so the CLI can
+//! sit between them as middleware: diagnostics the editor should not see are filtered out,
+//! paths are mapped between the editor's and the server's view of the files, and
+//! `initializationOptions` are added to the editor's `initialize`.
+
+use lsp_types::notification::{Notification, PublishDiagnostics};
+use lsp_types::request::{Initialize, Request};
+use lsp_types::{DiagnosticSeverity, NumberOrString};
+use serde_json::Value;
+
+use super::output;
+use super::CliError;
+
+/// A folder as the editor sees it and as the server does, e.g. on the host and in a
+/// container.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathMapping {
+    pub editor: String,
+    pub server: String,
+}
+
+impl PathMapping {
+    /// Parses `--map-path <editor>=<server>`.
+    pub fn parse(arg: &str) -> Result<PathMapping, CliError> {
+        let usage = || {
+            CliError::Usage(format!(
+                "--map-path expects <editor folder>=<server folder>, both absolute, not {:?}",
+                arg
+            ))
+        };
+        let (editor, server) = arg.split_once('=').ok_or_else(usage)?;
+        if !editor.starts_with('/') || !server.starts_with('/') {
+            return Err(usage());
+        }
+        Ok(PathMapping {
+            editor: editor.trim_end_matches('/').to_owned(),
+            server: server.trim_end_matches('/').to_owned(),
+        })
+    }
+}
+
+/// How `bridge` rewrites the traffic. Nothing is rewritten by default.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Transforms {
+    /// Published diagnostics less severe than this are not passed on to the editor.
+    pub min_severity: Option<DiagnosticSeverity>,
+    /// Published diagnostics with these codes are not passed on to the editor.
+    pub hidden_codes: Vec<String>,
+    pub paths: Vec<PathMapping>,
+    /// Merged into the `initializationOptions` of the editor's `initialize`, these winning.
+    pub initialization_options: Option<Value>,
+}
+
+/// Replaces the folder `from` at the start of `text` with `to`, as a path or as the path
+/// of a `file://` uri.
+fn map_prefix(text: &str, from: &str, to: &str) -> Option<String> {
+    let (scheme, path) = match text.strip_prefix("file://") {
+        Some(path) => ("file://", path),
+        None => ("", text),
+    };
+    let rest = path.strip_prefix(from)?;
+    if !rest.is_empty() && !rest.starts_with('/') {
+        // a sibling such as `/work/app2` of `/work/app`
+        return None;
+    }
+    Some(format!("{}{}{}", scheme, to, rest))
+}
+
+/// Rewrites every string in `value` starting with a mapped folder.
+fn map_paths(value: &mut Value, mappings: &[(&str, &str)]) {
+    match value {
+        Value::String(text) => {
+            if let Some(mapped) = mappings
+                .iter()
+                .find_map(|(from, to)| map_prefix(text, from, to))
+            {
+                *text = mapped;
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                map_paths(value, mappings);
+            }
+        }
+        Value::Object(fields) => {
+            for value in fields.values_mut() {
+                map_paths(value, mappings);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Merges `from` into `into`, objects key by key and everything else replaced.
+fn merge(into: &mut Value, from: &Value) {
+    match (into, from) {
+        (Value::Object(into), Value::Object(from)) => {
+            for (key, value) in from {
+                merge(into.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (into, from) => *into = from.clone(),
+    }
+}
+
+impl Transforms {
+    /// Rewrites a message the editor sent, before it goes to the server.
+    pub fn from_editor(&self, message: &mut Value) {
+        let mappings: Vec<(&str, &str)> = self
+            .paths
+            .iter()
+            .map(|mapping| (mapping.editor.as_str(), mapping.server.as_str()))
+            .collect();
+        map_paths(message, &mappings);
+        if message["method"] == Initialize::METHOD {
+            if let Some(options) = &self.initialization_options {
+                merge(&mut message["params"]["initializationOptions"], options);
+            }
+        }
+    }
+
+    /// Rewrites a message the server sent, before it goes to the editor.
+    pub fn from_server(&self, message: &mut Value) {
+        let mappings: Vec<(&str, &str)> = self
+            .paths
+            .iter()
+            .map(|mapping| (mapping.server.as_str(), mapping.editor.as_str()))
+            .collect();
+        map_paths(message, &mappings);
+        if message["method"] != PublishDiagnostics::METHOD {
+            return;
+        }
+        let Some(diagnostics) = message["params"]["diagnostics"].as_array_mut() else {
+            return;
+        };
+        diagnostics.retain(|diagnostic| {
+            let severity = serde_json::from_value(diagnostic["severity"].clone()).ok();
+            let severe = self.min_severity.is_none_or(|min| {
+                output::severity_rank(severity) <= output::severity_rank(Some(min))
+            });
+            let code = match serde_json::from_value(diagnostic["code"].clone()) {
+                Ok(NumberOrString::Number(code)) => Some(code.to_string()),
+                Ok(NumberOrString::String(code)) => Some(code),
+                Err(_) => None,
+            };
+            let hidden = code.is_some_and(|code| self.hidden_codes.contains(&code));
+            severe && !hidden
+        });
+    }
+
+    /// Whether any message is rewritten at all.
+    pub fn is_empty(&self) -> bool {
+        *self == Transforms::default()
+    }
+}
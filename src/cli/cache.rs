@@ -0,0 +1,168 @@
+//! The symbol cache `index` writes below the workspace root. Later invocations look
+//! `--symbol` paths up in it instead of asking the server to search the workspace, which
+//! many servers only answer once they loaded the whole project.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use lsp_types::Url;
+use serde::{Deserialize, Serialize};
+
+use super::output::Outcome;
+use super::session::Session;
+use super::CliError;
+use crate::lsp::client::ClientError;
+use crate::lsp::symbols::{self, DocumentSymbolNode};
+use crate::lsp::text_utils::PositionEncoding;
+
+/// Where the cache is kept, relative to the workspace root. Hidden, so crawling the
+/// workspace skips it.
+pub const CACHE_FILE: &str = ".lsp-client/symbols.json";
+
+/// The symbols of a file as the server reported them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedFile {
+    pub uri: Url,
+    /// When the file was modified before it was indexed. The symbols of a file modified
+    /// since are stale.
+    pub modified: SystemTime,
+    /// What the characters of the symbols' positions count.
+    pub encoding: PositionEncoding,
+    pub symbols: Vec<DocumentSymbolNode>,
+}
+
+impl CachedFile {
+    /// Whether the file is unchanged since it was indexed.
+    pub fn is_fresh(&self) -> bool {
+        self.uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| std::fs::metadata(path).ok())
+            .and_then(|metadata| metadata.modified().ok())
+            == Some(self.modified)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SymbolCache {
+    pub files: Vec<CachedFile>,
+}
+
+impl SymbolCache {
+    /// The cache of the workspace at `root`, empty when there is none or it cannot be read:
+    /// it only saves asking the server.
+    pub fn load(root: &Path) -> SymbolCache {
+        std::fs::read_to_string(root.join(CACHE_FILE))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, root: &Path) -> Result<PathBuf, CliError> {
+        let path = root.join(CACHE_FILE);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string(self)
+            .map_err(|err| CliError::Failed(format!("cannot encode the cache: {}", err)))?;
+        std::fs::write(&path, json)?;
+        Ok(path)
+    }
+
+    /// Replaces the symbols of the files indexed again, keeping those of the other files
+    /// unless they were deleted since.
+    pub fn update(&mut self, files: Vec<CachedFile>) {
+        self.files.retain(|cached| {
+            !files.iter().any(|file| file.uri == cached.uri)
+                && cached.uri.to_file_path().is_ok_and(|path| path.exists())
+        });
+        self.files.extend(files);
+        self.files.sort_by(|a, b| a.uri.cmp(&b.uri));
+    }
+
+    /// The symbols at the path of names `segments`, e.g. `["MyClass", "myMethod"]`, with
+    /// the file they are in, among the files unchanged since they were indexed.
+    pub fn find(&self, segments: &[&str]) -> Vec<(&CachedFile, &DocumentSymbolNode)> {
+        self.files
+            .iter()
+            .filter(|file| file.is_fresh())
+            .flat_map(|file| {
+                symbols::symbols_at_path(&file.symbols, segments)
+                    .into_iter()
+                    .map(move |symbol| (file, symbol))
+            })
+            .collect()
+    }
+}
+
+/// What `index` cached for a workspace.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CacheSummary {
+    pub path: PathBuf,
+    pub files: usize,
+    pub symbols: usize,
+}
+
+/// Indexes the symbols of `files` into the cache of the session's root. Every file is
+/// opened at once, so the server loads everything it needs before the symbols are asked
+/// for, and closed again at the end.
+///
+/// Like `inventory::collect`, files the server fails on are reported on stderr and left
+/// out; a server without document symbols fails the whole index.
+pub async fn build(session: &Session, files: &[PathBuf]) -> Result<Outcome, CliError> {
+    let mut opened = Vec::new();
+    for file in files {
+        // taken first, a change while indexing leaves the symbols stale
+        let modified = std::fs::metadata(file).and_then(|metadata| metadata.modified());
+        match (modified, session.sync(file).await) {
+            (Ok(modified), Ok(uri)) => opened.push((file, uri, modified)),
+            (Err(err), _) => eprintln!("skipping {}: {}", file.display(), err),
+            (_, Err(err)) => eprintln!("skipping {}: {}", file.display(), err.message()),
+        }
+    }
+    session.wait_for_index().await;
+
+    let encoding = session.encoding();
+    let mut cached = Vec::new();
+    let mut unsupported = None;
+    for (file, uri, modified) in &opened {
+        match session
+            .timed(session.server.document_symbols(uri.clone()))
+            .await
+        {
+            Ok(symbols) => cached.push(CachedFile {
+                uri: uri.clone(),
+                modified: *modified,
+                encoding,
+                symbols,
+            }),
+            Err(err @ CliError::Client(ClientError::Unsupported(_))) => {
+                unsupported = Some(err);
+                break;
+            }
+            Err(err) => eprintln!("skipping {}: {}", file.display(), err.message()),
+        }
+    }
+    for (_, uri, _) in &opened {
+        session.documents.close(uri).await;
+    }
+    if let Some(err) = unsupported {
+        return Err(err);
+    }
+
+    let indexed = cached.len();
+    let symbols = cached
+        .iter()
+        .flat_map(|file| &file.symbols)
+        .map(|symbol| symbol.descendants().len())
+        .sum();
+    let mut cache = SymbolCache::load(&session.root);
+    cache.update(cached);
+    let path = cache.save(&session.root)?;
+    Ok(Outcome::Cached(vec![CacheSummary {
+        path,
+        files: indexed,
+        symbols,
+    }]))
+}
@@ -0,0 +1,106 @@
+//! The lines of a file changed since a git ref, for `diagnostics --changed-only`: only the
+//! diagnostics on those lines are reported, so a project can adopt an analyzer without
+//! fixing everything it finds in existing code first.
+
+use std::ops;
+use std::path::Path;
+use std::process::Command;
+
+use lsp_types::{Diagnostic, Range};
+
+use super::CliError;
+
+/// What changed in a file since the ref.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Changes {
+    /// The file is not tracked, every line is new.
+    Whole,
+    /// The lines added or modified, counted from 0, deleted lines leaving no trace.
+    Lines(Vec<ops::Range<u32>>),
+}
+
+impl Changes {
+    /// Whether `range` spans a changed line.
+    pub fn touches(&self, range: &Range) -> bool {
+        match self {
+            Changes::Whole => true,
+            Changes::Lines(lines) => lines
+                .iter()
+                .any(|lines| lines.start <= range.end.line && range.start.line < lines.end),
+        }
+    }
+
+    /// Drops the diagnostics on lines that did not change.
+    pub fn retain(&self, diagnostics: &mut Vec<Diagnostic>) {
+        diagnostics.retain(|diagnostic| self.touches(&diagnostic.range));
+    }
+}
+
+/// The lines of the new side of the hunks of a diff made with `--unified=0`.
+pub fn changed_lines(diff: &str) -> Vec<ops::Range<u32>> {
+    diff.lines()
+        .filter_map(|line| line.strip_prefix("@@ "))
+        .filter_map(|header| {
+            // @@ -<start>[,<count>] +<start>[,<count>] @@
+            let new = header.split(' ').find_map(|side| side.strip_prefix('+'))?;
+            let (start, count) = match new.split_once(',') {
+                Some((start, count)) => (start.parse::<u32>().ok()?, count.parse().ok()?),
+                None => (new.parse::<u32>().ok()?, 1),
+            };
+            let start = start.saturating_sub(1);
+            (count > 0).then(|| start..start + count)
+        })
+        .collect()
+}
+
+/// Runs git in the folder of `file`, failing with what git printed when it fails.
+fn git(file: &Path, args: &[&str]) -> Result<std::process::Output, CliError> {
+    let dir = file
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|err| CliError::Failed(format!("cannot run git for --changed-only: {}", err)))
+}
+
+/// What changed in `file` since `base`, in the working tree: staged, unstaged or
+/// untracked.
+pub fn since(file: &Path, base: &str) -> Result<Changes, CliError> {
+    let name = file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| CliError::Usage(format!("{} is not a file", file.display())))?;
+    let diff = git(
+        file,
+        &[
+            "diff",
+            "--no-color",
+            "--no-ext-diff",
+            "--unified=0",
+            base,
+            "--",
+            name,
+        ],
+    )?;
+    if !diff.status.success() {
+        return Err(CliError::Failed(format!(
+            "git diff {} failed for {}: {}",
+            base,
+            file.display(),
+            String::from_utf8_lossy(&diff.stderr).trim()
+        )));
+    }
+    let diff = String::from_utf8_lossy(&diff.stdout);
+    if diff.is_empty() {
+        // unchanged, or unknown to git
+        let tracked = git(file, &["ls-files", "--error-unmatch", "--", name])?;
+        if !tracked.status.success() {
+            return Ok(Changes::Whole);
+        }
+    }
+    Ok(Changes::Lines(changed_lines(&diff)))
+}
@@ -0,0 +1,376 @@
+//! Shell completions and the man page, both generated from `USAGE` so they list the same
+//! commands and options as the help: `completions <shell>` prints a script to source,
+//! `--man` prints the help as a man page.
+
+use std::fmt::Write;
+
+use super::args::{self, USAGE};
+use super::bench::BenchMethod;
+use super::output::{ColorChoice, OutputFormat};
+use super::CliError;
+
+/// A shell `completions` writes a script for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub fn parse(arg: &str) -> Result<Shell, CliError> {
+        match arg {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(CliError::Usage(format!(
+                "unknown shell {:?}, expected bash, zsh or fish",
+                arg
+            ))),
+        }
+    }
+}
+
+/// An entry of a section of `USAGE`: the command, option or variable, and what it does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub term: &'static str,
+    pub description: String,
+}
+
+/// A section of `USAGE`, such as `commands:`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub title: &'static str,
+    pub entries: Vec<Entry>,
+}
+
+/// The sections of `USAGE`. An entry starts on a line indented by four spaces, its
+/// description in a column, continued on the lines indented up to that column.
+pub fn sections() -> Vec<Section> {
+    USAGE
+        .split("\n\n")
+        .filter_map(|block| {
+            let (title, body) = block.split_once(":\n")?;
+            let column = body
+                .lines()
+                .find(|line| line.starts_with("     "))
+                .map(|line| line.len() - line.trim_start().len())?;
+            let mut entries: Vec<Entry> = Vec::new();
+            for line in body.lines() {
+                match entries.last_mut() {
+                    Some(entry) if line.starts_with("     ") => {
+                        entry.description.push(' ');
+                        entry.description.push_str(line.trim());
+                    }
+                    _ => {
+                        let (term, description) = line.split_at(column.min(line.len()));
+                        entries.push(Entry {
+                            term: term.trim(),
+                            description: description.trim().to_owned(),
+                        });
+                    }
+                }
+            }
+            Some(Section { title, entries })
+        })
+        .collect()
+}
+
+fn section(title: &str) -> Vec<Entry> {
+    sections()
+        .into_iter()
+        .find(|section| section.title == title)
+        .map(|section| section.entries)
+        .unwrap_or_default()
+}
+
+/// What an option's value completes to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    /// The option takes no value.
+    Flag,
+    Words(&'static [&'static str]),
+    File,
+    Dir,
+    /// Anything, nothing to complete.
+    Free,
+}
+
+/// An option of `USAGE`, e.g. `--root` of `--root <dir>`.
+struct Flag {
+    name: &'static str,
+    value: Value,
+    description: String,
+}
+
+fn options() -> Vec<Flag> {
+    section("options")
+        .into_iter()
+        .map(|entry| {
+            // `--trace-lsp[=<file>]` takes its value after `=` only, as a flag otherwise
+            let name = entry.term.split([' ', '[']).next().unwrap_or(entry.term);
+            let argument = entry.term.split_once(' ').map(|(_, argument)| argument);
+            let value = match (name, argument) {
+                (_, None) => Value::Flag,
+                ("--output", _) => Value::Words(OutputFormat::NAMES),
+                ("--color", _) => Value::Words(ColorChoice::NAMES),
+                ("--fail-on" | "--min-severity", _) => Value::Words(args::SEVERITIES),
+                ("--methods", _) => Value::Words(BenchMethod::NAMES),
+                (_, Some(argument)) if argument.starts_with("<dir>") => Value::Dir,
+                (_, Some(argument)) if argument.starts_with("<file>") => Value::File,
+                _ => Value::Free,
+            };
+            Flag {
+                name,
+                value,
+                description: entry.description,
+            }
+        })
+        .collect()
+}
+
+/// The commands of `USAGE`, by name, with their descriptions.
+fn commands() -> Vec<(&'static str, String)> {
+    section("commands")
+        .into_iter()
+        .map(|entry| {
+            let name = entry.term.split(' ').next().unwrap_or(entry.term);
+            (name, entry.description)
+        })
+        .collect()
+}
+
+/// The completion script for `shell`, to source from its configuration.
+pub fn script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+    }
+}
+
+/// The names of `flags` with a value `matches`, as alternatives of a `case` pattern.
+fn bash_pattern(flags: &[Flag], matches: impl Fn(Value) -> bool) -> Option<String> {
+    let names: Vec<_> = flags
+        .iter()
+        .filter(|flag| matches(flag.value))
+        .map(|flag| flag.name)
+        .collect();
+    (!names.is_empty()).then(|| names.join("|"))
+}
+
+fn bash() -> String {
+    let flags = options();
+    let mut script = String::from(
+        "# bash completion for lsp-client, generated by `lsp-client completions bash`\n\
+         _lsp_client() {\n    \
+             local cur=${COMP_WORDS[COMP_CWORD]} prev=${COMP_WORDS[COMP_CWORD-1]} i\n    \
+             case $prev in\n",
+    );
+    for flag in &flags {
+        if let Value::Words(words) = flag.value {
+            writeln!(
+                script,
+                "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;",
+                flag.name,
+                words.join(" ")
+            )
+            .unwrap();
+        }
+    }
+    if let Some(pattern) = bash_pattern(&flags, |value| value == Value::File) {
+        writeln!(
+            script,
+            "        {}) COMPREPLY=($(compgen -f -- \"$cur\")); return ;;",
+            pattern
+        )
+        .unwrap();
+    }
+    if let Some(pattern) = bash_pattern(&flags, |value| value == Value::Dir) {
+        writeln!(
+            script,
+            "        {}) COMPREPLY=($(compgen -d -- \"$cur\")); return ;;",
+            pattern
+        )
+        .unwrap();
+    }
+    if let Some(pattern) = bash_pattern(&flags, |value| value == Value::Free) {
+        writeln!(script, "        {}) return ;;", pattern).unwrap();
+    }
+    let names: Vec<_> = flags.iter().map(|flag| flag.name).collect();
+    let commands: Vec<_> = commands().into_iter().map(|(name, _)| name).collect();
+    let valued = bash_pattern(&flags, |value| value != Value::Flag).unwrap_or_default();
+    write!(
+        script,
+        "    esac\n    \
+             if [[ $cur == -* ]]; then\n        \
+                 COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n        \
+                 return\n    \
+             fi\n    \
+             # files once the command is given, skipping the values of options\n    \
+             for ((i = 1; i < COMP_CWORD; i++)); do\n        \
+                 case ${{COMP_WORDS[i]}} in\n            \
+                     {}) ((i++)) ;;\n            \
+                     -*) ;;\n            \
+                     *) COMPREPLY=($(compgen -f -- \"$cur\")); return ;;\n        \
+                 esac\n    \
+             done\n    \
+             COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n\
+         }}\n\
+         complete -o filenames -F _lsp_client lsp-client\n",
+        names.join(" "),
+        valued,
+        commands.join(" ")
+    )
+    .unwrap();
+    script
+}
+
+/// `text` within single quotes in zsh, where a quote ends the string.
+fn zsh_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn zsh() -> String {
+    let mut script = String::from(
+        "#compdef lsp-client\n\
+         # zsh completion for lsp-client, generated by `lsp-client completions zsh`\n\n\
+         _lsp_client() {\n    \
+             local state\n    \
+             local -a commands\n    \
+             commands=(\n",
+    );
+    for (name, description) in commands() {
+        writeln!(
+            script,
+            "        {}",
+            zsh_quote(&format!("{}:{}", name, description))
+        )
+        .unwrap();
+    }
+    script.push_str("    )\n    _arguments \\\n");
+    for flag in options() {
+        // brackets and colons delimit the parts of a spec
+        let description = flag
+            .description
+            .replace('\\', "\\\\")
+            .replace('[', "\\[")
+            .replace(']', "\\]")
+            .replace(':', "\\:");
+        let value = match flag.value {
+            Value::Flag => String::new(),
+            Value::Words(words) => format!(":value:({})", words.join(" ")),
+            Value::File => ":file:_files".to_owned(),
+            Value::Dir => ":dir:_files -/".to_owned(),
+            Value::Free => ":value: ".to_owned(),
+        };
+        let spec = format!("*{}[{}]{}", flag.name, description, value);
+        writeln!(script, "        {} \\", zsh_quote(&spec)).unwrap();
+    }
+    script.push_str(
+        "        '1: :->command' \\\n        \
+                 '*:file:_files'\n    \
+             case $state in\n        \
+                 command) _describe command commands ;;\n    \
+             esac\n\
+         }\n\n\
+         _lsp_client \"$@\"\n",
+    );
+    script
+}
+
+/// `text` within single quotes in fish, where backslashes and quotes are escaped.
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn fish() -> String {
+    let mut script = String::from(
+        "# fish completion for lsp-client, generated by `lsp-client completions fish`\n\
+         complete -c lsp-client -f\n\
+         complete -c lsp-client -n 'not __fish_use_subcommand' -F\n",
+    );
+    for (name, description) in commands() {
+        writeln!(
+            script,
+            "complete -c lsp-client -n __fish_use_subcommand -a {} -d {}",
+            name,
+            fish_quote(&description)
+        )
+        .unwrap();
+    }
+    for flag in options() {
+        let value = match flag.value {
+            Value::Flag => String::new(),
+            Value::Words(words) => format!(" -x -a {}", fish_quote(&words.join(" "))),
+            Value::File => " -r -F".to_owned(),
+            Value::Dir => " -x -a '(__fish_complete_directories)'".to_owned(),
+            Value::Free => " -x".to_owned(),
+        };
+        writeln!(
+            script,
+            "complete -c lsp-client -l {}{} -d {}",
+            flag.name.trim_start_matches('-'),
+            value,
+            fish_quote(&flag.description)
+        )
+        .unwrap();
+    }
+    script
+}
+
+/// `text` as roff, with no request starting a line.
+fn roff(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    if text.starts_with(['.', '\'']) {
+        format!("\\&{}", text)
+    } else {
+        text
+    }
+}
+
+/// The help as a man page in roff, for `man -l`.
+pub fn man_page() -> String {
+    let mut page = format!(
+        ".TH LSP\\-CLIENT 1 \"\" \"lsp-client {}\" \"User Commands\"\n\
+         .SH NAME\n\
+         lsp\\-client \\- asks language servers about code from the command line\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    if let Some(synopsis) = USAGE
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("usage: "))
+    {
+        let (program, arguments) = synopsis.split_once(' ').unwrap_or((synopsis, ""));
+        writeln!(
+            page,
+            ".SH SYNOPSIS\n.B {}\n{}",
+            roff(program),
+            roff(arguments)
+        )
+        .unwrap();
+    }
+    for section in sections() {
+        writeln!(page, ".SH {}", section.title.to_uppercase()).unwrap();
+        for entry in section.entries {
+            writeln!(
+                page,
+                ".TP\n.B {}\n{}",
+                roff(entry.term),
+                roff(&entry.description)
+            )
+            .unwrap();
+        }
+    }
+    // the closing notes, which are not a section
+    if let Some(notes) = USAGE
+        .rsplit("\n\n")
+        .next()
+        .filter(|notes| !notes.contains(":\n"))
+    {
+        writeln!(page, ".SH NOTES\n{}", roff(&notes.replace('\n', " "))).unwrap();
+    }
+    page
+}
@@ -0,0 +1,232 @@
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use lsp_types::Url;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::args::Options;
+use super::daemon;
+use super::languages;
+use super::output::{OutputFormat, Style};
+use super::record::Record;
+use super::schema;
+use super::session::{Timeouts, Trace};
+use super::CliError;
+
+/// The name of the config file looked up in the current directory and its ancestors when
+/// no `--config` is given.
+pub const CONFIG_FILE: &str = "lsp.json";
+
+/// The command starting the server, either as a single string split at whitespace or as a
+/// list of arguments.
+#[derive(Debug, Clone, PartialEq, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ServerCommand {
+    Line(String),
+    Args(Vec<String>),
+}
+
+impl ServerCommand {
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            ServerCommand::Line(line) => line.split_whitespace().map(str::to_owned).collect(),
+            ServerCommand::Args(args) => args.clone(),
+        }
+    }
+}
+
+/// The contents of a config file, e.g.
+///
+/// ```json
+/// {
+///     "server": ["typescript-language-server", "--stdio"],
+///     "rootUri": "file:///home/me/project",
+///     "initializationOptions": { "preferences": { "includePackageJsonAutoImports": "auto" } },
+///     "extensions": ["ts", "tsx"],
+///     "output": "text"
+/// }
+/// ```
+///
+/// Every field is optional. `rootUri` may also be a path, relative to the config file.
+/// `extensions` lists the files the server handles, by default those of the languages it
+/// is known for. `initializationOptionsSchema` names a JSON Schema file, relative to the
+/// config file, the `initializationOptions` are checked against, such as the one a server
+/// publishes for its settings.
+///
+/// Configs are checked against `schema::config_schema` when loaded, which `config-schema`
+/// prints for editors, see `$schema`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Config {
+    /// The schema of the file, for editors, e.g. `lsp.schema.json` as printed by
+    /// `lsp-client config-schema`.
+    #[serde(rename = "$schema")]
+    pub schema: Option<String>,
+    /// The command starting the language server, as a line split at whitespace or as a
+    /// list of arguments.
+    pub server: Option<ServerCommand>,
+    /// The workspace root, as a `file:` URI or as a path relative to the config file.
+    pub root_uri: Option<String>,
+    /// Sent to the server as the `initializationOptions` of `initialize`.
+    pub initialization_options: Option<Value>,
+    /// A JSON Schema file, relative to the config file, `initializationOptions` must
+    /// follow.
+    pub initialization_options_schema: Option<String>,
+    /// The extensions of the files the server handles, without the dot.
+    pub extensions: Option<Vec<String>>,
+    /// How results are printed unless `--output` is given.
+    pub output: Option<OutputFormat>,
+    /// The config file, relative paths are resolved against its directory.
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+impl Config {
+    /// Reads the config file at `path`, checking it against the config schema and its
+    /// `initializationOptions` against their schema, if it names one.
+    pub fn load(path: &Path) -> Result<Config, CliError> {
+        let invalid = |message: String| CliError::Config {
+            path: path.to_path_buf(),
+            message,
+        };
+        let contents = std::fs::read_to_string(path).map_err(|err| invalid(err.to_string()))?;
+        let value: Value =
+            serde_json::from_str(&contents).map_err(|err| invalid(err.to_string()))?;
+        let mut violations = schema::validate(&schema::config_schema(), &value);
+        if violations.is_empty() {
+            if let (Some(file), Some(options)) = (
+                value
+                    .get("initializationOptionsSchema")
+                    .and_then(Value::as_str),
+                value.get("initializationOptions"),
+            ) {
+                let file = path.parent().unwrap_or(Path::new("")).join(file);
+                let options_schema = std::fs::read_to_string(&file)
+                    .map_err(|err| err.to_string())
+                    .and_then(|schema| serde_json::from_str(&schema).map_err(|err| err.to_string()))
+                    .map_err(|err| {
+                        invalid(format!(
+                            "cannot read initializationOptionsSchema {}: {}",
+                            file.display(),
+                            err
+                        ))
+                    })?;
+                violations = schema::validate(&options_schema, options);
+                for violation in &mut violations {
+                    violation.pointer.insert_str(0, "/initializationOptions");
+                }
+            }
+        }
+        if !violations.is_empty() {
+            let messages: Vec<_> = violations.iter().map(ToString::to_string).collect();
+            return Err(invalid(messages.join("\n")));
+        }
+        let mut config: Config =
+            serde_json::from_value(value).map_err(|err| invalid(err.to_string()))?;
+        config.path = path.to_path_buf();
+        Ok(config)
+    }
+
+    /// Reads the nearest `lsp.json` found in `dir` or its ancestors, if any.
+    pub fn find(dir: &Path) -> Result<Option<Config>, CliError> {
+        for dir in dir.ancestors() {
+            let path = dir.join(CONFIG_FILE);
+            if path.is_file() {
+                return Config::load(&path).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns true if the server of this config handles `path`, judged by its extension.
+    /// A config without a server handles the files of every known language, as the server
+    /// is picked by the file then.
+    pub fn handles(&self, path: &Path) -> bool {
+        let Some(extension) = path.extension().and_then(|extension| extension.to_str()) else {
+            return false;
+        };
+        match (&self.extensions, &self.server) {
+            (Some(extensions), _) => extensions.iter().any(|known| known == extension),
+            (None, Some(server)) => {
+                let program = server.args().into_iter().next().unwrap_or_default();
+                languages::server_extensions(&program).contains(&extension)
+            }
+            (None, None) => languages::language_of(path).is_some(),
+        }
+    }
+
+    /// The workspace root the config names, as a local path.
+    pub fn root(&self) -> Result<Option<PathBuf>, CliError> {
+        let Some(root) = &self.root_uri else {
+            return Ok(None);
+        };
+        if root.starts_with("file:") {
+            let invalid = || CliError::Config {
+                path: self.path.clone(),
+                message: format!("rootUri {} is not a local folder", root),
+            };
+            let uri = Url::parse(root).map_err(|_| invalid())?;
+            return uri.to_file_path().map(Some).map_err(|_| invalid());
+        }
+        let dir = self.path.parent().unwrap_or(Path::new(""));
+        Ok(Some(dir.join(root)))
+    }
+}
+
+/// The settings of an invocation: the command line options, with the config file filling
+/// in what they leave out.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Settings {
+    /// The command starting the server, picked from the file's extension when `None`.
+    pub server: Option<Vec<String>>,
+    /// The workspace root, the current directory when `None`.
+    pub root: Option<PathBuf>,
+    pub initialization_options: Option<Value>,
+    /// The extensions of the files the server handles, see `Config`.
+    pub extensions: Option<Vec<String>>,
+    pub output: OutputFormat,
+    pub timeouts: Timeouts,
+    pub trace: Option<Trace>,
+    pub record: Option<Record>,
+    pub index: Option<PathBuf>,
+    pub style: Style,
+    /// Whether to draw the progress the server reports, see `progress`.
+    pub progress: bool,
+    /// The socket of the daemon to ask before starting a server, none with `--no-daemon`.
+    pub daemon: Option<PathBuf>,
+    /// Whether to keep the results of slow queries, see `results`.
+    pub cache: bool,
+}
+
+impl Settings {
+    pub fn new(options: Options, config: Option<Config>) -> Result<Settings, CliError> {
+        let config = config.unwrap_or_default();
+        let root = match options.root {
+            Some(root) => Some(root),
+            None => config.root()?,
+        };
+        Ok(Settings {
+            server: options
+                .server
+                .or_else(|| config.server.as_ref().map(ServerCommand::args)),
+            root,
+            initialization_options: config.initialization_options,
+            extensions: config.extensions,
+            output: options.output.or(config.output).unwrap_or_default(),
+            timeouts: options.timeouts,
+            trace: options.trace,
+            record: options.record,
+            index: options.index,
+            style: Style {
+                color: options.color.enabled(),
+                context: options.context,
+            },
+            progress: !options.quiet && std::io::stderr().is_terminal(),
+            daemon: (!options.no_daemon)
+                .then(|| options.socket.unwrap_or_else(daemon::default_socket)),
+            cache: !options.no_cache,
+        })
+    }
+}
@@ -0,0 +1,324 @@
+//! Keeps language servers running between invocations, see `daemon`: the daemon listens on
+//! a Unix socket and answers the queries of later invocations with the server it started
+//! for the first query about the same root, so they skip starting the server and waiting
+//! for it to load the project.
+//!
+//! An invocation connects to the socket, sends one `Request` as a line of JSON and reads
+//! one `Response` line back. Without a daemon listening it starts a server of its own. The
+//! daemon answers, and invocations ask, only processes of the same user.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use super::args::Query;
+use super::output::{OutputFormat, Style};
+use super::session::{Session, Timeouts, Trace};
+use super::CliError;
+
+/// The socket when no `--socket` is given: in the user's runtime folder, or else in a folder
+/// of the temporary folder under the user's name, which the daemon creates for the user
+/// alone.
+pub fn default_socket() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("lsp-client.sock"),
+        None => private_folder().join("daemon.sock"),
+    }
+}
+
+/// The folder of the default socket without a runtime folder.
+fn private_folder() -> PathBuf {
+    let user = std::env::var("USER").unwrap_or_default();
+    std::env::temp_dir().join(format!("lsp-client-{}", user))
+}
+
+/// A query sent to the daemon, with what picks the server answering it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Request {
+    pub server: Vec<String>,
+    pub root: PathBuf,
+    pub initialization_options: Option<Value>,
+    /// The query, its file an absolute path.
+    pub query: Query,
+    pub output: OutputFormat,
+    pub style: Style,
+    /// The folder paths are printed relative to, the invocation's current directory.
+    pub base: PathBuf,
+}
+
+/// What the daemon answered: the output to print, and the error the query failed with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Response {
+    pub output: String,
+    pub error: Option<String>,
+    /// Whether the error is about the arguments, see `CliError::Usage`.
+    #[serde(default)]
+    pub usage: bool,
+}
+
+impl Response {
+    fn new(output: String, result: Result<(), CliError>) -> Response {
+        let (error, usage) = match result {
+            Ok(()) => (None, false),
+            Err(err) => (Some(err.message()), matches!(err, CliError::Usage(_))),
+        };
+        Response {
+            output,
+            error,
+            usage,
+        }
+    }
+
+    /// The error of the response as the invocation fails with it.
+    pub fn result(&self) -> Result<(), CliError> {
+        match &self.error {
+            None => Ok(()),
+            Some(message) if self.usage => Err(CliError::Usage(message.clone())),
+            Some(message) => Err(CliError::Failed(message.clone())),
+        }
+    }
+}
+
+/// What picks the server of a request: its command, root and initialization options.
+type Key = (Vec<String>, PathBuf, String);
+
+/// A server, `None` until it is started, locked while it answers a query.
+type Slot = Arc<Mutex<Option<Session>>>;
+
+/// The servers of the daemon. A server is started by the first query for it, and answers
+/// one query at a time.
+struct Daemon {
+    sessions: Mutex<HashMap<Key, Slot>>,
+    timeouts: Timeouts,
+    trace: Option<Trace>,
+}
+
+impl Daemon {
+    async fn answer(&self, request: Request) -> Response {
+        let key = (
+            request.server.clone(),
+            request.root.clone(),
+            request
+                .initialization_options
+                .as_ref()
+                .map(Value::to_string)
+                .unwrap_or_default(),
+        );
+        let slot = self.sessions.lock().await.entry(key).or_default().clone();
+        let mut slot = slot.lock().await;
+        if slot.as_mut().is_some_and(Session::exited) {
+            // crashed since the last query, started again
+            *slot = None;
+        }
+        let session = match &mut *slot {
+            Some(session) => session,
+            None => {
+                let started = Session::start(
+                    &request.server,
+                    &request.root,
+                    request.initialization_options,
+                    self.timeouts,
+                    self.trace.as_ref(),
+                    None,
+                    false,
+                )
+                .await;
+                match started {
+                    Ok(session) => slot.insert(session),
+                    Err(err) => return Response::new(String::new(), Err(err)),
+                }
+            }
+        };
+        session.style = request.style;
+        session.base = Some(request.base);
+        match super::answer(session, &request.query, request.output).await {
+            Ok((output, outcome)) => {
+                let result = super::check_severity(&request.query, &outcome);
+                Response::new(output, result)
+            }
+            Err(err) => Response::new(String::new(), Err(err)),
+        }
+    }
+
+    /// Shuts every server down, once the queries they are answering are answered.
+    async fn finish(&self) {
+        let slots: Vec<_> = self.sessions.lock().await.drain().collect();
+        for (_, slot) in slots {
+            if let Some(session) = slot.lock().await.take() {
+                session.finish().await;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::signal::unix::{signal, SignalKind};
+
+    use super::{private_folder, Daemon, Request, Response};
+    use crate::cli::session::{Timeouts, Trace};
+    use crate::cli::CliError;
+
+    /// Answers the queries sent to `socket` until interrupted, then shuts the servers
+    /// down.
+    pub async fn serve(
+        socket: &Path,
+        timeouts: Timeouts,
+        trace: Option<Trace>,
+    ) -> Result<(), CliError> {
+        if UnixStream::connect(socket).await.is_ok() {
+            return Err(CliError::Failed(format!(
+                "a daemon is already listening on {}",
+                socket.display()
+            )));
+        }
+        if socket.parent() == Some(private_folder().as_path()) {
+            create_private_folder(&private_folder())?;
+        }
+        // left behind by a daemon that was killed
+        let _ = std::fs::remove_file(socket);
+        // the daemon starts any command for whoever connects: no one else may, not even
+        // before the permissions are set
+        // SAFETY: umask only swaps the file mode creation mask of the process
+        let mask = unsafe { libc::umask(0o077) };
+        let bound = UnixListener::bind(socket);
+        // SAFETY: as above
+        unsafe { libc::umask(mask) };
+        let listener = bound.map_err(|err| {
+            CliError::Failed(format!("cannot listen on {}: {}", socket.display(), err))
+        })?;
+        std::fs::set_permissions(socket, std::fs::Permissions::from_mode(0o600))?;
+        eprintln!("listening on {}", socket.display());
+        let daemon = Arc::new(Daemon {
+            sessions: Default::default(),
+            timeouts,
+            trace,
+        });
+        let mut terminate = signal(SignalKind::terminate())?;
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) if !same_user(&stream) => {
+                        eprintln!("refused a connection from another user");
+                    }
+                    Ok((stream, _)) => {
+                        let daemon = daemon.clone();
+                        tokio::spawn(async move { reply(&daemon, stream).await });
+                    }
+                    Err(err) => eprintln!("failed to accept a connection: {}", err),
+                },
+                _ = tokio::signal::ctrl_c() => break,
+                _ = terminate.recv() => break,
+            }
+        }
+        let _ = std::fs::remove_file(socket);
+        daemon.finish().await;
+        Ok(())
+    }
+
+    /// Creates `folder` for the user alone, or checks that it is the user's alone, so that no
+    /// one else can put a socket of their own in it.
+    fn create_private_folder(folder: &Path) -> Result<(), CliError> {
+        if let Err(err) = std::fs::DirBuilder::new().mode(0o700).create(folder) {
+            if err.kind() != std::io::ErrorKind::AlreadyExists {
+                return Err(err.into());
+            }
+        }
+        let metadata = std::fs::symlink_metadata(folder)?;
+        if !metadata.is_dir() || metadata.uid() != current_user() || metadata.mode() & 0o077 != 0 {
+            return Err(CliError::Failed(format!(
+                "{} is not a folder of the current user alone, see --socket",
+                folder.display()
+            )));
+        }
+        Ok(())
+    }
+
+    fn current_user() -> u32 {
+        // SAFETY: geteuid has no preconditions and cannot fail
+        unsafe { libc::geteuid() }
+    }
+
+    /// Whether the other end of `stream` runs as the current user, the only one the daemon
+    /// answers, and the only one an invocation asks.
+    fn same_user(stream: &UnixStream) -> bool {
+        stream
+            .peer_cred()
+            .is_ok_and(|peer| peer.uid() == current_user())
+    }
+
+    async fn reply(daemon: &Daemon, stream: UnixStream) {
+        let (read, mut write) = stream.into_split();
+        let mut line = String::new();
+        if BufReader::new(read).read_line(&mut line).await.is_err() {
+            return;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => daemon.answer(request).await,
+            Err(err) => Response::new(
+                String::new(),
+                Err(CliError::Failed(format!("invalid request: {}", err))),
+            ),
+        };
+        let mut line = serde_json::to_string(&response).expect("responses are JSON");
+        line.push('\n');
+        let _ = write.write_all(line.as_bytes()).await;
+    }
+
+    /// Asks the daemon listening on `socket` to answer `request`, `None` when none is
+    /// listening.
+    pub async fn ask(socket: &Path, request: &Request) -> Option<Result<Response, CliError>> {
+        let stream = UnixStream::connect(socket).await.ok()?;
+        if !same_user(&stream) {
+            return Some(Err(CliError::Failed(format!(
+                "{} is the socket of another user",
+                socket.display()
+            ))));
+        }
+        Some(exchange(stream, request).await)
+    }
+
+    async fn exchange(stream: UnixStream, request: &Request) -> Result<Response, CliError> {
+        let (read, mut write) = stream.into_split();
+        let mut line = serde_json::to_string(request).expect("requests are JSON");
+        line.push('\n');
+        write.write_all(line.as_bytes()).await?;
+        let mut line = String::new();
+        BufReader::new(read).read_line(&mut line).await?;
+        serde_json::from_str(&line).map_err(|err| {
+            CliError::Failed(format!("the daemon sent an invalid response: {}", err))
+        })
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{ask, serve};
+
+#[cfg(not(unix))]
+pub async fn serve(
+    socket: &std::path::Path,
+    _: Timeouts,
+    _: Option<Trace>,
+) -> Result<(), CliError> {
+    Err(CliError::Usage(format!(
+        "cannot listen on {}, the daemon needs Unix domain sockets",
+        socket.display()
+    )))
+}
+
+#[cfg(not(unix))]
+pub async fn ask(_: &std::path::Path, _: &Request) -> Option<Result<Response, CliError>> {
+    None
+}
@@ -0,0 +1,193 @@
+use std::path::PathBuf;
+
+use lsp_types::{Range, SymbolKind, Url};
+
+use super::output::Outcome;
+use super::session::Session;
+use super::CliError;
+use crate::lsp::client::ClientError;
+use crate::lsp::symbols::{range_contains, DocumentSymbolNode};
+use crate::lsp::text_utils::{self, PositionEncoding};
+
+/// A public symbol referenced at most `--threshold` times.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unreferenced {
+    pub uri: Url,
+    pub name: String,
+    pub kind: SymbolKind,
+    pub range: Range,
+    pub selection_range: Range,
+    /// How often the symbol is referenced, leaving out its declaration and the references
+    /// within its own body.
+    pub references: usize,
+}
+
+/// The kinds of symbols worth checking. Fields, variables and the like are often used
+/// through other means, constructors are not referenced by their name.
+const CHECKED_KINDS: &[SymbolKind] = &[
+    SymbolKind::FUNCTION,
+    SymbolKind::METHOD,
+    SymbolKind::CLASS,
+    SymbolKind::STRUCT,
+    SymbolKind::INTERFACE,
+    SymbolKind::ENUM,
+    SymbolKind::CONSTANT,
+];
+
+/// Returns true if the words of `text` include `word`.
+fn has_word(text: &str, word: &str) -> bool {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|part| part == word)
+}
+
+/// Whether a symbol is visible outside its module, judged by the conventions of the
+/// language. `declaration` is the source of the symbol up to its name, e.g. `pub fn `, and
+/// `nested` tells whether it is declared inside another symbol.
+pub fn is_public(language_id: &str, declaration: &str, name: &str, nested: bool) -> bool {
+    match language_id {
+        "rust" => has_word(declaration, "pub"),
+        "typescript" | "typescriptreact" | "javascript" | "javascriptreact" if nested => {
+            !has_word(declaration, "private")
+                && !has_word(declaration, "protected")
+                && !name.starts_with('#')
+        }
+        "typescript" | "typescriptreact" | "javascript" | "javascriptreact" => {
+            has_word(declaration, "export")
+        }
+        "go" => name.starts_with(|c: char| c.is_uppercase()),
+        "python" => !name.starts_with('_'),
+        "c" | "cpp" => nested || !has_word(declaration, "static"),
+        _ => true,
+    }
+}
+
+/// The public symbols of a document worth looking up the references of. Entry points
+/// named `main` and the members of Rust trait implementations, which are called through
+/// the trait, are left out.
+pub fn candidates<'a>(
+    language_id: &str,
+    text: &str,
+    encoding: PositionEncoding,
+    symbols: &'a [DocumentSymbolNode],
+) -> Vec<&'a DocumentSymbolNode> {
+    fn visit<'a>(
+        language_id: &str,
+        text: &str,
+        encoding: PositionEncoding,
+        symbols: &'a [DocumentSymbolNode],
+        container: Option<&DocumentSymbolNode>,
+        found: &mut Vec<&'a DocumentSymbolNode>,
+    ) {
+        for symbol in symbols {
+            let start = text_utils::position_to_offset(text, symbol.range.start, encoding);
+            let name = text_utils::position_to_offset(text, symbol.selection_range.start, encoding);
+            let declaration = text.get(start..name.max(start)).unwrap_or_default();
+            let trait_impl = container.is_some_and(|container| {
+                language_id == "rust"
+                    && container.name.starts_with("impl ")
+                    && has_word(&container.name, "for")
+            });
+            if CHECKED_KINDS.contains(&symbol.kind)
+                && symbol.name != "main"
+                && !trait_impl
+                && is_public(language_id, declaration, &symbol.name, container.is_some())
+            {
+                found.push(symbol);
+            }
+            visit(
+                language_id,
+                text,
+                encoding,
+                &symbol.children,
+                Some(symbol),
+                found,
+            );
+        }
+    }
+
+    let mut found = Vec::new();
+    visit(language_id, text, encoding, symbols, None, &mut found);
+    found
+}
+
+/// Looks up the references of the public symbols of `files`, returning those referenced at
+/// most `threshold` times. Like `inventory::collect`, files are opened one at a time and
+/// files the server fails on are reported on stderr and left out.
+pub async fn find(
+    session: &Session,
+    files: &[PathBuf],
+    threshold: usize,
+) -> Result<Outcome, CliError> {
+    let mut unreferenced = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        let opened = match index {
+            0 => session.open(file).await,
+            _ => session.sync(file).await,
+        };
+        let uri = match opened {
+            Ok(uri) => uri,
+            Err(err) => {
+                eprintln!("skipping {}: {}", file.display(), err.message());
+                continue;
+            }
+        };
+        let result = async {
+            let symbols = session
+                .timed(session.server.document_symbols(uri.clone()))
+                .await?;
+            check_document(session, &uri, &symbols, threshold, &mut unreferenced).await
+        }
+        .await;
+        session.documents.close(&uri).await;
+        match result {
+            Ok(()) => {}
+            Err(err @ CliError::Client(ClientError::Unsupported(_))) => return Err(err),
+            Err(err) => eprintln!("skipping {}: {}", file.display(), err.message()),
+        }
+    }
+    Ok(Outcome::Unreferenced(unreferenced))
+}
+
+/// Looks up the references of the public ones among `symbols`, the symbols of the open
+/// document `uri`, adding those referenced at most `threshold` times to `unreferenced`.
+pub async fn check_document(
+    session: &Session,
+    uri: &Url,
+    symbols: &[DocumentSymbolNode],
+    threshold: usize,
+    unreferenced: &mut Vec<Unreferenced>,
+) -> Result<(), CliError> {
+    let text = session.text(uri).unwrap_or_default();
+    let language_id = session
+        .documents
+        .get(uri)
+        .map(|document| document.language_id)
+        .unwrap_or_default();
+    for symbol in candidates(&language_id, &text, session.encoding(), symbols) {
+        let references = session
+            .timed(
+                session
+                    .server
+                    .references(uri.clone(), symbol.selection_range.start, false),
+            )
+            .await?;
+        // recursive calls and servers reporting the declaration anyway do not count
+        let references = references
+            .iter()
+            .filter(|location| {
+                location.uri != *uri || !range_contains(&symbol.range, &location.range)
+            })
+            .count();
+        if references <= threshold {
+            unreferenced.push(Unreferenced {
+                uri: uri.clone(),
+                name: symbol.name.clone(),
+                kind: symbol.kind,
+                range: symbol.range,
+                selection_range: symbol.selection_range,
+                references,
+            });
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+/// How many unchanged lines surround each hunk.
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line {
+    /// The line at the two indices is the same in both texts.
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// Renders the changes from `old` to `new` as a unified diff with the given file names,
+/// e.g. `/dev/null` for a file that does not exist. Returns an empty string when the texts
+/// are the same.
+pub fn unified(old_name: &str, new_name: &str, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.split_inclusive('\n').collect();
+    let new: Vec<&str> = new.split_inclusive('\n').collect();
+    let script = diff_lines(&old, &new);
+    let mut out = String::new();
+    for hunk in hunks(&script) {
+        if out.is_empty() {
+            out.push_str(&format!("--- {}\n+++ {}\n", old_name, new_name));
+        }
+        let (mut old_start, mut old_len, mut new_start, mut new_len) = (None, 0, None, 0);
+        for line in hunk {
+            match *line {
+                Line::Same(i, j) => {
+                    old_start.get_or_insert(i);
+                    new_start.get_or_insert(j);
+                    old_len += 1;
+                    new_len += 1;
+                }
+                Line::Removed(i) => {
+                    old_start.get_or_insert(i);
+                    old_len += 1;
+                }
+                Line::Added(j) => {
+                    new_start.get_or_insert(j);
+                    new_len += 1;
+                }
+            }
+        }
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_len),
+            hunk_range(new_start, new_len)
+        ));
+        for line in hunk {
+            let (prefix, text) = match *line {
+                Line::Same(i, _) => (' ', old[i]),
+                Line::Removed(i) => ('-', old[i]),
+                Line::Added(j) => ('+', new[j]),
+            };
+            out.push(prefix);
+            out.push_str(text);
+            if !text.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+/// Formats one side of a hunk header. A side without lines, which only happens for an
+/// empty file since hunks carry context, is numbered 0.
+fn hunk_range(start: Option<usize>, len: usize) -> String {
+    let start = start.map_or(0, |start| start + 1);
+    match len {
+        1 => start.to_string(),
+        _ => format!("{},{}", start, len),
+    }
+}
+
+/// Splits an edit script into hunks of changes with up to `CONTEXT` unchanged lines around
+/// them, merging hunks whose context would overlap.
+fn hunks(script: &[Line]) -> Vec<&[Line]> {
+    let changes: Vec<usize> = script
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Same(..)))
+        .map(|(index, _)| index)
+        .collect();
+    let mut hunks = Vec::new();
+    let mut index = 0;
+    while index < changes.len() {
+        let start = changes[index].saturating_sub(CONTEXT);
+        let mut end = changes[index];
+        while index + 1 < changes.len() && changes[index + 1] - end <= 2 * CONTEXT {
+            index += 1;
+            end = changes[index];
+        }
+        hunks.push(&script[start..(end + 1 + CONTEXT).min(script.len())]);
+        index += 1;
+    }
+    hunks
+}
+
+/// Computes a shortest edit script turning `old` into `new` with Myers' algorithm.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Line> {
+    // common ends are cheap to skip and keep the search small
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let offset = n + m + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace = Vec::new();
+    'search: for d in 0..=n + m {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let index = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut middle = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let index = (k + offset) as usize;
+        let previous_k = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = v[(previous_k + offset) as usize];
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            middle.push(Line::Same(x as usize, y as usize));
+        }
+        if d > 0 {
+            if x == previous_x {
+                middle.push(Line::Added(previous_y as usize));
+            } else {
+                middle.push(Line::Removed(previous_x as usize));
+            }
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    middle.reverse();
+
+    let shift = |line: Line| match line {
+        Line::Same(i, j) => Line::Same(i + prefix, j + prefix),
+        Line::Removed(i) => Line::Removed(i + prefix),
+        Line::Added(j) => Line::Added(j + prefix),
+    };
+    let mut script: Vec<Line> = (0..prefix).map(|i| Line::Same(i, i)).collect();
+    script.extend(middle.into_iter().map(shift));
+    script.extend((0..suffix).map(|i| Line::Same(old.len() - suffix + i, new.len() - suffix + i)));
+    script
+}
@@ -0,0 +1,183 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::languages;
+use super::output::Outcome;
+use super::session::{Session, Timeouts, Trace};
+use super::CliError;
+use crate::lsp::client::ClientError;
+
+/// The result of one step of `doctor`, with a hint on how to fix it when it failed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn passed(name: &'static str, detail: String) -> Check {
+        Check {
+            name,
+            passed: true,
+            detail,
+            hint: None,
+        }
+    }
+
+    fn failed(name: &'static str, detail: String, hint: String) -> Check {
+        Check {
+            name,
+            passed: false,
+            detail,
+            hint: Some(hint),
+        }
+    }
+}
+
+/// Finds `program` the way the server is started: as a path when it has more than one
+/// component, on `PATH` otherwise.
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Explains why `initialize` failed and what to try.
+fn initialize_hint(err: &CliError, server: &[String]) -> String {
+    match err {
+        CliError::Client(ClientError::Json(_)) => {
+            "the answer is not an InitializeResult, the server may be too old or too new for \
+             this client, or not speak LSP on stdio"
+                .to_owned()
+        }
+        CliError::Client(ClientError::Server(_)) => {
+            "the server rejected initialize, check the initializationOptions of the config and \
+             that the server version matches its toolchain"
+                .to_owned()
+        }
+        CliError::Client(ClientError::Canceled) => format!(
+            "run `{}` by hand to see why, the error output of the server is not shown",
+            server.join(" ")
+        ),
+        _ => "check that the server speaks LSP on stdio, some need a flag such as --stdio, \
+              or raise --startup-timeout for a slow start"
+            .to_owned(),
+    }
+}
+
+/// Checks that the server can be used: that the root is a folder, the program exists,
+/// starts and answers `initialize` within the startup timeout, and then answers a request.
+/// Stops at the first check that fails.
+pub async fn run(
+    server: &[String],
+    root: &Path,
+    initialization_options: Option<Value>,
+    timeouts: Timeouts,
+    trace: Option<&Trace>,
+) -> Outcome {
+    let mut checks = Vec::new();
+    if !root.is_dir() {
+        checks.push(Check::failed(
+            "root",
+            format!("{} is not a folder", root.display()),
+            "pass --root, or fix the rootUri of the config".to_owned(),
+        ));
+        return Outcome::Checks(checks);
+    }
+    checks.push(Check::passed("root", root.display().to_string()));
+
+    let program = server.first().map(String::as_str).unwrap_or_default();
+    match find_program(program) {
+        Some(path) => checks.push(Check::passed("server", path.display().to_string())),
+        None => {
+            let hint = match languages::install_hint(program) {
+                Some(install) => format!("install it: {}", install),
+                None => {
+                    "install it, or point --server or the server of the config at it".to_owned()
+                }
+            };
+            checks.push(Check::failed(
+                "server",
+                format!("{} is not found", program),
+                hint,
+            ));
+            return Outcome::Checks(checks);
+        }
+    }
+
+    let started = Instant::now();
+    let session = match Session::start(
+        server,
+        root,
+        initialization_options,
+        timeouts,
+        trace,
+        None,
+        false,
+    )
+    .await
+    {
+        Ok(session) => session,
+        Err(err) => {
+            let hint = initialize_hint(&err, server);
+            checks.push(Check::failed("initialize", err.message(), hint));
+            return Outcome::Checks(checks);
+        }
+    };
+    let name = match session
+        .server
+        .initialize_result()
+        .and_then(|result| result.server_info)
+    {
+        Some(info) => match info.version {
+            Some(version) => format!("{} {}", info.name, version),
+            None => info.name,
+        },
+        None => "the server".to_owned(),
+    };
+    checks.push(Check::passed(
+        "initialize",
+        format!(
+            "{} answered in {:.2}s",
+            name,
+            started.elapsed().as_secs_f64()
+        ),
+    ));
+
+    // every server answers shutdown, and it leaves nothing running
+    let started = Instant::now();
+    match session.timed(session.server.shutdown()).await {
+        Ok(()) => checks.push(Check::passed(
+            "request",
+            format!(
+                "shutdown answered in {:.2}s",
+                started.elapsed().as_secs_f64()
+            ),
+        )),
+        Err(err) => checks.push(Check::failed(
+            "request",
+            err.message(),
+            "the server hangs after initializing, see what it sends with --trace-lsp".to_owned(),
+        )),
+    }
+    Outcome::Checks(checks)
+}
+
+/// Fails when one of the checks `doctor` ran failed.
+pub fn check(outcome: &Outcome) -> Result<(), CliError> {
+    match outcome {
+        Outcome::Checks(checks) if checks.iter().any(|check| !check.passed) => Err(
+            CliError::Failed("the server is not usable, see the hint above".to_owned()),
+        ),
+        _ => Ok(()),
+    }
+}
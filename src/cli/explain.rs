@@ -0,0 +1,62 @@
+//! Everything the server knows about the symbol at a position in one report, see
+//! `explain`: its hover, where it and its type are defined, how often it is used and what
+//! it is declared in.
+
+use lsp_types::{Hover, Location, Position, Url};
+
+use super::args::Target;
+use super::output::Outcome;
+use super::session::Session;
+use super::CliError;
+use crate::lsp::client::ClientError;
+use crate::lsp::navigation::GotoKind;
+use crate::lsp::symbols::{self, SymbolAtPoint};
+
+/// What `explain` found out about a position. What the server does not support is left
+/// empty.
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub uri: Url,
+    pub position: Position,
+    pub hover: Option<Hover>,
+    pub definitions: Vec<Location>,
+    pub type_definitions: Vec<Location>,
+    /// How many places use the symbol, its declaration left out, `None` when the server
+    /// cannot tell.
+    pub references: Option<usize>,
+    /// The innermost symbol around the position.
+    pub enclosing: Option<SymbolAtPoint>,
+}
+
+/// `result`, or `None` when the server does not support the request.
+fn supported<T>(result: Result<T, CliError>) -> Result<Option<T>, CliError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(CliError::Client(ClientError::Unsupported(_))) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Sends every request about `target` in `uri` at once and combines the answers.
+pub async fn run(session: &Session, uri: &Url, target: &Target) -> Result<Outcome, CliError> {
+    let (uri, position) = session.locate(uri, target).await?;
+    let server = &session.server;
+    let (hover, definitions, type_definitions, references, symbols) = tokio::join!(
+        session.timed(server.hover(uri.clone(), position)),
+        session.timed(server.goto(GotoKind::Definition, uri.clone(), position)),
+        session.timed(server.goto(GotoKind::TypeDefinition, uri.clone(), position)),
+        session.timed(server.references(uri.clone(), position, false)),
+        session.timed(server.document_symbols(uri.clone())),
+    );
+    let enclosing =
+        supported(symbols)?.and_then(|symbols| symbols::symbol_at_point(&symbols, position));
+    Ok(Outcome::Explanation(Explanation {
+        hover: supported(hover)?.flatten(),
+        definitions: supported(definitions)?.unwrap_or_default(),
+        type_definitions: supported(type_definitions)?.unwrap_or_default(),
+        references: supported(references)?.map(|references| references.len()),
+        enclosing,
+        uri,
+        position,
+    }))
+}
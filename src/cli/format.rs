@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use lsp_types::{FormattingOptions, Url};
+
+use super::output::Outcome;
+use super::session::Session;
+use super::CliError;
+use crate::lsp::client::ClientError;
+use crate::lsp::edits;
+
+/// A file the server formats differently than it is written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reformatted {
+    pub uri: Url,
+    pub before: String,
+    pub after: String,
+}
+
+/// The options formatting `text` is requested with: indented by tabs when its first
+/// indented line is, by four spaces otherwise. Servers usually prefer the project's own
+/// formatter config over these.
+pub fn formatting_options(text: &str) -> FormattingOptions {
+    let tabs = text
+        .lines()
+        .find(|line| line.starts_with([' ', '\t']))
+        .is_some_and(|line| line.starts_with('\t'));
+    FormattingOptions {
+        tab_size: 4,
+        insert_spaces: !tabs,
+        ..Default::default()
+    }
+}
+
+/// Formats `files` with the server, writing the result back unless `check` is set. The
+/// files are synced one at a time without waiting for the project to load, formatting
+/// only needs the file itself.
+///
+/// Like `inventory::collect`, files the server fails on are reported on stderr and left
+/// out; a server without document formatting fails the whole command.
+pub async fn run(session: &Session, files: &[PathBuf], check: bool) -> Result<Outcome, CliError> {
+    let mut reformatted = Vec::new();
+    for file in files {
+        let uri = match session.sync(file).await {
+            Ok(uri) => uri,
+            Err(err) => {
+                eprintln!("skipping {}: {}", file.display(), err.message());
+                continue;
+            }
+        };
+        let before = session.text(&uri).unwrap_or_default();
+        let formatted = session
+            .timed(
+                session
+                    .server
+                    .format_document(uri.clone(), formatting_options(&before)),
+            )
+            .await;
+        session.documents.close(&uri).await;
+        let after = match formatted {
+            Ok(edits) => edits::apply_text_edits(&before, &edits),
+            Err(err @ CliError::Client(ClientError::Unsupported(_))) => return Err(err),
+            Err(err) => {
+                eprintln!("skipping {}: {}", file.display(), err.message());
+                continue;
+            }
+        };
+        if after == before {
+            continue;
+        }
+        if !check {
+            tokio::fs::write(file, &after).await?;
+        }
+        reformatted.push(Reformatted { uri, before, after });
+    }
+    Ok(Outcome::Formatted {
+        files: reformatted,
+        written: !check,
+    })
+}
+
+/// Fails when `format --check` found files that are not formatted.
+pub fn check(outcome: &Outcome) -> Result<(), CliError> {
+    match outcome {
+        Outcome::Formatted {
+            files,
+            written: false,
+        } if !files.is_empty() => Err(CliError::Failed(format!(
+            "{} file{} would be reformatted",
+            files.len(),
+            if files.len() == 1 { "" } else { "s" }
+        ))),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,77 @@
+//! Highlights a file the way the server classifies it, see `highlight`: its semantic tokens
+//! color the source and its folding ranges make blocks collapsible, so the page shows what
+//! the server knows, such as which names are types, macros or mutable, rather than what a
+//! regex can guess.
+
+use std::path::Path;
+
+use lsp_types::{FoldingRange, Url};
+
+use super::output::Outcome;
+use super::results;
+use super::session::Session;
+use super::CliError;
+use crate::lsp::client::ClientError;
+use crate::lsp::semantic_tokens::DecodedSemanticToken;
+
+/// A file with the semantic tokens and folding ranges the server sent for it.
+#[derive(Debug, Clone)]
+pub struct Highlighted {
+    pub uri: Url,
+    /// The text the server classified.
+    pub text: String,
+    pub tokens: Vec<DecodedSemanticToken>,
+    pub folds: Vec<FoldingRange>,
+}
+
+/// Asks the server for the semantic tokens and folding ranges of `file`. A server without
+/// semantic tokens fails the command, one without folding ranges leaves the page without
+/// collapsible blocks.
+pub async fn run(session: &Session, file: &Path) -> Result<Outcome, CliError> {
+    let uri = session.open(file).await?;
+    let tokens = results::semantic_tokens(session, &uri).await?;
+    let folds = match session
+        .timed(session.server.folding_ranges(uri.clone()))
+        .await
+    {
+        Ok(folds) => folds,
+        Err(CliError::Client(ClientError::Unsupported(_))) => Vec::new(),
+        Err(err) => return Err(err),
+    };
+    let text = match session.documents.get(&uri) {
+        Some(document) => document.text,
+        None => std::fs::read_to_string(file)?,
+    };
+    Ok(Outcome::Highlighted(Highlighted {
+        uri,
+        text,
+        tokens,
+        folds,
+    }))
+}
+
+/// The folds that can be drawn as nested blocks, as `(start, end)` lines: those hiding at
+/// least one line, ending within the text and not crossing a fold kept before them. Of
+/// folds starting on the same line only the outermost is kept.
+pub fn nested_folds(folds: &[FoldingRange], lines: u32) -> Vec<(u32, u32)> {
+    let mut folds: Vec<(u32, u32)> = folds
+        .iter()
+        .map(|fold| (fold.start_line, fold.end_line.min(lines.saturating_sub(1))))
+        .filter(|(start, end)| start < end)
+        .collect();
+    folds.sort_by_key(|&(start, end)| (start, std::cmp::Reverse(end)));
+    let mut nested: Vec<(u32, u32)> = Vec::new();
+    let mut open: Vec<u32> = Vec::new();
+    for (start, end) in folds {
+        while open.last().is_some_and(|&last| last < start) {
+            open.pop();
+        }
+        let crosses = open.last().is_some_and(|&last| last < end);
+        let shares_start = nested.last().is_some_and(|&(last, _)| last == start);
+        if !crosses && !shares_start {
+            nested.push((start, end));
+            open.push(end);
+        }
+    }
+    nested
+}
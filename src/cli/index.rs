@@ -0,0 +1,106 @@
+//! Answers definition, references and hover from an index instead of a server, see
+//! `--index`: an LSIF dump such as `lsif` writes, or a SCIP index.
+
+use std::path::Path;
+
+use lsp_types::Url;
+
+use super::args::{Command, Query, Target};
+use super::output::{Outcome, OutputFormat, Printer, Style};
+use super::session::file_uri;
+use super::CliError;
+use crate::lsp::lsif::LsifIndex;
+use crate::lsp::scip;
+use crate::lsp::text_utils::{self, PositionEncoding};
+
+/// Reads the index at `path`, LSIF when it is JSON and SCIP otherwise. A dump made
+/// elsewhere, whose project root is not a folder here, is moved to `root`.
+pub fn load(path: &Path, root: &Path) -> Result<LsifIndex, CliError> {
+    let bytes = std::fs::read(path)?;
+    let index = match std::str::from_utf8(&bytes) {
+        Ok(dump) if dump.trim_start().starts_with(['{', '[']) => LsifIndex::parse(dump),
+        _ => scip::parse(&bytes),
+    };
+    let mut index = index
+        .map_err(|err| CliError::Usage(format!("{} is not an index: {}", path.display(), err)))?;
+    let here = index
+        .project_root
+        .to_file_path()
+        .is_ok_and(|root| root.is_dir());
+    if !here {
+        let root = Url::from_directory_path(root.canonicalize()?)
+            .map_err(|_| CliError::Usage(format!("{} is not a local folder", root.display())))?;
+        index.relocate(root);
+    }
+    Ok(index)
+}
+
+/// Runs `command` against the index at `path`, printing the answer in `format` and
+/// `style`.
+pub fn run(
+    path: &Path,
+    root: &Path,
+    command: &Command,
+    format: OutputFormat,
+    style: Style,
+) -> Result<(), CliError> {
+    let only =
+        || CliError::Usage("--index answers definition, references and hover only".to_owned());
+    let Command::Query(query) = command else {
+        return Err(only());
+    };
+    let (file, target) = match query {
+        Query::Definition { file, target }
+        | Query::References { file, target, .. }
+        | Query::Hover { file, target } => (file, target),
+        _ => return Err(only()),
+    };
+    let Target::At(at) = target else {
+        return Err(CliError::Usage(
+            "--symbol needs a server, pass <line>:<column> with --index".to_owned(),
+        ));
+    };
+    let index = load(path, root)?;
+    let uri = file_uri(file)?;
+    let text = std::fs::read_to_string(file)?;
+    let chars = lsp_types::Position::new(at.line - 1, at.column - 1);
+    let offset = text_utils::position_to_offset(&text, chars, PositionEncoding::Utf32);
+    let position = text_utils::offset_to_position(&text, offset, index.encoding);
+
+    let symbol = index.symbol_at(&uri, position);
+    let outcome = match query {
+        Query::Definition { .. } => Outcome::Locations(
+            symbol
+                .map(|symbol| symbol.definitions.clone())
+                .unwrap_or_default(),
+        ),
+        Query::References {
+            include_declaration,
+            ..
+        } => Outcome::Locations(
+            symbol
+                .map(|symbol| {
+                    let definitions = if *include_declaration {
+                        symbol.definitions.as_slice()
+                    } else {
+                        &[]
+                    };
+                    definitions
+                        .iter()
+                        .chain(&symbol.references)
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default(),
+        ),
+        _ => Outcome::Hover {
+            uri,
+            position,
+            hover: symbol.and_then(|symbol| symbol.hover.clone()),
+        },
+    };
+    let mut printer = Printer::new(index.encoding);
+    printer.style = style;
+    print!("{}", printer.render(format, &outcome)?);
+    Ok(())
+}
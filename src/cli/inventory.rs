@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+
+use super::languages;
+use super::output::Outcome;
+use super::session::Session;
+use super::CliError;
+use crate::lsp::client::ClientError;
+
+/// Directories holding dependencies and build output rather than sources, skipped when
+/// crawling the workspace along with hidden ones.
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "dist", "build", "__pycache__"];
+
+/// Lists the files below `root` that `server` handles, judged by their extensions, sorted
+/// by path. The extensions are those a config names, or else those of the languages the
+/// server is known for.
+pub fn workspace_files(
+    root: &Path,
+    server: &[String],
+    extensions: Option<&[String]>,
+) -> Result<Vec<PathBuf>, CliError> {
+    let program = server.first().map(String::as_str).unwrap_or_default();
+    let extensions: Vec<&str> = match extensions {
+        Some(extensions) => extensions.iter().map(String::as_str).collect(),
+        None => languages::server_extensions(program),
+    };
+    if extensions.is_empty() {
+        return Err(CliError::Usage(format!(
+            "cannot tell which files {} handles, pass them to dump-symbols",
+            program
+        )));
+    }
+    let mut files = Vec::new();
+    crawl(root, &extensions, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn crawl(dir: &Path, extensions: &[&str], files: &mut Vec<PathBuf>) -> Result<(), CliError> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if !SKIPPED_DIRS.contains(&name.as_ref()) {
+                crawl(&path, extensions, files)?;
+            }
+        } else if file_type.is_file()
+            && path
+                .extension()
+                .is_some_and(|extension| extensions.iter().any(|known| extension == *known))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Collects the symbols of `files`, opening each in turn and closing it again once its
+/// symbols are known. Only the first file waits for the server to load the project.
+///
+/// Files that cannot be read or that the server fails on are reported on stderr and left
+/// out; a server without document symbols fails the whole inventory.
+pub async fn collect(session: &Session, files: &[PathBuf]) -> Result<Outcome, CliError> {
+    let mut inventory = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        let opened = match index {
+            0 => session.open(file).await,
+            _ => session.sync(file).await,
+        };
+        let uri = match opened {
+            Ok(uri) => uri,
+            Err(err) => {
+                eprintln!("skipping {}: {}", file.display(), err.message());
+                continue;
+            }
+        };
+        let symbols = session
+            .timed(session.server.document_symbols(uri.clone()))
+            .await;
+        session.documents.close(&uri).await;
+        match symbols {
+            Ok(symbols) => inventory.push((uri, symbols)),
+            Err(err @ CliError::Client(ClientError::Unsupported(_))) => return Err(err),
+            Err(err) => eprintln!("skipping {}: {}", file.display(), err.message()),
+        }
+    }
+    Ok(Outcome::Inventory(inventory))
+}
@@ -0,0 +1,95 @@
+use std::path::Path;
+
+/// A language the CLI knows, with the server it starts for it by default.
+pub struct Language {
+    pub extensions: &'static [&'static str],
+    pub id: &'static str,
+    pub server: &'static [&'static str],
+}
+
+const TYPESCRIPT_SERVER: &[&str] = &["typescript-language-server", "--stdio"];
+
+pub const LANGUAGES: &[Language] = &[
+    Language {
+        extensions: &["ts", "mts", "cts"],
+        id: "typescript",
+        server: TYPESCRIPT_SERVER,
+    },
+    Language {
+        extensions: &["tsx"],
+        id: "typescriptreact",
+        server: TYPESCRIPT_SERVER,
+    },
+    Language {
+        extensions: &["js", "mjs", "cjs"],
+        id: "javascript",
+        server: TYPESCRIPT_SERVER,
+    },
+    Language {
+        extensions: &["jsx"],
+        id: "javascriptreact",
+        server: TYPESCRIPT_SERVER,
+    },
+    Language {
+        extensions: &["rs"],
+        id: "rust",
+        server: &["rust-analyzer"],
+    },
+    Language {
+        extensions: &["c", "h"],
+        id: "c",
+        server: &["clangd"],
+    },
+    Language {
+        extensions: &["cc", "cpp", "cxx", "hh", "hpp", "hxx"],
+        id: "cpp",
+        server: &["clangd"],
+    },
+    Language {
+        extensions: &["go"],
+        id: "go",
+        server: &["gopls"],
+    },
+    Language {
+        extensions: &["py"],
+        id: "python",
+        server: &["pylsp"],
+    },
+];
+
+/// How to install the default servers, by program.
+const INSTALL: &[(&str, &str)] = &[
+    (
+        "typescript-language-server",
+        "npm install -g typescript-language-server typescript",
+    ),
+    ("rust-analyzer", "rustup component add rust-analyzer"),
+    ("clangd", "install clangd with your package manager or LLVM"),
+    ("gopls", "go install golang.org/x/tools/gopls@latest"),
+    ("pylsp", "pip install python-lsp-server"),
+];
+
+/// How to install `program`, if it is one of the default servers.
+pub fn install_hint(program: &str) -> Option<&'static str> {
+    INSTALL
+        .iter()
+        .find(|(known, _)| *known == program)
+        .map(|(_, hint)| *hint)
+}
+
+/// The extensions of the files of the languages `program` is the default server of.
+pub fn server_extensions(program: &str) -> Vec<&'static str> {
+    LANGUAGES
+        .iter()
+        .filter(|language| language.server.first() == Some(&program))
+        .flat_map(|language| language.extensions.iter().copied())
+        .collect()
+}
+
+/// Looks up the language of a file by its extension.
+pub fn language_of(path: &Path) -> Option<&'static Language> {
+    let extension = path.extension()?.to_str()?;
+    LANGUAGES
+        .iter()
+        .find(|language| language.extensions.contains(&extension))
+}
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use lsp_types::Url;
+
+use super::languages;
+use super::output::Outcome;
+use super::session::{file_uri, Session};
+use super::CliError;
+use crate::lsp::client::ClientError;
+use crate::lsp::lsif::LsifIndex;
+
+/// Indexes the symbols of `files` for an LSIF dump, opening each in turn and closing it
+/// again once its symbols are indexed. Only the first file waits for the server to load
+/// the project. Symbols defined where an indexed symbol is, e.g. a declaration and its
+/// definition in C, are indexed once.
+///
+/// Like `inventory::collect`, files the server fails on are reported on stderr and left
+/// out; a server without document symbols fails the whole index.
+pub async fn index(session: &Session, files: &[PathBuf]) -> Result<Outcome, CliError> {
+    let root = Url::from_directory_path(&session.root).map_err(|_| {
+        CliError::Usage(format!("{} is not a local folder", session.root.display()))
+    })?;
+    let mut index = LsifIndex::new(root);
+    for file in files {
+        if let Ok(uri) = file_uri(file) {
+            let language_id =
+                languages::language_of(file).map_or("plaintext", |language| language.id);
+            index.add_document(uri, language_id);
+        }
+    }
+    for (position, file) in files.iter().enumerate() {
+        let opened = match position {
+            0 => session.open(file).await,
+            _ => session.sync(file).await,
+        };
+        let uri = match opened {
+            Ok(uri) => uri,
+            Err(err) => {
+                eprintln!("skipping {}: {}", file.display(), err.message());
+                continue;
+            }
+        };
+        let result = index_document(session, &uri, &mut index).await;
+        session.documents.close(&uri).await;
+        match result {
+            Ok(()) => {}
+            Err(err @ CliError::Client(ClientError::Unsupported(_))) => return Err(err),
+            Err(err) => eprintln!("skipping {}: {}", file.display(), err.message()),
+        }
+    }
+    Ok(Outcome::Lsif(index))
+}
+
+async fn index_document(
+    session: &Session,
+    uri: &Url,
+    index: &mut LsifIndex,
+) -> Result<(), CliError> {
+    let symbols = session
+        .timed(session.server.document_symbols(uri.clone()))
+        .await?;
+    let mut indexed = Vec::new();
+    for root in &symbols {
+        for symbol in root.descendants() {
+            let symbol = session
+                .timed(session.server.index_symbol(uri, symbol))
+                .await?;
+            let known = index
+                .symbols
+                .iter()
+                .chain(&indexed)
+                .any(|other| other.definitions == symbol.definitions);
+            if !known {
+                indexed.push(symbol);
+            }
+        }
+    }
+    index.symbols.extend(indexed);
+    Ok(())
+}
@@ -0,0 +1,626 @@
+//! The `lsp-client` command line tool: starts a language server for a file and runs a
+//! single query against it.
+
+pub mod actions;
+pub mod args;
+pub mod bench;
+pub mod bridge;
+pub mod cache;
+pub mod changes;
+pub mod completions;
+pub mod config;
+pub mod daemon;
+pub mod dead_code;
+pub mod diff;
+pub mod doctor;
+pub mod explain;
+pub mod format;
+pub mod highlight;
+pub mod index;
+pub mod inventory;
+pub mod languages;
+pub mod lsif;
+pub mod output;
+pub mod progress;
+pub mod proxy;
+pub mod record;
+pub mod repl;
+pub mod report;
+pub mod results;
+pub mod routing;
+pub mod schema;
+pub mod script;
+pub mod session;
+pub mod watch;
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use crate::lsp::client::ClientError;
+use crate::lsp::completion;
+use crate::lsp::edits::{self, EditError};
+use crate::lsp::navigation::GotoKind;
+use crate::lsp::text_utils::PositionEncoding;
+use args::{Command, Invocation, Options, Query};
+use config::{Config, Settings};
+use output::{Outcome, OutputFormat, Printer};
+use results::ResultCache;
+use script::Step;
+use session::Session;
+
+#[derive(Debug)]
+pub enum CliError {
+    /// The arguments are invalid.
+    Usage(String),
+    /// The config file could not be read.
+    Config {
+        path: PathBuf,
+        message: String,
+    },
+    /// The language server could not be started, or refused the query.
+    Server(String),
+    /// The command ran, but did not succeed.
+    Failed(String),
+    Io(io::Error),
+    Client(ClientError),
+    Edit(EditError),
+}
+
+impl CliError {
+    /// The exit status reported for the error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Usage(_) | CliError::Config { .. } => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl CliError {
+    /// The error without the usage text, for errors reported while running commands.
+    pub fn message(&self) -> String {
+        match self {
+            CliError::Usage(message) => message.clone(),
+            err => err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}\n\n{}", message, args::USAGE),
+            CliError::Config { path, message } => write!(f, "{}: {}", path.display(), message),
+            CliError::Server(message) | CliError::Failed(message) => f.write_str(message),
+            CliError::Io(err) => write!(f, "{}", err),
+            CliError::Client(ClientError::Unsupported(method)) => {
+                write!(f, "the server does not support {}", method)
+            }
+            CliError::Client(ClientError::Canceled) => {
+                f.write_str("the server exited before answering")
+            }
+            CliError::Client(ClientError::Timeout) => {
+                f.write_str("the server did not answer in time")
+            }
+            CliError::Client(ClientError::ClientClosed) => {
+                f.write_str("the client was closed before the server answered")
+            }
+            CliError::Client(err) => write!(f, "request failed: {:?}", err),
+            CliError::Edit(err) => write!(f, "applying the edit failed: {:?}", err),
+        }
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> CliError {
+        CliError::Io(err)
+    }
+}
+
+impl From<ClientError> for CliError {
+    fn from(err: ClientError) -> CliError {
+        CliError::Client(err)
+    }
+}
+
+impl From<EditError> for CliError {
+    fn from(err: EditError) -> CliError {
+        CliError::Edit(err)
+    }
+}
+
+/// Runs the command line given by `args`, which excludes the program name, printing the
+/// result to stdout.
+pub async fn run(args: &[String]) -> Result<(), CliError> {
+    let Invocation {
+        mut options,
+        mut command,
+    } = args::parse_over(Options::from_env(std::env::vars())?, args)?;
+    match &command {
+        Command::ServeRecording { recording } => return record::serve(recording).await,
+        Command::Completions { shell } => {
+            print!("{}", completions::script(*shell));
+            return Ok(());
+        }
+        Command::Man => {
+            print!("{}", completions::man_page());
+            return Ok(());
+        }
+        Command::ConfigSchema => {
+            println!("{:#}", schema::config_schema());
+            return Ok(());
+        }
+        Command::Daemon => {
+            let socket = options.socket.unwrap_or_else(daemon::default_socket);
+            return daemon::serve(&socket, options.timeouts, options.trace).await;
+        }
+        Command::Replay { recording } => {
+            Invocation { options, command } = record::replay(recording, options)?;
+        }
+        _ => {}
+    }
+    let mut configs = match options.config.as_slice() {
+        [] => Config::find(&std::env::current_dir()?)?
+            .into_iter()
+            .collect(),
+        paths => paths
+            .iter()
+            .map(|path| Config::load(path))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    if configs.len() > 1 {
+        return routing::run(options, configs, command).await;
+    }
+    let settings = Settings::new(options, configs.pop())?;
+    run_with(settings, &command).await
+}
+
+/// Fails when `format` cannot render the results of `command`.
+fn check_output(command: &Command, format: OutputFormat) -> Result<(), CliError> {
+    match (command, format) {
+        (
+            Command::Query(Query::Diagnostics { .. }) | Command::DiagnosticsReport { .. },
+            OutputFormat::Sarif,
+        ) => Ok(()),
+        (
+            Command::Query(_)
+            | Command::DumpSymbols { .. }
+            | Command::DeadCode { .. }
+            | Command::Report { .. }
+            | Command::Format { .. }
+            | Command::Lsif { .. }
+            | Command::Index { .. }
+            | Command::ApplyAction { .. }
+            | Command::Bench { .. }
+            | Command::Highlight { .. }
+            | Command::Capabilities { .. }
+            | Command::Doctor { .. },
+            OutputFormat::Sarif,
+        ) => Err(CliError::Usage(
+            "--output sarif is only supported by diagnostics".to_owned(),
+        )),
+        (Command::DumpSymbols { .. }, OutputFormat::Csv) => Ok(()),
+        (_, OutputFormat::Csv) => Err(CliError::Usage(
+            "--output csv is only supported by dump-symbols".to_owned(),
+        )),
+        (Command::Query(Query::CallGraph { .. }), OutputFormat::Dot) => Ok(()),
+        (_, OutputFormat::Dot) => Err(CliError::Usage(
+            "--output dot is only supported by callgraph".to_owned(),
+        )),
+        (
+            Command::Query(
+                Query::Definition { .. } | Query::References { .. } | Query::Diagnostics { .. },
+            )
+            | Command::DiagnosticsReport { .. },
+            OutputFormat::VimQuickfix | OutputFormat::Emacs,
+        ) => Ok(()),
+        (_, OutputFormat::VimQuickfix) => Err(CliError::Usage(
+            "--output vim-quickfix is only supported by definition, references and diagnostics"
+                .to_owned(),
+        )),
+        (_, OutputFormat::Emacs) => Err(CliError::Usage(
+            "--output emacs is only supported by definition, references and diagnostics".to_owned(),
+        )),
+        (Command::Report { .. } | Command::Highlight { .. }, OutputFormat::Html) => Ok(()),
+        (_, OutputFormat::Html) => Err(CliError::Usage(
+            "--output html is only supported by report and highlight".to_owned(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The command starting the server: the one configured, or else the one known for the
+/// language of `file`.
+fn server_for(server: Option<Vec<String>>, file: Option<&Path>) -> Result<Vec<String>, CliError> {
+    if let Some(server) = server {
+        return Ok(server);
+    }
+    let file = file.ok_or_else(|| {
+        CliError::Usage("pass --server or a file to pick the server by".to_owned())
+    })?;
+    languages::language_of(file)
+        .map(|language| language.server.iter().map(|arg| arg.to_string()).collect())
+        .ok_or_else(|| {
+            CliError::Usage(format!(
+                "no language server known for {}, pass --server",
+                file.display()
+            ))
+        })
+}
+
+/// Runs `command` with a single server, started as `settings` say.
+async fn run_with(settings: Settings, command: &Command) -> Result<(), CliError> {
+    check_output(command, settings.output)?;
+    let steps = match command {
+        Command::Run { script } => script::parse_script(&std::fs::read_to_string(script)?)?,
+        _ => Vec::new(),
+    };
+    let file = match command {
+        Command::Run { .. } => steps.iter().find_map(Step::file),
+        command => command.file(),
+    };
+    if let Some(index) = &settings.index {
+        let root = match &settings.root {
+            Some(root) => root.clone(),
+            None => std::env::current_dir()?,
+        };
+        return index::run(index, &root, command, settings.output, settings.style);
+    }
+    let server = server_for(settings.server, file)?;
+    if let Command::Proxy(proxy) = command {
+        // the editor on the other end initializes the server
+        return proxy::run(&server, proxy).await;
+    }
+    if let Command::Bridge(proxy) = command {
+        let mut proxy = proxy.clone();
+        proxy.transforms.initialization_options = settings.initialization_options;
+        return proxy::run(&server, &proxy).await;
+    }
+    let root = match settings.root {
+        Some(root) => root,
+        None => std::env::current_dir()?,
+    };
+    match (command, &settings.daemon) {
+        // the traffic is only traced and recorded with a server of our own
+        (Command::Query(query), Some(socket))
+            if settings.trace.is_none()
+                && settings.record.is_none()
+                && !matches!(query, Query::Diagnostics { watch: true, .. }) =>
+        {
+            let base = std::env::current_dir()?;
+            let request = daemon::Request {
+                server: server.clone(),
+                root: root.canonicalize()?,
+                initialization_options: settings.initialization_options.clone(),
+                query: query.clone().rebased(&base),
+                output: settings.output,
+                style: settings.style,
+                base,
+            };
+            if let Some(response) = daemon::ask(socket, &request).await {
+                let response = response?;
+                print!("{}", response.output);
+                return response.result();
+            }
+        }
+        _ => {}
+    }
+    if let Command::Doctor { .. } = command {
+        // the checks start the server themselves, step by step
+        let outcome = doctor::run(
+            &server,
+            &root,
+            settings.initialization_options,
+            settings.timeouts,
+            settings.trace.as_ref(),
+        )
+        .await;
+        print!(
+            "{}",
+            Printer::new(PositionEncoding::default()).render(settings.output, &outcome)?
+        );
+        return doctor::check(&outcome);
+    }
+
+    let mut session = Session::start(
+        &server,
+        &root,
+        settings.initialization_options,
+        settings.timeouts,
+        settings.trace.as_ref(),
+        settings.record.as_ref(),
+        settings.progress,
+    )
+    .await?;
+    session.style = settings.style;
+    if settings.cache {
+        let extensions = settings.extensions.as_deref();
+        session.results = Some(ResultCache::new(&root, &server, extensions));
+    }
+    let result = match command {
+        Command::Query(Query::Diagnostics {
+            file, watch: true, ..
+        }) => watch::run(&session, file, settings.output).await,
+        Command::Query(query) => match answer(&session, query, settings.output).await {
+            Ok((output, outcome)) => {
+                print!("{}", output);
+                check_severity(query, &outcome)
+            }
+            Err(err) => Err(err),
+        },
+        Command::Repl { file } => repl::run(&session, file.as_deref(), settings.output).await,
+        Command::Run { .. } => script::run(&session, &steps, settings.output).await,
+        Command::DumpSymbols { files }
+        | Command::DeadCode { files, .. }
+        | Command::Report { files, .. }
+        | Command::Format { files, .. }
+        | Command::DiagnosticsReport { files, .. }
+        | Command::Lsif { files }
+        | Command::Index { files } => {
+            let files = match files.as_slice() {
+                [] => inventory::workspace_files(&root, &server, settings.extensions.as_deref()),
+                files => Ok(files.to_vec()),
+            };
+            match files {
+                Ok(files) => gather(&session, command, &files).await.and_then(|outcome| {
+                    print(&session, settings.output, &outcome)?;
+                    check_gathered(command, &outcome)
+                }),
+                Err(err) => Err(err),
+            }
+        }
+        Command::ApplyAction { file, range, id } => {
+            actions::apply(&session, file, *range, id, settings.output).await
+        }
+        Command::Bench {
+            file,
+            positions,
+            methods,
+            iterations,
+        } => bench::run(&session, file, positions, methods, *iterations)
+            .await
+            .and_then(|outcome| print(&session, settings.output, &outcome)),
+        Command::Highlight { file } => highlight::run(&session, file)
+            .await
+            .and_then(|outcome| print(&session, settings.output, &outcome)),
+        Command::Capabilities { .. } => {
+            let outcome = Outcome::Capabilities {
+                server_info: session
+                    .server
+                    .initialize_result()
+                    .and_then(|result| result.server_info),
+                capabilities: Box::new(session.server.capabilities()),
+            };
+            print(&session, settings.output, &outcome)
+        }
+        Command::Proxy(_) | Command::Bridge(_) | Command::Doctor { .. } => {
+            unreachable!("the proxy, bridge and doctor start the server themselves")
+        }
+        Command::Replay { .. }
+        | Command::ServeRecording { .. }
+        | Command::Completions { .. }
+        | Command::Man
+        | Command::ConfigSchema
+        | Command::Daemon => {
+            unreachable!(
+                "replays, completions, the man page, the schema and the daemon are handled by run"
+            )
+        }
+    };
+    session.finish().await;
+    result
+}
+
+/// Runs a command about many files, all handled by the server of `session`.
+async fn gather(
+    session: &Session,
+    command: &Command,
+    files: &[PathBuf],
+) -> Result<Outcome, CliError> {
+    match command {
+        Command::DumpSymbols { .. } => inventory::collect(session, files).await,
+        Command::DeadCode { threshold, .. } => dead_code::find(session, files, *threshold).await,
+        Command::Report { threshold, .. } => report::analyze(session, files, *threshold).await,
+        Command::Format { check, .. } => format::run(session, files, *check).await,
+        Command::DiagnosticsReport { changed_since, .. } => {
+            report::collect(session, files, changed_since.as_deref()).await
+        }
+        Command::Lsif { .. } => lsif::index(session, files).await,
+        Command::Index { .. } => cache::build(session, files).await,
+        command => unreachable!("{:?} is not about many files", command),
+    }
+}
+
+/// Fails when what a command about many files found calls for it: files `format --check`
+/// would change, or diagnostics at or above `--fail-on`.
+fn check_gathered(command: &Command, outcome: &Outcome) -> Result<(), CliError> {
+    match (command, outcome) {
+        (Command::Format { .. }, outcome) => format::check(outcome),
+        (
+            Command::DiagnosticsReport {
+                fail_on: Some(fail_on),
+                ..
+            },
+            Outcome::DiagnosticsReport(files),
+        ) => check_diagnostics(
+            files.iter().flat_map(|(_, diagnostics)| diagnostics),
+            *fail_on,
+        ),
+        _ => Ok(()),
+    }
+}
+
+fn print(session: &Session, format: OutputFormat, outcome: &Outcome) -> Result<(), CliError> {
+    print!("{}", session.printer().render(format, outcome)?);
+    Ok(())
+}
+
+/// Answers `query`, rendering the result in `format`. Returns the output along with the
+/// outcome it shows. A rename is applied to disk after rendering when the query asks for it.
+pub async fn answer(
+    session: &Session,
+    query: &Query,
+    format: OutputFormat,
+) -> Result<(String, Outcome), CliError> {
+    let outcome = execute(session, query).await?;
+    // rendered before applying, the positions refer to the original text
+    let output = session.printer().render(format, &outcome)?;
+    if let (Query::Rename { apply: true, .. }, Outcome::Edit(edit)) = (query, &outcome) {
+        edits::apply_workspace_edit_to_disk(edit).await?;
+    }
+    Ok((output, outcome))
+}
+
+/// Fails when a diagnostics query with `--fail-on` found diagnostics of that severity or
+/// worse.
+pub fn check_severity(query: &Query, outcome: &Outcome) -> Result<(), CliError> {
+    let (
+        Query::Diagnostics {
+            fail_on: Some(fail_on),
+            ..
+        },
+        Outcome::Diagnostics { diagnostics, .. },
+    ) = (query, outcome)
+    else {
+        return Ok(());
+    };
+    check_diagnostics(diagnostics, *fail_on)
+}
+
+/// Fails when `diagnostics` include any of severity `fail_on` or worse.
+fn check_diagnostics<'a>(
+    diagnostics: impl IntoIterator<Item = &'a Diagnostic>,
+    fail_on: DiagnosticSeverity,
+) -> Result<(), CliError> {
+    let threshold = output::severity_rank(Some(fail_on));
+    let failing = diagnostics
+        .into_iter()
+        .filter(|diagnostic| output::severity_rank(diagnostic.severity) <= threshold)
+        .count();
+    match failing {
+        0 => Ok(()),
+        failing => Err(CliError::Failed(format!(
+            "{} diagnostic{} of severity {} or worse",
+            failing,
+            if failing == 1 { "" } else { "s" },
+            output::severity_name(Some(fail_on))
+        ))),
+    }
+}
+
+/// Runs `query` against the server.
+async fn execute(session: &Session, query: &Query) -> Result<Outcome, CliError> {
+    let uri = session.open(query.file()).await?;
+    let outcome = match query {
+        Query::Definition { target, .. } => {
+            let (uri, position) = session.locate(&uri, target).await?;
+            Outcome::Locations(
+                session
+                    .timed(session.server.goto(GotoKind::Definition, uri, position))
+                    .await?,
+            )
+        }
+        Query::References {
+            target,
+            include_declaration,
+            ..
+        } => {
+            let (uri, position) = session.locate(&uri, target).await?;
+            Outcome::Locations(
+                session
+                    .timed(
+                        session
+                            .server
+                            .references(uri, position, *include_declaration),
+                    )
+                    .await?,
+            )
+        }
+        Query::Hover { target, .. } => {
+            let (uri, position) = session.locate(&uri, target).await?;
+            let hover = session
+                .timed(session.server.hover(uri.clone(), position))
+                .await?;
+            Outcome::Hover {
+                uri,
+                position,
+                hover,
+            }
+        }
+        Query::Explain { target, .. } => explain::run(session, &uri, target).await?,
+        Query::Symbols { .. } => Outcome::Symbols {
+            symbols: session
+                .timed(session.server.document_symbols(uri.clone()))
+                .await?,
+            uri,
+        },
+        Query::Diagnostics {
+            file,
+            changed_since,
+            ..
+        } => {
+            let mut diagnostics = session.current_diagnostics(&uri, 0).await?;
+            if let Some(base) = changed_since {
+                changes::since(file, base)?.retain(&mut diagnostics);
+            }
+            Outcome::Diagnostics { uri, diagnostics }
+        }
+        Query::Rename {
+            target, new_name, ..
+        } => {
+            let (uri, position) = session.locate(&uri, target).await?;
+            let edit = session
+                .timed(session.server.rename(uri, position, new_name))
+                .await?
+                .ok_or_else(|| {
+                    CliError::Server("the symbol at the position cannot be renamed".to_owned())
+                })?;
+            Outcome::Edit(edit)
+        }
+        Query::Complete { at, resolve, .. } => {
+            let position = session.position(&uri, *at);
+            let mut list = session
+                .timed(session.server.completions(uri.clone(), position, None))
+                .await?;
+            if *resolve {
+                let items = session.server.resolve_completion_items(list.items);
+                list.items = session.timed(async { Ok(items.await) }).await?;
+            }
+            completion::sort_completion_items(&mut list.items);
+            Outcome::Completions {
+                uri,
+                position,
+                list,
+            }
+        }
+        Query::Actions { range, .. } => {
+            let range = actions::range(session, &uri, *range);
+            Outcome::Actions {
+                actions: actions::list(session, &uri, range).await?,
+                uri,
+                range,
+            }
+        }
+        Query::CallGraph {
+            target,
+            direction,
+            depth,
+            ..
+        } => {
+            let (uri, position) = session.locate(&uri, target).await?;
+            let graph = session
+                .timed(session.server.call_graph(uri, position, *direction, *depth))
+                .await?;
+            if graph.nodes.is_empty() {
+                return Err(CliError::Failed(
+                    "there is no function, method or constructor at the position".to_owned(),
+                ));
+            }
+            Outcome::CallGraph(graph)
+        }
+    };
+    Ok(outcome)
+}
@@ -0,0 +1,2218 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use lsp_types::{
+    CallHierarchyItem, CodeActionOrCommand, CompletionItem, CompletionItemKind, CompletionItemTag,
+    CompletionList, CompletionTextEdit, Diagnostic, DiagnosticSeverity, Hover, Location,
+    NumberOrString, Position, Range, ResourceOp, ServerInfo, SymbolKind, TextDocumentSyncKind,
+    TextEdit, Url, WorkspaceEdit,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::actions;
+use super::bench::Latencies;
+use super::cache::CacheSummary;
+use super::dead_code::Unreferenced;
+use super::diff;
+use super::doctor::Check;
+use super::explain::Explanation;
+use super::format::Reformatted;
+use super::highlight::{self, Highlighted};
+use super::report::FileAnalysis;
+use super::CliError;
+use crate::lsp::call_hierarchy::CallGraph;
+use crate::lsp::edits::{self, WorkspaceEditStep};
+use crate::lsp::lsif::LsifIndex;
+use crate::lsp::markup;
+use crate::lsp::semantic_tokens::DecodedSemanticToken;
+use crate::lsp::server_capabilities::Capabilities;
+use crate::lsp::symbols::DocumentSymbolNode;
+use crate::lsp::text_utils::{self, PositionEncoding};
+
+/// How results are printed, see `--output`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Human readable text.
+    #[default]
+    Text,
+    /// One `path:line:column: message` line per result, as grep and quickfix lists use.
+    Plain,
+    /// A single JSON document, see `Printer::json`.
+    Json,
+    /// A SARIF 2.1 log, for diagnostics only.
+    Sarif,
+    /// Comma separated values with a header row, for the symbol inventory only.
+    Csv,
+    /// A Graphviz digraph, for call graphs only.
+    Dot,
+    /// `path:line:column: message` lines with columns in bytes, as Vim's quickfix list
+    /// reads them, for locations and diagnostics only.
+    #[serde(rename = "vim-quickfix")]
+    VimQuickfix,
+    /// GNU style `path:line.column-column: message` lines, as Emacs' compilation mode
+    /// reads them, for locations and diagnostics only.
+    Emacs,
+    /// A self-contained HTML page, for `report` only.
+    Html,
+}
+
+impl OutputFormat {
+    /// The names `parse` takes.
+    pub const NAMES: &'static [&'static str] = &[
+        "text",
+        "plain",
+        "json",
+        "sarif",
+        "csv",
+        "dot",
+        "vim-quickfix",
+        "emacs",
+        "html",
+    ];
+
+    pub fn parse(arg: &str) -> Result<OutputFormat, CliError> {
+        match arg {
+            "text" => Ok(OutputFormat::Text),
+            "plain" => Ok(OutputFormat::Plain),
+            "json" => Ok(OutputFormat::Json),
+            "sarif" => Ok(OutputFormat::Sarif),
+            "csv" => Ok(OutputFormat::Csv),
+            "dot" => Ok(OutputFormat::Dot),
+            "vim-quickfix" => Ok(OutputFormat::VimQuickfix),
+            "emacs" => Ok(OutputFormat::Emacs),
+            "html" => Ok(OutputFormat::Html),
+            _ => Err(CliError::Usage(format!("unknown output format {}", arg))),
+        }
+    }
+}
+
+/// When text output is colored, see `--color`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// When printing to a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// The names `parse` takes.
+    pub const NAMES: &'static [&'static str] = &["auto", "always", "never"];
+
+    pub fn parse(arg: &str) -> Result<ColorChoice, CliError> {
+        match arg {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(CliError::Usage(format!(
+                "--color expects auto, always or never, not {:?}",
+                arg
+            ))),
+        }
+    }
+
+    /// Whether to color what is printed to stdout.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// How text output looks, see `--color` and `--context`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Style {
+    /// Colors the output with ANSI escapes.
+    pub color: bool,
+    /// Shows the source of locations, hovers and diagnostics with this many lines around
+    /// them, nothing when `None`.
+    pub context: Option<usize>,
+}
+
+/// The result of a command, before it is rendered.
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    Locations(Vec<Location>),
+    /// The hover of the symbol at `position`, if any.
+    Hover {
+        uri: Url,
+        position: Position,
+        hover: Option<Hover>,
+    },
+    Symbols {
+        uri: Url,
+        symbols: Vec<DocumentSymbolNode>,
+    },
+    Diagnostics {
+        uri: Url,
+        diagnostics: Vec<Diagnostic>,
+    },
+    Edit(WorkspaceEdit),
+    /// The diagnostics of many documents, see `report`.
+    DiagnosticsReport(Vec<(Url, Vec<Diagnostic>)>),
+    /// The completions at `position`, in the order they are shown.
+    Completions {
+        uri: Url,
+        position: Position,
+        list: CompletionList,
+    },
+    /// The code actions for `range`, numbered from 1 in the order the server sent them.
+    Actions {
+        uri: Url,
+        range: Range,
+        actions: Vec<CodeActionOrCommand>,
+    },
+    /// The symbols of many documents, see `dump-symbols`.
+    Inventory(Vec<(Url, Vec<DocumentSymbolNode>)>),
+    CallGraph(CallGraph),
+    /// The public symbols used at most `--threshold` times, see `dead-code`.
+    Unreferenced(Vec<Unreferenced>),
+    /// The files `format` changed, or would change when they were not `written`.
+    Formatted {
+        files: Vec<Reformatted>,
+        written: bool,
+    },
+    /// What the server announced and registered, see `capabilities`.
+    Capabilities {
+        server_info: Option<ServerInfo>,
+        capabilities: Box<Capabilities>,
+    },
+    /// The checks `doctor` ran, up to the first that failed.
+    Checks(Vec<Check>),
+    /// An index of the workspace, see `lsif`.
+    Lsif(LsifIndex),
+    /// The diagnostics, symbols and unused symbols of many documents, see `report`.
+    Analysis(Vec<FileAnalysis>),
+    /// What `index` cached, per workspace.
+    Cached(Vec<CacheSummary>),
+    /// How long the server took to answer, per method, see `bench`.
+    Bench(Vec<Latencies>),
+    /// A file with the server's classification of its text, see `highlight`.
+    Highlighted(Highlighted),
+    /// Everything about the symbol at a position, see `explain`.
+    Explanation(Explanation),
+}
+
+/// A position as users give it, counting lines and characters from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Point {
+    pub line: u32,
+    pub column: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub start: Point,
+    pub end: Point,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonLocation {
+    uri: Url,
+    path: Option<PathBuf>,
+    range: Span,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonSymbol {
+    name: String,
+    kind: String,
+    detail: Option<String>,
+    range: Span,
+    selection_range: Span,
+    children: Vec<JsonSymbol>,
+}
+
+/// A symbol of the inventory, with the names of the symbols containing it, outermost
+/// first.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InventorySymbol {
+    uri: Url,
+    path: Option<PathBuf>,
+    name: String,
+    kind: String,
+    container: Vec<String>,
+    detail: Option<String>,
+    range: Span,
+    selection_range: Span,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonCallNode {
+    name: String,
+    kind: String,
+    detail: Option<String>,
+    uri: Url,
+    path: Option<PathBuf>,
+    range: Span,
+    selection_range: Span,
+    /// Whether the graph was expanded from this node.
+    root: bool,
+}
+
+/// A call between two nodes, given by their index, with the call sites in the caller.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonCallEdge {
+    caller: usize,
+    callee: usize,
+    call_sites: Vec<Span>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonUnreferenced {
+    uri: Url,
+    path: Option<PathBuf>,
+    name: String,
+    kind: String,
+    range: Span,
+    selection_range: Span,
+    references: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonCompletion {
+    label: String,
+    kind: Option<String>,
+    detail: Option<String>,
+    /// As markdown.
+    documentation: Option<String>,
+    /// What accepting the completion inserts, the label unless the server says otherwise.
+    insert_text: String,
+    deprecated: bool,
+    preselect: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonAction {
+    id: usize,
+    title: String,
+    kind: String,
+    preferred: bool,
+    disabled: Option<String>,
+    /// The command run after the edit, if any.
+    command: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonReformatted {
+    uri: Url,
+    path: Option<PathBuf>,
+    diff: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonDiagnostic {
+    range: Span,
+    severity: &'static str,
+    code: Option<NumberOrString>,
+    source: Option<String>,
+    message: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonTextEdit {
+    range: Span,
+    new_text: String,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum JsonEditStep {
+    Edit {
+        uri: Url,
+        path: Option<PathBuf>,
+        edits: Vec<JsonTextEdit>,
+    },
+    #[serde(untagged)]
+    Resource(ResourceOp),
+}
+
+/// Renders results for a terminal or for scripts. Positions are shown the way users give
+/// them, counting lines and characters from 1.
+pub struct Printer {
+    /// The encoding of the positions the server sent.
+    pub encoding: PositionEncoding,
+    /// Paths below this directory are shown relative to it in text and SARIF output.
+    pub base: Option<PathBuf>,
+    /// The name of the language server, reported as the tool in SARIF output.
+    pub tool: Option<String>,
+    pub style: Style,
+    /// The contents of the files positions point into, `None` when unreadable.
+    texts: RefCell<HashMap<Url, Option<String>>>,
+}
+
+impl Printer {
+    pub fn new(encoding: PositionEncoding) -> Self {
+        Printer {
+            encoding,
+            base: std::env::current_dir().ok(),
+            tool: None,
+            style: Style::default(),
+            texts: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Renders `outcome` in `format`. Only diagnostics can be rendered as SARIF, only the
+    /// symbol inventory as CSV, only call graphs as DOT, only locations and diagnostics
+    /// for editors, and only reports and highlighted files as HTML.
+    pub fn render(&self, format: OutputFormat, outcome: &Outcome) -> Result<String, CliError> {
+        match (format, outcome) {
+            (OutputFormat::Text, _) => Ok(self.text(outcome)),
+            (OutputFormat::Plain, _) => Ok(self.plain(outcome)),
+            (OutputFormat::Json, _) => Ok(format!("{:#}\n", self.json(outcome))),
+            (OutputFormat::Sarif, Outcome::Diagnostics { uri, diagnostics }) => {
+                Ok(format!("{:#}\n", self.sarif(uri, diagnostics)))
+            }
+            (OutputFormat::Sarif, Outcome::DiagnosticsReport(files)) => {
+                Ok(format!("{:#}\n", self.sarif_report(files)))
+            }
+            (OutputFormat::Sarif, _) => Err(CliError::Usage(
+                "--output sarif is only supported by diagnostics".to_owned(),
+            )),
+            (OutputFormat::Csv, Outcome::Inventory(files)) => Ok(self.csv(files)),
+            (OutputFormat::Csv, _) => Err(CliError::Usage(
+                "--output csv is only supported by dump-symbols".to_owned(),
+            )),
+            (OutputFormat::Dot, Outcome::CallGraph(graph)) => Ok(self.dot(graph)),
+            (OutputFormat::Dot, _) => Err(CliError::Usage(
+                "--output dot is only supported by callgraph".to_owned(),
+            )),
+            (
+                OutputFormat::VimQuickfix | OutputFormat::Emacs,
+                Outcome::Locations(_) | Outcome::Diagnostics { .. } | Outcome::DiagnosticsReport(_),
+            ) => Ok(self.editor_lines(format, outcome)),
+            (OutputFormat::VimQuickfix, _) => Err(CliError::Usage(
+                "--output vim-quickfix is only supported by definition, references and diagnostics"
+                    .to_owned(),
+            )),
+            (OutputFormat::Emacs, _) => Err(CliError::Usage(
+                "--output emacs is only supported by definition, references and diagnostics"
+                    .to_owned(),
+            )),
+            (OutputFormat::Html, Outcome::Analysis(files)) => Ok(self.html(files)),
+            (OutputFormat::Html, Outcome::Highlighted(file)) => Ok(self.highlighted_html(file)),
+            (OutputFormat::Html, _) => Err(CliError::Usage(
+                "--output html is only supported by report and highlight".to_owned(),
+            )),
+        }
+    }
+
+    /// Shows a uri as a path when it is a local file.
+    pub fn path(&self, uri: &Url) -> String {
+        let Ok(path) = uri.to_file_path() else {
+            return uri.to_string();
+        };
+        let relative = self
+            .base
+            .as_deref()
+            .and_then(|base| path.strip_prefix(base).ok())
+            .unwrap_or(&path);
+        relative.display().to_string()
+    }
+
+    /// Converts a position the server sent, converting the column to characters when the
+    /// document can be read.
+    pub fn point(&self, uri: &Url, position: Position) -> Point {
+        Point {
+            line: position.line + 1,
+            column: self.column(uri, position, PositionEncoding::Utf32) + 1,
+        }
+    }
+
+    /// The column of a position the server sent counted in `encoding` from 0, as sent when
+    /// the document cannot be read.
+    fn column(&self, uri: &Url, position: Position, encoding: PositionEncoding) -> u32 {
+        let mut texts = self.texts.borrow_mut();
+        let text = texts.entry(uri.clone()).or_insert_with(|| read_local(uri));
+        match text {
+            Some(text) => {
+                let offset = text_utils::position_to_offset(text, position, self.encoding);
+                text_utils::offset_to_position(text, offset, encoding).character
+            }
+            None => position.character,
+        }
+    }
+
+    /// The screen column of a position from 1, with tab stops every 8 columns as the GNU
+    /// coding standards count them.
+    fn screen_column(&self, uri: &Url, position: Position) -> u32 {
+        let characters = self.column(uri, position, PositionEncoding::Utf32) as usize;
+        let texts = self.texts.borrow();
+        let Some(line) = texts
+            .get(uri)
+            .and_then(|text| text.as_deref()?.lines().nth(position.line as usize))
+        else {
+            return characters as u32 + 1;
+        };
+        let column = line
+            .chars()
+            .take(characters)
+            .fold(0, |column, ch| match ch {
+                '\t' => column / 8 * 8 + 8,
+                _ => column + 1,
+            });
+        column + 1
+    }
+
+    pub fn span(&self, uri: &Url, range: Range) -> Span {
+        Span {
+            start: self.point(uri, range.start),
+            end: self.point(uri, range.end),
+        }
+    }
+
+    fn source_line(&self, uri: &Url, line: u32) -> Option<String> {
+        let texts = self.texts.borrow();
+        let text = texts.get(uri)?.as_deref()?;
+        text.lines()
+            .nth(line as usize)
+            .map(|line| line.trim().to_owned())
+    }
+
+    /// Renders `outcome` as human readable text.
+    pub fn text(&self, outcome: &Outcome) -> String {
+        let mut out = String::new();
+        match outcome {
+            Outcome::Locations(locations) => {
+                for location in locations {
+                    let start = self.point(&location.uri, location.range.start);
+                    out.push_str(&format!(
+                        "{}:{}:{}",
+                        self.path(&location.uri),
+                        start.line,
+                        start.column
+                    ));
+                    if let Some(line) = self.source_line(&location.uri, location.range.start.line) {
+                        out.push_str(&format!(": {}", line));
+                    }
+                    out.push('\n');
+                    if self.style.context.is_some() {
+                        out.push_str(&self.snippet(&location.uri, location.range, HIGHLIGHT));
+                    }
+                }
+            }
+            Outcome::Hover {
+                uri,
+                position,
+                hover,
+            } => {
+                if let Some(hover) = hover {
+                    if self.style.context.is_some() {
+                        let range = hover.range.unwrap_or(Range::new(*position, *position));
+                        let start = self.point(uri, range.start);
+                        out.push_str(&format!(
+                            "  {} {}:{}:{}\n",
+                            self.paint(GUTTER, "-->"),
+                            self.path(uri),
+                            start.line,
+                            start.column
+                        ));
+                        out.push_str(&self.snippet(uri, range, HIGHLIGHT));
+                    }
+                    out.push_str(&markup::hover_to_plain_text(&hover.contents));
+                    if !out.ends_with('\n') {
+                        out.push('\n');
+                    }
+                }
+            }
+            Outcome::Symbols { uri, symbols } => self.symbols_text(uri, symbols, 0, &mut out),
+            Outcome::Inventory(files) => {
+                for (uri, symbols) in files {
+                    out.push_str(&format!("{}\n", self.path(uri)));
+                    self.symbols_text(uri, symbols, 1, &mut out);
+                }
+            }
+            Outcome::Unreferenced(symbols) => {
+                for symbol in symbols {
+                    let start = self.point(&symbol.uri, symbol.selection_range.start);
+                    out.push_str(&format!(
+                        "{}:{}:{}: {} {} is used {} time{}\n",
+                        self.path(&symbol.uri),
+                        start.line,
+                        start.column,
+                        symbol_kind_name(symbol.kind),
+                        symbol.name,
+                        symbol.references,
+                        if symbol.references == 1 { "" } else { "s" }
+                    ));
+                }
+            }
+            Outcome::CallGraph(graph) => {
+                // every caller with its callees, the roots first even when they call nothing
+                for (index, node) in graph.nodes.iter().enumerate() {
+                    let calls: Vec<_> = graph
+                        .edges
+                        .iter()
+                        .filter(|edge| edge.caller == index)
+                        .collect();
+                    if calls.is_empty() && !graph.roots.contains(&index) {
+                        continue;
+                    }
+                    out.push_str(&format!("{} {}\n", node.name, self.item_location(node)));
+                    for edge in calls {
+                        let callee = &graph.nodes[edge.callee];
+                        out.push_str(&format!(
+                            "  -> {} {}\n",
+                            callee.name,
+                            self.item_location(callee)
+                        ));
+                    }
+                }
+            }
+            Outcome::Diagnostics { uri, diagnostics } => {
+                self.diagnostics_text(uri, diagnostics, &mut out)
+            }
+            Outcome::DiagnosticsReport(files) => {
+                for (uri, diagnostics) in files {
+                    self.diagnostics_text(uri, diagnostics, &mut out);
+                }
+            }
+            Outcome::Edit(edit) => out.push_str(&self.edit_diff(edit)),
+            Outcome::Actions { actions, .. } => {
+                for (index, action) in actions.iter().enumerate() {
+                    out.push_str(&format!("{}\n", action_label(index, action)));
+                }
+            }
+            Outcome::Completions { list, .. } => {
+                for item in &list.items {
+                    out.push_str(&format!("{}\n", completion_label(item)));
+                    if let Some(documentation) = &item.documentation {
+                        let documentation = markup::documentation_to_plain_text(documentation);
+                        for line in documentation.lines() {
+                            out.push_str(format!("    {}", line).trim_end());
+                            out.push('\n');
+                        }
+                    }
+                }
+                if list.is_incomplete {
+                    out.push_str("(incomplete, typing more may bring up others)\n");
+                }
+            }
+            Outcome::Formatted {
+                files,
+                written: true,
+            } => {
+                for file in files {
+                    out.push_str(&format!("formatted {}\n", self.path(&file.uri)));
+                }
+            }
+            Outcome::Formatted {
+                files,
+                written: false,
+            } => {
+                for file in files {
+                    out.push_str(&self.format_diff(file));
+                }
+            }
+            Outcome::Capabilities {
+                server_info,
+                capabilities,
+            } => capabilities_text(server_info.as_ref(), capabilities, &mut out),
+            Outcome::Checks(checks) => {
+                for check in checks {
+                    let status = if check.passed { "ok" } else { "FAIL" };
+                    out.push_str(&format!("{:<5} {}: {}\n", status, check.name, check.detail));
+                    if let Some(hint) = &check.hint {
+                        out.push_str(&format!("      hint: {}\n", hint));
+                    }
+                }
+            }
+            Outcome::Lsif(index) => {
+                // the line-based format LSIF tools read
+                for element in index.elements() {
+                    out.push_str(&format!("{}\n", element));
+                }
+            }
+            Outcome::Analysis(files) => {
+                for file in files {
+                    let symbols: usize = file
+                        .symbols
+                        .iter()
+                        .map(|symbol| symbol.descendants().len())
+                        .sum();
+                    out.push_str(&format!(
+                        "{}: {} diagnostic{}, {} symbol{}, {} unused\n",
+                        self.path(&file.uri),
+                        file.diagnostics.len(),
+                        if file.diagnostics.len() == 1 { "" } else { "s" },
+                        symbols,
+                        if symbols == 1 { "" } else { "s" },
+                        file.unreferenced.len()
+                    ));
+                }
+            }
+            Outcome::Cached(caches) => {
+                for cache in caches {
+                    out.push_str(&format!(
+                        "cached {} symbols of {} files in {}\n",
+                        cache.symbols,
+                        cache.files,
+                        cache.path.display()
+                    ));
+                }
+            }
+            Outcome::Bench(latencies) => {
+                let millis = |duration: Option<Duration>| {
+                    duration.map_or("-".to_owned(), |duration| {
+                        format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+                    })
+                };
+                out.push_str(&format!(
+                    "{:<28} {:>8} {:>6} {:>9} {:>9} {:>9} {:>9} {:>9}\n",
+                    "method", "requests", "failed", "min", "p50", "p90", "p99", "max"
+                ));
+                for latency in latencies {
+                    out.push_str(&format!(
+                        "{:<28} {:>8} {:>6} {:>9} {:>9} {:>9} {:>9} {:>9}\n",
+                        latency.method,
+                        latency.samples.len() + latency.failed,
+                        latency.failed,
+                        millis(latency.samples.first().copied()),
+                        millis(latency.percentile(50.0)),
+                        millis(latency.percentile(90.0)),
+                        millis(latency.percentile(99.0)),
+                        millis(latency.samples.last().copied()),
+                    ));
+                }
+            }
+            // the page is what highlighting is for
+            Outcome::Highlighted(file) => out.push_str(&self.highlighted_html(file)),
+            Outcome::Explanation(explanation) => {
+                let uri = &explanation.uri;
+                let at = self.point(uri, explanation.position);
+                out.push_str(&format!("{}:{}:{}", self.path(uri), at.line, at.column));
+                if let Some(symbol) = &explanation.enclosing {
+                    let mut path = symbol.container_path.clone();
+                    path.push(symbol.name.clone());
+                    out.push_str(&format!(
+                        ", in {} {}",
+                        symbol_kind_name(symbol.kind),
+                        path.join(".")
+                    ));
+                }
+                out.push('\n');
+                let indented = |text: &str| -> String {
+                    match text.trim_end() {
+                        "" => "    none\n".to_owned(),
+                        text => text.lines().map(|line| format!("    {}\n", line)).collect(),
+                    }
+                };
+                let hover = explanation.hover.as_ref().map_or(String::new(), |hover| {
+                    markup::hover_to_plain_text(&hover.contents)
+                });
+                out.push_str("hover:\n");
+                out.push_str(&indented(&hover));
+                out.push_str("definition:\n");
+                out.push_str(&indented(
+                    &self.text(&Outcome::Locations(explanation.definitions.clone())),
+                ));
+                out.push_str("type definition:\n");
+                out.push_str(&indented(
+                    &self.text(&Outcome::Locations(explanation.type_definitions.clone())),
+                ));
+                match explanation.references {
+                    Some(references) => out.push_str(&format!("references: {}\n", references)),
+                    None => out.push_str("references: not supported\n"),
+                }
+            }
+        }
+        out
+    }
+
+    /// Renders locations and diagnostics one per line for an editor: Vim's quickfix list,
+    /// with columns in bytes, or Emacs' compilation mode, with the range in screen columns.
+    /// Severities are named so both pick them up, hints as notes, and messages are joined
+    /// into one line.
+    fn editor_lines(&self, format: OutputFormat, outcome: &Outcome) -> String {
+        let mut entries: Vec<(&Url, Range, String)> = Vec::new();
+        let mut diagnostics = |uri, diagnostics: &[Diagnostic]| {
+            for diagnostic in sorted_diagnostics(diagnostics) {
+                let severity = match diagnostic.severity {
+                    Some(DiagnosticSeverity::HINT) => "note",
+                    severity => severity_name(severity),
+                };
+                let label = severity_label(severity, diagnostic);
+                entries.push((
+                    uri,
+                    diagnostic.range,
+                    format!("{}: {}", label, diagnostic.message),
+                ));
+            }
+        };
+        match outcome {
+            Outcome::Diagnostics {
+                uri,
+                diagnostics: list,
+            } => diagnostics(uri, list),
+            Outcome::DiagnosticsReport(files) => {
+                for (uri, list) in files {
+                    diagnostics(uri, list);
+                }
+            }
+            Outcome::Locations(locations) => {
+                for location in locations {
+                    // reads the document, for its source line
+                    self.point(&location.uri, location.range.start);
+                    let source = self
+                        .source_line(&location.uri, location.range.start.line)
+                        .unwrap_or_default();
+                    entries.push((&location.uri, location.range, source));
+                }
+            }
+            outcome => unreachable!("{:?} has no positions for an editor", outcome),
+        }
+
+        let mut out = String::new();
+        for (uri, range, message) in entries {
+            let message = message.split_whitespace().collect::<Vec<_>>().join(" ");
+            let position = match format {
+                OutputFormat::VimQuickfix => format!(
+                    "{}:{}",
+                    range.start.line + 1,
+                    self.column(uri, range.start, PositionEncoding::Utf8) + 1
+                ),
+                _ => self.gnu_range(uri, range),
+            };
+            let line = format!("{}:{}: {}", self.path(uri), position, message);
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// A range as GNU tools print it, `line.column-column` within a line and
+    /// `line.column-line.column` across lines, the end being the last column covered.
+    fn gnu_range(&self, uri: &Url, range: Range) -> String {
+        let (line, column) = (range.start.line + 1, self.screen_column(uri, range.start));
+        if range.end <= range.start {
+            return format!("{}.{}", line, column);
+        }
+        let (end_line, end_column) = (
+            range.end.line + 1,
+            (self.screen_column(uri, range.end) - 1).max(1),
+        );
+        if end_line == line {
+            format!("{}.{}-{}", line, column, end_column)
+        } else {
+            format!("{}.{}-{}.{}", line, column, end_line, end_column)
+        }
+    }
+
+    /// Renders a workspace edit as the resource operations it performs, one per line,
+    /// followed by a unified diff of the files it changes. Files are read from disk, so this
+    /// has to happen before the edit is applied.
+    pub fn edit_diff(&self, edit: &WorkspaceEdit) -> String {
+        /// A file as it was and as the edit leaves it, `None` when it does not exist.
+        struct Change {
+            original: Url,
+            uri: Url,
+            before: Option<String>,
+            after: Option<String>,
+        }
+
+        let mut operations = String::new();
+        let mut changes: Vec<Change> = Vec::new();
+        let change = |changes: &mut Vec<Change>, uri: &Url| -> usize {
+            if let Some(index) = changes.iter().position(|change| change.uri == *uri) {
+                return index;
+            }
+            let text = read_local(uri);
+            changes.push(Change {
+                original: uri.clone(),
+                uri: uri.clone(),
+                before: text.clone(),
+                after: text,
+            });
+            changes.len() - 1
+        };
+        for step in edits::workspace_edit_steps(edit) {
+            match step {
+                WorkspaceEditStep::Edit { uri, edits } => {
+                    let index = change(&mut changes, &uri);
+                    let after = &mut changes[index].after;
+                    *after = Some(edits::apply_text_edits(
+                        after.as_deref().unwrap_or_default(),
+                        &edits,
+                    ));
+                }
+                WorkspaceEditStep::Resource(ResourceOp::Create(create)) => {
+                    operations.push_str(&format!("create {}\n", self.path(&create.uri)));
+                    let overwrite = create
+                        .options
+                        .as_ref()
+                        .and_then(|options| options.overwrite)
+                        .unwrap_or(false);
+                    let index = change(&mut changes, &create.uri);
+                    let after = &mut changes[index].after;
+                    if after.is_none() || overwrite {
+                        *after = Some(String::new());
+                    }
+                }
+                WorkspaceEditStep::Resource(ResourceOp::Rename(rename)) => {
+                    operations.push_str(&format!(
+                        "rename {} -> {}\n",
+                        self.path(&rename.old_uri),
+                        self.path(&rename.new_uri)
+                    ));
+                    let index = change(&mut changes, &rename.old_uri);
+                    changes[index].uri = rename.new_uri;
+                }
+                WorkspaceEditStep::Resource(ResourceOp::Delete(delete)) => {
+                    operations.push_str(&format!("delete {}\n", self.path(&delete.uri)));
+                    let index = change(&mut changes, &delete.uri);
+                    changes[index].after = None;
+                }
+            }
+        }
+
+        let mut out = operations;
+        for change in changes {
+            let name = |uri: &Url, text: &Option<String>| match text {
+                Some(_) => self.path(uri),
+                None => "/dev/null".to_owned(),
+            };
+            out.push_str(&diff::unified(
+                &name(&change.original, &change.before),
+                &name(&change.uri, &change.after),
+                change.before.as_deref().unwrap_or_default(),
+                change.after.as_deref().unwrap_or_default(),
+            ));
+        }
+        out
+    }
+
+    /// Renders `outcome` as one `path:line:column: message` line per location, diagnostic,
+    /// symbol or text edit. Hovers, which have no position, are printed as plain text.
+    pub fn plain(&self, outcome: &Outcome) -> String {
+        let mut out = String::new();
+        let mut line = |uri: &Url, position: Position, message: &str| {
+            let start = self.point(uri, position);
+            // one result per line, whatever the message holds
+            let message = message.split_whitespace().collect::<Vec<_>>().join(" ");
+            out.push_str(&format!(
+                "{}:{}:{}: {}\n",
+                self.path(uri),
+                start.line,
+                start.column,
+                message
+            ));
+        };
+        match outcome {
+            Outcome::Locations(locations) => {
+                for location in locations {
+                    let source = self
+                        .source_line(&location.uri, location.range.start.line)
+                        .unwrap_or_default();
+                    line(&location.uri, location.range.start, &source);
+                }
+            }
+            Outcome::Hover { .. } => return self.text(outcome),
+            Outcome::Symbols { uri, symbols } => {
+                for symbol in symbols.iter().flat_map(DocumentSymbolNode::descendants) {
+                    let message = format!("{} {}", symbol_kind_name(symbol.kind), symbol.name);
+                    line(uri, symbol.selection_range.start, &message);
+                }
+            }
+            Outcome::Inventory(files) => {
+                for (uri, symbols) in files {
+                    for symbol in symbols.iter().flat_map(DocumentSymbolNode::descendants) {
+                        let message = format!("{} {}", symbol_kind_name(symbol.kind), symbol.name);
+                        line(uri, symbol.selection_range.start, &message);
+                    }
+                }
+            }
+            Outcome::Unreferenced(_)
+            | Outcome::Checks(_)
+            | Outcome::Lsif(_)
+            | Outcome::Analysis(_)
+            | Outcome::Cached(_)
+            | Outcome::Bench(_)
+            | Outcome::Highlighted(_)
+            | Outcome::Explanation(_) => return self.text(outcome),
+            Outcome::CallGraph(graph) => {
+                // one line per call site
+                for edge in &graph.edges {
+                    let caller = &graph.nodes[edge.caller];
+                    let message = format!("{} -> {}", caller.name, graph.nodes[edge.callee].name);
+                    for range in &edge.from_ranges {
+                        line(&caller.uri, range.start, &message);
+                    }
+                }
+            }
+            Outcome::Diagnostics { uri, diagnostics } => {
+                for diagnostic in sorted_diagnostics(diagnostics) {
+                    let message =
+                        format!("{}: {}", diagnostic_label(diagnostic), diagnostic.message);
+                    line(uri, diagnostic.range.start, &message);
+                }
+            }
+            Outcome::DiagnosticsReport(files) => {
+                for (uri, diagnostics) in files {
+                    for diagnostic in sorted_diagnostics(diagnostics) {
+                        let message =
+                            format!("{}: {}", diagnostic_label(diagnostic), diagnostic.message);
+                        line(uri, diagnostic.range.start, &message);
+                    }
+                }
+            }
+            Outcome::Edit(edit) => {
+                for step in edits::workspace_edit_steps(edit) {
+                    if let WorkspaceEditStep::Edit { uri, edits } = step {
+                        for edit in edits {
+                            line(&uri, edit.range.start, &edit.new_text);
+                        }
+                    }
+                }
+            }
+            Outcome::Actions {
+                uri,
+                range,
+                actions,
+            } => {
+                for (index, action) in actions.iter().enumerate() {
+                    line(uri, range.start, &action_label(index, action));
+                }
+            }
+            Outcome::Completions {
+                uri,
+                position,
+                list,
+            } => {
+                for item in &list.items {
+                    line(uri, *position, &completion_label(item));
+                }
+            }
+            Outcome::Formatted { files, written } => {
+                let message = match written {
+                    true => "formatted",
+                    false => "not formatted",
+                };
+                for file in files {
+                    // the first line formatting changes
+                    let unchanged = file
+                        .before
+                        .lines()
+                        .zip(file.after.lines())
+                        .take_while(|(before, after)| before == after)
+                        .count();
+                    line(&file.uri, Position::new(unchanged as u32, 0), message);
+                }
+            }
+            Outcome::Capabilities { capabilities, .. } => {
+                for method in capabilities.supported_methods() {
+                    out.push_str(&format!("{}\n", method));
+                }
+            }
+        }
+        out
+    }
+
+    /// Renders the changes formatting makes to a file as a unified diff.
+    fn format_diff(&self, file: &Reformatted) -> String {
+        let path = self.path(&file.uri);
+        diff::unified(&path, &path, &file.before, &file.after)
+    }
+
+    fn diagnostics_text(&self, uri: &Url, diagnostics: &[Diagnostic], out: &mut String) {
+        for diagnostic in sorted_diagnostics(diagnostics) {
+            let start = self.point(uri, diagnostic.range.start);
+            let color = severity_color(diagnostic.severity);
+            out.push_str(&format!(
+                "{}: {}\n  {} {}:{}:{}\n",
+                self.paint(color, &diagnostic_label(diagnostic)),
+                self.paint(BOLD, &diagnostic.message),
+                self.paint(GUTTER, "-->"),
+                self.path(uri),
+                start.line,
+                start.column
+            ));
+            if self.style.context.is_some() {
+                out.push_str(&self.snippet(uri, diagnostic.range, color));
+            }
+        }
+    }
+
+    /// Wraps `text` in the ANSI escape `color` when the output is colored.
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.style.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_owned()
+        }
+    }
+
+    /// The source lines around `range` with the range underlined by carets, as rustc shows
+    /// them: `--context` lines before and after, tabs shown as 4 spaces. Empty when the
+    /// document cannot be read.
+    fn snippet(&self, uri: &Url, range: Range, color: &str) -> String {
+        let context = self.style.context.unwrap_or_default() as u32;
+        // the columns of the range in characters, which also reads the document
+        let (start, end) = (
+            self.column(uri, range.start, PositionEncoding::Utf32) as usize,
+            self.column(uri, range.end, PositionEncoding::Utf32) as usize,
+        );
+        let texts = self.texts.borrow();
+        let Some(text) = texts.get(uri).and_then(Option::as_deref) else {
+            return String::new();
+        };
+        let lines: Vec<&str> = text.lines().collect();
+        if range.start.line as usize >= lines.len() {
+            return String::new();
+        }
+        let first = range.start.line.saturating_sub(context);
+        let last = (range.end.line.max(range.start.line) + context).min(lines.len() as u32 - 1);
+        let width = (last + 1).to_string().len();
+        let gutter = |number: &str| self.paint(GUTTER, &format!("{:>width$} |", number));
+        // the width a line takes up to `characters`, tabs expanded
+        let shown = |line: &str, characters: usize| -> usize {
+            line.chars()
+                .take(characters)
+                .map(|ch| if ch == '\t' { 4 } else { 1 })
+                .sum()
+        };
+
+        let mut out = format!("{}\n", gutter(""));
+        for number in first..=last {
+            let line = lines[number as usize];
+            let source = line.replace('\t', "    ");
+            let numbered = format!("{} {}", gutter(&(number + 1).to_string()), source);
+            out.push_str(numbered.trim_end());
+            out.push('\n');
+            if number < range.start.line || number > range.end.line.max(range.start.line) {
+                continue;
+            }
+            // lines within a range spanning several are underlined from their indentation
+            let from = if number == range.start.line {
+                shown(line, start)
+            } else {
+                source.len() - source.trim_start().len()
+            };
+            let to = if number == range.end.line {
+                shown(line, end)
+            } else {
+                shown(line, usize::MAX)
+            };
+            let carets = "^".repeat(to.saturating_sub(from).max(1));
+            out.push_str(&format!(
+                "{} {}{}\n",
+                gutter(""),
+                " ".repeat(from),
+                self.paint(color, &carets)
+            ));
+        }
+        out
+    }
+
+    fn symbols_text(
+        &self,
+        uri: &Url,
+        symbols: &[DocumentSymbolNode],
+        depth: usize,
+        out: &mut String,
+    ) {
+        for symbol in symbols {
+            let start = self.point(uri, symbol.selection_range.start);
+            out.push_str(&format!(
+                "{}{} {} {}:{}\n",
+                "  ".repeat(depth),
+                symbol_kind_name(symbol.kind),
+                symbol.name,
+                start.line,
+                start.column
+            ));
+            self.symbols_text(uri, &symbol.children, depth + 1, out);
+        }
+    }
+
+    /// Renders `outcome` as JSON. The top level is an object with a single key naming
+    /// what it holds: `locations`, `hover`, `symbols`, `diagnostics`, `edit`, `inventory`,
+    /// a flat list of the symbols of many documents, `callGraph`, its nodes and the edges
+    /// between them by node index, `report`, the diagnostics of many documents,
+    /// `unreferenced`, `completions`, `actions`, numbered by `id`, `formatted`, the
+    /// changed files with their diff, next to `written`, or `capabilities`, the
+    /// `serverInfo`, the `announced` capabilities as the server sent them, its
+    /// `registrations` and the `supported` requests, or `checks`, those `doctor` ran with
+    /// whether they `passed`, a `detail` and a `hint`. Ranges are
+    /// `{ "start": { "line", "column" }, "end": ... }`, counting from 1, columns in
+    /// characters, and local files carry their `path` next to their `uri`.
+    pub fn json(&self, outcome: &Outcome) -> Value {
+        match outcome {
+            Outcome::Locations(locations) => {
+                let locations: Vec<JsonLocation> = locations
+                    .iter()
+                    .map(|location| JsonLocation {
+                        uri: location.uri.clone(),
+                        path: location.uri.to_file_path().ok(),
+                        range: self.span(&location.uri, location.range),
+                    })
+                    .collect();
+                json!({ "locations": locations })
+            }
+            Outcome::Hover { hover, .. } => {
+                let hover = hover.as_ref().map(|hover| {
+                    json!({
+                        "markdown": markup::hover_markdown(&hover.contents),
+                        "plainText": markup::hover_to_plain_text(&hover.contents),
+                    })
+                });
+                json!({ "hover": hover })
+            }
+            Outcome::Symbols { uri, symbols } => {
+                json!({
+                    "symbols": {
+                        "uri": uri,
+                        "path": uri.to_file_path().ok(),
+                        "symbols": self.json_symbols(uri, symbols),
+                    }
+                })
+            }
+            Outcome::Diagnostics { uri, diagnostics } => {
+                json!({ "diagnostics": self.json_diagnostics(uri, diagnostics) })
+            }
+            Outcome::DiagnosticsReport(files) => {
+                let files: Vec<Value> = files
+                    .iter()
+                    .map(|(uri, diagnostics)| self.json_diagnostics(uri, diagnostics))
+                    .collect();
+                json!({ "report": files })
+            }
+            Outcome::Edit(edit) => {
+                let steps: Vec<JsonEditStep> = edits::workspace_edit_steps(edit)
+                    .into_iter()
+                    .map(|step| match step {
+                        WorkspaceEditStep::Edit { uri, edits } => JsonEditStep::Edit {
+                            path: uri.to_file_path().ok(),
+                            edits: self.json_text_edits(&uri, &edits),
+                            uri,
+                        },
+                        WorkspaceEditStep::Resource(operation) => JsonEditStep::Resource(operation),
+                    })
+                    .collect();
+                json!({ "edit": { "steps": steps } })
+            }
+            Outcome::Inventory(files) => json!({ "inventory": self.inventory(files) }),
+            Outcome::Unreferenced(symbols) => {
+                json!({ "unreferenced": self.json_unreferenced(symbols) })
+            }
+            Outcome::CallGraph(graph) => {
+                let nodes: Vec<JsonCallNode> = graph
+                    .nodes
+                    .iter()
+                    .enumerate()
+                    .map(|(index, node)| JsonCallNode {
+                        name: node.name.clone(),
+                        kind: symbol_kind_name(node.kind),
+                        detail: node.detail.clone(),
+                        uri: node.uri.clone(),
+                        path: node.uri.to_file_path().ok(),
+                        range: self.span(&node.uri, node.range),
+                        selection_range: self.span(&node.uri, node.selection_range),
+                        root: graph.roots.contains(&index),
+                    })
+                    .collect();
+                let edges: Vec<JsonCallEdge> = graph
+                    .edges
+                    .iter()
+                    .map(|edge| {
+                        let caller = &graph.nodes[edge.caller].uri;
+                        JsonCallEdge {
+                            caller: edge.caller,
+                            callee: edge.callee,
+                            call_sites: edge
+                                .from_ranges
+                                .iter()
+                                .map(|range| self.span(caller, *range))
+                                .collect(),
+                        }
+                    })
+                    .collect();
+                json!({ "callGraph": { "nodes": nodes, "edges": edges } })
+            }
+            Outcome::Completions { list, .. } => {
+                let items: Vec<JsonCompletion> = list
+                    .items
+                    .iter()
+                    .map(|item| JsonCompletion {
+                        label: item.label.clone(),
+                        kind: item.kind.map(completion_kind_name),
+                        detail: item.detail.clone(),
+                        documentation: item
+                            .documentation
+                            .as_ref()
+                            .map(markup::documentation_markdown),
+                        insert_text: completion_insert_text(item).to_owned(),
+                        #[allow(deprecated)]
+                        deprecated: item.deprecated == Some(true)
+                            || item
+                                .tags
+                                .iter()
+                                .flatten()
+                                .any(|tag| *tag == CompletionItemTag::DEPRECATED),
+                        preselect: item.preselect == Some(true),
+                    })
+                    .collect();
+                json!({
+                    "completions": { "isIncomplete": list.is_incomplete, "items": items }
+                })
+            }
+            Outcome::Actions { actions, .. } => {
+                let actions: Vec<JsonAction> = actions
+                    .iter()
+                    .enumerate()
+                    .map(|(index, action)| {
+                        let (preferred, disabled, command) = match action {
+                            CodeActionOrCommand::Command(command) => {
+                                (false, None, Some(&command.command))
+                            }
+                            CodeActionOrCommand::CodeAction(action) => (
+                                action.is_preferred.unwrap_or(false),
+                                action
+                                    .disabled
+                                    .as_ref()
+                                    .map(|disabled| disabled.reason.clone()),
+                                action.command.as_ref().map(|command| &command.command),
+                            ),
+                        };
+                        JsonAction {
+                            id: index + 1,
+                            title: actions::action_title(action).to_owned(),
+                            kind: actions::action_kind(action).to_owned(),
+                            preferred,
+                            disabled,
+                            command: command.cloned(),
+                        }
+                    })
+                    .collect();
+                json!({ "actions": actions })
+            }
+            Outcome::Formatted { files, written } => {
+                let files: Vec<JsonReformatted> = files
+                    .iter()
+                    .map(|file| JsonReformatted {
+                        uri: file.uri.clone(),
+                        path: file.uri.to_file_path().ok(),
+                        diff: self.format_diff(file),
+                    })
+                    .collect();
+                json!({ "formatted": files, "written": written })
+            }
+            Outcome::Capabilities {
+                server_info,
+                capabilities,
+            } => json!({
+                "capabilities": {
+                    "serverInfo": server_info,
+                    "announced": capabilities.announced(),
+                    "registrations": capabilities.registrations(),
+                    "supported": capabilities.supported_methods(),
+                }
+            }),
+            Outcome::Checks(checks) => json!({ "checks": checks }),
+            Outcome::Lsif(index) => json!(index.elements()),
+            Outcome::Analysis(files) => {
+                let files: Vec<Value> = files
+                    .iter()
+                    .map(|file| {
+                        let mut json = self.json_diagnostics(&file.uri, &file.diagnostics);
+                        json["symbols"] = json!(self.json_symbols(&file.uri, &file.symbols));
+                        json["unreferenced"] = json!(self.json_unreferenced(&file.unreferenced));
+                        json
+                    })
+                    .collect();
+                json!({ "analysis": files })
+            }
+            Outcome::Cached(caches) => json!({ "cached": caches }),
+            Outcome::Bench(latencies) => {
+                let millis = |duration: Option<Duration>| {
+                    duration.map(|duration| duration.as_secs_f64() * 1000.0)
+                };
+                let methods: Vec<Value> = latencies
+                    .iter()
+                    .map(|latency| {
+                        json!({
+                            "method": latency.method,
+                            "requests": latency.samples.len() + latency.failed,
+                            "failed": latency.failed,
+                            "milliseconds": {
+                                "min": millis(latency.samples.first().copied()),
+                                "mean": millis(latency.mean()),
+                                "p50": millis(latency.percentile(50.0)),
+                                "p90": millis(latency.percentile(90.0)),
+                                "p99": millis(latency.percentile(99.0)),
+                                "max": millis(latency.samples.last().copied()),
+                            },
+                        })
+                    })
+                    .collect();
+                json!({ "bench": methods })
+            }
+            Outcome::Highlighted(file) => {
+                let tokens: Vec<Value> = file
+                    .tokens
+                    .iter()
+                    .map(|token| {
+                        json!({
+                            "range": self.span(&file.uri, token.range),
+                            "type": token.token_type.as_str(),
+                            "modifiers": token
+                                .modifiers
+                                .iter()
+                                .map(|modifier| modifier.as_str())
+                                .collect::<Vec<_>>(),
+                        })
+                    })
+                    .collect();
+                let folds: Vec<Value> = file
+                    .folds
+                    .iter()
+                    .map(|fold| {
+                        json!({
+                            "startLine": fold.start_line + 1,
+                            "endLine": fold.end_line + 1,
+                            "kind": fold.kind,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "highlighted": {
+                        "uri": file.uri,
+                        "tokens": tokens,
+                        "folds": folds,
+                    }
+                })
+            }
+            Outcome::Explanation(explanation) => {
+                let uri = &explanation.uri;
+                let hover = self.json(&Outcome::Hover {
+                    uri: uri.clone(),
+                    position: explanation.position,
+                    hover: explanation.hover.clone(),
+                });
+                let locations = |locations: &[Location]| {
+                    self.json(&Outcome::Locations(locations.to_vec()))["locations"].take()
+                };
+                let enclosing = explanation.enclosing.as_ref().map(|symbol| {
+                    json!({
+                        "name": symbol.name,
+                        "kind": symbol_kind_name(symbol.kind),
+                        "containerPath": symbol.container_path,
+                        "range": self.span(uri, symbol.range),
+                    })
+                });
+                json!({
+                    "explanation": {
+                        "uri": uri,
+                        "path": uri.to_file_path().ok(),
+                        "position": self.point(uri, explanation.position),
+                        "hover": hover["hover"],
+                        "definitions": locations(&explanation.definitions),
+                        "typeDefinitions": locations(&explanation.type_definitions),
+                        "references": explanation.references,
+                        "enclosingSymbol": enclosing,
+                    }
+                })
+            }
+        }
+    }
+
+    /// Where a call hierarchy item is declared, as `path:line:column`.
+    fn item_location(&self, item: &CallHierarchyItem) -> String {
+        let start = self.point(&item.uri, item.selection_range.start);
+        format!("{}:{}:{}", self.path(&item.uri), start.line, start.column)
+    }
+
+    /// Renders a call graph as a Graphviz digraph, callers pointing at their callees. Nodes
+    /// are labelled with their name and where they are declared, the roots drawn bold, and
+    /// calls made from several places labelled with their count.
+    pub fn dot(&self, graph: &CallGraph) -> String {
+        let mut out = String::from("digraph calls {\n    rankdir=LR;\n    node [shape=box];\n");
+        for (index, node) in graph.nodes.iter().enumerate() {
+            let label = format!("{}\n{}", node.name, self.item_location(node));
+            let style = if graph.roots.contains(&index) {
+                ", style=bold"
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "    n{} [label={}{}];\n",
+                index,
+                dot_string(&label),
+                style
+            ));
+        }
+        for edge in &graph.edges {
+            out.push_str(&format!("    n{} -> n{}", edge.caller, edge.callee));
+            if edge.from_ranges.len() > 1 {
+                out.push_str(&format!(" [label=\"{}\"]", edge.from_ranges.len()));
+            }
+            out.push_str(";\n");
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Flattens the symbols of many documents, in document order.
+    fn inventory(&self, files: &[(Url, Vec<DocumentSymbolNode>)]) -> Vec<InventorySymbol> {
+        fn visit(
+            printer: &Printer,
+            uri: &Url,
+            symbols: &[DocumentSymbolNode],
+            container: &mut Vec<String>,
+            out: &mut Vec<InventorySymbol>,
+        ) {
+            for symbol in symbols {
+                out.push(InventorySymbol {
+                    uri: uri.clone(),
+                    path: uri.to_file_path().ok(),
+                    name: symbol.name.clone(),
+                    kind: symbol_kind_name(symbol.kind),
+                    container: container.clone(),
+                    detail: symbol.detail.clone(),
+                    range: printer.span(uri, symbol.range),
+                    selection_range: printer.span(uri, symbol.selection_range),
+                });
+                container.push(symbol.name.clone());
+                visit(printer, uri, &symbol.children, container, out);
+                container.pop();
+            }
+        }
+
+        let mut out = Vec::new();
+        for (uri, symbols) in files {
+            visit(self, uri, symbols, &mut Vec::new(), &mut out);
+        }
+        out
+    }
+
+    /// Renders the symbol inventory as CSV, one row per symbol. `line` and `column` are
+    /// where the name of the symbol starts, `end_line` where its body ends, and `container`
+    /// the names of the symbols containing it joined by dots.
+    pub fn csv(&self, files: &[(Url, Vec<DocumentSymbolNode>)]) -> String {
+        let mut out = String::from("path,name,kind,container,line,column,end_line\n");
+        for symbol in self.inventory(files) {
+            let fields = [
+                self.path(&symbol.uri),
+                symbol.name,
+                symbol.kind,
+                symbol.container.join("."),
+                symbol.selection_range.start.line.to_string(),
+                symbol.selection_range.start.column.to_string(),
+                symbol.range.end.line.to_string(),
+            ];
+            let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            out.push_str(&fields.join(","));
+            out.push('\n');
+        }
+        out
+    }
+
+    fn json_symbols(&self, uri: &Url, symbols: &[DocumentSymbolNode]) -> Vec<JsonSymbol> {
+        symbols
+            .iter()
+            .map(|symbol| JsonSymbol {
+                name: symbol.name.clone(),
+                kind: symbol_kind_name(symbol.kind),
+                detail: symbol.detail.clone(),
+                range: self.span(uri, symbol.range),
+                selection_range: self.span(uri, symbol.selection_range),
+                children: self.json_symbols(uri, &symbol.children),
+            })
+            .collect()
+    }
+
+    fn json_unreferenced(&self, symbols: &[Unreferenced]) -> Vec<JsonUnreferenced> {
+        symbols
+            .iter()
+            .map(|symbol| JsonUnreferenced {
+                uri: symbol.uri.clone(),
+                path: symbol.uri.to_file_path().ok(),
+                name: symbol.name.clone(),
+                kind: symbol_kind_name(symbol.kind),
+                range: self.span(&symbol.uri, symbol.range),
+                selection_range: self.span(&symbol.uri, symbol.selection_range),
+                references: symbol.references,
+            })
+            .collect()
+    }
+
+    fn json_diagnostics(&self, uri: &Url, diagnostics: &[Diagnostic]) -> Value {
+        let diagnostics: Vec<JsonDiagnostic> = sorted_diagnostics(diagnostics)
+            .into_iter()
+            .map(|diagnostic| JsonDiagnostic {
+                range: self.span(uri, diagnostic.range),
+                severity: severity_name(diagnostic.severity),
+                code: diagnostic.code.clone(),
+                source: diagnostic.source.clone(),
+                message: diagnostic.message.clone(),
+            })
+            .collect();
+        json!({
+            "uri": uri,
+            "path": uri.to_file_path().ok(),
+            "diagnostics": diagnostics,
+        })
+    }
+
+    fn json_text_edits(&self, uri: &Url, edits: &[TextEdit]) -> Vec<JsonTextEdit> {
+        edits
+            .iter()
+            .map(|edit| JsonTextEdit {
+                range: self.span(uri, edit.range),
+                new_text: edit.new_text.clone(),
+            })
+            .collect()
+    }
+}
+
+impl Printer {
+    /// Renders the diagnostics of a document as a SARIF 2.1 log with a single run. The
+    /// diagnostic codes become the rule ids, related information the related locations.
+    /// Local files below `base` are given relative to `%SRCROOT%`, as code scanning expects.
+    pub fn sarif(&self, uri: &Url, diagnostics: &[Diagnostic]) -> Value {
+        self.sarif_report(&[(uri.clone(), diagnostics.to_vec())])
+    }
+
+    /// Renders the diagnostics of many documents as a SARIF 2.1 log with a single run,
+    /// see `sarif`.
+    pub fn sarif_report(&self, files: &[(Url, Vec<Diagnostic>)]) -> Value {
+        let mut rules: Vec<String> = Vec::new();
+        let results: Vec<Value> = files
+            .iter()
+            .flat_map(|(uri, diagnostics)| {
+                sorted_diagnostics(diagnostics)
+                    .into_iter()
+                    .map(move |diagnostic| (uri, diagnostic))
+            })
+            .map(|(uri, diagnostic)| {
+                let mut result = json!({
+                    "level": sarif_level(diagnostic.severity),
+                    "message": { "text": diagnostic.message },
+                    "locations": [self.sarif_location(uri, diagnostic.range)],
+                });
+                if let Some(code) = &diagnostic.code {
+                    let id = match code {
+                        NumberOrString::Number(code) => code.to_string(),
+                        NumberOrString::String(code) => code.clone(),
+                    };
+                    let index = match rules.iter().position(|rule| *rule == id) {
+                        Some(index) => index,
+                        None => {
+                            rules.push(id.clone());
+                            rules.len() - 1
+                        }
+                    };
+                    result["ruleId"] = json!(id);
+                    result["ruleIndex"] = json!(index);
+                }
+                let related: Vec<Value> = diagnostic
+                    .related_information
+                    .iter()
+                    .flatten()
+                    .enumerate()
+                    .map(|(id, related)| {
+                        let mut location =
+                            self.sarif_location(&related.location.uri, related.location.range);
+                        location["id"] = json!(id);
+                        location["message"] = json!({ "text": related.message });
+                        location
+                    })
+                    .collect();
+                if !related.is_empty() {
+                    result["relatedLocations"] = json!(related);
+                }
+                result
+            })
+            .collect();
+        let rules: Vec<Value> = rules.iter().map(|id| json!({ "id": id })).collect();
+        json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": self.tool.as_deref().unwrap_or("lsp-client"),
+                        "rules": rules,
+                    }
+                },
+                "columnKind": "unicodeCodePoints",
+                "results": results,
+            }],
+        })
+    }
+
+    fn sarif_location(&self, uri: &Url, range: Range) -> Value {
+        let span = self.span(uri, range);
+        let relative = uri.to_file_path().ok().and_then(|path| {
+            let relative = path.strip_prefix(self.base.as_deref()?).ok()?;
+            // sarif uris use forward slashes on every platform
+            let components: Vec<String> = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            Some(components.join("/"))
+        });
+        let artifact = match relative {
+            Some(relative) => json!({ "uri": relative, "uriBaseId": "%SRCROOT%" }),
+            None => json!({ "uri": uri }),
+        };
+        json!({
+            "physicalLocation": {
+                "artifactLocation": artifact,
+                "region": {
+                    "startLine": span.start.line,
+                    "startColumn": span.start.column,
+                    "endLine": span.end.line,
+                    "endColumn": span.end.column,
+                },
+            }
+        })
+    }
+}
+
+/// The styles of HTML reports, inlined so a report is a single file.
+const HTML_STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 70em; color: #222; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { padding: 0.25em 0.75em; text-align: left; border-bottom: 1px solid #ddd; }
+td.count, th.count { text-align: right; }
+tr.total th { border-top: 2px solid #888; }
+ul { padding-left: 1.25em; }
+li { margin: 0.5em 0; }
+pre { background: #f5f5f5; padding: 0.25em 0.5em; margin: 0.25em 0; overflow-x: auto; }
+.message { white-space: pre-wrap; }
+.position { color: #666; }
+.error { color: #b00; font-weight: bold; }
+.warning { color: #a60; font-weight: bold; }
+.info, .hint { color: #07a; font-weight: bold; }
+";
+
+impl Printer {
+    /// Renders what `report` found as a single HTML page, for people who do not use the
+    /// CLI: a table of the files with their counts, then for each file its diagnostics with
+    /// their source line, how many symbols of each kind it declares, and its unused public
+    /// symbols. The page has no scripts and links nothing outside it.
+    pub fn html(&self, files: &[FileAnalysis]) -> String {
+        // errors, warnings, infos and hints
+        let severities = |diagnostics: &[Diagnostic]| {
+            let mut counts = [0; 4];
+            for diagnostic in diagnostics {
+                counts[severity_rank(diagnostic.severity) as usize] += 1;
+            }
+            counts
+        };
+        let symbol_count = |file: &FileAnalysis| -> usize {
+            file.symbols
+                .iter()
+                .map(|symbol| symbol.descendants().len())
+                .sum()
+        };
+        let row = |cells: &[usize]| -> String {
+            cells
+                .iter()
+                .map(|cell| format!("<td class=\"count\">{}</td>", cell))
+                .collect()
+        };
+
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        out.push_str("<title>lsp-client report</title>\n");
+        out.push_str(&format!(
+            "<style>\n{}</style>\n</head>\n<body>\n",
+            HTML_STYLE
+        ));
+        out.push_str("<h1>lsp-client report</h1>\n");
+        if let Some(tool) = &self.tool {
+            out.push_str(&format!("<p>Analyzed with {}.</p>\n", html_escape(tool)));
+        }
+
+        out.push_str(
+            "<table>\n<tr><th>file</th><th class=\"count\">errors</th>\
+             <th class=\"count\">warnings</th><th class=\"count\">infos</th>\
+             <th class=\"count\">hints</th><th class=\"count\">symbols</th>\
+             <th class=\"count\">unused</th></tr>\n",
+        );
+        let mut totals = [0; 6];
+        for (index, file) in files.iter().enumerate() {
+            let [errors, warnings, infos, hints] = severities(&file.diagnostics);
+            let counts = [
+                errors,
+                warnings,
+                infos,
+                hints,
+                symbol_count(file),
+                file.unreferenced.len(),
+            ];
+            for (total, count) in totals.iter_mut().zip(counts) {
+                *total += count;
+            }
+            out.push_str(&format!(
+                "<tr><td><a href=\"#file-{}\">{}</a></td>{}</tr>\n",
+                index + 1,
+                html_escape(&self.path(&file.uri)),
+                row(&counts)
+            ));
+        }
+        out.push_str(&format!(
+            "<tr class=\"total\"><th>{} file{}</th>{}</tr>\n</table>\n",
+            files.len(),
+            if files.len() == 1 { "" } else { "s" },
+            row(&totals)
+        ));
+
+        for (index, file) in files.iter().enumerate() {
+            let uri = &file.uri;
+            out.push_str(&format!(
+                "<section id=\"file-{}\">\n<h2>{}</h2>\n",
+                index + 1,
+                html_escape(&self.path(uri))
+            ));
+
+            out.push_str("<h3>Diagnostics</h3>\n");
+            if file.diagnostics.is_empty() {
+                out.push_str("<p>None.</p>\n");
+            } else {
+                out.push_str("<ul>\n");
+                for diagnostic in sorted_diagnostics(&file.diagnostics) {
+                    let start = self.point(uri, diagnostic.range.start);
+                    out.push_str(&format!(
+                        "<li><span class=\"{}\">{}</span> <span class=\"position\">{}:{}</span> \
+                         <span class=\"message\">{}</span>",
+                        severity_name(diagnostic.severity),
+                        html_escape(&diagnostic_label(diagnostic)),
+                        start.line,
+                        start.column,
+                        html_escape(&diagnostic.message)
+                    ));
+                    if let Some(source) = self.source_line(uri, diagnostic.range.start.line) {
+                        out.push_str(&format!("<pre>{}</pre>", html_escape(&source)));
+                    }
+                    out.push_str("</li>\n");
+                }
+                out.push_str("</ul>\n");
+            }
+
+            out.push_str("<h3>Symbols</h3>\n");
+            let mut kinds: BTreeMap<String, usize> = BTreeMap::new();
+            for symbol in file
+                .symbols
+                .iter()
+                .flat_map(DocumentSymbolNode::descendants)
+            {
+                *kinds.entry(symbol_kind_name(symbol.kind)).or_default() += 1;
+            }
+            if kinds.is_empty() {
+                out.push_str("<p>None.</p>\n");
+            } else {
+                let mut kinds: Vec<_> = kinds.into_iter().collect();
+                // the most common kinds first, then by name
+                kinds.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+                out.push_str("<table>\n<tr><th>kind</th><th class=\"count\">count</th></tr>\n");
+                for (kind, count) in kinds {
+                    out.push_str(&format!(
+                        "<tr><td>{}</td>{}</tr>\n",
+                        html_escape(&kind),
+                        row(&[count])
+                    ));
+                }
+                out.push_str("</table>\n");
+            }
+
+            out.push_str("<h3>Unused public symbols</h3>\n");
+            if file.unreferenced.is_empty() {
+                out.push_str("<p>None.</p>\n");
+            } else {
+                out.push_str("<ul>\n");
+                for symbol in &file.unreferenced {
+                    let start = self.point(uri, symbol.selection_range.start);
+                    out.push_str(&format!(
+                        "<li>{} <code>{}</code> <span class=\"position\">{}:{}</span>, \
+                         used {} time{}</li>\n",
+                        symbol_kind_name(symbol.kind),
+                        html_escape(&symbol.name),
+                        start.line,
+                        start.column,
+                        symbol.references,
+                        if symbol.references == 1 { "" } else { "s" }
+                    ));
+                }
+                out.push_str("</ul>\n");
+            }
+            out.push_str("</section>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    /// Renders a file as a single HTML page highlighted by its semantic tokens, one class
+    /// per token type and modifier, e.g. `t-function m-declaration`, with a stylesheet for
+    /// the standard ones. Folding ranges become blocks collapsing below their first line.
+    /// The page has no scripts and links nothing outside it.
+    pub fn highlighted_html(&self, file: &Highlighted) -> String {
+        let lines: Vec<&str> = file.text.lines().collect();
+        let mut tokens: BTreeMap<u32, Vec<&DecodedSemanticToken>> = BTreeMap::new();
+        for token in &file.tokens {
+            tokens
+                .entry(token.range.start.line)
+                .or_default()
+                .push(token);
+        }
+        let folds = highlight::nested_folds(&file.folds, lines.len() as u32);
+        let path = html_escape(&self.path(&file.uri));
+
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        out.push_str(&format!("<title>{}</title>\n", path));
+        out.push_str(&format!(
+            "<style>\n{}</style>\n</head>\n<body>\n",
+            HIGHLIGHT_STYLE
+        ));
+        out.push_str(&format!("<h1>{}</h1>\n", path));
+        if let Some(tool) = &self.tool {
+            out.push_str(&format!("<p>Highlighted by {}.</p>\n", html_escape(tool)));
+        }
+        out.push_str("<div class=\"source\">\n");
+        for (index, line) in lines.iter().enumerate() {
+            let number = index as u32;
+            let mut content = String::new();
+            let mut done = 0;
+            let mut on_line = tokens.remove(&number).unwrap_or_default();
+            on_line.sort_by_key(|token| token.range.start.character);
+            for token in on_line {
+                let offset = |character| {
+                    text_utils::position_to_offset(line, Position::new(0, character), self.encoding)
+                };
+                let start = offset(token.range.start.character);
+                let end = offset(token.range.end.character);
+                if start < done || end <= start {
+                    // overlapping the previous token, or empty
+                    continue;
+                }
+                let mut classes = format!("t-{}", token.token_type.as_str());
+                for modifier in &token.modifiers {
+                    classes.push_str(&format!(" m-{}", modifier.as_str()));
+                }
+                content.push_str(&html_escape(&line[done..start]));
+                content.push_str(&format!(
+                    "<span class=\"{}\">{}</span>",
+                    html_escape(&classes),
+                    html_escape(&line[start..end])
+                ));
+                done = end;
+            }
+            content.push_str(&html_escape(&line[done..]));
+            let line = format!(
+                "<span class=\"line\" id=\"L{}\"><span class=\"number\">{}</span>{}</span>\n",
+                index + 1,
+                index + 1,
+                content
+            );
+            if folds.iter().any(|&(start, _)| start == number) {
+                out.push_str(&format!("<details open>\n<summary>{}</summary>\n", line));
+            } else {
+                out.push_str(&line);
+            }
+            for _ in folds.iter().filter(|&&(_, end)| end == number) {
+                out.push_str("</details>\n");
+            }
+        }
+        out.push_str("</div>\n</body>\n</html>\n");
+        out
+    }
+}
+
+/// The stylesheet of `Printer::highlighted_html`, coloring the standard token types.
+const HIGHLIGHT_STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 90em; color: #222; }
+.source { font-family: ui-monospace, monospace; background: #fafafa; padding: 0.5em 0; }
+.line { display: block; white-space: pre; }
+.number { display: inline-block; width: 5ch; margin-right: 2ch; text-align: right; color: #999; }
+summary { list-style: none; cursor: pointer; }
+summary::-webkit-details-marker { display: none; }
+summary .number::after { content: \"\\25BE\"; }
+details:not([open]) > summary .number::after { content: \"\\25B8\"; }
+.t-keyword, .t-modifier { color: #a626a4; }
+.t-comment { color: #8a8b90; font-style: italic; }
+.t-string, .t-regexp { color: #50a14f; }
+.t-number { color: #986801; }
+.t-operator { color: #0184bc; }
+.t-namespace { color: #7a5c00; }
+.t-type, .t-class, .t-enum, .t-interface, .t-struct, .t-typeParameter { color: #c18401; }
+.t-function, .t-method, .t-macro, .t-decorator { color: #4078f2; }
+.t-parameter { color: #b0527a; }
+.t-property, .t-enumMember, .t-event { color: #e45649; }
+.t-label { color: #0997b3; }
+.m-declaration, .m-definition { font-weight: bold; }
+.m-deprecated { text-decoration: line-through; }
+.m-static { font-style: italic; }
+";
+
+/// Escapes text for HTML content and attribute values.
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reads the current contents of a local file.
+pub fn read_local(uri: &Url) -> Option<String> {
+    std::fs::read_to_string(uri.to_file_path().ok()?).ok()
+}
+
+/// Describes a completion on one line: its label, kind and detail.
+fn completion_label(item: &CompletionItem) -> String {
+    let mut label = item.label.clone();
+    if let Some(kind) = item.kind {
+        label.push_str(&format!(" [{}]", completion_kind_name(kind)));
+    }
+    if let Some(detail) = item.detail.as_deref().filter(|detail| !detail.is_empty()) {
+        label.push_str(&format!(" {}", detail));
+    }
+    label
+}
+
+/// The text accepting a completion inserts: its edit, its insert text or its label, in
+/// that order of preference.
+fn completion_insert_text(item: &CompletionItem) -> &str {
+    match &item.text_edit {
+        Some(CompletionTextEdit::Edit(edit)) => &edit.new_text,
+        Some(CompletionTextEdit::InsertAndReplace(edit)) => &edit.new_text,
+        None => item.insert_text.as_deref().unwrap_or(&item.label),
+    }
+}
+
+pub fn completion_kind_name(kind: CompletionItemKind) -> String {
+    format!("{:?}", kind).to_lowercase()
+}
+
+/// Describes what a server supports: its name and version, how positions and documents
+/// are synchronized, the requests it answers and the commands it executes.
+fn capabilities_text(
+    server_info: Option<&ServerInfo>,
+    capabilities: &Capabilities,
+    out: &mut String,
+) {
+    let server = match server_info {
+        Some(ServerInfo {
+            name,
+            version: Some(version),
+        }) => format!("{} {}", name, version),
+        Some(ServerInfo {
+            name,
+            version: None,
+        }) => name.clone(),
+        None => "unnamed".to_owned(),
+    };
+    let encoding = match capabilities.position_encoding() {
+        PositionEncoding::Utf8 => "utf-8",
+        PositionEncoding::Utf16 => "utf-16",
+        PositionEncoding::Utf32 => "utf-32",
+    };
+    let sync = match capabilities.text_document_sync_kind() {
+        TextDocumentSyncKind::FULL => "full",
+        TextDocumentSyncKind::INCREMENTAL => "incremental",
+        _ => "none",
+    };
+    out.push_str(&format!(
+        "server: {}\nposition encoding: {}\ndocument sync: {}\n",
+        server, encoding, sync
+    ));
+    let announced = capabilities.announced();
+    let triggers = [
+        (
+            "completion",
+            announced
+                .completion_provider
+                .as_ref()
+                .and_then(|options| options.trigger_characters.as_ref()),
+        ),
+        (
+            "signature help",
+            announced
+                .signature_help_provider
+                .as_ref()
+                .and_then(|options| options.trigger_characters.as_ref()),
+        ),
+    ];
+    for (name, characters) in triggers {
+        if let Some(characters) = characters.filter(|characters| !characters.is_empty()) {
+            out.push_str(&format!("{} triggers: {}\n", name, characters.join(" ")));
+        }
+    }
+    let lists: [(&str, Vec<String>); 3] = [
+        (
+            "requests",
+            capabilities
+                .supported_methods()
+                .into_iter()
+                .map(str::to_owned)
+                .collect(),
+        ),
+        ("commands", capabilities.commands()),
+        (
+            "registered dynamically",
+            capabilities
+                .registrations()
+                .iter()
+                .map(|registration| registration.method.clone())
+                .collect(),
+        ),
+    ];
+    for (name, items) in lists {
+        if items.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("{}:\n", name));
+        for item in items {
+            out.push_str(&format!("    {}\n", item));
+        }
+    }
+}
+
+/// Describes a code action on one line: its number, title and kind, and whether the server
+/// prefers it or it is disabled.
+fn action_label(index: usize, action: &CodeActionOrCommand) -> String {
+    let mut notes = vec![match actions::action_kind(action) {
+        "" => "unknown kind".to_owned(),
+        kind => kind.to_owned(),
+    }];
+    if let CodeActionOrCommand::CodeAction(action) = action {
+        if action.is_preferred == Some(true) {
+            notes.push("preferred".to_owned());
+        }
+        if let Some(disabled) = &action.disabled {
+            notes.push(format!("disabled: {}", disabled.reason));
+        }
+    }
+    format!(
+        "{}. {} [{}]",
+        index + 1,
+        actions::action_title(action),
+        notes.join(", ")
+    )
+}
+
+/// The diagnostics most severe first, then in document order.
+fn sorted_diagnostics(diagnostics: &[Diagnostic]) -> Vec<&Diagnostic> {
+    let mut diagnostics: Vec<&Diagnostic> = diagnostics.iter().collect();
+    diagnostics.sort_by_key(|diagnostic| {
+        (
+            severity_rank(diagnostic.severity),
+            diagnostic.range.start.line,
+            diagnostic.range.start.character,
+        )
+    });
+    diagnostics
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+/// The line numbers and arrows of source snippets.
+const GUTTER: &str = "\x1b[1;34m";
+/// The carets under ranges other than diagnostics.
+const HIGHLIGHT: &str = "\x1b[1;32m";
+
+/// The color of a diagnostic's label and carets, as rustc colors them.
+fn severity_color(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::WARNING) => "\x1b[1;33m",
+        Some(DiagnosticSeverity::INFORMATION) | Some(DiagnosticSeverity::HINT) => "\x1b[1;36m",
+        _ => "\x1b[1;31m",
+    }
+}
+
+/// The severity of a diagnostic followed by its code, e.g. `error[E0308]`.
+fn diagnostic_label(diagnostic: &Diagnostic) -> String {
+    severity_label(severity_name(diagnostic.severity), diagnostic)
+}
+
+/// `severity` followed by the code of the diagnostic.
+fn severity_label(severity: &str, diagnostic: &Diagnostic) -> String {
+    match &diagnostic.code {
+        Some(NumberOrString::Number(code)) => format!("{}[{}]", severity, code),
+        Some(NumberOrString::String(code)) => format!("{}[{}]", severity, code),
+        None => severity.to_owned(),
+    }
+}
+
+/// Orders severities from the most severe, 0 for errors. Servers may leave out the severity;
+/// clients treat those as errors.
+pub fn severity_rank(severity: Option<DiagnosticSeverity>) -> u8 {
+    match severity {
+        Some(DiagnosticSeverity::WARNING) => 1,
+        Some(DiagnosticSeverity::INFORMATION) => 2,
+        Some(DiagnosticSeverity::HINT) => 3,
+        _ => 0,
+    }
+}
+
+pub fn severity_name(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "info",
+        Some(DiagnosticSeverity::HINT) => "hint",
+        _ => "error",
+    }
+}
+
+/// SARIF has no level below `note`, hints are reported as notes too.
+fn sarif_level(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) | Some(DiagnosticSeverity::HINT) => "note",
+        _ => "error",
+    }
+}
+
+/// Quotes a DOT string, keeping line breaks as `\n` escapes.
+fn dot_string(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// Quotes a CSV field when it holds a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// The name of a symbol kind in lower case, e.g. `function`.
+pub fn symbol_kind_name(kind: SymbolKind) -> String {
+    format!("{:?}", kind).to_lowercase()
+}
@@ -0,0 +1,108 @@
+//! Progress bars on stderr for the work the server reports with `$/progress`, such as
+//! indexing, drawn while the CLI waits for the server to start and to load the project.
+//! Only drawn when stderr is a terminal, and not with `--quiet`.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use lsp_types::NumberOrString;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::lsp::client::ServerProgress;
+
+/// How many columns a line takes at most, so none wraps and the bars can be redrawn in
+/// place.
+const WIDTH: usize = 79;
+
+/// How many columns the bar itself takes.
+const BAR: usize = 20;
+
+/// The line drawn for `progress`: the title, a bar when the server says how much is done,
+/// and the message.
+pub fn line(progress: &ServerProgress) -> String {
+    let mut line = progress.title.clone();
+    if let Some(percentage) = progress.percentage {
+        let filled = BAR * percentage.min(100) as usize / 100;
+        line.push_str(&format!(
+            " [{}{}] {:>3}%",
+            "#".repeat(filled),
+            " ".repeat(BAR - filled),
+            percentage.min(100)
+        ));
+    }
+    if let Some(message) = &progress.message {
+        line.push(' ');
+        line.push_str(message);
+    }
+    match line.char_indices().nth(WIDTH) {
+        Some((end, _)) => line[..end].to_owned(),
+        None => line,
+    }
+}
+
+/// The lines for every progress, in a stable order.
+fn lines(active: &HashMap<NumberOrString, ServerProgress>) -> Vec<String> {
+    let mut progress: Vec<_> = active.iter().collect();
+    progress.sort_by_key(|(token, progress)| (&progress.title, format!("{:?}", token)));
+    progress
+        .into_iter()
+        .map(|(_, progress)| line(progress))
+        .collect()
+}
+
+/// Replaces the `drawn` lines above the cursor with `lines`.
+fn draw(drawn: &mut usize, lines: &[String]) {
+    let mut stderr = std::io::stderr().lock();
+    if *drawn > 0 {
+        // up to the first line, clearing everything below
+        let _ = write!(stderr, "\x1b[{}A\x1b[J", drawn);
+    }
+    for line in lines {
+        let _ = writeln!(stderr, "{}", line);
+    }
+    let _ = stderr.flush();
+    *drawn = lines.len();
+}
+
+/// Bars redrawn whenever the server reports progress, until dropped, which erases them.
+pub struct ProgressBars {
+    task: JoinHandle<()>,
+    /// How many lines are drawn, `None` once the bars are erased for good.
+    drawn: Arc<Mutex<Option<usize>>>,
+}
+
+impl ProgressBars {
+    pub fn show(
+        mut progress: watch::Receiver<HashMap<NumberOrString, ServerProgress>>,
+    ) -> ProgressBars {
+        let drawn = Arc::new(Mutex::new(Some(0)));
+        let task = tokio::spawn({
+            let drawn = drawn.clone();
+            async move {
+                loop {
+                    let lines = lines(&progress.borrow_and_update());
+                    match drawn.lock().unwrap().as_mut() {
+                        Some(drawn) => draw(drawn, &lines),
+                        None => return,
+                    }
+                    if progress.changed().await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        ProgressBars { task, drawn }
+    }
+}
+
+impl Drop for ProgressBars {
+    fn drop(&mut self) {
+        self.task.abort();
+        // the task may be drawing on another thread, the lock waits for it
+        if let Some(mut drawn) = self.drawn.lock().unwrap().take() {
+            draw(&mut drawn, &[]);
+        }
+    }
+}
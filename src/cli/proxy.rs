@@ -0,0 +1,227 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncWrite, BufReader};
+use tokio::process::Command;
+use tokio::sync::Mutex as AsyncMutex;
+use tokio::time;
+
+use super::bridge::Transforms;
+use super::CliError;
+use crate::lsp::parsing::{read_message, write_message, ParseError};
+
+/// How long the server gets to exit once the client is gone before it is killed.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What the proxy does with the traffic besides forwarding it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProxyOptions {
+    /// Appends every message to this file, one JSON object per line.
+    pub log: Option<PathBuf>,
+    /// Messages with these methods are not forwarded. Dropped requests are answered with a
+    /// `MethodNotFound` error so their sender does not wait forever.
+    pub drop: Vec<String>,
+    /// Sends the messages in this file, one per line, to the server right after the
+    /// client's `initialized` notification.
+    pub inject: Option<PathBuf>,
+    /// How the messages are rewritten on the way, see `bridge`.
+    pub transforms: Transforms,
+}
+
+/// Which side sent a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Peer {
+    Client,
+    Server,
+}
+
+impl Peer {
+    fn name(self) -> &'static str {
+        match self {
+            Peer::Client => "client",
+            Peer::Server => "server",
+        }
+    }
+}
+
+/// The traffic log, see `ProxyOptions::log`.
+struct Log {
+    file: Option<Mutex<File>>,
+    start: Instant,
+}
+
+impl Log {
+    fn record(&self, from: &str, message: &str, dropped: bool) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let message = serde_json::from_str(message).unwrap_or_else(|_| json!(message));
+        let mut entry = json!({
+            "time": self.start.elapsed().as_secs_f64(),
+            "from": from,
+            "message": message,
+        });
+        if dropped {
+            entry["dropped"] = json!(true);
+        }
+        let mut file = file.lock().unwrap();
+        if let Err(err) = writeln!(file, "{}", entry) {
+            eprintln!("failed to write the proxy log: {}", err);
+        }
+    }
+}
+
+/// Forwards the messages of one direction until its sender closes the stream, rewritten
+/// as the transforms of `options` say.
+///
+/// `reply` is where the answers to dropped requests go, back to the sender. `inject` is sent
+/// after the client's `initialized`, and the responses with the ids in `swallow`, the
+/// answers to injected requests, are only logged.
+#[allow(clippy::too_many_arguments)]
+async fn forward<R, W, A>(
+    from: Peer,
+    reader: &mut R,
+    writer: &AsyncMutex<W>,
+    reply: &AsyncMutex<A>,
+    options: &ProxyOptions,
+    log: &Log,
+    inject: &[String],
+    swallow: &[Value],
+) -> Result<(), CliError>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+    A: AsyncWrite + Unpin,
+{
+    loop {
+        let body = match read_message(reader).await {
+            Ok(body) => body,
+            Err(ParseError::Io(_)) => return Ok(()),
+            Err(err) => {
+                eprintln!("malformed message from the {}: {:?}", from.name(), err);
+                continue;
+            }
+        };
+        let message: Value = serde_json::from_str(&body).unwrap_or(Value::Null);
+        let method = message["method"].as_str().unwrap_or_default();
+        if options.drop.iter().any(|dropped| dropped == method) {
+            log.record(from.name(), &body, true);
+            if let Some(id) = message.get("id") {
+                let error = json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32601, "message": format!("{} was dropped by the proxy", method) },
+                });
+                write_message(&mut *reply.lock().await, &error.to_string()).await?;
+            }
+            continue;
+        }
+
+        let body = if options.transforms.is_empty() {
+            body
+        } else {
+            let mut message = message.clone();
+            match from {
+                Peer::Client => options.transforms.from_editor(&mut message),
+                Peer::Server => options.transforms.from_server(&mut message),
+            }
+            message.to_string()
+        };
+        log.record(from.name(), &body, false);
+        let response = message.get("method").is_none();
+        if response && message.get("id").is_some_and(|id| swallow.contains(id)) {
+            continue;
+        }
+        write_message(&mut *writer.lock().await, &body).await?;
+        if from == Peer::Client && method == "initialized" {
+            for body in inject {
+                log.record("proxy", body, false);
+                write_message(&mut *writer.lock().await, body).await?;
+            }
+        }
+    }
+}
+
+/// Starts `command` and relays the messages between it and the client talking to this
+/// process over stdin and stdout, applying `options` on the way. Returns once either side
+/// closes its end.
+pub async fn run(command: &[String], options: &ProxyOptions) -> Result<(), CliError> {
+    let inject: Vec<String> = match &options.inject {
+        Some(path) => std::fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        None => Vec::new(),
+    };
+    let inject_ids: Vec<Value> = inject
+        .iter()
+        .filter_map(|body| serde_json::from_str::<Value>(body).ok()?.get("id").cloned())
+        .collect();
+    let log = Log {
+        file: match &options.log {
+            Some(path) => Some(Mutex::new(
+                File::options().create(true).append(true).open(path)?,
+            )),
+            None => None,
+        },
+        start: Instant::now(),
+    };
+
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| CliError::Usage("empty server command".to_owned()))?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        // the server's own log goes where the client expects it
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| CliError::Server(format!("failed to start {}: {}", program, err)))?;
+    let server_in = AsyncMutex::new(child.stdin.take().expect("stdin is piped"));
+    let mut server_out = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    let client_out = AsyncMutex::new(tokio::io::stdout());
+    let mut client_in = BufReader::new(tokio::io::stdin());
+
+    let to_server = forward(
+        Peer::Client,
+        &mut client_in,
+        &server_in,
+        &client_out,
+        options,
+        &log,
+        &inject,
+        &[],
+    );
+    let to_client = forward(
+        Peer::Server,
+        &mut server_out,
+        &client_out,
+        &server_in,
+        options,
+        &log,
+        &[],
+        &inject_ids,
+    );
+    tokio::pin!(to_server, to_client);
+    let result = tokio::select! {
+        result = &mut to_server => {
+            // still relays what the server sends until it exits, e.g. the shutdown answer
+            match time::timeout(EXIT_TIMEOUT, &mut to_client).await {
+                Ok(Err(err)) => Err(err),
+                Ok(Ok(())) | Err(_) => result,
+            }
+        }
+        result = &mut to_client => result,
+    };
+    let _ = child.kill().await;
+    result
+}
@@ -0,0 +1,166 @@
+//! Records the messages exchanged with the server during one invocation, see `--record`,
+//! and plays a recording back in place of the server, see `replay`.
+//!
+//! A recording is a JSON document with the command line it was made with, less
+//! `--record`, and every message in the order it was sent:
+//!
+//! ```json
+//! {
+//!   "args": ["hover", "src/main.rs", "3:5"],
+//!   "frames": [{ "from": "client", "message": { "jsonrpc": "2.0", "id": 1, ... } }, ...]
+//! }
+//! ```
+//!
+//! The frame log `--log-frames` writes can be served as a recording too, though it has no
+//! command line to replay.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::args::{self, Invocation, Options};
+use super::CliError;
+use crate::lsp::client::Direction;
+use crate::lsp::frame_log::{self, LoggedFrame};
+use crate::lsp::replay::Replay;
+
+/// Where `--record` writes the session, and the command line it replays.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub path: PathBuf,
+    /// The arguments of the invocation, without `--record` and its file.
+    pub args: Vec<String>,
+}
+
+/// Which side sent a recorded message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sender {
+    Client,
+    Server,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    pub from: Sender,
+    pub message: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub args: Vec<String>,
+    pub frames: Vec<Frame>,
+}
+
+impl Recording {
+    /// Loads a recording, or a frame log as a recording without arguments.
+    pub fn load(path: &Path) -> Result<Recording, CliError> {
+        let contents = std::fs::read_to_string(path)?;
+        serde_json::from_str(&contents).or_else(|err| {
+            let frames = frame_log::parse(&contents).map_err(|_| {
+                CliError::Usage(format!("{} is not a recording: {}", path.display(), err))
+            })?;
+            let frames = frames
+                .into_iter()
+                .map(|frame| Frame {
+                    from: match frame.direction {
+                        Direction::Sent => Sender::Client,
+                        Direction::Received => Sender::Server,
+                    },
+                    message: frame.message,
+                })
+                .collect();
+            Ok(Recording {
+                args: Vec::new(),
+                frames,
+            })
+        })
+    }
+}
+
+/// Collects the frames of a session while it runs, saved when it finishes.
+pub struct Recorder {
+    record: Record,
+    frames: Arc<Mutex<Vec<Frame>>>,
+}
+
+impl Recorder {
+    pub fn new(record: Record) -> Recorder {
+        Recorder {
+            record,
+            frames: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// A traffic handler adding every message to the recording.
+    pub fn handler(&self) -> impl Fn(Direction, &str) + Send + Sync + 'static {
+        let frames = self.frames.clone();
+        move |direction, body| {
+            let from = match direction {
+                Direction::Sent => Sender::Client,
+                Direction::Received => Sender::Server,
+            };
+            let message = serde_json::from_str(body).unwrap_or_else(|_| json!(body));
+            frames.lock().unwrap().push(Frame { from, message });
+        }
+    }
+
+    /// Writes the recording to the file `--record` names.
+    pub fn save(&self) -> Result<(), CliError> {
+        let recording = Recording {
+            args: self.record.args.clone(),
+            frames: self.frames.lock().unwrap().clone(),
+        };
+        let json = serde_json::to_string_pretty(&recording).expect("recordings serialize");
+        std::fs::write(&self.record.path, json + "\n")?;
+        Ok(())
+    }
+}
+
+/// The invocation replaying the recording at `path`: its command line, with the server
+/// replaced by this program serving the recording. The output format and tracing may be
+/// changed from the replay's own command line.
+pub fn replay(path: &Path, options: Options) -> Result<Invocation, CliError> {
+    let recording = Recording::load(path)?;
+    if recording.args.is_empty() {
+        return Err(CliError::Usage(format!(
+            "{} has no command line to replay, serve it with serve-recording instead",
+            path.display()
+        )));
+    }
+    let mut invocation = args::parse(&recording.args)?;
+    let program = std::env::current_exe()?;
+    invocation.options.server = Some(vec![
+        program.to_string_lossy().into_owned(),
+        "serve-recording".to_owned(),
+        path.to_string_lossy().into_owned(),
+    ]);
+    invocation.options.output = options.output.or(invocation.options.output);
+    invocation.options.trace = options.trace.or(invocation.options.trace);
+    Ok(invocation)
+}
+
+/// Acts as the server of the recording at `path` on stdin and stdout, see
+/// `lsp::replay::Replay::serve`.
+pub async fn serve(path: &Path) -> Result<(), CliError> {
+    let recording = Recording::load(path)?;
+    let frames = recording
+        .frames
+        .into_iter()
+        .map(|frame| LoggedFrame {
+            direction: match frame.from {
+                Sender::Client => Direction::Sent,
+                Sender::Server => Direction::Received,
+            },
+            timestamp: 0,
+            bytes: frame.message.to_string().len(),
+            message: frame.message,
+        })
+        .collect();
+    Replay::new(frames)
+        .serve(tokio::io::stdin(), tokio::io::stdout())
+        .await?;
+    Ok(())
+}
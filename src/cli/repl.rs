@@ -0,0 +1,271 @@
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use super::args::{LineColumn, Query, Target};
+use super::output::OutputFormat;
+use super::session::Session;
+use super::{answer, CliError};
+use crate::lsp::client::ClientError;
+
+pub const HELP: &str = "\
+commands:
+    open <file>                                opens a file, or syncs it with the disk
+    def <file> <line> <column>                 where the symbol is defined
+    refs <file> <line> <column>                where the symbol is used
+    hover <file> <line> <column>               the hover of the symbol
+    symbols <file>                             the symbols of a file
+    diagnostics <file>                         the diagnostics of a file
+    rename <file> <line> <column> <name>       the edit renaming the symbol
+    raw <json>                                 sends a JSON-RPC message, a request when it
+                                               has an \"id\", printing the response
+    history                                    lists the commands run so far
+    !<n>, !!                                   runs command <n> of the history, or the last
+    help                                       shows this help
+    quit                                       shuts the server down and exits
+
+def, refs, hover and rename also take a symbol path such as MyClass.myMethod in place
+of <line> <column>
+
+files are opened on first use, lines and columns count from 1";
+
+/// A line entered in the REPL.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplCommand {
+    Query(Query),
+    Open(PathBuf),
+    /// A request when it has an `id`, a notification otherwise.
+    Raw {
+        method: String,
+        params: Value,
+        request: bool,
+    },
+    History,
+    /// Runs entry `n` of the history, counting from 1.
+    Rerun(usize),
+    /// Runs the last entry of the history.
+    RerunLast,
+    Help,
+    Quit,
+    Empty,
+}
+
+fn usage(message: &str) -> CliError {
+    CliError::Usage(message.to_owned())
+}
+
+fn position(line: &str, column: &str) -> Result<Target, CliError> {
+    LineColumn::parse(&format!("{}:{}", line, column)).map(Target::At)
+}
+
+fn symbol(path: &str) -> Target {
+    Target::Symbol(path.to_owned())
+}
+
+/// Whether a word names a symbol rather than starting a position, `def a.ts 10` is missing
+/// a column.
+fn is_symbol(word: &str) -> bool {
+    !word.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Parses a line entered in the REPL.
+pub fn parse_line(line: &str) -> Result<ReplCommand, CliError> {
+    let line = line.trim();
+    if let Some(json) = line.strip_prefix("raw ") {
+        let message: Value = serde_json::from_str(json)
+            .map_err(|err| CliError::Usage(format!("invalid JSON: {}", err)))?;
+        let method = message["method"]
+            .as_str()
+            .ok_or_else(|| usage("the message has no method"))?
+            .to_owned();
+        return Ok(ReplCommand::Raw {
+            method,
+            params: message.get("params").cloned().unwrap_or(Value::Null),
+            request: message.get("id").is_some(),
+        });
+    }
+
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let command = match words.as_slice() {
+        [] => ReplCommand::Empty,
+        ["open", file] => ReplCommand::Open(PathBuf::from(file)),
+        ["def" | "definition", file, line, column] => ReplCommand::Query(Query::Definition {
+            file: PathBuf::from(file),
+            target: position(line, column)?,
+        }),
+        ["def" | "definition", file, path] if is_symbol(path) => {
+            ReplCommand::Query(Query::Definition {
+                file: PathBuf::from(file),
+                target: symbol(path),
+            })
+        }
+        ["refs" | "references", file, line, column] => ReplCommand::Query(Query::References {
+            file: PathBuf::from(file),
+            target: position(line, column)?,
+            include_declaration: true,
+        }),
+        ["refs" | "references", file, path] if is_symbol(path) => {
+            ReplCommand::Query(Query::References {
+                file: PathBuf::from(file),
+                target: symbol(path),
+                include_declaration: true,
+            })
+        }
+        ["hover", file, line, column] => ReplCommand::Query(Query::Hover {
+            file: PathBuf::from(file),
+            target: position(line, column)?,
+        }),
+        ["hover", file, path] if is_symbol(path) => ReplCommand::Query(Query::Hover {
+            file: PathBuf::from(file),
+            target: symbol(path),
+        }),
+        ["symbols", file] => ReplCommand::Query(Query::Symbols {
+            file: PathBuf::from(file),
+        }),
+        ["diagnostics", file] => ReplCommand::Query(Query::Diagnostics {
+            file: PathBuf::from(file),
+            watch: false,
+            fail_on: None,
+            changed_since: None,
+        }),
+        ["rename", file, line, column, new_name] => ReplCommand::Query(Query::Rename {
+            file: PathBuf::from(file),
+            target: position(line, column)?,
+            new_name: new_name.to_string(),
+            apply: false,
+        }),
+        ["rename", file, path, new_name] if is_symbol(path) => ReplCommand::Query(Query::Rename {
+            file: PathBuf::from(file),
+            target: symbol(path),
+            new_name: new_name.to_string(),
+            apply: false,
+        }),
+        ["history"] => ReplCommand::History,
+        ["!!"] => ReplCommand::RerunLast,
+        [rerun] if rerun.starts_with('!') => ReplCommand::Rerun(
+            rerun[1..]
+                .parse()
+                .map_err(|_| usage("expected !<number> or !!"))?,
+        ),
+        ["help"] => ReplCommand::Help,
+        ["quit" | "exit"] => ReplCommand::Quit,
+        [command, ..] => {
+            return Err(CliError::Usage(format!(
+                "unknown command or wrong arguments: {}, see help",
+                command
+            )))
+        }
+    };
+    Ok(command)
+}
+
+/// Answers the commands read from stdin with `session` until stdin ends or `quit`. Results
+/// go to stdout, errors and the prompt to stderr, so a failed command does not end the
+/// REPL.
+pub async fn run(
+    session: &Session,
+    file: Option<&Path>,
+    format: OutputFormat,
+) -> Result<(), CliError> {
+    if let Some(file) = file {
+        session.open(file).await?;
+    }
+    let interactive = std::io::stdin().is_terminal();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut history: Vec<String> = Vec::new();
+    loop {
+        if interactive {
+            eprint!("> ");
+        }
+        let Some(mut line) = lines.next_line().await? else {
+            return Ok(());
+        };
+        let mut command = parse_line(&line);
+        // reruns are replaced by the command they name, in the history too
+        let rerun = match &command {
+            Ok(ReplCommand::Rerun(n)) => Some(n.checked_sub(1).and_then(|n| history.get(n))),
+            Ok(ReplCommand::RerunLast) => Some(history.last()),
+            _ => None,
+        };
+        if let Some(entry) = rerun {
+            match entry {
+                Some(entry) => {
+                    line = entry.clone();
+                    eprintln!("{}", line);
+                    command = parse_line(&line);
+                }
+                None => command = Err(usage("no such history entry")),
+            }
+        }
+
+        let result = match command {
+            Ok(ReplCommand::Quit) => return Ok(()),
+            Ok(ReplCommand::Empty) => continue,
+            Ok(ReplCommand::Help) => Ok(format!("{}\n", HELP)),
+            Ok(ReplCommand::History) => Ok(history
+                .iter()
+                .enumerate()
+                .map(|(n, entry)| format!("{:>4}  {}\n", n + 1, entry))
+                .collect()),
+            Ok(command) => {
+                history.push(line.trim().to_owned());
+                execute(session, command, format).await
+            }
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(output) => print!("{}", output),
+            Err(err) => eprintln!("{}", err.message()),
+        }
+    }
+}
+
+/// Runs a command that talks to the server, returning what to print.
+pub async fn execute(
+    session: &Session,
+    command: ReplCommand,
+    format: OutputFormat,
+) -> Result<String, CliError> {
+    match command {
+        ReplCommand::Query(query) => answer(session, &query, format)
+            .await
+            .map(|(output, _)| output),
+        ReplCommand::Open(file) => {
+            let uri = session.open(&file).await?;
+            match format {
+                OutputFormat::Json => Ok(format!("{}\n", serde_json::json!({ "opened": uri }))),
+                _ => Ok(format!("opened {}\n", uri)),
+            }
+        }
+        ReplCommand::Raw {
+            method,
+            params,
+            request: true,
+        } => match session
+            .timed(session.server.request_value(&method, params))
+            .await
+        {
+            Ok(result) => Ok(format!("{:#}\n", result)),
+            Err(CliError::Client(ClientError::Server(error))) => {
+                Ok(format!("{:#}\n", serde_json::json!(error)))
+            }
+            Err(err) => Err(err),
+        },
+        ReplCommand::Raw {
+            method,
+            params,
+            request: false,
+        } => {
+            session.server.send_notification(&method, &params).await;
+            Ok(String::new())
+        }
+        ReplCommand::History
+        | ReplCommand::Rerun(_)
+        | ReplCommand::RerunLast
+        | ReplCommand::Help
+        | ReplCommand::Quit
+        | ReplCommand::Empty => Ok(String::new()),
+    }
+}
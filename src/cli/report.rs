@@ -0,0 +1,118 @@
+use std::path::PathBuf;
+
+use lsp_types::{Diagnostic, Url};
+
+use super::changes;
+use super::dead_code::{self, Unreferenced};
+use super::output::Outcome;
+use super::session::Session;
+use super::CliError;
+use crate::lsp::client::ClientError;
+use crate::lsp::symbols::DocumentSymbolNode;
+
+/// What `report` found in a file.
+#[derive(Debug, Clone)]
+pub struct FileAnalysis {
+    pub uri: Url,
+    pub diagnostics: Vec<Diagnostic>,
+    pub symbols: Vec<DocumentSymbolNode>,
+    /// The public symbols used at most `--threshold` times, see `dead_code`.
+    pub unreferenced: Vec<Unreferenced>,
+}
+
+/// Collects the diagnostics of `files`, opening each in turn and closing it again once
+/// they are known. Only the first file waits for the server to load the project.
+///
+/// Like `inventory::collect`, files that cannot be read or that the server fails on are
+/// reported on stderr and left out. With `changed_since`, only the diagnostics on lines
+/// changed since that git ref are kept.
+pub async fn collect(
+    session: &Session,
+    files: &[PathBuf],
+    changed_since: Option<&str>,
+) -> Result<Outcome, CliError> {
+    let mut report = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        let changes = match changed_since {
+            Some(base) => Some(changes::since(file, base)?),
+            None => None,
+        };
+        let opened = match index {
+            0 => session.open(file).await,
+            _ => session.sync(file).await,
+        };
+        let uri = match opened {
+            Ok(uri) => uri,
+            Err(err) => {
+                eprintln!("skipping {}: {}", file.display(), err.message());
+                continue;
+            }
+        };
+        let diagnostics = session.current_diagnostics(&uri, 0).await;
+        session.documents.close(&uri).await;
+        match diagnostics {
+            Ok(mut diagnostics) => {
+                if let Some(changes) = &changes {
+                    changes.retain(&mut diagnostics);
+                }
+                report.push((uri, diagnostics))
+            }
+            Err(err @ CliError::Client(ClientError::Unsupported(_))) => return Err(err),
+            Err(err) => eprintln!("skipping {}: {}", file.display(), err.message()),
+        }
+    }
+    Ok(Outcome::DiagnosticsReport(report))
+}
+
+/// Analyzes `files` for `report`: their diagnostics, their symbols, and their public
+/// symbols referenced at most `threshold` times.
+///
+/// Like `collect`, files the server fails on are reported on stderr and left out; a
+/// server without document symbols or references fails the whole report.
+pub async fn analyze(
+    session: &Session,
+    files: &[PathBuf],
+    threshold: usize,
+) -> Result<Outcome, CliError> {
+    let mut analyses = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        let opened = match index {
+            0 => session.open(file).await,
+            _ => session.sync(file).await,
+        };
+        let uri = match opened {
+            Ok(uri) => uri,
+            Err(err) => {
+                eprintln!("skipping {}: {}", file.display(), err.message());
+                continue;
+            }
+        };
+        let analysis = analyze_document(session, &uri, threshold).await;
+        session.documents.close(&uri).await;
+        match analysis {
+            Ok(analysis) => analyses.push(analysis),
+            Err(err @ CliError::Client(ClientError::Unsupported(_))) => return Err(err),
+            Err(err) => eprintln!("skipping {}: {}", file.display(), err.message()),
+        }
+    }
+    Ok(Outcome::Analysis(analyses))
+}
+
+async fn analyze_document(
+    session: &Session,
+    uri: &Url,
+    threshold: usize,
+) -> Result<FileAnalysis, CliError> {
+    let diagnostics = session.current_diagnostics(uri, 0).await?;
+    let symbols = session
+        .timed(session.server.document_symbols(uri.clone()))
+        .await?;
+    let mut unreferenced = Vec::new();
+    dead_code::check_document(session, uri, &symbols, threshold, &mut unreferenced).await?;
+    Ok(FileAnalysis {
+        uri: uri.clone(),
+        diagnostics,
+        symbols,
+        unreferenced,
+    })
+}
@@ -0,0 +1,227 @@
+//! Results of queries servers are slow to answer, kept below the workspace root so that
+//! later invocations on unchanged files skip the server: workspace symbols, keyed by the
+//! contents of every file the server handles, and semantic tokens, keyed by the contents of
+//! their file. `--no-cache` leaves it out.
+//!
+//! The keys are sha256 digests of the server command, the contents, the method and the
+//! params, which each entry keeps in full to be checked on reading. Entries unused for
+//! `MAX_AGE`, then the least recently used past `MAX_SIZE` in all, are pruned as new ones
+//! are written.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use lsp_types::request::{Request, SemanticTokensFullRequest, WorkspaceSymbolRequest};
+use lsp_types::{Url, WorkspaceSymbol};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use super::inventory;
+use super::session::Session;
+use super::CliError;
+use crate::lsp::semantic_tokens::DecodedSemanticToken;
+
+/// Where the results are kept, relative to the workspace root, one file each. Hidden, so
+/// crawling the workspace skips it.
+pub const RESULTS_DIR: &str = ".lsp-client/results";
+
+/// How long an entry is kept without being used, unless `ResultCache::with_limits` says
+/// otherwise.
+pub const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The size of all the entries kept, in bytes, unless `ResultCache::with_limits` says
+/// otherwise.
+pub const MAX_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The results one server gave for a workspace, which another server may give differently.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultCache {
+    root: PathBuf,
+    server: Vec<String>,
+    /// The extensions of the files the server handles, see `inventory::workspace_files`.
+    extensions: Option<Vec<String>>,
+    max_size: u64,
+    max_age: Duration,
+}
+
+impl ResultCache {
+    pub fn new(root: &Path, server: &[String], extensions: Option<&[String]>) -> ResultCache {
+        ResultCache {
+            root: root.to_owned(),
+            server: server.to_vec(),
+            extensions: extensions.map(<[String]>::to_vec),
+            max_size: MAX_SIZE,
+            max_age: MAX_AGE,
+        }
+    }
+
+    /// Keeps at most `max_size` bytes of entries, used within `max_age`.
+    pub fn with_limits(mut self, max_size: u64, max_age: Duration) -> ResultCache {
+        self.max_size = max_size;
+        self.max_age = max_age;
+        self
+    }
+
+    /// The result of `method` with `params` cached for `contents`, the digest of what it is
+    /// about.
+    pub fn get<T: DeserializeOwned>(
+        &self,
+        contents: &str,
+        method: &str,
+        params: &Value,
+    ) -> Option<T> {
+        let key = self.key(contents, method, params);
+        let path = self.path(&key);
+        let mut entry: Value = serde_json::from_str(&std::fs::read_to_string(&path).ok()?).ok()?;
+        if entry["key"] != key {
+            return None;
+        }
+        // the age of an entry is the time since it was last used
+        let _ = std::fs::File::options()
+            .append(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        serde_json::from_value(entry["result"].take()).ok()
+    }
+
+    /// Caches `result`, pruning the entries past the limits first. A failure is reported on
+    /// stderr, the cache only saves asking the server.
+    pub fn put<T: Serialize>(&self, contents: &str, method: &str, params: &Value, result: &T) {
+        let key = self.key(contents, method, params);
+        let path = self.path(&key);
+        let written = serde_json::to_string(&json!({ "key": key, "result": result }))
+            .map_err(std::io::Error::other)
+            .and_then(|json| {
+                let dir = self.root.join(RESULTS_DIR);
+                std::fs::create_dir_all(&dir)?;
+                self.prune(&dir, json.len() as u64)?;
+                std::fs::write(&path, json)
+            });
+        if let Err(err) = written {
+            eprintln!("cannot cache {} in {}: {}", method, path.display(), err);
+        }
+    }
+
+    /// Removes the entries of `dir` unused for `max_age`, then the least recently used until
+    /// `incoming` more bytes fit in `max_size`.
+    fn prune(&self, dir: &Path, incoming: u64) -> std::io::Result<()> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            entries.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+        // most recently used first
+        entries.sort_by(|a, b| b.0.cmp(&a.0));
+        let now = SystemTime::now();
+        let mut size = incoming;
+        for (modified, len, path) in entries {
+            let age = now.duration_since(modified).unwrap_or_default();
+            if age >= self.max_age || size + len > self.max_size {
+                std::fs::remove_file(path)?;
+            } else {
+                size += len;
+            }
+        }
+        Ok(())
+    }
+
+    fn key(&self, contents: &str, method: &str, params: &Value) -> Value {
+        json!({
+            "server": self.server,
+            "contents": contents,
+            "method": method,
+            "params": params,
+        })
+    }
+
+    fn path(&self, key: &Value) -> PathBuf {
+        self.root
+            .join(RESULTS_DIR)
+            .join(format!("{}.json", digest(key.to_string().as_bytes())))
+    }
+
+    /// The digest of the paths and contents of every file of the workspace the server
+    /// handles, `None` when they cannot be listed or read.
+    pub fn workspace_contents(&self) -> Option<String> {
+        let files =
+            inventory::workspace_files(&self.root, &self.server, self.extensions.as_deref())
+                .ok()?;
+        let mut hasher = Sha256::new();
+        for file in files {
+            let text = std::fs::read(&file).ok()?;
+            // lengths first, so that no two workspaces hash the same bytes
+            let path = file.to_string_lossy();
+            hasher.update((path.len() as u64).to_le_bytes());
+            hasher.update(path.as_bytes());
+            hasher.update((text.len() as u64).to_le_bytes());
+            hasher.update(&text);
+        }
+        Some(format!("{:x}", hasher.finalize()))
+    }
+}
+
+/// The sha256 digest of `bytes`, in hexadecimal.
+fn digest(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// The digest of the contents of a file.
+pub fn contents(text: &str) -> String {
+    digest(text.as_bytes())
+}
+
+/// The workspace symbols matching `query`, from the cache when the workspace is unchanged.
+pub async fn workspace_symbols(
+    session: &Session,
+    query: &str,
+) -> Result<Vec<WorkspaceSymbol>, CliError> {
+    let cached = session.results.as_ref().and_then(|results| {
+        let contents = results.workspace_contents()?;
+        Some((results, contents))
+    });
+    let params = json!({ "query": query });
+    let method = WorkspaceSymbolRequest::METHOD;
+    if let Some(symbols) = cached
+        .as_ref()
+        .and_then(|(results, contents)| results.get(contents, method, &params))
+    {
+        return Ok(symbols);
+    }
+    let symbols = session
+        .timed(session.server.workspace_symbols(query))
+        .await?;
+    if let Some((results, contents)) = &cached {
+        results.put(contents, method, &params, &symbols);
+    }
+    Ok(symbols)
+}
+
+/// The semantic tokens of the open document `uri`, from the cache when its text is
+/// unchanged.
+pub async fn semantic_tokens(
+    session: &Session,
+    uri: &Url,
+) -> Result<Vec<DecodedSemanticToken>, CliError> {
+    let cached = session
+        .results
+        .as_ref()
+        .zip(session.text(uri).map(|text| contents(&text)));
+    let params = json!({ "textDocument": { "uri": uri } });
+    let method = SemanticTokensFullRequest::METHOD;
+    if let Some(tokens) = cached
+        .as_ref()
+        .and_then(|(results, contents)| results.get(contents, method, &params))
+    {
+        return Ok(tokens);
+    }
+    let tokens = session
+        .timed(session.server.semantic_tokens_full(uri.clone()))
+        .await?;
+    if let Some((results, contents)) = &cached {
+        results.put(contents, method, &params, &tokens);
+    }
+    Ok(tokens)
+}
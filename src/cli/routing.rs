@@ -0,0 +1,242 @@
+//! Runs a command with several config files, each naming the server for some files: a
+//! command about one file runs with the config handling it, a command about many files
+//! starts a server for each group of files and prints what they found together.
+
+use std::path::{Path, PathBuf};
+
+use super::args::{Command, Options};
+use super::config::{Config, Settings};
+use super::output::Outcome;
+use super::script::{self, Step};
+use super::session::Session;
+use super::{check_gathered, check_output, gather, inventory, run_with, server_for, CliError};
+
+/// Runs `command` with the servers of `configs`.
+pub async fn run(options: Options, configs: Vec<Config>, command: Command) -> Result<(), CliError> {
+    if options.server.is_some() {
+        return Err(CliError::Usage(
+            "--server names a single server, it cannot be combined with several --config files"
+                .to_owned(),
+        ));
+    }
+    if options.record.is_some() {
+        return Err(CliError::Usage(
+            "--record records a single server, it cannot be combined with several --config files"
+                .to_owned(),
+        ));
+    }
+    if options.index.is_some() {
+        return Err(CliError::Usage(
+            "--index answers from a single index, it cannot be combined with several --config \
+             files"
+                .to_owned(),
+        ));
+    }
+    let settings = configs
+        .iter()
+        .map(|config| Settings::new(options.clone(), Some(config.clone())))
+        .collect::<Result<Vec<_>, _>>()?;
+    match &command {
+        Command::DumpSymbols { files }
+        | Command::DeadCode { files, .. }
+        | Command::Report { files, .. }
+        | Command::Format { files, .. }
+        | Command::DiagnosticsReport { files, .. }
+        | Command::Lsif { files }
+        | Command::Index { files } => run_grouped(&configs, settings, &command, files).await,
+        command => {
+            let steps = match command {
+                Command::Run { script } => script::parse_script(&std::fs::read_to_string(script)?)?,
+                _ => Vec::new(),
+            };
+            let file = match command {
+                Command::Run { .. } => steps.iter().find_map(Step::file),
+                command => command.file(),
+            };
+            let file = file.ok_or_else(|| {
+                CliError::Usage(
+                    "with several --config files, pass a file to pick the server by".to_owned(),
+                )
+            })?;
+            let index = config_for(&configs, file).ok_or_else(|| {
+                CliError::Usage(format!("no --config handles {}", file.display()))
+            })?;
+            run_with(settings.into_iter().nth(index).unwrap(), command).await
+        }
+    }
+}
+
+/// The index of the first of `configs` handling `file`.
+fn config_for(configs: &[Config], file: &Path) -> Option<usize> {
+    configs.iter().position(|config| config.handles(file))
+}
+
+/// Files handled by the same server.
+struct Group {
+    /// The index of the config naming the server.
+    config: usize,
+    server: Vec<String>,
+    files: Vec<PathBuf>,
+}
+
+/// Splits `files` among the servers of `configs`, in the order the files were given.
+/// Without files, each config contributes the files below its root its server handles.
+fn group(
+    configs: &[Config],
+    settings: &[Settings],
+    files: &[PathBuf],
+) -> Result<Vec<Group>, CliError> {
+    let mut groups: Vec<Group> = Vec::new();
+    if files.is_empty() {
+        for (index, (config, settings)) in configs.iter().zip(settings).enumerate() {
+            let server = settings.server.clone().ok_or_else(|| {
+                CliError::Usage(format!(
+                    "{} names no server to crawl the workspace with, pass files",
+                    config.path.display()
+                ))
+            })?;
+            let root = match &settings.root {
+                Some(root) => root.clone(),
+                None => std::env::current_dir()?,
+            };
+            let files = inventory::workspace_files(&root, &server, settings.extensions.as_deref())?;
+            groups.push(Group {
+                config: index,
+                server,
+                files,
+            });
+        }
+        return Ok(groups);
+    }
+    for file in files {
+        let Some(index) = config_for(configs, file) else {
+            eprintln!("skipping {}: no --config handles it", file.display());
+            continue;
+        };
+        let server = server_for(settings[index].server.clone(), Some(file))?;
+        match groups
+            .iter_mut()
+            .find(|group| group.config == index && group.server == server)
+        {
+            Some(group) => group.files.push(file.clone()),
+            None => groups.push(Group {
+                config: index,
+                server,
+                files: vec![file.clone()],
+            }),
+        }
+    }
+    Ok(groups)
+}
+
+/// Runs a command about many files once per group of files, then prints the outcomes as
+/// one.
+async fn run_grouped(
+    configs: &[Config],
+    settings: Vec<Settings>,
+    command: &Command,
+    files: &[PathBuf],
+) -> Result<(), CliError> {
+    let format = settings[0].output;
+    check_output(command, format)?;
+    let groups = group(configs, &settings, files)?;
+    let several = groups
+        .iter()
+        .filter(|group| !group.files.is_empty())
+        .count()
+        > 1;
+    let mut printer = None;
+    let mut merged: Option<Outcome> = None;
+    for Group {
+        config,
+        server,
+        files,
+    } in groups
+    {
+        if files.is_empty() {
+            continue;
+        }
+        let settings = &settings[config];
+        let root = match &settings.root {
+            Some(root) => root.clone(),
+            None => std::env::current_dir()?,
+        };
+        let mut session = Session::start(
+            &server,
+            &root,
+            settings.initialization_options.clone(),
+            settings.timeouts,
+            settings.trace.as_ref(),
+            None,
+            settings.progress,
+        )
+        .await?;
+        session.style = settings.style;
+        let outcome = gather(&session, command, &files).await;
+        printer.get_or_insert_with(|| session.printer());
+        session.finish().await;
+        let outcome = outcome?;
+        merged = Some(match merged {
+            Some(merged) => merge(merged, outcome),
+            None => outcome,
+        });
+    }
+    let (Some(mut printer), Some(outcome)) = (printer, merged) else {
+        return Err(CliError::Failed(
+            "no --config handles any of the files".to_owned(),
+        ));
+    };
+    if several {
+        // the results come from different servers
+        printer.tool = None;
+    }
+    print!("{}", printer.render(format, &outcome)?);
+    check_gathered(command, &outcome)
+}
+
+/// Appends the results of another server to those found so far, both of the same command.
+fn merge(merged: Outcome, outcome: Outcome) -> Outcome {
+    match (merged, outcome) {
+        (Outcome::Inventory(mut files), Outcome::Inventory(more)) => {
+            files.extend(more);
+            Outcome::Inventory(files)
+        }
+        (Outcome::Unreferenced(mut symbols), Outcome::Unreferenced(more)) => {
+            symbols.extend(more);
+            Outcome::Unreferenced(symbols)
+        }
+        (Outcome::Analysis(mut files), Outcome::Analysis(more)) => {
+            files.extend(more);
+            Outcome::Analysis(files)
+        }
+        (Outcome::DiagnosticsReport(mut files), Outcome::DiagnosticsReport(more)) => {
+            files.extend(more);
+            Outcome::DiagnosticsReport(files)
+        }
+        (Outcome::Formatted { mut files, written }, Outcome::Formatted { files: more, .. }) => {
+            files.extend(more);
+            Outcome::Formatted { files, written }
+        }
+        (Outcome::Lsif(mut index), Outcome::Lsif(more)) => {
+            for (uri, language_id) in more.documents {
+                index.add_document(uri, &language_id);
+            }
+            index.symbols.extend(more.symbols);
+            Outcome::Lsif(index)
+        }
+        (Outcome::Cached(mut caches), Outcome::Cached(more)) => {
+            for summary in more {
+                // servers sharing a root share its cache
+                match caches.iter_mut().find(|cache| cache.path == summary.path) {
+                    Some(cache) => {
+                        cache.files += summary.files;
+                        cache.symbols += summary.symbols;
+                    }
+                    None => caches.push(summary),
+                }
+            }
+            Outcome::Cached(caches)
+        }
+        (merged, outcome) => unreachable!("cannot merge {:?} with {:?}", merged, outcome),
+    }
+}
@@ -0,0 +1,370 @@
+//! The JSON Schema of config files, generated from `Config`, and a validator for it and for
+//! the schemas servers publish for their `initializationOptions`, so a typo in a config is
+//! reported with where it is rather than ignored by the server.
+//!
+//! The validator knows the keywords these schemas use: `type`, `enum`, `const`,
+//! `properties`, `required`, `additionalProperties`, `items`, `minimum`, `maximum`,
+//! `allOf`, `anyOf`, `oneOf` and `$ref` within the schema. Other keywords are not checked.
+
+use std::cell::RefCell;
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+use super::config::Config;
+
+/// The schema of config files, as shipped in `lsp.schema.json`.
+pub fn config_schema() -> Value {
+    serde_json::to_value(schemars::schema_for!(Config)).expect("schemas are JSON")
+}
+
+/// Where a value breaks its schema, and how.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// The JSON pointer to the value, e.g. `/server/1`, empty for the whole document.
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.pointer.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "at {}: {}", self.pointer, self.message)
+        }
+    }
+}
+
+/// How `value` breaks `schema`, nothing when it follows it.
+pub fn validate(schema: &Value, value: &Value) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let validator = Validator {
+        root: schema,
+        followed: RefCell::new(Vec::new()),
+    };
+    validator.check(schema, value, "", &mut violations);
+    violations
+}
+
+struct Validator<'a> {
+    /// The schema `$ref`s point into.
+    root: &'a Value,
+    /// The `$ref`s followed to get to the schema being checked, with the pointer of the
+    /// value they were followed for. Following one again for the same value is a loop.
+    followed: RefCell<Vec<(&'a str, String)>>,
+}
+
+impl<'a> Validator<'a> {
+    /// The schema a `$ref` within the schema points to, `None` for other documents.
+    fn resolve(&self, reference: &str) -> Option<&'a Value> {
+        self.root.pointer(reference.strip_prefix('#')?)
+    }
+
+    fn check(
+        &self,
+        schema: &'a Value,
+        value: &Value,
+        pointer: &str,
+        violations: &mut Vec<Violation>,
+    ) {
+        let violation = |message: String| Violation {
+            pointer: pointer.to_owned(),
+            message,
+        };
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => {
+                return violations.push(violation("no value is allowed here".to_owned()))
+            }
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            if let Some(target) = self.resolve(reference) {
+                let step = (reference, pointer.to_owned());
+                if self.followed.borrow().contains(&step) {
+                    return violations.push(violation(format!(
+                        "the schema loops through {} here",
+                        reference
+                    )));
+                }
+                self.followed.borrow_mut().push(step);
+                self.check(target, value, pointer, violations);
+                self.followed.borrow_mut().pop();
+            }
+        }
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&str> = match types {
+                Value::String(name) => vec![name],
+                Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !types.iter().any(|name| has_type(value, name)) {
+                let expected: Vec<String> = types.iter().map(|name| type_name(name)).collect();
+                return violations.push(violation(format!(
+                    "expected {}, found {}",
+                    alternatives(&expected),
+                    found(value)
+                )));
+            }
+        }
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+            if !allowed.contains(value) {
+                let expected: Vec<String> = allowed.iter().map(Value::to_string).collect();
+                return violations.push(violation(format!(
+                    "expected {}, found {}",
+                    alternatives(&expected),
+                    found(value)
+                )));
+            }
+        }
+        if let Some(constant) = schema.get("const") {
+            if constant != value {
+                return violations.push(violation(format!(
+                    "expected {}, found {}",
+                    constant,
+                    found(value)
+                )));
+            }
+        }
+        if let (Some(minimum), Some(number)) = (
+            schema.get("minimum").and_then(Value::as_f64),
+            value.as_f64(),
+        ) {
+            if number < minimum {
+                violations.push(violation(format!(
+                    "expected at least {}, found {}",
+                    minimum, number
+                )));
+            }
+        }
+        if let (Some(maximum), Some(number)) = (
+            schema.get("maximum").and_then(Value::as_f64),
+            value.as_f64(),
+        ) {
+            if number > maximum {
+                violations.push(violation(format!(
+                    "expected at most {}, found {}",
+                    maximum, number
+                )));
+            }
+        }
+        match value {
+            Value::Object(object) => self.check_object(schema, object, pointer, violations),
+            Value::Array(items) => {
+                if let Some(item_schema) = schema.get("items") {
+                    for (index, item) in items.iter().enumerate() {
+                        let pointer = format!("{}/{}", pointer, index);
+                        self.check(item_schema, item, &pointer, violations);
+                    }
+                }
+            }
+            _ => {}
+        }
+        if let Some(branches) = schema.get("allOf").and_then(Value::as_array) {
+            for branch in branches {
+                self.check(branch, value, pointer, violations);
+            }
+        }
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(branches) = schema.get(keyword).and_then(Value::as_array) {
+                self.check_branches(branches, value, pointer, violations);
+            }
+        }
+    }
+
+    fn check_object(
+        &self,
+        schema: &'a Map<String, Value>,
+        object: &Map<String, Value>,
+        pointer: &str,
+        violations: &mut Vec<Violation>,
+    ) {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    violations.push(Violation {
+                        pointer: pointer.to_owned(),
+                        message: format!("missing field \"{}\"", name),
+                    });
+                }
+            }
+        }
+        for (name, value) in object {
+            let pointer = format!("{}/{}", pointer, name.replace('~', "~0").replace('/', "~1"));
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(property) => self.check(property, value, &pointer, violations),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        let known = properties
+                            .into_iter()
+                            .flat_map(|properties| properties.keys());
+                        let message = match closest(name, known) {
+                            Some(known) => {
+                                format!("unknown field \"{}\", did you mean \"{}\"?", name, known)
+                            }
+                            None => format!("unknown field \"{}\"", name),
+                        };
+                        violations.push(Violation { pointer, message });
+                    }
+                    Some(additional) => self.check(additional, value, &pointer, violations),
+                    None => {}
+                },
+            }
+        }
+    }
+
+    /// Checks `value` against the branches of an `anyOf` or a `oneOf`. Unless one matches,
+    /// the violations of a branch the value got into are reported, or the values every
+    /// branch expects when it fits none of them.
+    fn check_branches(
+        &self,
+        branches: &'a [Value],
+        value: &Value,
+        pointer: &str,
+        violations: &mut Vec<Violation>,
+    ) {
+        let mut failures = Vec::new();
+        for branch in branches {
+            let mut branch_violations = Vec::new();
+            self.check(branch, value, pointer, &mut branch_violations);
+            if branch_violations.is_empty() {
+                return;
+            }
+            failures.push(branch_violations);
+        }
+        // a branch failing deeper than here is the one the value was meant for
+        if let Some(deeper) = failures.into_iter().find(|failure| {
+            failure
+                .iter()
+                .all(|violation| violation.pointer.len() > pointer.len())
+        }) {
+            violations.extend(deeper);
+            return;
+        }
+        let expected: Vec<String> = branches
+            .iter()
+            .flat_map(|branch| self.describe(branch, &mut Vec::new()))
+            .collect();
+        violations.push(Violation {
+            pointer: pointer.to_owned(),
+            message: format!(
+                "expected {}, found {}",
+                alternatives(&expected),
+                found(value)
+            ),
+        });
+    }
+
+    /// The values `schema` allows, in words, e.g. `"text"` or `an array`. `followed` holds
+    /// the `$ref`s followed to get to `schema`, any value being allowed where they loop.
+    fn describe(&self, schema: &'a Value, followed: &mut Vec<&'a str>) -> Vec<String> {
+        let Some(schema) = schema.as_object() else {
+            return vec!["any value".to_owned()];
+        };
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            if let Some(target) = self.resolve(reference) {
+                if followed.contains(&reference) {
+                    return vec!["any value".to_owned()];
+                }
+                followed.push(reference);
+                let described = self.describe(target, followed);
+                followed.pop();
+                return described;
+            }
+        }
+        if let Some(constant) = schema.get("const") {
+            return vec![constant.to_string()];
+        }
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+            return allowed.iter().map(Value::to_string).collect();
+        }
+        if let Some(branches) = schema
+            .get("anyOf")
+            .or_else(|| schema.get("oneOf"))
+            .and_then(Value::as_array)
+        {
+            return branches
+                .iter()
+                .flat_map(|branch| self.describe(branch, followed))
+                .collect();
+        }
+        match schema.get("type") {
+            Some(Value::String(name)) => vec![type_name(name)],
+            Some(Value::Array(names)) => names
+                .iter()
+                .filter_map(Value::as_str)
+                .map(type_name)
+                .collect(),
+            _ => vec!["any value".to_owned()],
+        }
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+/// A JSON Schema type in words.
+fn type_name(name: &str) -> String {
+    match name {
+        "null" => "null".to_owned(),
+        "object" | "array" | "integer" => format!("an {}", name),
+        _ => format!("a {}", name),
+    }
+}
+
+/// `value` in a message: scalars as they are, objects and arrays by their type.
+fn found(value: &Value) -> String {
+    match value {
+        Value::Object(_) => "an object".to_owned(),
+        Value::Array(_) => "an array".to_owned(),
+        _ => value.to_string(),
+    }
+}
+
+/// `a, b or c`.
+fn alternatives(items: &[String]) -> String {
+    match items {
+        [] => "nothing".to_owned(),
+        [item] => item.clone(),
+        [rest @ .., last] => format!("{} or {}", rest.join(", "), last),
+    }
+}
+
+/// The name among `known` `name` is most likely a typo of, if any is close enough.
+fn closest<'k>(name: &str, known: impl Iterator<Item = &'k String>) -> Option<&'k String> {
+    known
+        .map(|known| (distance(&name.to_lowercase(), &known.to_lowercase()), known))
+        .filter(|&(distance, known)| distance <= 2.max(known.len() / 4))
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, known)| known)
+}
+
+/// The Levenshtein distance between `a` and `b`, in characters.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
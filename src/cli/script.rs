@@ -0,0 +1,224 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::args::{LineColumn, Query, Target};
+use super::output::OutputFormat;
+use super::repl::{self, ReplCommand};
+use super::session::Session;
+use super::CliError;
+
+/// A command on a line of a script, e.g.
+///
+/// ```json
+/// {"command": "definition", "file": "src/app.ts", "line": 10, "column": 4}
+/// {"command": "references", "file": "src/app.ts", "symbol": "App.render"}
+/// ```
+///
+/// Lines without a `command` are JSON-RPC messages sent as is, see `raw` in the REPL.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "command", rename_all = "camelCase", deny_unknown_fields)]
+enum ScriptCommand {
+    Open {
+        file: PathBuf,
+    },
+    Definition {
+        file: PathBuf,
+        line: Option<u32>,
+        column: Option<u32>,
+        symbol: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    References {
+        file: PathBuf,
+        line: Option<u32>,
+        column: Option<u32>,
+        symbol: Option<String>,
+        #[serde(default = "default_true")]
+        include_declaration: bool,
+    },
+    Hover {
+        file: PathBuf,
+        line: Option<u32>,
+        column: Option<u32>,
+        symbol: Option<String>,
+    },
+    Symbols {
+        file: PathBuf,
+    },
+    Diagnostics {
+        file: PathBuf,
+    },
+    #[serde(rename_all = "camelCase")]
+    Rename {
+        file: PathBuf,
+        line: Option<u32>,
+        column: Option<u32>,
+        symbol: Option<String>,
+        new_name: String,
+        #[serde(default)]
+        apply: bool,
+    },
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// A command of a script with the line it is on, counting from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub line: usize,
+    pub command: ReplCommand,
+}
+
+impl Step {
+    /// The file the step works on, if any.
+    pub fn file(&self) -> Option<&Path> {
+        match &self.command {
+            ReplCommand::Query(query) => Some(query.file()),
+            ReplCommand::Open(file) => Some(file),
+            _ => None,
+        }
+    }
+}
+
+/// The target of a step, given either by `line` and `column` or by `symbol`.
+fn target(
+    line: Option<u32>,
+    column: Option<u32>,
+    symbol: Option<String>,
+) -> Result<Target, CliError> {
+    match (line, column, symbol) {
+        (Some(line), Some(column), None) => {
+            LineColumn::parse(&format!("{}:{}", line, column)).map(Target::At)
+        }
+        (None, None, Some(symbol)) => Ok(Target::Symbol(symbol)),
+        _ => Err(CliError::Usage(
+            "expected either \"line\" and \"column\" or \"symbol\"".to_owned(),
+        )),
+    }
+}
+
+impl ScriptCommand {
+    fn into_command(self) -> Result<ReplCommand, CliError> {
+        let query = match self {
+            ScriptCommand::Open { file } => return Ok(ReplCommand::Open(file)),
+            ScriptCommand::Definition {
+                file,
+                line,
+                column,
+                symbol,
+            } => Query::Definition {
+                file,
+                target: target(line, column, symbol)?,
+            },
+            ScriptCommand::References {
+                file,
+                line,
+                column,
+                symbol,
+                include_declaration,
+            } => Query::References {
+                file,
+                target: target(line, column, symbol)?,
+                include_declaration,
+            },
+            ScriptCommand::Hover {
+                file,
+                line,
+                column,
+                symbol,
+            } => Query::Hover {
+                file,
+                target: target(line, column, symbol)?,
+            },
+            ScriptCommand::Symbols { file } => Query::Symbols { file },
+            ScriptCommand::Diagnostics { file } => Query::Diagnostics {
+                file,
+                watch: false,
+                fail_on: None,
+                changed_since: None,
+            },
+            ScriptCommand::Rename {
+                file,
+                line,
+                column,
+                symbol,
+                new_name,
+                apply,
+            } => Query::Rename {
+                file,
+                target: target(line, column, symbol)?,
+                new_name,
+                apply,
+            },
+        };
+        Ok(ReplCommand::Query(query))
+    }
+}
+
+/// Parses a script, one JSON object per line. Blank lines and lines starting with `//` are
+/// skipped.
+pub fn parse_script(script: &str) -> Result<Vec<Step>, CliError> {
+    let mut steps = Vec::new();
+    for (index, text) in script.lines().enumerate() {
+        let line = index + 1;
+        let text = text.trim();
+        if text.is_empty() || text.starts_with("//") {
+            continue;
+        }
+        let invalid = |message: String| CliError::Usage(format!("line {}: {}", line, message));
+        let value: Value = serde_json::from_str(text).map_err(|err| invalid(err.to_string()))?;
+        let command = if value.get("command").is_some() {
+            serde_json::from_value::<ScriptCommand>(value)
+                .map_err(|err| invalid(err.to_string()))?
+                .into_command()
+                .map_err(|err| match err {
+                    CliError::Usage(message) => invalid(message),
+                    err => err,
+                })?
+        } else if value.get("method").is_some() {
+            repl::parse_line(&format!("raw {}", text))?
+        } else {
+            return Err(invalid("expected a \"command\" or a \"method\"".to_owned()));
+        };
+        steps.push(Step { line, command });
+    }
+    Ok(steps)
+}
+
+/// Runs the steps of a script in order, printing the result of each as soon as it is
+/// known. A failing step does not stop the script; the failures are counted instead.
+///
+/// With `--output json` every step prints one line, `{"line": 3, "result": ...}` or
+/// `{"line": 3, "error": "..."}`, where the result is what the query prints on its own.
+pub async fn run(session: &Session, steps: &[Step], format: OutputFormat) -> Result<(), CliError> {
+    let mut failed = 0;
+    for step in steps {
+        let result = repl::execute(session, step.command.clone(), format).await;
+        if result.is_err() {
+            failed += 1;
+        }
+        match (format, result) {
+            (OutputFormat::Json, Ok(output)) => {
+                let result: Value = serde_json::from_str(&output).unwrap_or(Value::Null);
+                println!("{}", json!({ "line": step.line, "result": result }));
+            }
+            (OutputFormat::Json, Err(err)) => {
+                println!("{}", json!({ "line": step.line, "error": err.message() }));
+            }
+            (_, Ok(output)) => print!("{}", output),
+            (_, Err(err)) => eprintln!("line {}: {}", step.line, err.message()),
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        failed => Err(CliError::Failed(format!(
+            "{} of {} steps failed",
+            failed,
+            steps.len()
+        ))),
+    }
+}
@@ -0,0 +1,481 @@
+use std::fs::File;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lsp_types::{
+    Diagnostic, InitializeParams, NumberOrString, OneOf, Position, Range, TraceValue, Url,
+    WorkDoneProgressParams, WorkspaceFolder,
+};
+use serde_json::Value;
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::time;
+
+use super::args::{LineColumn, Target};
+use super::cache::SymbolCache;
+use super::languages;
+use super::output::{Printer, Style};
+use super::progress::ProgressBars;
+use super::record::{Record, Recorder};
+use super::results::{self, ResultCache};
+use super::CliError;
+use crate::lsp::capabilities::client_capabilities;
+use crate::lsp::client::{
+    start_language_server, ClientError, Direction, LanguageServerRef, RequestTimeouts,
+};
+use crate::lsp::diagnostics::{PullDiagnostics, PushDiagnostics};
+use crate::lsp::documents::DocumentManager;
+use crate::lsp::frame_log::FrameLog;
+use crate::lsp::readiness::Readiness;
+use crate::lsp::symbols::{self, DocumentSymbolNode};
+use crate::lsp::text_utils::{self, PositionEncoding};
+
+/// How long the server must stay idle before it counts as done loading.
+const READY_SETTLE: Duration = Duration::from_millis(500);
+
+/// How long to wait for a server that pushes diagnostics to publish them.
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the server gets to answer `shutdown`, then to exit, before it is killed.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Converts a path given on the command line into a `file://` uri.
+pub fn file_uri(path: &Path) -> Result<Url, CliError> {
+    let path = path.canonicalize()?;
+    Url::from_file_path(&path)
+        .map_err(|_| CliError::Usage(format!("{} is not a local file", path.display())))
+}
+
+/// How long the CLI waits for the server, see `--startup-timeout`, `--wait-for-index` and
+/// `--timeout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// For the answer to `initialize`.
+    pub startup: Duration,
+    /// For the server to finish loading the project once a file is opened, after which
+    /// queries are sent anyway. Zero does not wait at all.
+    pub index: Duration,
+    /// For the answer to each query.
+    pub request: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Timeouts {
+        Timeouts {
+            startup: Duration::from_secs(30),
+            index: Duration::from_secs(60),
+            request: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Where `--trace-lsp` and `--log-frames` write the messages exchanged with the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trace {
+    Stderr,
+    /// Appended to the file.
+    File(PathBuf),
+    /// Appended to the file as a frame log, one line of JSON per message, see
+    /// `lsp::frame_log`.
+    Frames(PathBuf),
+}
+
+impl Trace {
+    /// A traffic handler writing every message the server sends and receives, one per line
+    /// with the time since the server started and the direction.
+    fn handler(&self) -> Result<impl Fn(Direction, &str) + Send + Sync + 'static, CliError> {
+        let writer: Box<dyn Write + Send> = match self {
+            Trace::Stderr => Box::new(std::io::stderr()),
+            Trace::File(path) => Box::new(File::options().create(true).append(true).open(path)?),
+            Trace::Frames(_) => unreachable!("frame logs are written by the client"),
+        };
+        let writer = Mutex::new(writer);
+        let start = Instant::now();
+        Ok(move |direction, message: &str| {
+            let direction = match direction {
+                Direction::Sent => "client -> server",
+                Direction::Received => "server -> client",
+            };
+            let mut writer = writer.lock().unwrap();
+            let time = start.elapsed().as_secs_f64();
+            if let Err(err) = writeln!(writer, "[{:>9.3}s] {}: {}", time, direction, message) {
+                eprintln!("failed to write the trace: {}", err);
+            }
+        })
+    }
+}
+
+/// A language server started for one invocation of the CLI.
+pub struct Session {
+    child: Child,
+    pub server: LanguageServerRef<ChildStdin>,
+    pub documents: DocumentManager<ChildStdin>,
+    pub diagnostics: PushDiagnostics,
+    /// The workspace folder the server was initialized with.
+    pub root: PathBuf,
+    /// How the printers of the session render text, see `--color` and `--context`.
+    pub style: Style,
+    /// The folder paths are printed relative to, the current directory by default.
+    pub base: Option<PathBuf>,
+    timeouts: Timeouts,
+    recorder: Option<Recorder>,
+    /// Whether to draw the progress the server reports while waiting for it, see
+    /// `progress`.
+    progress: bool,
+    /// The results of slow queries kept between invocations, none with `--no-cache`.
+    pub results: Option<ResultCache>,
+}
+
+impl Session {
+    /// Starts `command` and initializes it with `root` as the only workspace folder. The
+    /// traffic is traced and recorded when `trace` and `record` say so, and the progress
+    /// the server reports is drawn with `progress`.
+    pub async fn start(
+        command: &[String],
+        root: &Path,
+        initialization_options: Option<Value>,
+        timeouts: Timeouts,
+        trace: Option<&Trace>,
+        record: Option<&Record>,
+        progress: bool,
+    ) -> Result<Session, CliError> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| CliError::Usage("empty server command".to_owned()))?;
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| CliError::Server(format!("failed to start {}: {}", program, err)))?;
+        let (child, server) = start_language_server(child).await;
+        let trace = match trace {
+            Some(Trace::Frames(path)) => {
+                server.log_frames(FrameLog::create(path)?);
+                None
+            }
+            trace => trace.map(Trace::handler).transpose()?,
+        };
+        let recorder = record.cloned().map(Recorder::new);
+        let recording = recorder.as_ref().map(Recorder::handler);
+        if trace.is_some() || recording.is_some() {
+            server.on_traffic(move |direction, message| {
+                if let Some(trace) = &trace {
+                    trace(direction, message);
+                }
+                if let Some(recording) = &recording {
+                    recording(direction, message);
+                }
+            });
+        }
+        // the server may publish as soon as it is initialized
+        let diagnostics = PushDiagnostics::new(&server);
+
+        let root = root.canonicalize()?;
+        let root_uri = Url::from_directory_path(&root)
+            .map_err(|_| CliError::Usage(format!("{} is not a local folder", root.display())))?;
+        let name = root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        #[allow(deprecated)]
+        let params = InitializeParams {
+            // servers exit on their own once this process is gone, which is not wanted
+            process_id: None,
+            root_uri: Some(root_uri.clone()),
+            root_path: None,
+            initialization_options,
+            capabilities: client_capabilities(),
+            trace: Some(TraceValue::Off),
+            client_info: None,
+            locale: None,
+            workspace_folders: Some(vec![WorkspaceFolder {
+                uri: root_uri,
+                name,
+            }]),
+            work_done_progress_params: WorkDoneProgressParams {
+                // for the server to report the progress of initializing, which is drawn
+                work_done_token: progress.then(|| NumberOrString::String("initialize".to_owned())),
+            },
+        };
+        let bars = progress.then(|| ProgressBars::show(server.server_progress()));
+        let initialized = time::timeout(timeouts.startup, server.initialize(params)).await;
+        drop(bars);
+        match initialized {
+            Ok(result) => result?,
+            Err(_) => {
+                return Err(CliError::Server(format!(
+                    "{} did not answer initialize within {:?}, see --startup-timeout",
+                    program, timeouts.startup
+                )))
+            }
+        };
+        // from now on, for requests to be canceled on the server once they time out
+        server.set_request_timeouts(RequestTimeouts::new(timeouts.request));
+
+        Ok(Session {
+            child,
+            documents: DocumentManager::new(server.clone()),
+            server,
+            diagnostics,
+            root,
+            style: Style::default(),
+            base: std::env::current_dir().ok(),
+            timeouts,
+            recorder,
+            progress,
+            results: None,
+        })
+    }
+
+    /// Opens a file on the server and waits for the server to finish loading the project
+    /// it belongs to. Returns the file's uri.
+    ///
+    /// A file opened before is synced with its contents on disk instead.
+    pub async fn open(&self, path: &Path) -> Result<Url, CliError> {
+        let uri = file_uri(path)?;
+        let opened = !self.documents.is_open(&uri);
+        self.sync(path).await?;
+        if opened {
+            self.wait_for_index().await;
+        }
+        Ok(uri)
+    }
+
+    /// Waits for the server to finish loading the project, up to `--wait-for-index`,
+    /// warning when it is still busy after that.
+    pub async fn wait_for_index(&self) {
+        if self.timeouts.index.is_zero() {
+            return;
+        }
+        let readiness = Readiness::Progress {
+            settle: READY_SETTLE,
+        };
+        let bars = self
+            .progress
+            .then(|| ProgressBars::show(self.server.server_progress()));
+        let ready = self
+            .server
+            .wait_until_ready(readiness, self.timeouts.index)
+            .await;
+        drop(bars);
+        if ready.is_err() {
+            eprintln!(
+                "the server is still busy after {:?}, results may be incomplete, see --wait-for-index",
+                self.timeouts.index
+            );
+        }
+    }
+
+    /// Waits for the answer to requests sent to the server, failing once one of them went
+    /// unanswered for `--timeout`, which the client cancels on the server.
+    pub async fn timed<T>(
+        &self,
+        request: impl Future<Output = Result<T, ClientError>>,
+    ) -> Result<T, CliError> {
+        match request.await {
+            Ok(result) => Ok(result),
+            Err(ClientError::Timeout) => {
+                let mut message = format!(
+                    "the server did not answer within {:?}, see --timeout",
+                    self.timeouts.request
+                );
+                let pending = self.server.pending_requests();
+                if !pending.is_empty() {
+                    let pending: Vec<_> = pending.iter().map(ToString::to_string).collect();
+                    message += &format!("; still waiting on {}", pending.join(", "));
+                }
+                Err(CliError::Server(message))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Opens a file on the server, or syncs it with its contents on disk when it is open,
+    /// without waiting for the server to load the project. Returns the file's uri.
+    pub async fn sync(&self, path: &Path) -> Result<Url, CliError> {
+        let uri = file_uri(path)?;
+        let text = tokio::fs::read_to_string(path).await?;
+        if let Some(document) = self.documents.get(&uri) {
+            if document.text != text {
+                self.documents.change(&uri, text).await;
+            }
+            return Ok(uri);
+        }
+        let language_id = languages::language_of(path).map_or("plaintext", |language| language.id);
+        self.documents.open(uri.clone(), language_id, text).await;
+        Ok(uri)
+    }
+
+    /// The encoding of the `character` of positions exchanged with the server.
+    pub fn encoding(&self) -> PositionEncoding {
+        self.server.capabilities().position_encoding()
+    }
+
+    /// The diagnostics of an open document, pulled when the server supports it. Otherwise
+    /// they are the diagnostics the server publishes after it published `published` times,
+    /// see `PushDiagnostics::publications`, or the last published ones if it takes too
+    /// long.
+    pub async fn current_diagnostics(
+        &self,
+        uri: &Url,
+        published: usize,
+    ) -> Result<Vec<Diagnostic>, CliError> {
+        if self
+            .server
+            .capabilities()
+            .supports("textDocument/diagnostic")
+        {
+            let pull = PullDiagnostics::new(self.server.clone());
+            return self.timed(pull.document(uri.clone())).await;
+        }
+        // servers without pull diagnostics publish them once the document is checked
+        let waited = self.diagnostics.wait_for_publication(uri, published);
+        match time::timeout(PUBLISH_TIMEOUT, waited).await {
+            Ok(Some(diagnostics)) => Ok(diagnostics),
+            Ok(None) | Err(_) => Ok(self.diagnostics.get(uri).unwrap_or_default()),
+        }
+    }
+
+    /// A printer for results this server sent.
+    pub fn printer(&self) -> Printer {
+        let mut printer = Printer::new(self.encoding());
+        printer.style = self.style;
+        printer.base = self.base.clone();
+        printer.tool = self
+            .server
+            .initialize_result()
+            .and_then(|result| result.server_info)
+            .map(|info| info.name);
+        printer
+    }
+
+    /// The text of a document, from the server's copy when it is open.
+    pub fn text(&self, uri: &Url) -> Option<String> {
+        match self.documents.get(uri) {
+            Some(document) => Some(document.text),
+            None => std::fs::read_to_string(uri.to_file_path().ok()?).ok(),
+        }
+    }
+
+    /// Converts a user given position into a position within an open document.
+    pub fn position(&self, uri: &Url, at: LineColumn) -> Position {
+        let text = self.text(uri).unwrap_or_default();
+        let chars = Position::new(at.line - 1, at.column - 1);
+        let offset = text_utils::position_to_offset(&text, chars, PositionEncoding::Utf32);
+        text_utils::offset_to_position(&text, offset, self.encoding())
+    }
+
+    /// Resolves the target of a query about the open document `uri` into a document and a
+    /// position within it.
+    ///
+    /// A symbol path is looked up in the symbols of `uri` first, then in the cache `index`
+    /// wrote, then through `workspace/symbol`, opening the file the symbol is found in.
+    /// When several symbols match, the first one is used.
+    pub async fn locate(&self, uri: &Url, target: &Target) -> Result<(Url, Position), CliError> {
+        let path = match target {
+            Target::At(at) => return Ok((uri.clone(), self.position(uri, *at))),
+            Target::Symbol(path) => path,
+        };
+        let segments: Vec<&str> = path.split('.').collect();
+        let symbols = self.document_symbols(uri).await?;
+        let found = symbols::symbols_at_path(&symbols, &segments);
+        if let Some(symbol) = found.first() {
+            if found.len() > 1 {
+                eprintln!(
+                    "{} matches {} symbols, using the one on line {}",
+                    path,
+                    found.len(),
+                    symbol.selection_range.start.line + 1
+                );
+            }
+            return Ok((uri.clone(), symbol.selection_range.start));
+        }
+
+        // the cache `index` wrote knows the symbols of every file
+        let cache = SymbolCache::load(&self.root);
+        if let Some((cached, symbol)) = cache.find(&segments).first() {
+            if let Ok(file) = cached.uri.to_file_path() {
+                let uri = self.open(&file).await?;
+                let text = self.text(&uri).unwrap_or_default();
+                let start = symbol.selection_range.start;
+                let offset = text_utils::position_to_offset(&text, start, cached.encoding);
+                return Ok((
+                    uri,
+                    text_utils::offset_to_position(&text, offset, self.encoding()),
+                ));
+            }
+        }
+
+        let (name, containers) = segments.split_last().expect("split yields a segment");
+        let candidates = match results::workspace_symbols(self, name).await {
+            Ok(candidates) => candidates,
+            Err(CliError::Client(ClientError::Unsupported(_))) => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        let mut locations = candidates.into_iter().filter_map(|candidate| {
+            let container = candidate.container_name.as_deref().unwrap_or_default();
+            // containers may be qualified, e.g. `shapes.Shape` or `shapes::Shape`
+            let container = container.rsplit(['.', ':']).next().unwrap_or_default();
+            let matches = symbols::symbol_name_matches(&candidate.name, name)
+                && containers
+                    .last()
+                    .is_none_or(|last| symbols::symbol_name_matches(container, last));
+            match candidate.location {
+                OneOf::Left(location) if matches => Some(location),
+                _ => None,
+            }
+        });
+        let location = locations
+            .next()
+            .ok_or_else(|| CliError::Failed(format!("no symbol {} found", path)))?;
+        let Ok(file) = location.uri.to_file_path() else {
+            return Ok((location.uri, location.range.start));
+        };
+
+        // workspace symbols span the whole declaration, the document symbol gives its name
+        let uri = self.open(&file).await?;
+        let start = Range::new(location.range.start, location.range.start);
+        let symbols = self.document_symbols(&uri).await?;
+        let position = symbols::symbols_at_path(&symbols, &segments)
+            .into_iter()
+            .find(|symbol| symbols::range_contains(&symbol.range, &start))
+            .map_or(location.range.start, |symbol| symbol.selection_range.start);
+        Ok((uri, position))
+    }
+
+    /// The symbols of a document, none when the server does not support them.
+    async fn document_symbols(&self, uri: &Url) -> Result<Vec<DocumentSymbolNode>, CliError> {
+        match self.timed(self.server.document_symbols(uri.clone())).await {
+            Ok(symbols) => Ok(symbols),
+            Err(CliError::Client(ClientError::Unsupported(_))) => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Whether the server has exited, e.g. after crashing.
+    pub fn exited(&mut self) -> bool {
+        !matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Shuts the server down, killing it if it does not exit in time, then saves the
+    /// recording of the session.
+    pub async fn finish(mut self) {
+        let shutdown = time::timeout(EXIT_TIMEOUT, self.server.shutdown()).await;
+        let exited = matches!(shutdown, Ok(Ok(())))
+            && time::timeout(EXIT_TIMEOUT, self.child.wait()).await.is_ok();
+        if !exited {
+            let _ = self.child.kill().await;
+        }
+        if let Some(recorder) = &self.recorder {
+            if let Err(err) = recorder.save() {
+                eprintln!("failed to save the recording: {}", err);
+            }
+        }
+    }
+}
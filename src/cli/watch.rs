@@ -0,0 +1,70 @@
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use tokio::time;
+
+use super::output::{Outcome, OutputFormat};
+use super::session::Session;
+use super::CliError;
+
+/// How often the watched file is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// When a file was last modified, `None` while it cannot be read.
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Prints the diagnostics of `file`, then again every time it changes on disk, until
+/// interrupted with ctrl-c. Changes are sent to the server through `didChange`.
+///
+/// The file is polled rather than watched through the OS, which works the same on every
+/// platform and file system. JSON and SARIF are printed one document per line, so the
+/// output can be consumed as a stream.
+pub async fn run(session: &Session, file: &Path, format: OutputFormat) -> Result<(), CliError> {
+    let uri = session.open(file).await?;
+    let mut published = 0;
+    loop {
+        let diagnostics = session.current_diagnostics(&uri, published).await?;
+        let outcome = Outcome::Diagnostics {
+            uri: uri.clone(),
+            diagnostics,
+        };
+        let printer = session.printer();
+        match (format, &outcome) {
+            (OutputFormat::Json, _) => println!("{}", printer.json(&outcome)),
+            (OutputFormat::Sarif, Outcome::Diagnostics { uri, diagnostics }) => {
+                println!("{}", printer.sarif(uri, diagnostics))
+            }
+            _ => {
+                print!("{}", printer.render(format, &outcome)?);
+                eprintln!("-- watching {} for changes", printer.path(&uri));
+            }
+        }
+
+        // waits for a change of the contents, touching the file is not enough
+        let mut last_modified = modified(file);
+        loop {
+            tokio::select! {
+                _ = time::sleep(POLL_INTERVAL) => {}
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+            }
+            let now = modified(file);
+            if now == last_modified {
+                continue;
+            }
+            last_modified = now;
+            let Ok(text) = tokio::fs::read_to_string(file).await else {
+                // e.g. deleted and not yet written again by an editor saving atomically
+                continue;
+            };
+            if session.text(&uri).as_deref() != Some(text.as_str()) {
+                break;
+            }
+        }
+        published = session.diagnostics.publications(&uri);
+        session.open(file).await?;
+    }
+}
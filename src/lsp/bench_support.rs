@@ -0,0 +1,107 @@
+//! Synthetic traffic for measuring how fast the client reads and dispatches what servers
+//! send, see the benchmarks in `benches/`. The messages mimic the chattiest and largest a
+//! server sends: progress and diagnostics notifications, semantic tokens and workspace
+//! symbols. Behind the `bench_support` feature.
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncWriteExt, Sink};
+
+use super::client::{self, LanguageServerRef, UnknownResponsePolicy};
+
+/// `bodies` framed as a server writes them, one after the other.
+pub fn framed(bodies: &[String]) -> Vec<u8> {
+    let mut stream = Vec::new();
+    for body in bodies {
+        stream.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+        stream.extend_from_slice(body.as_bytes());
+    }
+    stream
+}
+
+/// A `$/progress` report, the `n`th of an indexing run.
+pub fn progress_notification(n: usize) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "$/progress",
+        "params": {
+            "token": "indexing",
+            "value": {
+                "kind": "report",
+                "message": format!("{}/10000 src/module_{}.rs", n, n),
+                "percentage": n % 100,
+            },
+        },
+    })
+    .to_string()
+}
+
+/// A `textDocument/publishDiagnostics` notification with `count` diagnostics.
+pub fn diagnostics_notification(count: usize) -> String {
+    let diagnostics: Vec<Value> = (0..count)
+        .map(|line| {
+            json!({
+                "range": {
+                    "start": { "line": line, "character": 4 },
+                    "end": { "line": line, "character": 16 },
+                },
+                "severity": 2,
+                "code": "unused_variables",
+                "source": "rustc",
+                "message": format!("unused variable: `value_{}`", line),
+            })
+        })
+        .collect();
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": "file:///project/src/main.rs", "diagnostics": diagnostics },
+    })
+    .to_string()
+}
+
+/// The response `id` to `textDocument/semanticTokens/full`, with `tokens` tokens.
+pub fn semantic_tokens_response(id: usize, tokens: usize) -> String {
+    let data: Vec<usize> = (0..tokens)
+        .flat_map(|token| [token % 2, token % 40, 1 + token % 12, token % 20, 0])
+        .collect();
+    json!({ "jsonrpc": "2.0", "id": id, "result": { "resultId": "1", "data": data } }).to_string()
+}
+
+/// The response `id` to `workspace/symbol`, with `count` symbols.
+pub fn workspace_symbols_response(id: usize, count: usize) -> String {
+    let symbols: Vec<Value> = (0..count)
+        .map(|n| {
+            json!({
+                "name": format!("symbol_{}", n),
+                "kind": 12,
+                "location": {
+                    "uri": format!("file:///project/src/module_{}.rs", n % 100),
+                    "range": {
+                        "start": { "line": n, "character": 0 },
+                        "end": { "line": n, "character": 20 },
+                    },
+                },
+                "containerName": format!("module_{}", n % 100),
+            })
+        })
+        .collect();
+    json!({ "jsonrpc": "2.0", "id": id, "result": symbols }).to_string()
+}
+
+/// A client talking to no server, for `dispatch`. Responses to no request are ignored
+/// rather than reported, so responses can be dispatched without sending requests. Must be
+/// called within a Tokio runtime.
+pub fn client() -> LanguageServerRef<Sink> {
+    let server = client::connect(tokio::io::empty(), tokio::io::sink());
+    server.set_unknown_response_policy(UnknownResponsePolicy::Ignore);
+    server
+}
+
+/// Dispatches `body` as if `server` had read it, returning the response to write back when
+/// it was a request.
+pub fn dispatch<W: AsyncWriteExt + Unpin>(
+    server: &LanguageServerRef<W>,
+    body: &str,
+) -> Option<Value> {
+    server.handle_msg(body)
+}
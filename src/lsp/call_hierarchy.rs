@@ -0,0 +1,205 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+
+use lsp_types::request::{
+    CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare,
+};
+use lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams, CallHierarchyItem,
+    CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+    Position, Range, Url,
+};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::navigation::text_document_position;
+
+/// Which way to follow calls when expanding a call graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CallDirection {
+    /// Follow callers of each item.
+    Incoming,
+    /// Follow the callees of each item.
+    Outgoing,
+}
+
+/// A call from `caller` to `callee`, both indices into `CallGraph::nodes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallEdge {
+    pub caller: usize,
+    pub callee: usize,
+    /// The call sites, as ranges within the caller.
+    pub from_ranges: Vec<Range>,
+}
+
+/// A call graph with each function, method or constructor appearing once.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    pub nodes: Vec<CallHierarchyItem>,
+    pub edges: Vec<CallEdge>,
+    /// Indices of the items the graph was expanded from.
+    pub roots: Vec<usize>,
+    /// Keyed by uri, line and character of the selection range start, and name.
+    index: HashMap<(Url, u32, u32, String), usize>,
+}
+
+impl CallGraph {
+    /// Returns the index of `item`, adding it if it is not yet part of the graph. Items are
+    /// identified by uri, selection range start and name, since servers hand out a fresh
+    /// `data` payload with every response.
+    fn insert(&mut self, item: CallHierarchyItem) -> (usize, bool) {
+        let key = (
+            item.uri.clone(),
+            item.selection_range.start.line,
+            item.selection_range.start.character,
+            item.name.clone(),
+        );
+        if let Some(index) = self.index.get(&key) {
+            return (*index, false);
+        }
+        self.nodes.push(item);
+        self.index.insert(key, self.nodes.len() - 1);
+        (self.nodes.len() - 1, true)
+    }
+
+    /// Expands a call graph from `roots`, asking `calls` for the items calling or called by
+    /// each item (per `direction`) together with the call sites, for at most `max_depth`
+    /// hops. Each item is expanded only once, so recursive call chains terminate.
+    pub async fn expand<F, Fut>(
+        roots: Vec<CallHierarchyItem>,
+        direction: CallDirection,
+        max_depth: usize,
+        mut calls: F,
+    ) -> Result<CallGraph, ClientError>
+    where
+        F: FnMut(CallHierarchyItem) -> Fut,
+        Fut: Future<Output = Result<Vec<(CallHierarchyItem, Vec<Range>)>, ClientError>>,
+    {
+        let mut graph = CallGraph::default();
+        let mut queue = VecDeque::new();
+        for item in roots {
+            let (index, inserted) = graph.insert(item);
+            graph.roots.push(index);
+            if inserted {
+                queue.push_back((index, 0));
+            }
+        }
+
+        while let Some((index, depth)) = queue.pop_front() {
+            if depth >= max_depth {
+                continue;
+            }
+            for (neighbour, from_ranges) in calls(graph.nodes[index].clone()).await? {
+                let (neighbour, inserted) = graph.insert(neighbour);
+                match direction {
+                    CallDirection::Incoming => graph.add_edge(neighbour, index, from_ranges),
+                    CallDirection::Outgoing => graph.add_edge(index, neighbour, from_ranges),
+                }
+                if inserted {
+                    queue.push_back((neighbour, depth + 1));
+                }
+            }
+        }
+        Ok(graph)
+    }
+
+    fn add_edge(&mut self, caller: usize, callee: usize, from_ranges: Vec<Range>) {
+        if let Some(edge) = self
+            .edges
+            .iter_mut()
+            .find(|edge| edge.caller == caller && edge.callee == callee)
+        {
+            for range in from_ranges {
+                if !edge.from_ranges.contains(&range) {
+                    edge.from_ranges.push(range);
+                }
+            }
+            return;
+        }
+        self.edges.push(CallEdge {
+            caller,
+            callee,
+            from_ranges,
+        });
+    }
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Resolves the call hierarchy items at `position`, the entry point for the call
+    /// hierarchy requests.
+    pub async fn prepare_call_hierarchy(
+        &self,
+        uri: Url,
+        position: Position,
+    ) -> Result<Vec<CallHierarchyItem>, ClientError> {
+        let params = CallHierarchyPrepareParams {
+            text_document_position_params: text_document_position(uri, position),
+            work_done_progress_params: Default::default(),
+        };
+        Ok(self
+            .request_if_supported::<CallHierarchyPrepare>(params)
+            .await?
+            .unwrap_or_default())
+    }
+
+    pub async fn incoming_calls(
+        &self,
+        item: CallHierarchyItem,
+    ) -> Result<Vec<CallHierarchyIncomingCall>, ClientError> {
+        let params = CallHierarchyIncomingCallsParams {
+            item,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        Ok(self
+            .request_if_supported::<CallHierarchyIncomingCalls>(params)
+            .await?
+            .unwrap_or_default())
+    }
+
+    pub async fn outgoing_calls(
+        &self,
+        item: CallHierarchyItem,
+    ) -> Result<Vec<CallHierarchyOutgoingCall>, ClientError> {
+        let params = CallHierarchyOutgoingCallsParams {
+            item,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        Ok(self
+            .request_if_supported::<CallHierarchyOutgoingCalls>(params)
+            .await?
+            .unwrap_or_default())
+    }
+
+    /// Builds the call graph around the item(s) at `position`, following calls in
+    /// `direction` for at most `max_depth` hops. See `CallGraph::expand`.
+    pub async fn call_graph(
+        &self,
+        uri: Url,
+        position: Position,
+        direction: CallDirection,
+        max_depth: usize,
+    ) -> Result<CallGraph, ClientError> {
+        let roots = self.prepare_call_hierarchy(uri, position).await?;
+        CallGraph::expand(roots, direction, max_depth, |item| async move {
+            let calls = match direction {
+                CallDirection::Incoming => self
+                    .incoming_calls(item)
+                    .await?
+                    .into_iter()
+                    .map(|call| (call.from, call.from_ranges))
+                    .collect(),
+                CallDirection::Outgoing => self
+                    .outgoing_calls(item)
+                    .await?
+                    .into_iter()
+                    .map(|call| (call.to, call.from_ranges))
+                    .collect(),
+            };
+            Ok(calls)
+        })
+        .await
+    }
+}
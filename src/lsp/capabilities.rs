@@ -0,0 +1,281 @@
+use lsp_types::{
+    ClientCapabilities, CodeActionCapabilityResolveSupport, CodeActionClientCapabilities,
+    CodeActionKind, CodeActionKindLiteralSupport, CodeActionLiteralSupport,
+    CodeLensClientCapabilities, CompletionClientCapabilities, CompletionItemCapability,
+    CompletionItemCapabilityResolveSupport, DiagnosticClientCapabilities,
+    DiagnosticWorkspaceClientCapabilities, DidChangeConfigurationClientCapabilities,
+    DidChangeWatchedFilesClientCapabilities, DocumentColorClientCapabilities,
+    DocumentLinkClientCapabilities, DocumentSymbolClientCapabilities,
+    DynamicRegistrationClientCapabilities, ExecuteCommandClientCapabilities,
+    FoldingRangeClientCapabilities, GotoCapability, HoverClientCapabilities,
+    InlayHintClientCapabilities, InlayHintResolveClientCapabilities, InlineValueClientCapabilities,
+    LinkedEditingRangeClientCapabilities, MarkupKind, MonikerClientCapabilities,
+    RenameClientCapabilities, SelectionRangeClientCapabilities, SemanticTokenModifier,
+    SemanticTokenType, SemanticTokensClientCapabilities, SemanticTokensClientCapabilitiesRequests,
+    SemanticTokensFullOptions, SignatureHelpClientCapabilities, SymbolKind, SymbolKindCapability,
+    TextDocumentClientCapabilities, TextDocumentSyncClientCapabilities, TokenFormat,
+    WindowClientCapabilities, WorkspaceClientCapabilities, WorkspaceEditClientCapabilities,
+    WorkspaceFileOperationsClientCapabilities, WorkspaceSymbolClientCapabilities,
+    WorkspaceSymbolResolveSupportCapability,
+};
+
+/// Upper bound on the folding ranges a server should return for a single document.
+const FOLDING_RANGE_LIMIT: u32 = 5000;
+
+/// The capabilities this client advertises in `initialize`. Servers use these to decide
+/// which requests they may receive and which response shapes the client understands.
+pub fn client_capabilities() -> ClientCapabilities {
+    ClientCapabilities {
+        text_document: Some(TextDocumentClientCapabilities {
+            declaration: Some(GotoCapability {
+                dynamic_registration: Some(true),
+                link_support: Some(true),
+            }),
+            definition: Some(GotoCapability {
+                dynamic_registration: Some(true),
+                link_support: Some(true),
+            }),
+            call_hierarchy: Some(DynamicRegistrationClientCapabilities {
+                dynamic_registration: Some(true),
+            }),
+            code_action: Some(CodeActionClientCapabilities {
+                dynamic_registration: Some(true),
+                code_action_literal_support: Some(CodeActionLiteralSupport {
+                    code_action_kind: CodeActionKindLiteralSupport {
+                        value_set: vec![
+                            CodeActionKind::EMPTY.as_str().to_owned(),
+                            CodeActionKind::QUICKFIX.as_str().to_owned(),
+                            CodeActionKind::REFACTOR.as_str().to_owned(),
+                            CodeActionKind::REFACTOR_EXTRACT.as_str().to_owned(),
+                            CodeActionKind::REFACTOR_INLINE.as_str().to_owned(),
+                            CodeActionKind::REFACTOR_REWRITE.as_str().to_owned(),
+                            CodeActionKind::SOURCE.as_str().to_owned(),
+                            CodeActionKind::SOURCE_ORGANIZE_IMPORTS.as_str().to_owned(),
+                        ],
+                    },
+                }),
+                data_support: Some(true),
+                resolve_support: Some(CodeActionCapabilityResolveSupport {
+                    properties: vec!["edit".to_string()],
+                }),
+                ..Default::default()
+            }),
+            color_provider: Some(DocumentColorClientCapabilities {
+                dynamic_registration: Some(true),
+            }),
+            completion: Some(CompletionClientCapabilities {
+                dynamic_registration: Some(true),
+                completion_item: Some(CompletionItemCapability {
+                    snippet_support: Some(true),
+                    documentation_format: Some(vec![MarkupKind::Markdown, MarkupKind::PlainText]),
+                    deprecated_support: Some(true),
+                    label_details_support: Some(true),
+                    resolve_support: Some(CompletionItemCapabilityResolveSupport {
+                        properties: vec!["documentation".to_owned(), "detail".to_owned()],
+                    }),
+                    ..Default::default()
+                }),
+                context_support: Some(true),
+                ..Default::default()
+            }),
+            diagnostic: Some(DiagnosticClientCapabilities {
+                dynamic_registration: Some(true),
+                related_document_support: Some(true),
+            }),
+            document_highlight: Some(DynamicRegistrationClientCapabilities {
+                dynamic_registration: Some(true),
+            }),
+            document_link: Some(DocumentLinkClientCapabilities {
+                dynamic_registration: Some(true),
+                tooltip_support: Some(true),
+            }),
+            document_symbol: Some(DocumentSymbolClientCapabilities {
+                dynamic_registration: Some(true),
+                // nested symbols carry the full extent of each symbol next to its name
+                hierarchical_document_symbol_support: Some(true),
+                ..Default::default()
+            }),
+            folding_range: Some(FoldingRangeClientCapabilities {
+                dynamic_registration: Some(true),
+                range_limit: Some(FOLDING_RANGE_LIMIT),
+                // folds are consumed per line, so spare servers computing character offsets
+                line_folding_only: Some(true),
+                ..Default::default()
+            }),
+            code_lens: Some(CodeLensClientCapabilities {
+                dynamic_registration: Some(true),
+            }),
+            hover: Some(HoverClientCapabilities {
+                dynamic_registration: Some(true),
+                content_format: Some(vec![MarkupKind::Markdown, MarkupKind::PlainText]),
+            }),
+            implementation: Some(GotoCapability {
+                dynamic_registration: Some(true),
+                link_support: Some(true),
+            }),
+            linked_editing_range: Some(LinkedEditingRangeClientCapabilities {
+                dynamic_registration: Some(true),
+            }),
+            moniker: Some(MonikerClientCapabilities {
+                dynamic_registration: Some(true),
+            }),
+            references: Some(DynamicRegistrationClientCapabilities {
+                dynamic_registration: Some(true),
+            }),
+            rename: Some(RenameClientCapabilities {
+                dynamic_registration: Some(true),
+                prepare_support: Some(true),
+                ..Default::default()
+            }),
+            signature_help: Some(SignatureHelpClientCapabilities {
+                dynamic_registration: Some(true),
+                // lets servers keep the active signature stable while the user types
+                context_support: Some(true),
+                ..Default::default()
+            }),
+            inlay_hint: Some(InlayHintClientCapabilities {
+                dynamic_registration: Some(true),
+                resolve_support: Some(InlayHintResolveClientCapabilities {
+                    properties: vec![
+                        "tooltip".to_string(),
+                        "label.tooltip".to_string(),
+                        "label.location".to_string(),
+                        "label.command".to_string(),
+                    ],
+                }),
+            }),
+            inline_value: Some(InlineValueClientCapabilities {
+                dynamic_registration: Some(true),
+            }),
+            selection_range: Some(SelectionRangeClientCapabilities {
+                dynamic_registration: Some(true),
+            }),
+            semantic_tokens: Some(SemanticTokensClientCapabilities {
+                dynamic_registration: Some(true),
+                requests: SemanticTokensClientCapabilitiesRequests {
+                    range: Some(true),
+                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                },
+                token_types: vec![
+                    SemanticTokenType::NAMESPACE,
+                    SemanticTokenType::TYPE,
+                    SemanticTokenType::CLASS,
+                    SemanticTokenType::ENUM,
+                    SemanticTokenType::INTERFACE,
+                    SemanticTokenType::STRUCT,
+                    SemanticTokenType::TYPE_PARAMETER,
+                    SemanticTokenType::PARAMETER,
+                    SemanticTokenType::VARIABLE,
+                    SemanticTokenType::PROPERTY,
+                    SemanticTokenType::ENUM_MEMBER,
+                    SemanticTokenType::EVENT,
+                    SemanticTokenType::FUNCTION,
+                    SemanticTokenType::METHOD,
+                    SemanticTokenType::MACRO,
+                    SemanticTokenType::KEYWORD,
+                    SemanticTokenType::MODIFIER,
+                    SemanticTokenType::COMMENT,
+                    SemanticTokenType::STRING,
+                    SemanticTokenType::NUMBER,
+                    SemanticTokenType::REGEXP,
+                    SemanticTokenType::OPERATOR,
+                    SemanticTokenType::DECORATOR,
+                ],
+                token_modifiers: vec![
+                    SemanticTokenModifier::DECLARATION,
+                    SemanticTokenModifier::DEFINITION,
+                    SemanticTokenModifier::READONLY,
+                    SemanticTokenModifier::STATIC,
+                    SemanticTokenModifier::DEPRECATED,
+                    SemanticTokenModifier::ABSTRACT,
+                    SemanticTokenModifier::ASYNC,
+                    SemanticTokenModifier::MODIFICATION,
+                    SemanticTokenModifier::DOCUMENTATION,
+                    SemanticTokenModifier::DEFAULT_LIBRARY,
+                ],
+                formats: vec![TokenFormat::RELATIVE],
+                ..Default::default()
+            }),
+            synchronization: Some(TextDocumentSyncClientCapabilities {
+                dynamic_registration: Some(true),
+                will_save: Some(true),
+                will_save_wait_until: Some(true),
+                did_save: Some(true),
+            }),
+            ..Default::default()
+        }),
+        workspace: Some(WorkspaceClientCapabilities {
+            apply_edit: Some(true),
+            execute_command: Some(ExecuteCommandClientCapabilities {
+                dynamic_registration: Some(true),
+            }),
+            did_change_configuration: Some(DidChangeConfigurationClientCapabilities {
+                dynamic_registration: Some(true),
+            }),
+            did_change_watched_files: Some(DidChangeWatchedFilesClientCapabilities {
+                dynamic_registration: Some(true),
+                relative_pattern_support: Some(true),
+            }),
+            symbol: Some(WorkspaceSymbolClientCapabilities {
+                dynamic_registration: Some(true),
+                symbol_kind: Some(SymbolKindCapability {
+                    value_set: Some(vec![
+                        SymbolKind::FILE,
+                        SymbolKind::MODULE,
+                        SymbolKind::NAMESPACE,
+                        SymbolKind::PACKAGE,
+                        SymbolKind::CLASS,
+                        SymbolKind::METHOD,
+                        SymbolKind::PROPERTY,
+                        SymbolKind::FIELD,
+                        SymbolKind::CONSTRUCTOR,
+                        SymbolKind::ENUM,
+                        SymbolKind::INTERFACE,
+                        SymbolKind::FUNCTION,
+                        SymbolKind::VARIABLE,
+                        SymbolKind::CONSTANT,
+                        SymbolKind::STRING,
+                        SymbolKind::NUMBER,
+                        SymbolKind::BOOLEAN,
+                        SymbolKind::ARRAY,
+                        SymbolKind::OBJECT,
+                        SymbolKind::KEY,
+                        SymbolKind::NULL,
+                        SymbolKind::STRUCT,
+                        SymbolKind::EVENT,
+                        SymbolKind::OPERATOR,
+                    ]),
+                }),
+                resolve_support: Some(WorkspaceSymbolResolveSupportCapability {
+                    properties: vec!["location.range".to_string()],
+                }),
+                ..Default::default()
+            }),
+            workspace_edit: Some(WorkspaceEditClientCapabilities {
+                document_changes: Some(true),
+                ..Default::default()
+            }),
+            diagnostic: Some(DiagnosticWorkspaceClientCapabilities {
+                refresh_support: Some(true),
+            }),
+            workspace_folders: Some(true),
+            configuration: Some(true),
+            file_operations: Some(WorkspaceFileOperationsClientCapabilities {
+                did_create: Some(true),
+                will_create: Some(true),
+                did_rename: Some(true),
+                will_rename: Some(true),
+                did_delete: Some(true),
+                will_delete: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+        window: Some(WindowClientCapabilities {
+            // lets servers report indexing, which `wait_until_ready` waits for
+            work_done_progress: Some(true),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
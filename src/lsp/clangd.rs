@@ -0,0 +1,107 @@
+use lsp_types::notification::Notification;
+use lsp_types::request::Request;
+use lsp_types::{Range, TextDocumentIdentifier, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+
+/// `textDocument/switchSourceHeader`: the header belonging to a source file or the other way
+/// around, `None` if clangd does not know one.
+pub enum SwitchSourceHeader {}
+
+impl Request for SwitchSourceHeader {
+    type Params = TextDocumentIdentifier;
+    type Result = Option<Url>;
+    const METHOD: &'static str = "textDocument/switchSourceHeader";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AstParams {
+    pub text_document: TextDocumentIdentifier,
+    /// The range whose enclosing node is returned, the whole document if `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<Range>,
+}
+
+/// A node of clang's AST.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AstNode {
+    /// The general kind of node, such as `expression` or `declaration`.
+    pub role: String,
+    /// The specific kind of node, such as `BinaryOperator` or `FunctionDecl`.
+    pub kind: String,
+    /// A short description, e.g. the name of a declaration.
+    #[serde(default)]
+    pub detail: Option<String>,
+    /// The node as clang's `-ast-dump` prints it.
+    #[serde(default)]
+    pub arcana: Option<String>,
+    #[serde(default)]
+    pub range: Option<Range>,
+    #[serde(default)]
+    pub children: Vec<AstNode>,
+}
+
+/// `textDocument/ast`: the AST node enclosing a range.
+pub enum Ast {}
+
+impl Request for Ast {
+    type Params = AstParams;
+    type Result = Option<AstNode>;
+    const METHOD: &'static str = "textDocument/ast";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileStatus {
+    pub uri: Url,
+    /// What clangd is doing with the file, such as `parsing includes` or `idle`.
+    pub state: String,
+}
+
+/// `textDocument/clangd.fileStatus`: the progress of building a file, only sent when the
+/// `initialize` request enables it, see `initialization_options`.
+pub enum FileStatusNotification {}
+
+impl Notification for FileStatusNotification {
+    type Params = FileStatus;
+    const METHOD: &'static str = "textDocument/clangd.fileStatus";
+}
+
+/// The `initializationOptions` turning on clangd's extension notifications.
+pub fn initialization_options() -> Value {
+    json!({ "clangdFileStatus": true })
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Finds the header of a source file, or the source file of a header.
+    pub async fn switch_source_header(&self, uri: Url) -> Result<Option<Url>, ClientError> {
+        self.request::<SwitchSourceHeader>(TextDocumentIdentifier { uri })
+            .await
+    }
+
+    /// Returns the innermost AST node enclosing `range`, or the translation unit when no
+    /// range is given.
+    pub async fn ast(
+        &self,
+        uri: Url,
+        range: Option<Range>,
+    ) -> Result<Option<AstNode>, ClientError> {
+        let params = AstParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+        };
+        self.request::<Ast>(params).await
+    }
+
+    /// Registers `handler` to receive clangd's file status updates. Returns an id for
+    /// `remove_notification_handler`.
+    pub fn on_file_status<F>(&self, handler: F) -> usize
+    where
+        F: 'static + Send + FnMut(FileStatus),
+    {
+        self.on_typed_notification::<FileStatusNotification, _>(handler)
+    }
+}
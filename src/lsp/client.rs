@@ -1,37 +1,508 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::sync::{Arc, Mutex};
-use tokio::io::AsyncWriteExt;
+use std::time::{Duration, Instant};
 use tokio::io::BufReader;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt};
 use tokio::process::Child;
 use tokio::process::ChildStdin;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc;
+use tokio::sync::oneshot;
+use tokio::sync::watch;
+use tokio::sync::Mutex as AsyncMutex;
+use tokio::task::AbortHandle;
 
-use serde_json::value::Value;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::value::{RawValue, Value};
 use serde_json::{self, json};
 
-use jsonrpc_lite::{Error, Id, JsonRpc};
+use jsonrpc_lite::Error;
+use lsp_types::notification::{Cancel, Exit, Initialized, Notification};
+use lsp_types::request::{
+    ApplyWorkspaceEdit, Initialize, RegisterCapability, Request, Shutdown, UnregisterCapability,
+    WorkDoneProgressCreate,
+};
+use lsp_types::{
+    ApplyWorkspaceEditParams, ApplyWorkspaceEditResponse, InitializeParams, InitializeResult,
+    InitializedParams, NumberOrString, ProgressParams, ProgressParamsValue, Registration,
+    RegistrationParams, Unregistration, UnregistrationParams, WorkDoneProgress,
+    WorkDoneProgressCreateParams, WorkspaceEdit,
+};
 
-use super::parsing;
+use super::edits::EditError;
+use super::frame_log::FrameLog;
+use super::interceptor::{Flow, Interceptor};
+use super::log;
+use super::metrics::Metrics;
+use super::parsing::{self, MessageReader, ParseError, ParseMode};
+use super::server_capabilities::Capabilities;
+use super::validation::{self, Member};
+
+/// The longest message read from a server unless `LanguageServerRef::set_max_message_size`
+/// says otherwise, well over what servers send for a whole workspace.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 256 * 1024 * 1024;
+
+/// The `result` or the `error` of a response, as the server wrote it, for the caller to
+/// deserialize into what it expects.
+type RawResponse = Result<Box<RawValue>, Box<RawValue>>;
 
 trait Callable: Send {
-    fn call(self: Box<Self>, result: Result<Value, Value>);
+    fn call(self: Box<Self>, result: RawResponse);
 }
 
-impl<F: Send + FnOnce(Result<Value, Value>)> Callable for F {
-    fn call(self: Box<F>, result: Result<Value, Value>) {
+impl<F: Send + FnOnce(RawResponse)> Callable for F {
+    fn call(self: Box<F>, result: RawResponse) {
         (*self)(result)
     }
 }
 
 type Callback = Box<dyn Callable>;
 
+/// The response to a request the client sent, `None` when none arrived, and whether waiting
+/// for it timed out.
+type Outcome = (Option<RawResponse>, bool);
+
+/// A coalesced request, shared by the callers waiting for its response, see
+/// `LanguageServerRef::coalesce_requests`.
+struct Coalesced {
+    /// The params of the request as sent, which the requests sharing it have too.
+    params: String,
+    /// The id of the request, once it is sent.
+    id: Option<usize>,
+    /// `None` once the response arrived or the request was canceled, when no one can join it
+    /// anymore.
+    waiters: Option<Vec<oneshot::Sender<RawResponse>>>,
+}
+
+impl Coalesced {
+    /// Waits for the response with `waiter` when the request has `params` and can still be
+    /// joined, giving `waiter` back otherwise.
+    fn join(
+        &mut self,
+        params: &str,
+        waiter: oneshot::Sender<RawResponse>,
+    ) -> Result<(), oneshot::Sender<RawResponse>> {
+        match &mut self.waiters {
+            Some(waiters) if self.params == params => {
+                waiters.push(waiter);
+                Ok(())
+            }
+            _ => Err(waiter),
+        }
+    }
+
+    /// Whether a caller still waits for the response.
+    fn is_waited_for(&self) -> bool {
+        self.waiters
+            .iter()
+            .flatten()
+            .any(|waiter| !waiter.is_closed())
+    }
+}
+
+/// The callback of a coalesced request, handing its response to every caller waiting then.
+/// Dropped without a response, it drops them.
+struct Answer {
+    coalesced: Arc<Mutex<Coalesced>>,
+    method: String,
+    sent: Instant,
+    metrics: Arc<Metrics>,
+    answered: bool,
+}
+
+impl Answer {
+    fn send(mut self, response: RawResponse) {
+        self.answered = true;
+        let failed = response.is_err();
+        self.metrics
+            .response_received(&self.method, self.sent.elapsed(), failed);
+        let waiters = self.coalesced.lock().unwrap().waiters.take();
+        for waiter in waiters.into_iter().flatten() {
+            let _ = waiter.send(response.clone());
+        }
+    }
+}
+
+impl Drop for Answer {
+    fn drop(&mut self) {
+        if !self.answered {
+            self.metrics
+                .response_received(&self.method, self.sent.elapsed(), true);
+            self.coalesced.lock().unwrap().waiters = None;
+        }
+    }
+}
+
+/// A caller waiting for the response to a coalesced request. The request stays in
+/// `in_flight` until no caller waits for it anymore.
+struct Waiting<'a, W: AsyncWriteExt> {
+    server: &'a LanguageServerRef<W>,
+    key: (String, u64),
+    coalesced: Arc<Mutex<Coalesced>>,
+    response: oneshot::Receiver<RawResponse>,
+}
+
+impl<W: AsyncWriteExt + Unpin> Waiting<'_, W> {
+    /// Stops waiting, canceling the request when no one else waits for it.
+    async fn give_up(&mut self) {
+        self.response.close();
+        let abandoned = {
+            let mut coalesced = self.coalesced.lock().unwrap();
+            if coalesced.is_waited_for() {
+                None
+            } else {
+                coalesced.waiters = None;
+                coalesced.id
+            }
+        };
+        if let Some(id) = abandoned {
+            self.server.cancel_request(id).await;
+        }
+    }
+}
+
+impl<W: AsyncWriteExt> Drop for Waiting<'_, W> {
+    fn drop(&mut self) {
+        self.response.close();
+        // not locked along with the server, which is locked first elsewhere
+        if self.coalesced.lock().unwrap().is_waited_for() {
+            return;
+        }
+        if let Ok(mut inner) = self.server.0.lock() {
+            let current = inner.in_flight.get(&self.key);
+            if current.is_some_and(|current| Arc::ptr_eq(current, &self.coalesced)) {
+                inner.in_flight.remove(&self.key);
+            }
+        }
+    }
+}
+
+/// A request waiting for its response.
+struct Pending {
+    method: String,
+    sent: Instant,
+    callback: Callback,
+}
+
+/// A request sent to the server and not answered yet, see
+/// `LanguageServerRef::pending_requests`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingRequest {
+    pub id: usize,
+    pub method: String,
+    /// The time since it was sent.
+    pub elapsed: Duration,
+}
+
+impl std::fmt::Display for PendingRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (id {}, {:.1}s)",
+            self.method,
+            self.id,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+/// Receives the `value` of `$/progress` notifications addressed to a registered token.
+type ProgressListener = Box<dyn FnMut(Value) + Send>;
+
+/// Answers a request sent by the server, given its params.
+type RequestHandler = Box<dyn FnMut(Value) -> Result<Value, Error> + Send>;
+
+/// A capability the server registered or unregistered dynamically, see
+/// `LanguageServerRef::on_registration`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CapabilityChange {
+    Register(Registration),
+    Unregister(Unregistration),
+}
+
+/// Receives the dynamic registrations of one method.
+type RegistrationHandler = Box<dyn FnMut(CapabilityChange) -> Result<(), Error> + Send>;
+
+/// Receives the params of a notification the server sent, see
+/// `LanguageServerRef::on_notification`.
+type NotificationHandler = Box<dyn FnMut(Value) + Send>;
+
+/// Answers a request nothing else handles, given its method and params, see
+/// `LanguageServerRef::on_unhandled_request`.
+type UnhandledRequestHandler = Box<dyn FnMut(&str, Value) -> Result<Value, Error> + Send>;
+
+/// Receives a notification nothing else handles, given its method and params, see
+/// `LanguageServerRef::on_unhandled_notification`.
+type UnhandledNotificationHandler = Box<dyn FnMut(&str, Value) + Send>;
+
+/// A work done progress the server started on its own, as its last `$/progress`
+/// notifications left it, see `LanguageServerRef::server_progress`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerProgress {
+    pub title: String,
+    pub message: Option<String>,
+    /// How much is done, from 0 to 100, when the server says.
+    pub percentage: Option<u32>,
+}
+
+/// Which way a message went, see `LanguageServerRef::on_traffic`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Written to the server.
+    Sent,
+    /// Read from the server.
+    Received,
+}
+
+/// Receives every message exchanged with the server, see `LanguageServerRef::on_traffic`.
+type TrafficHandler = Arc<dyn Fn(Direction, &str) + Send + Sync>;
+
+/// Applies an edit the server sent through `workspace/applyEdit`, see
+/// `LanguageServerRef::on_apply_edit`.
+type EditHandler = Box<dyn FnMut(&WorkspaceEdit) -> Result<(), String> + Send>;
+
+/// Runs before a request is sent, with its params, see `LanguageServerRef::add_request_hook`.
+type RequestHook<W> =
+    Arc<dyn Fn(&LanguageServerRef<W>, &Value) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// A message from the server breaking the protocol, which the client skips rather than
+/// failing on, or a message deviating from the specification with
+/// `LanguageServerRef::set_strict_validation`, see `LanguageServerRef::protocol_violations`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtocolViolation {
+    /// A message that could not be read or is not JSON-RPC, described.
+    Malformed(String),
+    /// A message whose `Content-Length` is not the length of its JSON body, see
+    /// `ParseError::LengthMismatch`.
+    LengthMismatch {
+        declared: usize,
+        json: Option<usize>,
+    },
+    /// The bytes skipped after a malformed message to get to the next one.
+    Skipped(usize),
+    /// A message longer than `set_max_message_size` allows, skipped.
+    TooLarge { declared: usize, max: usize },
+    /// A message body that is not UTF-8, which `set_lossy_utf8` had decoded with
+    /// replacement characters rather than skipped.
+    InvalidUtf8 { bytes: usize },
+    /// A response to no request waiting for one: never sent, or already answered.
+    UnknownId { id: Value, response: Value },
+    /// A response with both a result and an error, answered with the error. An `error` of
+    /// `null` is taken for no error.
+    ResultAndError { id: Value },
+    /// A message sent or received whose `params` or `result` deviate from their lsp_types
+    /// structure, described by `validation::validate`. The message is handled all the same.
+    Invalid {
+        direction: Direction,
+        method: String,
+        error: String,
+    },
+}
+
+impl std::fmt::Display for ProtocolViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProtocolViolation::Malformed(error) => write!(f, "malformed message: {}", error),
+            ProtocolViolation::LengthMismatch {
+                declared,
+                json: Some(json),
+            } => write!(
+                f,
+                "Content-Length of {} bytes for a message of {} bytes",
+                declared, json
+            ),
+            ProtocolViolation::LengthMismatch {
+                declared,
+                json: None,
+            } => write!(
+                f,
+                "Content-Length of {} bytes cuts the message short",
+                declared
+            ),
+            ProtocolViolation::Skipped(bytes) => {
+                write!(f, "skipped {} bytes to the next message", bytes)
+            }
+            ProtocolViolation::TooLarge { declared, max } => write!(
+                f,
+                "skipped a message of {} bytes, over the maximum of {}",
+                declared, max
+            ),
+            ProtocolViolation::InvalidUtf8 { bytes } => write!(
+                f,
+                "message of {} bytes is not UTF-8, decoded with replacement characters",
+                bytes
+            ),
+            ProtocolViolation::UnknownId { id, .. } => {
+                write!(f, "response to no pending request: id {}", id)
+            }
+            ProtocolViolation::ResultAndError { id } => {
+                write!(f, "response with both a result and an error: id {}", id)
+            }
+            ProtocolViolation::Invalid {
+                direction,
+                method,
+                error,
+            } => {
+                let direction = match direction {
+                    Direction::Sent => "sent",
+                    Direction::Received => "received",
+                };
+                write!(f, "invalid {} message {}: {}", direction, method, error)
+            }
+        }
+    }
+}
+
+/// What the client does with a response to no pending request, which servers send after
+/// answering twice or answering a request the client gave up on, see
+/// `LanguageServerRef::set_unknown_response_policy`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownResponsePolicy {
+    /// Drops it silently.
+    Ignore,
+    /// Drops it with a warning.
+    Log,
+    /// Drops it with a warning and a `ProtocolViolation::UnknownId`.
+    #[default]
+    Report,
+}
+
+/// How long requests wait for their response when the caller gives no timeout, see
+/// `LanguageServerRef::set_request_timeouts`, e.g.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use lsp_client::lsp::client::RequestTimeouts;
+/// let timeouts = RequestTimeouts::new(Duration::from_secs(10))
+///     .with("textDocument/hover", Duration::from_secs(2))
+///     .with("workspace/symbol", Duration::from_secs(30))
+///     .with("initialize", Duration::from_secs(60));
+/// assert_eq!(timeouts.get("textDocument/definition"), Some(Duration::from_secs(10)));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestTimeouts {
+    /// For the methods `methods` leaves out, none when `None`.
+    pub default: Option<Duration>,
+    pub methods: HashMap<String, Duration>,
+}
+
+impl RequestTimeouts {
+    /// `default` for every method.
+    pub fn new(default: Duration) -> RequestTimeouts {
+        RequestTimeouts {
+            default: Some(default),
+            methods: HashMap::new(),
+        }
+    }
+
+    /// Gives `method` a timeout of its own.
+    pub fn with(mut self, method: &str, timeout: Duration) -> RequestTimeouts {
+        self.methods.insert(method.to_owned(), timeout);
+        self
+    }
+
+    /// The timeout of `method`.
+    pub fn get(&self, method: &str) -> Option<Duration> {
+        self.methods.get(method).copied().or(self.default)
+    }
+}
+
+/// Errors surfaced by the typed request helpers.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The server answered with an error.
+    Server(Error),
+    /// The response could not be deserialized into the expected type.
+    Json(serde_json::Error),
+    /// The request was dropped before a response arrived.
+    Canceled,
+    /// The server did not advertise support for the named method.
+    Unsupported(&'static str),
+    /// The server did not advertise the named `workspace/executeCommand` command.
+    UnknownCommand(String),
+    /// Applying an edit returned by the server failed.
+    Edit(EditError),
+    /// The server did not get to the awaited state, or answer the request, in time.
+    Timeout,
+    /// The client was closed before a response arrived, or before the request was sent.
+    ClientClosed,
+}
+
+impl From<serde_json::Error> for ClientError {
+    fn from(err: serde_json::Error) -> ClientError {
+        ClientError::Json(err)
+    }
+}
+
+impl From<EditError> for ClientError {
+    fn from(err: EditError) -> ClientError {
+        ClientError::Edit(err)
+    }
+}
+
 /// Represents (and mediates communcation with) a Language Server.
 ///
 /// LanguageServer should only ever be instantiated or accessed through an instance of
 /// LanguageServerRef, which mediates access to a single shared LanguageServer through a Mutex.
 struct LanguageServer<W: AsyncWriteExt> {
-    peer: W,
-    pending: HashMap<usize, Callback>,
+    // writes happen outside of the state lock so the read loop can keep dispatching
+    // responses while a large message is being written
+    peer: Arc<AsyncMutex<W>>,
+    pending: HashMap<usize, Pending>,
+    /// The requests canceled after timing out, whose late response is expected.
+    canceled: HashSet<usize>,
     next_id: usize,
+    progress_listeners: HashMap<NumberOrString, ProgressListener>,
+    next_token: usize,
+    initialize_result: Option<InitializeResult>,
+    request_handlers: HashMap<String, RequestHandler>,
+    /// The capabilities the server registered dynamically and has not unregistered.
+    registrations: Vec<Registration>,
+    registration_handlers: HashMap<String, Vec<(usize, RegistrationHandler)>>,
+    notification_handlers: HashMap<String, Vec<(usize, NotificationHandler)>>,
+    next_handler_id: usize,
+    unhandled_request: Option<UnhandledRequestHandler>,
+    unhandled_notification: Option<UnhandledNotificationHandler>,
+    /// The work done progress the server started on its own and has not ended, by token.
+    server_progress: watch::Sender<HashMap<NumberOrString, ServerProgress>>,
+    violations: broadcast::Sender<ProtocolViolation>,
+    unknown_responses: UnknownResponsePolicy,
+    timeouts: RequestTimeouts,
+    edit_handler: Option<EditHandler>,
+    traffic_handler: Option<TrafficHandler>,
+    interceptors: Vec<(usize, Arc<dyn Interceptor>)>,
+    request_hooks: Vec<(usize, RequestHook<W>)>,
+    frame_log: Option<Arc<FrameLog>>,
+    metrics: Arc<Metrics>,
+    parse_mode: ParseMode,
+    /// The longest message read, see `LanguageServerRef::set_max_message_size`.
+    max_message_size: Option<usize>,
+    /// Set when the last message read was found to have a wrong length, for the reader to
+    /// skip to the next message.
+    resync: bool,
+    lossy_utf8: bool,
+    /// Whether messages are checked with `validation::validate`.
+    strict_validation: bool,
+    /// The commands being executed, oldest first, with where to send the edits the server
+    /// asks to apply while they run when there is no `edit_handler`, along with the id of
+    /// the `workspace/applyEdit` request to answer.
+    commands: Vec<(usize, mpsc::UnboundedSender<(Value, WorkspaceEdit)>)>,
+    next_command: usize,
+    /// The methods whose identical requests are sent once while in flight, see
+    /// `LanguageServerRef::coalesce_requests`.
+    coalesced: HashSet<String>,
+    /// The coalesced requests waiting for their response, by method and hash of their
+    /// params.
+    in_flight: HashMap<(String, u64), Arc<Mutex<Coalesced>>>,
+    /// Set by `LanguageServerRef::close`, after which nothing is sent or read.
+    closed: bool,
+    /// The task reading the messages from the server.
+    read_loop: Option<AbortHandle>,
 }
 
 /// Generates a Language Server Protocol compliant message.
@@ -44,16 +515,104 @@ fn prepare_lsp_json(msg: &Value) -> Result<String, serde_json::error::Error> {
     ))
 }
 
-impl<W: AsyncWriteExt + Unpin> LanguageServer<W> {
-    async fn write(&mut self, msg: &str) {
-        self.peer
-            .write_all(msg.as_bytes())
-            .await
-            .expect("error writing to stdin");
-        self.peer.flush().await.expect("error flushing child stdin");
+/// Frames and writes a message to the server.
+async fn write_rpc<W: AsyncWriteExt + Unpin>(
+    peer: &AsyncMutex<W>,
+    rpc: &Value,
+) -> std::io::Result<()> {
+    let rpc = match prepare_lsp_json(rpc) {
+        Ok(r) => r,
+        Err(err) => panic!("error encoding rpc {:?}", err),
+    };
+    let mut peer = peer.lock().await;
+    peer.write_all(rpc.as_bytes()).await?;
+    peer.flush().await
+}
+
+/// The result of `workspace/applyEdit`, from whether the edit was applied.
+fn apply_edit_response(applied: Result<(), String>) -> Value {
+    json!(ApplyWorkspaceEditResponse {
+        applied: applied.is_ok(),
+        failure_reason: applied.err(),
+        failed_change: None,
+    })
+}
+
+/// The response to the request `id`, answered with `response`'s result or error.
+fn response_message(id: &Value, response: Result<Value, Value>) -> Value {
+    match response {
+        Ok(result) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result
+        }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": error
+        }),
     }
+}
+
+/// Runs `message` through `interceptors` until one does more than let it continue.
+fn intercept(
+    interceptors: &[Arc<dyn Interceptor>],
+    direction: Direction,
+    message: &mut Value,
+) -> Flow {
+    for interceptor in interceptors {
+        let flow = match direction {
+            Direction::Sent => interceptor.on_outgoing(message),
+            Direction::Received => interceptor.on_incoming(message),
+        };
+        if flow != Flow::Continue {
+            return flow;
+        }
+    }
+    Flow::Continue
+}
+
+/// A message read from the server, its members left as the server wrote them until the
+/// handler or caller they are for deserializes them, so large results are parsed once.
+/// A member set to `null` is `Some`, unlike one left out.
+#[derive(Deserialize)]
+struct Incoming<'a> {
+    #[serde(borrow, default, deserialize_with = "present")]
+    id: Option<&'a RawValue>,
+    #[serde(borrow)]
+    method: Option<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "present")]
+    params: Option<&'a RawValue>,
+    #[serde(borrow, default, deserialize_with = "present")]
+    result: Option<&'a RawValue>,
+    #[serde(borrow, default, deserialize_with = "present")]
+    error: Option<&'a RawValue>,
+}
+
+/// Deserializes a member that is there, even as `null`, which `Option` would take for
+/// missing.
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<&'de RawValue>, D::Error> {
+    <&RawValue>::deserialize(deserializer).map(Some)
+}
+
+/// Parses a member of a message, valid JSON since the message is.
+fn value(raw: &RawValue) -> Value {
+    serde_json::from_str(raw.get()).unwrap_or(Value::Null)
+}
 
-    async fn send_request(&mut self, method: &str, params: &Value, completion: Callback) {
+impl<W: AsyncWriteExt> Drop for LanguageServer<W> {
+    fn drop(&mut self) {
+        // the read loop only holds on to the server while dispatching a message
+        if let Some(read_loop) = self.read_loop.take() {
+            read_loop.abort();
+        }
+    }
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServer<W> {
+    /// Records `completion` for a new request and returns the message to send. Once the
+    /// client is closed `completion` is dropped at once, the request never being sent.
+    fn prepare_request(&mut self, method: &str, params: &Value, completion: Callback) -> Value {
         let request = json!({
             "jsonrpc": "2.0",
             "id": self.next_id,
@@ -61,113 +620,1112 @@ impl<W: AsyncWriteExt + Unpin> LanguageServer<W> {
             "params": params
         });
 
-        self.pending.insert(self.next_id, completion);
+        if !self.closed {
+            let pending = Pending {
+                method: method.to_owned(),
+                sent: Instant::now(),
+                callback: completion,
+            };
+            self.pending.insert(self.next_id, pending);
+        }
         self.next_id += 1;
-        self.send_rpc(&request).await;
+        request
     }
 
-    async fn send_notification(&mut self, method: &str, params: &Value) {
-        let notification = json!({
-            "jsonrpc": "2.0",
-            "method": method,
-            "params": params
-        });
-        self.send_rpc(&notification).await;
+    /// Answers `workspace/applyEdit` with the edit handler if there is one. Otherwise the edit
+    /// is handed to the oldest command being executed, which servers that run one command at
+    /// a time are sending it for, and `None` is returned: `execute_command_with` answers once
+    /// it applied the edit.
+    fn apply_edit(&mut self, id: &Value, params: Value) -> Option<Result<Value, Error>> {
+        let params: ApplyWorkspaceEditParams = match serde_json::from_value(params) {
+            Ok(params) => params,
+            Err(_) => return Some(Err(Error::invalid_params())),
+        };
+        let result = match (&mut self.edit_handler, self.commands.first()) {
+            (Some(handler), _) => handler(&params.edit),
+            (None, Some((_, edits))) => match edits.send((id.clone(), params.edit)) {
+                Ok(()) => return None,
+                // the command finished meanwhile
+                Err(_) => Err("the command the edit was sent for has finished".to_owned()),
+            },
+            (None, None) => Err("no command is being executed to receive the edit".to_owned()),
+        };
+        Some(Ok(apply_edit_response(result)))
     }
 
-    fn handle_response(&mut self, id: usize, result: Value) {
-        let callback = self
-            .pending
-            .remove(&id)
-            .expect(&format!("id {} missing from request table", id));
-        callback.call(Ok(result));
+    /// Accepts a progress token created by the server, which `$/progress` notifications
+    /// then report on. Progress between begin and end is tracked in `server_progress`.
+    fn create_progress(&mut self, params: Value) -> Result<Value, Error> {
+        let _: WorkDoneProgressCreateParams =
+            serde_json::from_value(params).map_err(|_| Error::invalid_params())?;
+        Ok(Value::Null)
     }
 
-    fn handle_error(&mut self, id: usize, error: Error) {
-        let callback = self
-            .pending
-            .remove(&id)
-            .expect(&format!("id {} missing from request table", id));
-        callback.call(Err(error.data.unwrap_or(serde_json::Value::Null)));
+    /// Hands every registration to the handlers of its method. Registrations of methods
+    /// nobody handles are acknowledged all the same, the server just never hears of them.
+    fn register(&mut self, params: Value) -> Result<Value, Error> {
+        let params: RegistrationParams =
+            serde_json::from_value(params).map_err(|_| Error::invalid_params())?;
+        for registration in params.registrations {
+            let change = CapabilityChange::Register(registration.clone());
+            self.dispatch_registration(&registration.method, change)?;
+            self.registrations.push(registration);
+        }
+        Ok(Value::Null)
+    }
+
+    /// Like `register`, for `client/unregisterCapability`.
+    fn unregister(&mut self, params: Value) -> Result<Value, Error> {
+        let params: UnregistrationParams =
+            serde_json::from_value(params).map_err(|_| Error::invalid_params())?;
+        for unregistration in params.unregisterations {
+            let change = CapabilityChange::Unregister(unregistration.clone());
+            self.dispatch_registration(&unregistration.method, change)?;
+            self.registrations
+                .retain(|registration| registration.id != unregistration.id);
+        }
+        Ok(Value::Null)
+    }
+
+    fn dispatch_registration(
+        &mut self,
+        method: &str,
+        change: CapabilityChange,
+    ) -> Result<(), Error> {
+        for (_, handler) in self
+            .registration_handlers
+            .get_mut(method)
+            .into_iter()
+            .flatten()
+        {
+            handler(change.clone())?;
+        }
+        Ok(())
     }
 
-    async fn send_rpc(&mut self, rpc: &Value) {
-        let rpc = match prepare_lsp_json(&rpc) {
-            Ok(r) => r,
-            Err(err) => panic!("error encoding rpc {:?}", err),
+    /// Runs the handler registered for a server request and returns the response to send.
+    /// Requests nobody registered a handler for go to the `unhandled_request` handler, and
+    /// are left unanswered without one.
+    fn handle_request(&mut self, id: Value, method: &str, params: Value) -> Option<Value> {
+        self.validate(Direction::Received, method, Member::Params, &params);
+        let result = match self.request_handlers.get_mut(method) {
+            Some(handler) => handler(params),
+            None if method == ApplyWorkspaceEdit::METHOD => self.apply_edit(&id, params)?,
+            None if method == WorkDoneProgressCreate::METHOD => self.create_progress(params),
+            None if method == RegisterCapability::METHOD => self.register(params),
+            None if method == UnregisterCapability::METHOD => self.unregister(params),
+            None => match &mut self.unhandled_request {
+                Some(handler) => handler(method, params),
+                None => {
+                    log::warning!("unhandled server request: {}", method);
+                    return None;
+                }
+            },
         };
-        self.write(&rpc).await;
+        if let Ok(result) = &result {
+            self.validate(Direction::Sent, method, Member::Result, result);
+        }
+        Some(response_message(&id, result.map_err(|error| json!(error))))
     }
-}
 
-/// Access control and convenience wrapper around a shared LanguageServer instance.
-pub struct LanguageServerRef<W: AsyncWriteExt>(Arc<Mutex<LanguageServer<W>>>);
+    /// Completes the request `id` answers.
+    fn handle_response(&mut self, id: &Value, response: RawResponse) {
+        let pending = id
+            .as_u64()
+            .and_then(|id| usize::try_from(id).ok())
+            .and_then(|id| self.pending.remove(&id));
+        let Some(Pending {
+            method, callback, ..
+        }) = pending
+        else {
+            let canceled = id.as_u64().and_then(|id| usize::try_from(id).ok());
+            if canceled.is_some_and(|id| self.canceled.remove(&id)) {
+                return;
+            }
+            let violation = ProtocolViolation::UnknownId {
+                id: id.clone(),
+                response: value(&response.unwrap_or_else(|error| error)),
+            };
+            return match self.unknown_responses {
+                UnknownResponsePolicy::Ignore => {}
+                UnknownResponsePolicy::Log => self.warn(&violation),
+                UnknownResponsePolicy::Report => self.violation(violation),
+            };
+        };
+        // parsed once more only when it is checked
+        if let (true, Ok(result)) = (self.strict_validation, &response) {
+            self.validate(Direction::Received, &method, Member::Result, &value(result));
+        }
+        callback.call(response);
+    }
+
+    fn warn(&self, violation: &ProtocolViolation) {
+        log::warning!("protocol violation: {}", violation);
+    }
+
+    /// Reports a message the client skips.
+    fn violation(&self, violation: ProtocolViolation) {
+        self.warn(&violation);
+        // nobody may be listening
+        let _ = self.violations.send(violation);
+    }
 
-//FIXME: this is hacky, and prevents good error propogation,
-fn number_from_id(id: Option<&Value>) -> usize {
-    let id = id.expect("response missing id field");
-    let id = match id {
-        &Value::Number(ref n) => n.as_u64().expect("failed to take id as u64"),
-        &Value::String(ref s) => {
-            u64::from_str_radix(s, 10).expect("failed to convert string id to u64")
+    /// Reports how `value`, a member of a `method` message, deviates from the specification
+    /// when strict validation is on.
+    fn validate(&self, direction: Direction, method: &str, member: Member, value: &Value) {
+        if !self.strict_validation {
+            return;
         }
-        other => panic!("unexpected value for id field: {:?}", other),
-    };
+        for error in validation::validate(method, member, value) {
+            self.violation(ProtocolViolation::Invalid {
+                direction,
+                method: method.to_owned(),
+                error,
+            });
+        }
+    }
 
-    id as usize
+    fn handle_progress(&mut self, params: &Value) {
+        let token = match params.get("token").cloned().map(serde_json::from_value) {
+            Some(Ok(token)) => token,
+            _ => {
+                log::warning!("progress notification without valid token: {:?}", params);
+                return;
+            }
+        };
+        if let Some(listener) = self.progress_listeners.get_mut(&token) {
+            listener(params.get("value").cloned().unwrap_or(Value::Null));
+            return;
+        }
+        // progress nobody listens to was started by the server itself, e.g. indexing
+        let progress = match serde_json::from_value(params.clone()) {
+            Ok(ProgressParams {
+                value: ProgressParamsValue::WorkDone(progress),
+                ..
+            }) => progress,
+            _ => return,
+        };
+        self.server_progress
+            .send_if_modified(|active| match progress {
+                WorkDoneProgress::Begin(begin) => {
+                    let progress = ServerProgress {
+                        title: begin.title,
+                        message: begin.message,
+                        percentage: begin.percentage,
+                    };
+                    active.insert(token, progress);
+                    true
+                }
+                WorkDoneProgress::Report(report) => match active.get_mut(&token) {
+                    Some(progress) => {
+                        // a report leaves out what did not change
+                        progress.message = report.message.or(progress.message.take());
+                        progress.percentage = report.percentage.or(progress.percentage);
+                        true
+                    }
+                    None => false,
+                },
+                WorkDoneProgress::End(_) => active.remove(&token).is_some(),
+            });
+    }
+
+    /// Runs the handlers registered for a notification, or the `unhandled_notification`
+    /// handler when there are none. `$/progress` is always handled, see `handle_progress`.
+    fn handle_notification(&mut self, method: &str, params: Value) {
+        self.validate(Direction::Received, method, Member::Params, &params);
+        let Some(handlers) = self.notification_handlers.get_mut(method) else {
+            if method == "$/progress" {
+                return;
+            }
+            if let Some(handler) = &mut self.unhandled_notification {
+                handler(method, params);
+            }
+            return;
+        };
+        for (_, handler) in handlers {
+            handler(params.clone());
+        }
+    }
 }
 
+/// Access control and convenience wrapper around a shared LanguageServer instance.
+pub struct LanguageServerRef<W: AsyncWriteExt>(Arc<Mutex<LanguageServer<W>>>);
+
 impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
     fn new(peer: W) -> Self {
         LanguageServerRef(Arc::new(Mutex::new(LanguageServer {
-            peer: peer,
+            peer: Arc::new(AsyncMutex::new(peer)),
             pending: HashMap::new(),
+            canceled: HashSet::new(),
             next_id: 1,
+            progress_listeners: HashMap::new(),
+            next_token: 1,
+            initialize_result: None,
+            request_handlers: HashMap::new(),
+            registrations: Vec::new(),
+            registration_handlers: HashMap::new(),
+            notification_handlers: HashMap::new(),
+            next_handler_id: 1,
+            unhandled_request: None,
+            unhandled_notification: None,
+            server_progress: watch::channel(HashMap::new()).0,
+            violations: broadcast::channel(64).0,
+            unknown_responses: UnknownResponsePolicy::default(),
+            timeouts: RequestTimeouts::default(),
+            edit_handler: None,
+            traffic_handler: None,
+            interceptors: Vec::new(),
+            request_hooks: Vec::new(),
+            frame_log: None,
+            metrics: Arc::default(),
+            parse_mode: ParseMode::Strict,
+            max_message_size: Some(DEFAULT_MAX_MESSAGE_SIZE),
+            resync: false,
+            lossy_utf8: false,
+            strict_validation: false,
+            commands: Vec::new(),
+            next_command: 1,
+            coalesced: HashSet::new(),
+            in_flight: HashMap::new(),
+            closed: false,
+            read_loop: None,
         })))
     }
 
-    fn handle_msg(&self, val: &str) {
-        let parsed_value = JsonRpc::parse(val);
-        if let Err(err) = parsed_value {
-            println!("error parsing json: {:?}", err);
-            return;
+    /// Dispatches a message read from the server, returning the response to write back
+    /// when the message was a request. The interceptors see it first.
+    pub(crate) fn handle_msg(&self, val: &str) -> Option<Value> {
+        let interceptors = self.interceptors();
+        if interceptors.is_empty() {
+            return self.dispatch(val);
+        }
+        // not JSON, left for `dispatch` to report
+        let Ok(mut message) = serde_json::from_str::<Value>(val) else {
+            return self.dispatch(val);
+        };
+        match intercept(&interceptors, Direction::Received, &mut message) {
+            Flow::Continue => self.dispatch(&message.to_string()),
+            Flow::Drop => None,
+            Flow::Respond(response) => match (message.get("id"), message.get("method")) {
+                (Some(id), Some(_)) => Some(response_message(id, response)),
+                _ => None,
+            },
         }
-        let parsed_value = parsed_value.expect("to be present");
-        let id = parsed_value.get_id();
-        let response = parsed_value.get_result();
-        let error = parsed_value.get_error();
-        match (id, response, error) {
-            (Some(Id::Num(id)), Some(response), None) => {
+    }
+
+    /// Dispatches a message read from the server, as `handle_msg` does past the interceptors.
+    fn dispatch(&self, val: &str) -> Option<Value> {
+        let message: Incoming = match serde_json::from_str(val) {
+            Ok(message) => message,
+            Err(err) => {
                 let mut inner = self.0.lock().unwrap();
-                inner.handle_response(id.try_into().unwrap(), response.clone());
+                let violation = match parsing::check_length(val) {
+                    // the reader is in the middle of a message, skip to the next one
+                    Err(ParseError::LengthMismatch { declared, json }) => {
+                        inner.resync = true;
+                        ProtocolViolation::LengthMismatch { declared, json }
+                    }
+                    _ => ProtocolViolation::Malformed(format!("{:?}", err)),
+                };
+                inner.violation(violation);
+                return None;
+            }
+        };
+        let id = message.id.map(value);
+        let method = message.method.as_deref();
+        log::message_received(method, id.as_ref(), val.len());
+        // an error of `null` is no error
+        let error = message.error.filter(|error| error.get() != "null");
+        match (id, method, message.result, error) {
+            (Some(id), _, Some(result), None) => {
+                let mut inner = self.0.lock().unwrap();
+                inner.handle_response(&id, Ok(result.to_owned()));
+            }
+            (Some(id), _, None, Some(error)) => {
+                let mut inner = self.0.lock().unwrap();
+                inner.handle_response(&id, Err(error.to_owned()));
             }
-            (Some(Id::Num(id)), None, Some(error)) => {
+            // the error wins, whatever the result says may not be trusted
+            (Some(id), _, Some(_), Some(error)) => {
                 let mut inner = self.0.lock().unwrap();
-                inner.handle_error(id.try_into().unwrap(), error.clone());
+                inner.violation(ProtocolViolation::ResultAndError { id: id.clone() });
+                inner.handle_response(&id, Err(error.to_owned()));
             }
-            (Some(Id::Num(id)), Some(response), Some(error)) => {
-                panic!("We got both response and error.. what even??");
+            (Some(id), Some(method), None, None) => {
+                let params = message.params.map(value).unwrap_or(Value::Null);
+                let mut inner = self.0.lock().unwrap();
+                return inner.handle_request(id, method, params);
+            }
+            (None, Some(method), None, None) => {
+                let params = message.params.map(value).unwrap_or(Value::Null);
+                let mut inner = self.0.lock().unwrap();
+                if method == "$/progress" {
+                    inner.handle_progress(&params);
+                }
+                inner.handle_notification(method, params);
+            }
+            _ => {
+                let violation = ProtocolViolation::Malformed(
+                    "neither a request, a response nor a notification".to_owned(),
+                );
+                self.0.lock().unwrap().violation(violation);
             }
-            _ => {}
         }
+        None
     }
 
-    /// Sends a JSON-RPC request message with the provided method and parameters.
-    /// `completion` should be a callback which will be executed with the server's response.
-    pub async fn send_request<CB>(&self, method: &str, params: &Value, completion: CB)
+    /// Registers `handler` to answer requests the server sends for `method`, replacing any
+    /// previous handler. Like progress listeners, handlers run on the read loop while the
+    /// server lock is held and must not call back into this `LanguageServerRef`.
+    pub fn on_request<F>(&self, method: &str, handler: F)
     where
-        CB: 'static + Send + FnOnce(Result<Value, Value>),
+        F: 'static + Send + FnMut(Value) -> Result<Value, Error>,
     {
         let mut inner = self.0.lock().unwrap();
         inner
-            .send_request(method, params, Box::new(completion))
-            .await;
+            .request_handlers
+            .insert(method.to_owned(), Box::new(handler));
+    }
+
+    /// Removes the handler registered for `method`, leaving such requests unanswered.
+    pub fn remove_request_handler(&self, method: &str) {
+        let mut inner = self.0.lock().unwrap();
+        inner.request_handlers.remove(method);
+    }
+
+    /// Registers `handler` to receive the capabilities the server registers and unregisters
+    /// dynamically for `method`, e.g. `workspace/didChangeWatchedFiles`, next to any handlers
+    /// already registered. Returns an id for `remove_registration_handler`. The server is
+    /// answered with an error when a handler returns one. Like the other handlers it runs on
+    /// the read loop while the server lock is held and must not call back into this
+    /// `LanguageServerRef`.
+    pub fn on_registration<F>(&self, method: &str, handler: F) -> usize
+    where
+        F: 'static + Send + FnMut(CapabilityChange) -> Result<(), Error>,
+    {
+        let mut inner = self.0.lock().unwrap();
+        let id = inner.next_handler_id;
+        inner.next_handler_id += 1;
+        inner
+            .registration_handlers
+            .entry(method.to_owned())
+            .or_default()
+            .push((id, Box::new(handler)));
+        id
+    }
+
+    /// Removes a handler registered with `on_registration`.
+    pub fn remove_registration_handler(&self, id: usize) {
+        let mut inner = self.0.lock().unwrap();
+        for handlers in inner.registration_handlers.values_mut() {
+            handlers.retain(|(handler_id, _)| *handler_id != id);
+        }
+        inner
+            .registration_handlers
+            .retain(|_, handlers| !handlers.is_empty());
+    }
+
+    /// Registers `handler` to receive the params of every notification the server sends for
+    /// `method`, next to any handlers already registered. Returns an id for
+    /// `remove_notification_handler`. Like the other handlers it runs on the read loop while
+    /// the server lock is held and must not call back into this `LanguageServerRef`.
+    pub fn on_notification<F>(&self, method: &str, handler: F) -> usize
+    where
+        F: 'static + Send + FnMut(Value),
+    {
+        let mut inner = self.0.lock().unwrap();
+        let id = inner.next_handler_id;
+        inner.next_handler_id += 1;
+        inner
+            .notification_handlers
+            .entry(method.to_owned())
+            .or_default()
+            .push((id, Box::new(handler)));
+        id
+    }
+
+    /// Removes a handler registered with `on_notification`.
+    pub fn remove_notification_handler(&self, id: usize) {
+        let mut inner = self.0.lock().unwrap();
+        for handlers in inner.notification_handlers.values_mut() {
+            handlers.retain(|(handler_id, _)| *handler_id != id);
+        }
+        inner
+            .notification_handlers
+            .retain(|_, handlers| !handlers.is_empty());
+    }
+
+    /// Registers `handler` to answer the requests from the server that nothing else handles,
+    /// replacing any previous one, e.g. with `Error::method_not_found()` so that the server
+    /// does not wait for an answer forever. Without it they are left unanswered with a
+    /// warning. Like the other handlers it runs on the read loop while the server lock is
+    /// held and must not call back into this `LanguageServerRef`.
+    pub fn on_unhandled_request<F>(&self, handler: F)
+    where
+        F: 'static + Send + FnMut(&str, Value) -> Result<Value, Error>,
+    {
+        self.0.lock().unwrap().unhandled_request = Some(Box::new(handler));
+    }
+
+    /// Registers `handler` to receive the notifications from the server that no handler
+    /// registered with `on_notification` receives, replacing any previous one, e.g. to log
+    /// them. Like the other handlers it runs on the read loop while the server lock is held
+    /// and must not call back into this `LanguageServerRef`.
+    pub fn on_unhandled_notification<F>(&self, handler: F)
+    where
+        F: 'static + Send + FnMut(&str, Value),
+    {
+        self.0.lock().unwrap().unhandled_notification = Some(Box::new(handler));
+    }
+
+    /// Watches the work done progress the server started on its own and has not ended yet,
+    /// such as indexing, by token.
+    pub fn server_progress(&self) -> watch::Receiver<HashMap<NumberOrString, ServerProgress>> {
+        self.0.lock().unwrap().server_progress.subscribe()
+    }
+
+    /// Registers `handler` to apply the edits the server sends through `workspace/applyEdit`,
+    /// replacing any previous one. The server is told the edit was applied when it returns
+    /// `Ok`, and the reason it was not otherwise. Without a handler the edits are applied by
+    /// the command being executed, see `execute_command_with`. Like the other handlers it
+    /// runs on the read loop while the server lock is held and must not call back into this
+    /// `LanguageServerRef`.
+    pub fn on_apply_edit<F>(&self, handler: F)
+    where
+        F: 'static + Send + FnMut(&WorkspaceEdit) -> Result<(), String>,
+    {
+        self.0.lock().unwrap().edit_handler = Some(Box::new(handler));
+    }
+
+    /// Registers `handler` to receive the body of every message written to or read from the
+    /// server, replacing any previous one, e.g. to trace the traffic. Messages are passed
+    /// before they are written and before they are dispatched. Unlike the other handlers it
+    /// runs without the server lock held.
+    pub fn on_traffic<F>(&self, handler: F)
+    where
+        F: 'static + Send + Sync + Fn(Direction, &str),
+    {
+        self.0.lock().unwrap().traffic_handler = Some(Arc::new(handler));
+    }
+
+    /// Adds `interceptor` after those already added, to see every message written to or
+    /// read from the server from now on, see `Interceptor`. Returns an id for
+    /// `remove_interceptor`. The traffic handler and the frame log see the messages written
+    /// as the interceptors left them, and the messages read as the server wrote them.
+    pub fn add_interceptor<I: 'static + Interceptor>(&self, interceptor: I) -> usize {
+        let mut inner = self.0.lock().unwrap();
+        let id = inner.next_handler_id;
+        inner.next_handler_id += 1;
+        inner.interceptors.push((id, Arc::new(interceptor)));
+        id
+    }
+
+    /// Removes an interceptor added with `add_interceptor`.
+    pub fn remove_interceptor(&self, id: usize) {
+        let mut inner = self.0.lock().unwrap();
+        inner
+            .interceptors
+            .retain(|(interceptor_id, _)| *interceptor_id != id);
+    }
+
+    fn interceptors(&self) -> Vec<Arc<dyn Interceptor>> {
+        let inner = self.0.lock().unwrap();
+        inner
+            .interceptors
+            .iter()
+            .map(|(_, interceptor)| interceptor.clone())
+            .collect()
+    }
+
+    /// Adds `hook` to run before every request is sent, with its params, and be waited for,
+    /// e.g. to send the server the state it needs to answer the request. Returns an id for
+    /// `remove_request_hook`.
+    pub fn add_request_hook<F>(&self, hook: F) -> usize
+    where
+        F: 'static + Send + Sync + Fn(&LanguageServerRef<W>, &Value) -> BoxFuture<'static, ()>,
+    {
+        let mut inner = self.0.lock().unwrap();
+        let id = inner.next_handler_id;
+        inner.next_handler_id += 1;
+        inner.request_hooks.push((id, Arc::new(hook)));
+        id
+    }
+
+    /// Removes a hook added with `add_request_hook`.
+    pub fn remove_request_hook(&self, id: usize) {
+        let mut inner = self.0.lock().unwrap();
+        inner.request_hooks.retain(|(hook_id, _)| *hook_id != id);
+    }
+
+    /// Writes every message exchanged with the server from now on to `log`, replacing any
+    /// previous log. It is kept apart from the traffic handler, so both can be used at once.
+    pub fn log_frames(&self, log: FrameLog) {
+        self.0.lock().unwrap().frame_log = Some(Arc::new(log));
+    }
+
+    /// The requests sent to the server and the bytes exchanged with it so far, updated as
+    /// the client goes on.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.0.lock().unwrap().metrics.clone()
+    }
+
+    /// The messages from the server breaking the protocol from now on, which the client
+    /// reports as warnings and otherwise skips. A receiver that falls more than 64
+    /// violations behind misses the oldest.
+    pub fn protocol_violations(&self) -> broadcast::Receiver<ProtocolViolation> {
+        self.0.lock().unwrap().violations.subscribe()
+    }
+
+    /// The requests sent to the server that it has not answered yet, oldest first, e.g. to
+    /// show what a server that stopped answering is stuck on. A request whose caller gave
+    /// up waiting, e.g. on a timeout, is listed until the server answers it all the same.
+    pub fn pending_requests(&self) -> Vec<PendingRequest> {
+        let inner = self.0.lock().unwrap();
+        let mut requests: Vec<_> = inner
+            .pending
+            .iter()
+            .map(|(id, pending)| PendingRequest {
+                id: *id,
+                method: pending.method.clone(),
+                elapsed: pending.sent.elapsed(),
+            })
+            .collect();
+        requests.sort_by_key(|request| request.id);
+        requests
+    }
+
+    /// Sets how long `request_value` and the helpers built on it wait for a response, by
+    /// method. Without timeouts, the default, they wait as long as the server runs.
+    pub fn set_request_timeouts(&self, timeouts: RequestTimeouts) {
+        self.0.lock().unwrap().timeouts = timeouts;
+    }
+
+    /// Sets what to do with responses to no pending request, reported by default.
+    pub fn set_unknown_response_policy(&self, policy: UnknownResponsePolicy) {
+        self.0.lock().unwrap().unknown_responses = policy;
+    }
+
+    /// Reads the headers of the messages from the server as `mode` says, strictly by
+    /// default. It applies to the messages that start arriving after the call.
+    pub fn set_parse_mode(&self, mode: ParseMode) {
+        self.0.lock().unwrap().parse_mode = mode;
+    }
+
+    /// Skips the messages from the server longer than `max` bytes, reporting a
+    /// `ProtocolViolation::TooLarge`, rather than trusting any `Content-Length` to allocate
+    /// their body. `DEFAULT_MAX_MESSAGE_SIZE` by default, `None` for no maximum. It applies to
+    /// the messages that start arriving after the call.
+    pub fn set_max_message_size(&self, max: Option<usize>) {
+        self.0.lock().unwrap().max_message_size = max;
+    }
+
+    /// How the messages from the server are read: the parse mode and the maximum size.
+    fn reading(&self) -> (ParseMode, Option<usize>) {
+        let inner = self.0.lock().unwrap();
+        (inner.parse_mode, inner.max_message_size)
+    }
+
+    /// Decodes message bodies that are not UTF-8 with replacement characters, reporting a
+    /// `ProtocolViolation::InvalidUtf8`, rather than skipping them, e.g. for servers sending
+    /// the paths of filesystems that are not UTF-8 as they are. Off by default.
+    pub fn set_lossy_utf8(&self, lossy: bool) {
+        self.0.lock().unwrap().lossy_utf8 = lossy;
+    }
+
+    /// Checks every message sent or received against the structures of lsp_types, reporting
+    /// unknown fields, missing or mistyped fields and enum values out of the specification as
+    /// `ProtocolViolation::Invalid`, e.g. to catch a server deviating from the specification
+    /// while writing it. Off by default, as it parses every message once more.
+    pub fn set_strict_validation(&self, strict: bool) {
+        self.0.lock().unwrap().strict_validation = strict;
+    }
+
+    /// Reads the next message from the server, reporting and skipping those that cannot be
+    /// read. `None` once the server closed its output.
+    async fn next_message<'r, B: AsyncBufReadExt + Unpin>(
+        &self,
+        messages: &'r mut MessageReader<B>,
+    ) -> Option<Cow<'r, str>> {
+        let (lossy_utf8, mut resync) = {
+            let mut inner = self.0.lock().unwrap();
+            (inner.lossy_utf8, std::mem::take(&mut inner.resync))
+        };
+        // a malformed message may leave the reader anywhere in the stream
+        loop {
+            if resync {
+                let skipped = messages.resync().await.ok()?;
+                if skipped > 0 {
+                    let violation = ProtocolViolation::Skipped(skipped);
+                    self.0.lock().unwrap().violation(violation);
+                }
+            }
+            let violation = match messages.read().await.map(|_| ()) {
+                Ok(()) => return Some(Cow::Borrowed(messages.message())),
+                // the server exited or closed its output, nothing more will arrive
+                Err(ParseError::Io(_)) => return None,
+                Err(ParseError::Utf8(err)) if lossy_utf8 => {
+                    let violation = ProtocolViolation::InvalidUtf8 {
+                        bytes: err.as_bytes().len(),
+                    };
+                    self.0.lock().unwrap().violation(violation);
+                    return Some(Cow::Owned(
+                        String::from_utf8_lossy(err.as_bytes()).into_owned(),
+                    ));
+                }
+                // the body was skipped, the next message follows
+                Err(ParseError::TooLarge { declared, max }) => {
+                    let violation = ProtocolViolation::TooLarge { declared, max };
+                    self.0.lock().unwrap().violation(violation);
+                    resync = false;
+                    continue;
+                }
+                Err(err) => ProtocolViolation::Malformed(format!("{:?}", err)),
+            };
+            self.0.lock().unwrap().violation(violation);
+            resync = true;
+        }
+    }
+
+    /// Passes a message written or read to the traffic handler, the frame log and the
+    /// metrics.
+    fn observe(&self, direction: Direction, body: &str) {
+        let (handler, frame_log) = {
+            let inner = self.0.lock().unwrap();
+            inner.metrics.message(direction, body.len());
+            (inner.traffic_handler.clone(), inner.frame_log.clone())
+        };
+        if let Some(handler) = handler {
+            handler(direction, body);
+        }
+        if let Some(frame_log) = frame_log {
+            frame_log.write(direction, body);
+        }
+    }
+
+    /// Starts receiving the edits the server sends while a command is executed, returning
+    /// the id to pass to `end_command` and the edits, with the id of the request to answer
+    /// through `answer_apply_edit`.
+    pub(crate) fn begin_command(&self) -> (usize, mpsc::UnboundedReceiver<(Value, WorkspaceEdit)>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut inner = self.0.lock().unwrap();
+        let id = inner.next_command;
+        inner.next_command += 1;
+        inner.commands.push((id, sender));
+        (id, receiver)
+    }
+
+    /// Stops receiving edits for the command `id`.
+    pub(crate) fn end_command(&self, id: usize) {
+        let mut inner = self.0.lock().unwrap();
+        inner.commands.retain(|(command, _)| *command != id);
+    }
+
+    /// Answers the `workspace/applyEdit` request `id` with whether the edit was applied.
+    pub(crate) async fn answer_apply_edit(&self, id: &Value, applied: Result<(), String>) {
+        let response = response_message(id, Ok(apply_edit_response(applied)));
+        self.send_rpc(&response).await;
+    }
+
+    /// Registers `listener` to receive the `value` of every `$/progress` notification sent
+    /// with the returned token, until `remove_progress_listener` is called.
+    ///
+    /// The listener runs on the read loop while the server lock is held, so it must not call
+    /// back into this `LanguageServerRef`.
+    pub fn register_progress_listener<F>(&self, listener: F) -> NumberOrString
+    where
+        F: 'static + Send + FnMut(Value),
+    {
+        let mut inner = self.0.lock().unwrap();
+        let token = NumberOrString::String(format!("lsp_client/{}", inner.next_token));
+        inner.next_token += 1;
+        inner
+            .progress_listeners
+            .insert(token.clone(), Box::new(listener));
+        token
+    }
+
+    /// Stops routing `$/progress` notifications for `token`.
+    pub fn remove_progress_listener(&self, token: &NumberOrString) {
+        let mut inner = self.0.lock().unwrap();
+        inner.progress_listeners.remove(token);
+    }
+
+    /// Sends a JSON-RPC request message with the provided method and parameters, returning
+    /// its id. `completion` should be a callback which will be executed with the server's
+    /// response, either the `result` or the whole `error` object. It is dropped without being
+    /// called when no response can arrive anymore: the server closed its output or the
+    /// client was closed.
+    pub async fn send_request<CB>(&self, method: &str, params: &Value, completion: CB) -> usize
+    where
+        CB: 'static + Send + FnOnce(Result<Value, Value>),
+    {
+        let completion = move |response: RawResponse| match response {
+            Ok(result) => completion(Ok(value(&result))),
+            Err(error) => completion(Err(value(&error))),
+        };
+        self.send_raw_request(method, params, Box::new(completion))
+            .await
+    }
+
+    /// `send_request`, with `completion` given the response as the server wrote it.
+    async fn send_raw_request(&self, method: &str, params: &Value, completion: Callback) -> usize {
+        let hooks: Vec<RequestHook<W>> = {
+            let inner = self.0.lock().unwrap();
+            inner
+                .request_hooks
+                .iter()
+                .map(|(_, hook)| hook.clone())
+                .collect()
+        };
+        for hook in hooks {
+            hook(self, params).await;
+        }
+        let (id, request) = {
+            let mut inner = self.0.lock().unwrap();
+            let id = inner.next_id;
+            (id, inner.prepare_request(method, params, completion))
+        };
+        self.send_rpc(&request).await;
+        id
     }
 
     /// Sends a JSON-RPC notification message with the provided method and parameters.
     pub async fn send_notification(&self, method: &str, params: &Value) {
-        let mut inner = self.0.lock().unwrap();
-        inner.send_notification(method, params).await;
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params
+        });
+        self.send_rpc(&notification).await;
+    }
+
+    async fn send_rpc(&self, rpc: &Value) {
+        let interceptors = self.interceptors();
+        let mut intercepted;
+        let mut rpc = rpc;
+        if !interceptors.is_empty() {
+            intercepted = rpc.clone();
+            match intercept(&interceptors, Direction::Sent, &mut intercepted) {
+                Flow::Continue => rpc = &intercepted,
+                Flow::Drop => return self.answer_locally(rpc, None),
+                Flow::Respond(response) => return self.answer_locally(rpc, Some(response)),
+            }
+        }
+        let peer = {
+            let inner = self.0.lock().unwrap();
+            if inner.closed {
+                return;
+            }
+            // responses to the server are checked by `handle_request`, which knows the method
+            if let (Some(method), Some(params)) = (rpc["method"].as_str(), rpc.get("params")) {
+                inner.validate(Direction::Sent, method, Member::Params, params);
+            }
+            inner.peer.clone()
+        };
+        self.observe(Direction::Sent, &rpc.to_string());
+        log::message_sent(rpc);
+        if let Err(err) = write_rpc(&peer, rpc).await {
+            // the server stopped reading, e.g. it exited: no answer is coming, as when it
+            // closes its output
+            log::warning!("error writing to the server: {}", err);
+            self.0.lock().unwrap().pending.clear();
+        }
+    }
+
+    /// Completes a request an interceptor kept from being written with `response`, or drops
+    /// its callback without one. Other messages are just not written.
+    fn answer_locally(&self, rpc: &Value, response: Option<Result<Value, Value>>) {
+        if rpc.get("method").is_none() {
+            return;
+        }
+        let Some(id) = rpc.get("id").and_then(Value::as_u64) else {
+            return;
+        };
+        let pending = {
+            let mut inner = self.0.lock().unwrap();
+            usize::try_from(id)
+                .ok()
+                .and_then(|id| inner.pending.remove(&id))
+        };
+        let (Some(pending), Some(response)) = (pending, response) else {
+            return;
+        };
+        let raw = |value: Value| {
+            serde_json::value::to_raw_value(&value).expect("a `Value` is always valid JSON")
+        };
+        pending.callback.call(response.map(raw).map_err(raw));
+    }
+
+    /// Sends the LSP request `R` and waits for its typed response.
+    pub async fn request<R>(&self, params: R::Params) -> Result<R::Result, ClientError>
+    where
+        R: lsp_types::request::Request,
+    {
+        let timeout = self.request_timeout(R::METHOD);
+        // deserialized straight from the message, with no `Value` in between
+        let result = self.request_raw(R::METHOD, json!(params), timeout).await?;
+        Ok(serde_json::from_str(result.get())?)
+    }
+
+    /// Sends a request for an arbitrary method and waits for its untyped response, failing
+    /// with `ClientError::Timeout` after the timeout `set_request_timeouts` gives the method.
+    pub async fn request_value(&self, method: &str, params: Value) -> Result<Value, ClientError> {
+        let timeout = self.request_timeout(method);
+        let result = self.request_raw(method, params, timeout).await?;
+        Ok(value(&result))
+    }
+
+    /// `request_value`, waiting at most `timeout` whatever the timeouts set for the method.
+    pub async fn request_value_within(
+        &self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value, ClientError> {
+        let result = self.request_raw(method, params, Some(timeout)).await?;
+        Ok(value(&result))
+    }
+
+    fn request_timeout(&self, method: &str) -> Option<Duration> {
+        self.0.lock().unwrap().timeouts.get(method)
+    }
+
+    /// Sends a request and waits for its result as the server wrote it.
+    async fn request_raw(
+        &self,
+        method: &str,
+        params: Value,
+        timeout: Option<Duration>,
+    ) -> Result<Box<RawValue>, ClientError> {
+        log::in_request_span(method, async {
+            let (response, timed_out) = if self.is_coalesced(method) {
+                self.send_coalesced(method, &params, timeout).await
+            } else {
+                self.send_and_wait(method, &params, timeout).await
+            };
+            let unanswered = if timed_out {
+                ClientError::Timeout
+            } else if self.is_closed() {
+                ClientError::ClientClosed
+            } else {
+                ClientError::Canceled
+            };
+            match response.ok_or(unanswered)? {
+                Ok(result) => Ok(result),
+                Err(error) => Err(ClientError::Server(serde_json::from_str(error.get())?)),
+            }
+        })
+        .await
+    }
+
+    /// Sends a request and waits for its response, `None` when none arrived, and whether it
+    /// timed out.
+    async fn send_and_wait(
+        &self,
+        method: &str,
+        params: &Value,
+        timeout: Option<Duration>,
+    ) -> Outcome {
+        let (tx, rx) = oneshot::channel();
+        let completion = move |response: RawResponse| {
+            let _ = tx.send(response);
+        };
+        let id = self
+            .send_raw_request(method, params, Box::new(completion))
+            .await;
+        log::request_sent(id);
+        let metrics = self.metrics();
+        metrics.request_sent(method);
+        let sent = Instant::now();
+        let (response, timed_out) = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, rx).await {
+                Ok(response) => (response.ok(), false),
+                Err(_) => {
+                    self.cancel_request(id).await;
+                    (None, true)
+                }
+            },
+            None => (rx.await.ok(), false),
+        };
+        log::response_received(sent.elapsed(), response.as_ref());
+        let failed = !matches!(response, Some(Ok(_)));
+        metrics.response_received(method, sent.elapsed(), failed);
+        (response, timed_out)
+    }
+
+    /// Stops waiting for the request `id`, and tells the server through `$/cancelRequest`
+    /// that its answer is no longer needed. The response it still sends is dropped.
+    async fn cancel_request(&self, id: usize) {
+        {
+            let mut inner = self.0.lock().unwrap();
+            if inner.pending.remove(&id).is_none() {
+                return;
+            }
+            inner.canceled.insert(id);
+        }
+        self.send_notification(Cancel::METHOD, &json!({ "id": id }))
+            .await;
+    }
+
+    /// Shares the response of the identical request in flight, if there is one, rather than
+    /// sending another. Callers giving up, the first one included, leave the request to the
+    /// others.
+    async fn send_coalesced(
+        &self,
+        method: &str,
+        params: &Value,
+        timeout: Option<Duration>,
+    ) -> Outcome {
+        let serialized = params.to_string();
+        let mut hasher = DefaultHasher::new();
+        serialized.hash(&mut hasher);
+        let key = (method.to_owned(), hasher.finish());
+        let (tx, rx) = oneshot::channel();
+        let (coalesced, joined) = {
+            let mut inner = self.0.lock().unwrap();
+            let joined = match inner.in_flight.get(&key) {
+                Some(coalesced) => {
+                    let joined = coalesced.lock().unwrap().join(&serialized, tx);
+                    joined.map(|()| coalesced.clone())
+                }
+                None => Err(tx),
+            };
+            match joined {
+                Ok(coalesced) => (coalesced, true),
+                Err(tx) => {
+                    let coalesced = Arc::new(Mutex::new(Coalesced {
+                        params: serialized,
+                        id: None,
+                        waiters: Some(vec![tx]),
+                    }));
+                    inner.in_flight.insert(key.clone(), coalesced.clone());
+                    (coalesced, false)
+                }
+            }
+        };
+        let mut waiting = Waiting {
+            server: self,
+            key,
+            coalesced: coalesced.clone(),
+            response: rx,
+        };
+        if !joined {
+            let answer = Answer {
+                coalesced: coalesced.clone(),
+                method: method.to_owned(),
+                sent: Instant::now(),
+                metrics: self.metrics(),
+                answered: false,
+            };
+            let completion = move |response: RawResponse| answer.send(response);
+            let id = self
+                .send_raw_request(method, params, Box::new(completion))
+                .await;
+            coalesced.lock().unwrap().id = Some(id);
+            log::request_sent(id);
+            self.metrics().request_sent(method);
+        }
+        let sent = Instant::now();
+        let (response, timed_out) = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, &mut waiting.response).await {
+                Ok(response) => (response.ok(), false),
+                Err(_) => {
+                    waiting.give_up().await;
+                    (None, true)
+                }
+            },
+            None => ((&mut waiting.response).await.ok(), false),
+        };
+        log::response_received(sent.elapsed(), response.as_ref());
+        (response, timed_out)
+    }
+
+    fn is_coalesced(&self, method: &str) -> bool {
+        self.0.lock().unwrap().coalesced.contains(method)
+    }
+
+    /// Sends the requests for `methods` only once while they are identical and in flight:
+    /// whoever makes the same request, with the same params, while one is waiting for its
+    /// response shares that response, or its failure, instead of sending another, e.g. for
+    /// `textDocument/hover` or `textDocument/definition` asked by several parts of an editor
+    /// at once. Each caller still times out on its own, and the request is canceled only once
+    /// none waits for it. Replaces the methods coalesced before, none by default. Only methods
+    /// without side effects should be.
+    pub fn coalesce_requests(&self, methods: &[&str]) {
+        self.0.lock().unwrap().coalesced =
+            methods.iter().map(|&method| method.to_owned()).collect();
+    }
+
+    /// Performs the initialize handshake: sends `initialize`, records the server's answer
+    /// for later use by the helpers, and confirms with the `initialized` notification.
+    pub async fn initialize(
+        &self,
+        params: InitializeParams,
+    ) -> Result<InitializeResult, ClientError> {
+        let result = self.request::<Initialize>(params).await?;
+        self.0.lock().unwrap().initialize_result = Some(result.clone());
+        self.notify::<Initialized>(InitializedParams {}).await;
+        Ok(result)
+    }
+
+    /// Asks the server to shut down, then tells it to exit. The server process should be
+    /// expected to terminate afterwards.
+    pub async fn shutdown(&self) -> Result<(), ClientError> {
+        self.request::<Shutdown>(()).await?;
+        self.notify::<Exit>(()).await;
+        Ok(())
+    }
+
+    /// Stops talking to the server: the read loop is stopped, the requests waiting for a
+    /// response fail with `ClientError::ClientClosed`, as do those sent from now on, and the
+    /// writer is shut down, which closes the input of a server process. Notifications are
+    /// dropped. Dropping every `LanguageServerRef` of a client stops its read loop too.
+    pub async fn close(&self) {
+        let peer = {
+            let mut inner = self.0.lock().unwrap();
+            if inner.closed {
+                return;
+            }
+            inner.closed = true;
+            if let Some(read_loop) = inner.read_loop.take() {
+                read_loop.abort();
+            }
+            // dropping the callbacks wakes up the requests waiting for them
+            inner.pending.clear();
+            inner.peer.clone()
+        };
+        // the server may be gone already
+        let _ = peer.lock().await.shutdown().await;
+    }
+
+    /// Whether `close` was called.
+    pub fn is_closed(&self) -> bool {
+        self.0.lock().unwrap().closed
+    }
+
+    /// The server's answer to `initialize`, once `initialize` has completed.
+    pub fn initialize_result(&self) -> Option<InitializeResult> {
+        self.0.lock().unwrap().initialize_result.clone()
+    }
+
+    /// The capabilities the server announced in `initialize` and registered since. Before
+    /// `initialize` has completed the server supports nothing.
+    pub fn capabilities(&self) -> Capabilities {
+        let inner = self.0.lock().unwrap();
+        let announced = inner
+            .initialize_result
+            .as_ref()
+            .map(|result| result.capabilities.clone())
+            .unwrap_or_default();
+        Capabilities::new(announced, inner.registrations.clone())
+    }
+
+    /// Sends the LSP notification `N`.
+    pub async fn notify<N>(&self, params: N::Params)
+    where
+        N: lsp_types::notification::Notification,
+    {
+        self.send_notification(N::METHOD, &json!(params)).await;
     }
 }
 
@@ -180,18 +1738,45 @@ impl<W: AsyncWriteExt> Clone for LanguageServerRef<W> {
 pub async fn start_language_server(mut child: Child) -> (Child, LanguageServerRef<ChildStdin>) {
     let child_stdin = child.stdin.take().unwrap();
     let child_stdout = child.stdout.take().unwrap();
-    let lang_server = LanguageServerRef::new(child_stdin);
-    {
-        let lang_server = lang_server.clone();
-        tokio::task::spawn(async move {
-            let mut reader = BufReader::new(child_stdout);
-            loop {
-                match parsing::read_message(&mut reader).await {
-                    Ok(ref val) => lang_server.handle_msg(val),
-                    Err(err) => println!("parse error: {:?}", err),
-                };
+    (child, connect(child_stdout, child_stdin))
+}
+
+/// A client reading the messages of the server from `reader` and writing its own to
+/// `writer`, e.g. over a socket, or in memory as `testing::pipe` does. It reads messages
+/// until `reader` ends.
+pub fn connect<R, W>(reader: R, writer: W) -> LanguageServerRef<W>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWriteExt + Unpin + Send + 'static,
+{
+    let lang_server = LanguageServerRef::new(writer);
+    // a weak reference, so the client is dropped with the last `LanguageServerRef` handed out
+    let weak = Arc::downgrade(&lang_server.0);
+    let read_loop = tokio::task::spawn(async move {
+        let mut messages = MessageReader::new(BufReader::new(reader), ParseMode::Strict);
+        loop {
+            // looked up once the message starts arriving, so setting the mode before
+            // sending `initialize` is early enough
+            let _ = messages.get_mut().fill_buf().await;
+            let Some(lang_server) = weak.upgrade().map(LanguageServerRef) else {
+                return;
+            };
+            let (mode, max_length) = lang_server.reading();
+            messages.set_mode(mode);
+            messages.set_max_length(max_length);
+            let Some(val) = lang_server.next_message(&mut messages).await else {
+                break;
+            };
+            lang_server.observe(Direction::Received, &val);
+            if let Some(response) = lang_server.handle_msg(&val) {
+                lang_server.send_rpc(&response).await;
             }
-        });
-    }
-    (child, lang_server)
+        }
+        // fails the requests still waiting for a response with `ClientError::Canceled`
+        if let Some(inner) = weak.upgrade() {
+            inner.lock().unwrap().pending.clear();
+        }
+    });
+    lang_server.0.lock().unwrap().read_loop = Some(read_loop.abort_handle());
+    lang_server
 }
@@ -0,0 +1,70 @@
+use lsp_types::request::{CodeActionRequest, CodeActionResolveRequest};
+use lsp_types::{
+    CodeAction, CodeActionContext, CodeActionOrCommand, CodeActionParams, Range,
+    TextDocumentIdentifier, Url,
+};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::documents::DocumentManager;
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Lists the code actions (and bare commands) available for `range`.
+    pub async fn code_actions(
+        &self,
+        uri: Url,
+        range: Range,
+        context: CodeActionContext,
+    ) -> Result<Vec<CodeActionOrCommand>, ClientError> {
+        let params = CodeActionParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            context,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        Ok(self
+            .request_if_supported::<CodeActionRequest>(params)
+            .await?
+            .unwrap_or_default())
+    }
+
+    /// Fills in the lazily computed parts of a code action, usually its `edit`.
+    pub async fn resolve_code_action(&self, action: CodeAction) -> Result<CodeAction, ClientError> {
+        self.request_if_supported::<CodeActionResolveRequest>(action)
+            .await
+    }
+
+    /// Performs a code action: resolves it if the server left its edit out, applies its
+    /// edit through `documents`, then executes its command, in that order as the
+    /// specification requires. Edits the server sends while running the command are applied
+    /// through `documents` as well.
+    pub async fn apply_code_action(
+        &self,
+        documents: &DocumentManager<W>,
+        action: CodeActionOrCommand,
+    ) -> Result<(), ClientError> {
+        let action = match action {
+            CodeActionOrCommand::Command(command) => {
+                self.run_command_with(&command, move |edit| documents.apply_command_edit(edit))
+                    .await?;
+                return Ok(());
+            }
+            CodeActionOrCommand::CodeAction(action) => action,
+        };
+        let action = if action.edit.is_none() && action.data.is_some() {
+            self.resolve_code_action(action).await?
+        } else {
+            action
+        };
+
+        if let Some(edit) = &action.edit {
+            documents.apply_workspace_edit(edit).await?;
+        }
+        if let Some(command) = &action.command {
+            self.run_command_with(command, move |edit| documents.apply_command_edit(edit))
+                .await?;
+        }
+        Ok(())
+    }
+}
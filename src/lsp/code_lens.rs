@@ -0,0 +1,42 @@
+use lsp_types::request::{CodeLensRequest, CodeLensResolve};
+use lsp_types::{CodeLens, CodeLensParams, TextDocumentIdentifier, Url};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::commands::CommandOutcome;
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Lists the code lenses of a document. Lenses may come back without a command, in
+    /// which case `resolve_code_lens` fills it in.
+    pub async fn code_lenses(&self, uri: Url) -> Result<Vec<CodeLens>, ClientError> {
+        let params = CodeLensParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        Ok(self
+            .request_if_supported::<CodeLensRequest>(params)
+            .await?
+            .unwrap_or_default())
+    }
+
+    pub async fn resolve_code_lens(&self, lens: CodeLens) -> Result<CodeLens, ClientError> {
+        self.request_if_supported::<CodeLensResolve>(lens).await
+    }
+
+    /// Executes the command behind a lens, resolving the lens first if needed.
+    /// Returns `None` when the lens has no command even after resolving.
+    pub async fn execute_code_lens(
+        &self,
+        lens: CodeLens,
+    ) -> Result<Option<CommandOutcome>, ClientError> {
+        let lens = match lens.command {
+            Some(_) => lens,
+            None => self.resolve_code_lens(lens).await?,
+        };
+        match &lens.command {
+            Some(command) => Ok(Some(self.run_command(command).await?)),
+            None => Ok(None),
+        }
+    }
+}
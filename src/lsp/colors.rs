@@ -0,0 +1,38 @@
+use lsp_types::request::{ColorPresentationRequest, DocumentColor};
+use lsp_types::{
+    Color, ColorInformation, ColorPresentation, ColorPresentationParams, DocumentColorParams,
+    Range, TextDocumentIdentifier, Url,
+};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Lists the color literals in a document together with their RGBA values.
+    pub async fn document_color(&self, uri: Url) -> Result<Vec<ColorInformation>, ClientError> {
+        let params = DocumentColorParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        self.request_if_supported::<DocumentColor>(params).await
+    }
+
+    /// Asks how `color` could be written at `range`, e.g. `#ff0000` or `rgb(255, 0, 0)`.
+    pub async fn color_presentation(
+        &self,
+        uri: Url,
+        range: Range,
+        color: Color,
+    ) -> Result<Vec<ColorPresentation>, ClientError> {
+        let params = ColorPresentationParams {
+            text_document: TextDocumentIdentifier { uri },
+            color,
+            range,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        self.request_if_supported::<ColorPresentationRequest>(params)
+            .await
+    }
+}
@@ -0,0 +1,131 @@
+use std::future::Future;
+
+use lsp_types::request::{ExecuteCommand, Request};
+use lsp_types::{Command, ExecuteCommandParams, WorkspaceEdit};
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::edits::apply_workspace_edit_to_disk;
+
+/// What running a command produced: its result, and the edits the server asked the client
+/// to apply through `workspace/applyEdit` while the command was running, whether they
+/// applied or not. Edits applied by a handler registered with
+/// `LanguageServerRef::on_apply_edit` are not among them.
+#[derive(Debug, Clone, Default)]
+pub struct CommandOutcome {
+    pub result: Option<Value>,
+    pub edits: Vec<WorkspaceEdit>,
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Runs a server command through `workspace/executeCommand`, applying the edits it sends
+    /// to the files on disk. See `execute_command_with`.
+    pub async fn execute_command(
+        &self,
+        command: &str,
+        arguments: Vec<Value>,
+    ) -> Result<CommandOutcome, ClientError> {
+        self.execute_command_with(command, arguments, |edit| async move {
+            apply_workspace_edit_to_disk(&edit)
+                .await
+                .map_err(|err| format!("{:?}", err))
+        })
+        .await
+    }
+
+    /// Runs a server command through `workspace/executeCommand`, applying the edits it sends
+    /// with `apply`.
+    ///
+    /// Commands the server did not list in its `executeCommandProvider`, nor registered since,
+    /// are rejected with `ClientError::UnknownCommand` without contacting the server. Servers
+    /// commonly carry out a command by sending `workspace/applyEdit` back before answering;
+    /// unless an `on_apply_edit` handler applies them, each such edit is passed to `apply`
+    /// while the command runs, and the server is told whether it applied once `apply`
+    /// returns.
+    pub async fn execute_command_with<F, Fut>(
+        &self,
+        command: &str,
+        arguments: Vec<Value>,
+        mut apply: F,
+    ) -> Result<CommandOutcome, ClientError>
+    where
+        F: FnMut(WorkspaceEdit) -> Fut,
+        Fut: Future<Output = Result<(), String>>,
+    {
+        let capabilities = self.capabilities();
+        if !capabilities.supports(ExecuteCommand::METHOD) {
+            return Err(ClientError::Unsupported(ExecuteCommand::METHOD));
+        }
+        if !capabilities.commands().iter().any(|known| known == command) {
+            return Err(ClientError::UnknownCommand(command.to_owned()));
+        }
+
+        let (id, mut requested) = self.begin_command();
+        let in_flight = InFlightCommand { server: self, id };
+        let params = ExecuteCommandParams {
+            command: command.to_owned(),
+            arguments,
+            work_done_progress_params: Default::default(),
+        };
+        let request = self.request::<ExecuteCommand>(params);
+        tokio::pin!(request);
+        let mut edits = Vec::new();
+        let result = loop {
+            tokio::select! {
+                result = &mut request => break result,
+                Some((request_id, edit)) = requested.recv() => {
+                    self.answer_apply_edit(&request_id, apply(edit.clone()).await).await;
+                    edits.push(edit);
+                }
+            }
+        };
+        drop(in_flight);
+        // edits read along with the response
+        while let Ok((request_id, edit)) = requested.try_recv() {
+            self.answer_apply_edit(&request_id, apply(edit.clone()).await)
+                .await;
+            edits.push(edit);
+        }
+        Ok(CommandOutcome {
+            result: result?,
+            edits,
+        })
+    }
+
+    /// Executes a `Command` attached to a code action, code lens or similar, applying the
+    /// edits it sends to the files on disk.
+    pub async fn run_command(&self, command: &Command) -> Result<CommandOutcome, ClientError> {
+        let arguments = command.arguments.clone().unwrap_or_default();
+        self.execute_command(&command.command, arguments).await
+    }
+
+    /// Executes a `Command` attached to a code action, code lens or similar, applying the
+    /// edits it sends with `apply`.
+    pub async fn run_command_with<F, Fut>(
+        &self,
+        command: &Command,
+        apply: F,
+    ) -> Result<CommandOutcome, ClientError>
+    where
+        F: FnMut(WorkspaceEdit) -> Fut,
+        Fut: Future<Output = Result<(), String>>,
+    {
+        let arguments = command.arguments.clone().unwrap_or_default();
+        self.execute_command_with(&command.command, arguments, apply)
+            .await
+    }
+}
+
+/// Stops receiving edits for a command also when `execute_command_with` is dropped before
+/// it finishes.
+struct InFlightCommand<'a, W: AsyncWriteExt + Unpin> {
+    server: &'a LanguageServerRef<W>,
+    id: usize,
+}
+
+impl<W: AsyncWriteExt + Unpin> Drop for InFlightCommand<'_, W> {
+    fn drop(&mut self) {
+        self.server.end_command(self.id);
+    }
+}
@@ -0,0 +1,91 @@
+use futures::future::join_all;
+use lsp_types::request::{Completion, Request, ResolveCompletionItem};
+use lsp_types::{
+    CompletionContext, CompletionItem, CompletionList, CompletionParams, CompletionResponse,
+    Position, Url,
+};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::log;
+use super::navigation::text_document_position;
+
+/// Converts a completion response of either shape into a list. A bare array is complete.
+pub fn normalize_completion_response(response: CompletionResponse) -> CompletionList {
+    match response {
+        CompletionResponse::Array(items) => CompletionList {
+            is_incomplete: false,
+            items,
+        },
+        CompletionResponse::List(list) => list,
+    }
+}
+
+/// Sorts completion items the way editors present them: by `sortText`, falling back to
+/// the label, keeping the server's order among equals.
+pub fn sort_completion_items(items: &mut [CompletionItem]) {
+    items.sort_by(|a, b| {
+        let key = |item: &CompletionItem| item.sort_text.clone().unwrap_or(item.label.clone());
+        key(a).cmp(&key(b))
+    });
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Requests the completions at `position`. `context` may be left out for a one-off
+    /// invocation. A server answering nothing yields an empty, complete list.
+    pub async fn completions(
+        &self,
+        uri: Url,
+        position: Position,
+        context: Option<CompletionContext>,
+    ) -> Result<CompletionList, ClientError> {
+        let params = CompletionParams {
+            text_document_position: text_document_position(uri, position),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context,
+        };
+        let response = self.request_if_supported::<Completion>(params).await?;
+        Ok(response
+            .map(normalize_completion_response)
+            .unwrap_or(CompletionList {
+                is_incomplete: false,
+                items: Vec::new(),
+            }))
+    }
+
+    /// Fills in the lazily computed parts of a completion item, such as its documentation
+    /// or detail.
+    pub async fn resolve_completion_item(
+        &self,
+        item: CompletionItem,
+    ) -> Result<CompletionItem, ClientError> {
+        self.request_if_supported::<ResolveCompletionItem>(item)
+            .await
+    }
+
+    /// Resolves all of `items` at once. Items the server fails to resolve are returned as
+    /// they were received, as are all items when the server cannot resolve them.
+    pub async fn resolve_completion_items(
+        &self,
+        items: Vec<CompletionItem>,
+    ) -> Vec<CompletionItem> {
+        if !self.capabilities().supports(ResolveCompletionItem::METHOD) {
+            return items;
+        }
+        let resolved = items.into_iter().map(|item| async move {
+            match self.resolve_completion_item(item.clone()).await {
+                Ok(item) => item,
+                Err(err) => {
+                    log::warning!(
+                        "failed to resolve completion item {}: {:?}",
+                        item.label,
+                        err
+                    );
+                    item
+                }
+            }
+        });
+        join_all(resolved).await
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use lsp_types::notification::PublishDiagnostics as PublishDiagnosticsNotification;
+use lsp_types::request::{DocumentDiagnosticRequest, WorkspaceDiagnosticRequest};
+use lsp_types::{
+    Diagnostic, DocumentDiagnosticParams, DocumentDiagnosticReport, DocumentDiagnosticReportKind,
+    DocumentDiagnosticReportResult, PreviousResultId, PublishDiagnosticsParams,
+    TextDocumentIdentifier, Url, WorkspaceDiagnosticParams, WorkspaceDiagnosticReportResult,
+    WorkspaceDocumentDiagnosticReport,
+};
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{watch, Notify};
+
+use super::client::{ClientError, LanguageServerRef};
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Pulls the diagnostics of a single document. Passing the `result_id` of the previous
+    /// report lets the server answer with an "unchanged" report instead of resending them.
+    pub async fn document_diagnostics(
+        &self,
+        uri: Url,
+        previous_result_id: Option<String>,
+    ) -> Result<DocumentDiagnosticReportResult, ClientError> {
+        let params = DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier { uri },
+            identifier: None,
+            previous_result_id,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        self.request_if_supported::<DocumentDiagnosticRequest>(params)
+            .await
+    }
+
+    /// Pulls the diagnostics of the whole workspace, with the result ids of previously
+    /// pulled documents.
+    pub async fn workspace_diagnostics(
+        &self,
+        previous_result_ids: Vec<PreviousResultId>,
+    ) -> Result<WorkspaceDiagnosticReportResult, ClientError> {
+        let params = WorkspaceDiagnosticParams {
+            identifier: None,
+            previous_result_ids,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        self.request_if_supported::<WorkspaceDiagnosticRequest>(params)
+            .await
+    }
+}
+
+/// The diagnostics of one document as last reported by the server.
+#[derive(Debug, Clone, Default)]
+struct CachedReport {
+    result_id: Option<String>,
+    items: Vec<Diagnostic>,
+}
+
+/// Pull model diagnostics (LSP 3.17) with result-id caching.
+///
+/// Every pulled report is cached with its result id, which is sent along with the next pull
+/// so the server can skip unchanged documents. Creating a `PullDiagnostics` also answers
+/// the server's `workspace/diagnostic/refresh` requests, see `refresh_requested`.
+pub struct PullDiagnostics<W: AsyncWriteExt> {
+    server: LanguageServerRef<W>,
+    reports: Arc<Mutex<HashMap<Url, CachedReport>>>,
+    refresh: Arc<Notify>,
+}
+
+impl<W: AsyncWriteExt + Unpin> PullDiagnostics<W> {
+    pub fn new(server: LanguageServerRef<W>) -> Self {
+        let refresh = Arc::new(Notify::new());
+        {
+            let refresh = refresh.clone();
+            server.on_request("workspace/diagnostic/refresh", move |_| {
+                refresh.notify_one();
+                Ok(Value::Null)
+            });
+        }
+        PullDiagnostics {
+            server,
+            reports: Arc::new(Mutex::new(HashMap::new())),
+            refresh,
+        }
+    }
+
+    /// Waits until the server asks for diagnostics to be pulled again, e.g. after a
+    /// configuration change. A refresh requested while nobody was waiting is not lost.
+    pub async fn refresh_requested(&self) {
+        self.refresh.notified().await;
+    }
+
+    /// Returns the cached diagnostics of a document without contacting the server.
+    pub fn cached(&self, uri: &Url) -> Option<Vec<Diagnostic>> {
+        let reports = self.reports.lock().unwrap();
+        reports.get(uri).map(|report| report.items.clone())
+    }
+
+    /// Pulls the current diagnostics of a document.
+    pub async fn document(&self, uri: Url) -> Result<Vec<Diagnostic>, ClientError> {
+        let previous_result_id = self
+            .reports
+            .lock()
+            .unwrap()
+            .get(&uri)
+            .and_then(|report| report.result_id.clone());
+        let result = self
+            .server
+            .document_diagnostics(uri.clone(), previous_result_id)
+            .await?;
+
+        let mut reports = self.reports.lock().unwrap();
+        let related_documents = match result {
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => {
+                let full = report.full_document_diagnostic_report;
+                store_report(&mut reports, uri.clone(), full.result_id, Some(full.items));
+                report.related_documents
+            }
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(report)) => {
+                let unchanged = report.unchanged_document_diagnostic_report;
+                store_report(&mut reports, uri.clone(), Some(unchanged.result_id), None);
+                report.related_documents
+            }
+            DocumentDiagnosticReportResult::Partial(partial) => partial.related_documents,
+        };
+        for (related, kind) in related_documents.unwrap_or_default() {
+            match kind {
+                DocumentDiagnosticReportKind::Full(full) => {
+                    store_report(&mut reports, related, full.result_id, Some(full.items))
+                }
+                DocumentDiagnosticReportKind::Unchanged(unchanged) => {
+                    store_report(&mut reports, related, Some(unchanged.result_id), None)
+                }
+            }
+        }
+        Ok(reports
+            .get(&uri)
+            .map(|report| report.items.clone())
+            .unwrap_or_default())
+    }
+
+    /// Pulls the diagnostics of the whole workspace, returning every document the server
+    /// reported on, including documents it reported as unchanged.
+    pub async fn workspace(&self) -> Result<HashMap<Url, Vec<Diagnostic>>, ClientError> {
+        let previous_result_ids = self
+            .reports
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(uri, report)| {
+                report.result_id.clone().map(|value| PreviousResultId {
+                    uri: uri.clone(),
+                    value,
+                })
+            })
+            .collect();
+        let items = match self
+            .server
+            .workspace_diagnostics(previous_result_ids)
+            .await?
+        {
+            WorkspaceDiagnosticReportResult::Report(report) => report.items,
+            WorkspaceDiagnosticReportResult::Partial(partial) => partial.items,
+        };
+
+        let mut reports = self.reports.lock().unwrap();
+        let mut diagnostics = HashMap::new();
+        for item in items {
+            let uri = match item {
+                WorkspaceDocumentDiagnosticReport::Full(report) => {
+                    let full = report.full_document_diagnostic_report;
+                    store_report(
+                        &mut reports,
+                        report.uri.clone(),
+                        full.result_id,
+                        Some(full.items),
+                    );
+                    report.uri
+                }
+                WorkspaceDocumentDiagnosticReport::Unchanged(report) => {
+                    let result_id = report.unchanged_document_diagnostic_report.result_id;
+                    store_report(&mut reports, report.uri.clone(), Some(result_id), None);
+                    report.uri
+                }
+            };
+            let items = reports[&uri].items.clone();
+            diagnostics.insert(uri, items);
+        }
+        Ok(diagnostics)
+    }
+}
+
+/// Updates the cached report of `uri`. `items` is `None` for unchanged reports, which only
+/// move the result id forward.
+fn store_report(
+    reports: &mut HashMap<Url, CachedReport>,
+    uri: Url,
+    result_id: Option<String>,
+    items: Option<Vec<Diagnostic>>,
+) {
+    let report = reports.entry(uri).or_default();
+    report.result_id = result_id;
+    if let Some(items) = items {
+        report.items = items;
+    }
+}
+
+impl<W: AsyncWriteExt> Clone for PullDiagnostics<W> {
+    fn clone(&self) -> Self {
+        PullDiagnostics {
+            server: self.server.clone(),
+            reports: self.reports.clone(),
+            refresh: self.refresh.clone(),
+        }
+    }
+}
+
+/// Push model diagnostics: the diagnostics of every document as last published by the
+/// server through `textDocument/publishDiagnostics`.
+pub struct PushDiagnostics {
+    published: watch::Receiver<HashMap<Url, Published>>,
+}
+
+/// The diagnostics last published for a document, and how often they were published.
+#[derive(Debug, Clone)]
+struct Published {
+    count: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl PushDiagnostics {
+    /// Starts collecting the diagnostics `server` publishes from now on.
+    pub fn new<W: AsyncWriteExt + Unpin>(server: &LanguageServerRef<W>) -> Self {
+        let (sender, published) = watch::channel(HashMap::<Url, Published>::new());
+        server.on_typed_notification::<PublishDiagnosticsNotification, _>(
+            move |params: PublishDiagnosticsParams| {
+                sender.send_modify(|published| {
+                    let count = published.get(&params.uri).map_or(0, |last| last.count);
+                    published.insert(
+                        params.uri,
+                        Published {
+                            count: count + 1,
+                            diagnostics: params.diagnostics,
+                        },
+                    );
+                });
+            },
+        );
+        PushDiagnostics { published }
+    }
+
+    /// Returns the diagnostics last published for a document, `None` if the server did not
+    /// publish any yet.
+    pub fn get(&self, uri: &Url) -> Option<Vec<Diagnostic>> {
+        let published = self.published.borrow();
+        published.get(uri).map(|last| last.diagnostics.clone())
+    }
+
+    /// Returns the diagnostics of every document the server published diagnostics for.
+    pub fn all(&self) -> HashMap<Url, Vec<Diagnostic>> {
+        let published = self.published.borrow();
+        published
+            .iter()
+            .map(|(uri, last)| (uri.clone(), last.diagnostics.clone()))
+            .collect()
+    }
+
+    /// How many times the server published diagnostics for a document so far.
+    pub fn publications(&self, uri: &Url) -> usize {
+        self.published
+            .borrow()
+            .get(uri)
+            .map_or(0, |last| last.count)
+    }
+
+    /// Waits until the server published diagnostics for a document, returning them. Returns
+    /// at once if it already did.
+    pub async fn wait_for(&self, uri: &Url) -> Option<Vec<Diagnostic>> {
+        self.wait_for_publication(uri, 0).await
+    }
+
+    /// Waits until the server published diagnostics for a document more than `count` times,
+    /// returning them. Pass the `publications` from before changing the document to wait
+    /// for the diagnostics of the change.
+    pub async fn wait_for_publication(&self, uri: &Url, count: usize) -> Option<Vec<Diagnostic>> {
+        let mut published = self.published.clone();
+        let published = published
+            .wait_for(|published| published.get(uri).is_some_and(|last| last.count > count))
+            .await
+            .ok()?;
+        published.get(uri).map(|last| last.diagnostics.clone())
+    }
+}
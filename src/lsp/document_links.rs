@@ -0,0 +1,52 @@
+use lsp_types::request::{DocumentLinkRequest, DocumentLinkResolve};
+use lsp_types::{DocumentLink, DocumentLinkParams, TextDocumentIdentifier, Url};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::log;
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Lists the links in a document, such as import paths and urls in comments. Servers
+    /// may leave `target` empty until the link is resolved.
+    pub async fn document_links(&self, uri: Url) -> Result<Vec<DocumentLink>, ClientError> {
+        let params = DocumentLinkParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        Ok(self
+            .request_if_supported::<DocumentLinkRequest>(params)
+            .await?
+            .unwrap_or_default())
+    }
+
+    pub async fn resolve_document_link(
+        &self,
+        link: DocumentLink,
+    ) -> Result<DocumentLink, ClientError> {
+        self.request_if_supported::<DocumentLinkResolve>(link).await
+    }
+
+    /// Lists the links in a document, resolving every link that came back without a target.
+    /// Links the server fails to resolve are returned unchanged.
+    pub async fn resolved_document_links(
+        &self,
+        uri: Url,
+    ) -> Result<Vec<DocumentLink>, ClientError> {
+        let mut links = Vec::new();
+        for link in self.document_links(uri).await? {
+            if link.target.is_some() {
+                links.push(link);
+                continue;
+            }
+            match self.resolve_document_link(link.clone()).await {
+                Ok(resolved) => links.push(resolved),
+                Err(err) => {
+                    log::warning!("failed to resolve document link: {:?}", err);
+                    links.push(link);
+                }
+            }
+        }
+        Ok(links)
+    }
+}
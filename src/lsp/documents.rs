@@ -0,0 +1,418 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use lsp_types::notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument};
+use lsp_types::{
+    CreateFile, DeleteFile, DeleteFileOptions, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, RenameFile, ResourceOp,
+    TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem, TextEdit, Url,
+    VersionedTextDocumentIdentifier, WorkspaceEdit,
+};
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::edits::{self, EditError, WorkspaceEditStep};
+use super::response_cache::{ResponseCache, CACHED_METHODS};
+
+/// The client side copy of a document the server has been told is open.
+#[derive(Debug, Clone)]
+pub struct TextDocument {
+    pub uri: Url,
+    pub language_id: String,
+    pub version: i32,
+    pub text: String,
+}
+
+/// How `change` delays `didChange`, see `DocumentManager::set_change_debounce`.
+struct Debounce<W: AsyncWriteExt> {
+    window: Duration,
+    /// Spawns the task sending the `didChange` of a document once the window is over.
+    schedule: fn(DocumentManager<W>, Url, Duration),
+}
+
+/// Tracks the documents opened on a language server and keeps the server in sync with
+/// their contents through `didOpen`/`didChange`/`didClose` notifications.
+pub struct DocumentManager<W: AsyncWriteExt> {
+    server: LanguageServerRef<W>,
+    documents: Arc<Mutex<HashMap<Url, TextDocument>>>,
+    debounce: Arc<Mutex<Option<Debounce<W>>>>,
+    /// The documents changed since the server was last sent their text.
+    unsent: Arc<Mutex<HashSet<Url>>>,
+    cache: Arc<Mutex<Option<ResponseCache>>>,
+    /// The id of the request hook flushing the changes held back, once there is a debounce
+    /// window.
+    flush_hook: Arc<Mutex<Option<usize>>>,
+}
+
+impl<W: AsyncWriteExt + Unpin> DocumentManager<W> {
+    pub fn new(server: LanguageServerRef<W>) -> Self {
+        DocumentManager {
+            server,
+            documents: Arc::new(Mutex::new(HashMap::new())),
+            debounce: Arc::new(Mutex::new(None)),
+            unsent: Arc::new(Mutex::new(HashSet::new())),
+            cache: Arc::new(Mutex::new(None)),
+            flush_hook: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Caches the responses to `CACHED_METHODS` until the document they are about changes
+    /// or closes in this manager, returning the cache. Calling it again returns the same
+    /// cache.
+    pub fn cache_responses(&self) -> ResponseCache {
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(cache) = &*cache {
+            return cache.clone();
+        }
+        let created = ResponseCache::new(CACHED_METHODS);
+        self.server.add_interceptor(created.clone());
+        *cache = Some(created.clone());
+        created
+    }
+
+    /// Tells the response cache that `uri` changed.
+    fn invalidate(&self, uri: &Url, version: Option<i32>) {
+        if let Some(cache) = &*self.cache.lock().unwrap() {
+            cache.document_changed(uri, version);
+        }
+    }
+
+    /// Returns a snapshot of an open document.
+    pub fn get(&self, uri: &Url) -> Option<TextDocument> {
+        self.documents.lock().unwrap().get(uri).cloned()
+    }
+
+    pub fn is_open(&self, uri: &Url) -> bool {
+        self.documents.lock().unwrap().contains_key(uri)
+    }
+
+    /// Opens a document on the server. Opening an already open document replaces its text.
+    pub async fn open(&self, uri: Url, language_id: &str, text: String) {
+        if self.is_open(&uri) {
+            self.change(&uri, text).await;
+            return;
+        }
+        let document = TextDocument {
+            uri: uri.clone(),
+            language_id: language_id.to_owned(),
+            version: 1,
+            text,
+        };
+        let params = DidOpenTextDocumentParams {
+            text_document: TextDocumentItem {
+                uri: document.uri.clone(),
+                language_id: document.language_id.clone(),
+                version: document.version,
+                text: document.text.clone(),
+            },
+        };
+        self.invalidate(&uri, Some(document.version));
+        self.documents.lock().unwrap().insert(uri, document);
+        self.server.notify::<DidOpenTextDocument>(params).await;
+    }
+
+    /// Replaces the full text of an open document. Does nothing if the document is not open.
+    /// With a debounce window the server is told later, see `set_change_debounce`.
+    pub async fn change(&self, uri: &Url, text: String) {
+        {
+            let mut documents = self.documents.lock().unwrap();
+            let document = match documents.get_mut(uri) {
+                Some(document) => document,
+                None => return,
+            };
+            document.version += 1;
+            document.text = text;
+        }
+        if let Some(debounce) = &*self.debounce.lock().unwrap() {
+            // the task already scheduled sends this change with the others
+            if self.unsent.lock().unwrap().insert(uri.clone()) {
+                (debounce.schedule)(self.clone(), uri.clone(), debounce.window);
+            }
+            return;
+        }
+        self.send_change(uri).await;
+    }
+
+    /// Sends the changes to `uri` the debounce window is holding back at once, e.g. before a
+    /// request about the document, so the server answers about its latest text.
+    pub async fn flush(&self, uri: &Url) {
+        if self.unsent.lock().unwrap().remove(uri) {
+            self.send_change(uri).await;
+        }
+    }
+
+    /// `flush` for every document.
+    pub async fn flush_all(&self) {
+        let unsent: Vec<Url> = self.unsent.lock().unwrap().drain().collect();
+        for uri in unsent {
+            self.send_change(&uri).await;
+        }
+    }
+
+    /// Sends the whole text of `uri` in a `didChange`, if it is still open. The responses
+    /// cached until then were about the text the server had.
+    async fn send_change(&self, uri: &Url) {
+        let Some(TextDocument { version, text, .. }) = self.get(uri) else {
+            return;
+        };
+        self.invalidate(uri, Some(version));
+        let params = DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier {
+                uri: uri.clone(),
+                version,
+            },
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text,
+            }],
+        };
+        self.server.notify::<DidChangeTextDocument>(params).await;
+    }
+
+    /// Applies `text_edits` to the buffer of an open document and syncs the server.
+    /// Returns false if the document is not open.
+    pub async fn apply_edits(&self, uri: &Url, text_edits: &[TextEdit]) -> bool {
+        let text = match self.get(uri) {
+            Some(document) => edits::apply_text_edits(&document.text, text_edits),
+            None => return false,
+        };
+        self.change(uri, text).await;
+        true
+    }
+
+    /// Applies `text_edits` to the buffer of `uri` if it is open, or to the file on disk
+    /// otherwise.
+    pub async fn apply_text_edits(
+        &self,
+        uri: &Url,
+        text_edits: &[TextEdit],
+    ) -> Result<(), EditError> {
+        if !self.apply_edits(uri, text_edits).await {
+            edits::apply_text_edits_to_file(uri, text_edits).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn close(&self, uri: &Url) {
+        self.flush(uri).await;
+        if self.documents.lock().unwrap().remove(uri).is_none() {
+            return;
+        }
+        self.invalidate(uri, None);
+        let params = DidCloseTextDocumentParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+        };
+        self.server.notify::<DidCloseTextDocument>(params).await;
+    }
+
+    /// Applies an edit the server sent while running a command, for `execute_command_with`,
+    /// with the reason it failed to tell the server.
+    pub async fn apply_command_edit(&self, edit: WorkspaceEdit) -> Result<(), String> {
+        self.apply_workspace_edit(&edit)
+            .await
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    /// Applies a workspace edit, editing the buffers of open documents and the files on disk
+    /// for everything else.
+    pub async fn apply_workspace_edit(&self, edit: &WorkspaceEdit) -> Result<(), EditError> {
+        for step in edits::workspace_edit_steps(edit) {
+            match step {
+                WorkspaceEditStep::Edit {
+                    uri,
+                    edits: text_edits,
+                } => {
+                    if !self.apply_edits(&uri, &text_edits).await {
+                        edits::apply_text_edits_to_file(&uri, &text_edits).await?;
+                    }
+                }
+                WorkspaceEditStep::Resource(op) => edits::apply_resource_op(&op).await?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The part of `uri` below the folder `dir`, or `None` if it is not inside `dir`.
+fn relative_to<'a>(uri: &'a Url, dir: &Url) -> Option<&'a str> {
+    uri.as_str()
+        .strip_prefix(dir.as_str().trim_end_matches('/'))?
+        .strip_prefix('/')
+}
+
+fn is_at_or_below(uri: &Url, dir: &Url) -> bool {
+    uri == dir || relative_to(uri, dir).is_some()
+}
+
+/// Maps `uri` from below `old` to below `new`, for documents inside a renamed folder.
+fn rebase_uri(uri: &Url, old: &Url, new: &Url) -> Option<Url> {
+    if uri == old {
+        return Some(new.clone());
+    }
+    let rest = relative_to(uri, old)?;
+    Url::parse(&format!("{}/{}", new.as_str().trim_end_matches('/'), rest)).ok()
+}
+
+impl<W: AsyncWriteExt + Unpin> DocumentManager<W> {
+    /// Creates an empty file at `uri`, applying the edits the server asks for in
+    /// `workspace/willCreateFiles` first and sending `workspace/didCreateFiles` after.
+    pub async fn create_file(&self, uri: &Url) -> Result<(), ClientError> {
+        if let Some(edit) = self.server.will_create_files(vec![uri.clone()]).await? {
+            self.apply_workspace_edit(&edit).await?;
+        }
+        let op = ResourceOp::Create(CreateFile {
+            uri: uri.clone(),
+            options: None,
+            annotation_id: None,
+        });
+        edits::apply_resource_op(&op).await?;
+        self.server.did_create_files(vec![uri.clone()]).await;
+        Ok(())
+    }
+
+    /// Renames a file or folder on disk, with the `willRenameFiles`/`didRenameFiles`
+    /// round trip. Open documents at or below `old` are closed and reopened under their
+    /// new uri.
+    pub async fn rename_file(&self, old: &Url, new: &Url) -> Result<(), ClientError> {
+        let renames = vec![(old.clone(), new.clone())];
+        if let Some(edit) = self.server.will_rename_files(renames.clone()).await? {
+            self.apply_workspace_edit(&edit).await?;
+        }
+        let op = ResourceOp::Rename(RenameFile {
+            old_uri: old.clone(),
+            new_uri: new.clone(),
+            options: None,
+            annotation_id: None,
+        });
+        edits::apply_resource_op(&op).await?;
+
+        let moved: Vec<(TextDocument, Url)> = {
+            let documents = self.documents.lock().unwrap();
+            documents
+                .values()
+                .filter_map(|document| {
+                    rebase_uri(&document.uri, old, new).map(|uri| (document.clone(), uri))
+                })
+                .collect()
+        };
+        for (document, uri) in moved {
+            self.close(&document.uri).await;
+            self.open(uri, &document.language_id, document.text).await;
+        }
+        self.server.did_rename_files(renames).await;
+        Ok(())
+    }
+
+    /// Deletes a file, or a folder with its contents, with the
+    /// `willDeleteFiles`/`didDeleteFiles` round trip. Open documents at or below `uri` are
+    /// closed.
+    pub async fn delete_file(&self, uri: &Url) -> Result<(), ClientError> {
+        if let Some(edit) = self.server.will_delete_files(vec![uri.clone()]).await? {
+            self.apply_workspace_edit(&edit).await?;
+        }
+        let op = ResourceOp::Delete(DeleteFile {
+            uri: uri.clone(),
+            options: Some(DeleteFileOptions {
+                recursive: Some(true),
+                ignore_if_not_exists: None,
+                annotation_id: None,
+            }),
+        });
+        edits::apply_resource_op(&op).await?;
+
+        let deleted: Vec<Url> = {
+            let documents = self.documents.lock().unwrap();
+            documents
+                .keys()
+                .filter(|document| is_at_or_below(document, uri))
+                .cloned()
+                .collect()
+        };
+        for document in deleted {
+            self.close(&document).await;
+        }
+        self.server.did_delete_files(vec![uri.clone()]).await;
+        Ok(())
+    }
+}
+
+impl<W: AsyncWriteExt + Unpin + Send + 'static> DocumentManager<W> {
+    /// Holds the changes to a document back for `window` after the first, then sends its
+    /// latest text in one `didChange`, e.g. when replaying a large stream of edits. The
+    /// versions the server sees skip those merged. `None`, the default, sends every change
+    /// at once; the changes already held back are still sent when their window is over.
+    ///
+    /// The changes held back are sent before any request, so the server answers about the
+    /// latest text: those to its `textDocument`, or to every document for a request about
+    /// none, e.g. `workspace/symbol`.
+    pub fn set_change_debounce(&self, window: Option<Duration>) {
+        *self.debounce.lock().unwrap() = window.map(|window| Debounce {
+            window,
+            schedule: Self::schedule_flush,
+        });
+        let mut flush_hook = self.flush_hook.lock().unwrap();
+        if window.is_some() && flush_hook.is_none() {
+            *flush_hook = Some(self.server.add_request_hook(self.flush_before_requests()));
+        }
+    }
+
+    /// The request hook sending the changes held back a request may be about. It holds the
+    /// documents rather than the manager, which holds the server holding the hook.
+    fn flush_before_requests(
+        &self,
+    ) -> impl Fn(&LanguageServerRef<W>, &Value) -> BoxFuture<'static, ()> + Send + Sync + 'static
+    {
+        let (documents, debounce, unsent, cache, flush_hook) = (
+            self.documents.clone(),
+            self.debounce.clone(),
+            self.unsent.clone(),
+            self.cache.clone(),
+            self.flush_hook.clone(),
+        );
+        move |server, params| {
+            let manager = DocumentManager {
+                server: server.clone(),
+                documents: documents.clone(),
+                debounce: debounce.clone(),
+                unsent: unsent.clone(),
+                cache: cache.clone(),
+                flush_hook: flush_hook.clone(),
+            };
+            let uri = params
+                .get("textDocument")
+                .and_then(|document| document.get("uri"))
+                .and_then(Value::as_str)
+                .and_then(|uri| Url::parse(uri).ok());
+            Box::pin(async move {
+                match uri {
+                    Some(uri) => manager.flush(&uri).await,
+                    None => manager.flush_all().await,
+                }
+            })
+        }
+    }
+
+    fn schedule_flush(documents: DocumentManager<W>, uri: Url, window: Duration) {
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            documents.flush(&uri).await;
+        });
+    }
+}
+
+impl<W: AsyncWriteExt> Clone for DocumentManager<W> {
+    fn clone(&self) -> Self {
+        DocumentManager {
+            server: self.server.clone(),
+            documents: self.documents.clone(),
+            debounce: self.debounce.clone(),
+            unsent: self.unsent.clone(),
+            cache: self.cache.clone(),
+            flush_hook: self.flush_hook.clone(),
+        }
+    }
+}
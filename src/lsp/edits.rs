@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use lsp_types::{
+    DocumentChangeOperation, DocumentChanges, OneOf, ResourceOp, TextDocumentEdit, TextEdit, Url,
+    WorkspaceEdit,
+};
+
+use super::text_utils::{self, PositionEncoding};
+
+#[derive(Debug)]
+pub enum EditError {
+    Io(std::io::Error),
+    /// The edit targets a uri which does not map to a local file.
+    NotAFile(Url),
+}
+
+impl From<std::io::Error> for EditError {
+    fn from(err: std::io::Error) -> EditError {
+        EditError::Io(err)
+    }
+}
+
+/// A single step of a `WorkspaceEdit`, in the order the server asked for it to be applied.
+#[derive(Debug, Clone)]
+pub enum WorkspaceEditStep {
+    Edit { uri: Url, edits: Vec<TextEdit> },
+    Resource(ResourceOp),
+}
+
+/// Flattens both the `changes` map and the `documentChanges` list of a workspace edit into
+/// the ordered steps needed to apply it. Per the specification `documentChanges` wins when
+/// a server sends both.
+pub fn workspace_edit_steps(edit: &WorkspaceEdit) -> Vec<WorkspaceEditStep> {
+    match &edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => edits.iter().map(text_document_edit_step).collect(),
+        Some(DocumentChanges::Operations(operations)) => operations
+            .iter()
+            .map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => text_document_edit_step(edit),
+                DocumentChangeOperation::Op(op) => WorkspaceEditStep::Resource(op.clone()),
+            })
+            .collect(),
+        None => edit
+            .changes
+            .iter()
+            .flatten()
+            .map(|(uri, edits)| WorkspaceEditStep::Edit {
+                uri: uri.clone(),
+                edits: edits.clone(),
+            })
+            .collect(),
+    }
+}
+
+fn text_document_edit_step(edit: &TextDocumentEdit) -> WorkspaceEditStep {
+    WorkspaceEditStep::Edit {
+        uri: edit.text_document.uri.clone(),
+        edits: edit
+            .edits
+            .iter()
+            .map(|edit| match edit {
+                OneOf::Left(edit) => edit.clone(),
+                OneOf::Right(annotated) => annotated.text_edit.clone(),
+            })
+            .collect(),
+    }
+}
+
+/// Groups the text edits of a workspace edit by document, ignoring resource operations.
+pub fn workspace_edit_text_edits(edit: &WorkspaceEdit) -> HashMap<Url, Vec<TextEdit>> {
+    let mut grouped: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for step in workspace_edit_steps(edit) {
+        if let WorkspaceEditStep::Edit { uri, edits } = step {
+            grouped.entry(uri).or_default().extend(edits);
+        }
+    }
+    grouped
+}
+
+/// Applies `edits` to `text`. All ranges refer to the original text, as required by the
+/// specification; edits sharing a start position are applied in the order given.
+pub fn apply_text_edits(text: &str, edits: &[TextEdit]) -> String {
+    let mut resolved: Vec<(usize, usize, &str)> = edits
+        .iter()
+        .map(|edit| {
+            let offsets = text_utils::range_to_offsets(text, edit.range, PositionEncoding::Utf16);
+            (offsets.start, offsets.end, edit.new_text.as_str())
+        })
+        .collect();
+    resolved.sort_by_key(|(start, _, _)| *start);
+
+    let mut result = text.to_owned();
+    for (start, end, new_text) in resolved.into_iter().rev() {
+        result.replace_range(start..end, new_text);
+    }
+    result
+}
+
+/// Applies a workspace edit directly to the files on disk.
+pub async fn apply_workspace_edit_to_disk(edit: &WorkspaceEdit) -> Result<(), EditError> {
+    for step in workspace_edit_steps(edit) {
+        match step {
+            WorkspaceEditStep::Edit { uri, edits } => {
+                apply_text_edits_to_file(&uri, &edits).await?
+            }
+            WorkspaceEditStep::Resource(op) => apply_resource_op(&op).await?,
+        }
+    }
+    Ok(())
+}
+
+/// Rewrites the file behind `uri` with `edits` applied.
+pub async fn apply_text_edits_to_file(uri: &Url, edits: &[TextEdit]) -> Result<(), EditError> {
+    let path = uri
+        .to_file_path()
+        .map_err(|_| EditError::NotAFile(uri.clone()))?;
+    let text = tokio::fs::read_to_string(&path).await?;
+    tokio::fs::write(&path, apply_text_edits(&text, edits)).await?;
+    Ok(())
+}
+
+pub(crate) async fn apply_resource_op(op: &ResourceOp) -> Result<(), EditError> {
+    let to_path = |uri: &Url| {
+        uri.to_file_path()
+            .map_err(|_| EditError::NotAFile(uri.clone()))
+    };
+    match op {
+        ResourceOp::Create(create) => {
+            let path = to_path(&create.uri)?;
+            let options = create.options.as_ref();
+            let overwrite = options.and_then(|o| o.overwrite).unwrap_or(false);
+            if path.exists() && !overwrite {
+                if options.and_then(|o| o.ignore_if_exists).unwrap_or(false) {
+                    return Ok(());
+                }
+                return Err(EditError::Io(std::io::ErrorKind::AlreadyExists.into()));
+            }
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&path, "").await?;
+        }
+        ResourceOp::Rename(rename) => {
+            let old_path = to_path(&rename.old_uri)?;
+            let new_path = to_path(&rename.new_uri)?;
+            let options = rename.options.as_ref();
+            let overwrite = options.and_then(|o| o.overwrite).unwrap_or(false);
+            if new_path.exists() && !overwrite {
+                if options.and_then(|o| o.ignore_if_exists).unwrap_or(false) {
+                    return Ok(());
+                }
+                return Err(EditError::Io(std::io::ErrorKind::AlreadyExists.into()));
+            }
+            if let Some(parent) = new_path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::rename(&old_path, &new_path).await?;
+        }
+        ResourceOp::Delete(delete) => {
+            let path = to_path(&delete.uri)?;
+            let options = delete.options.as_ref();
+            if !path.exists() {
+                if options
+                    .and_then(|o| o.ignore_if_not_exists)
+                    .unwrap_or(false)
+                {
+                    return Ok(());
+                }
+                return Err(EditError::Io(std::io::ErrorKind::NotFound.into()));
+            }
+            if path.is_dir() {
+                if options.and_then(|o| o.recursive).unwrap_or(false) {
+                    tokio::fs::remove_dir_all(&path).await?;
+                } else {
+                    tokio::fs::remove_dir(&path).await?;
+                }
+            } else {
+                tokio::fs::remove_file(&path).await?;
+            }
+        }
+    }
+    Ok(())
+}
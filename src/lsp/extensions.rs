@@ -0,0 +1,43 @@
+use jsonrpc_lite::Error;
+use lsp_types::notification::Notification;
+use lsp_types::request::Request;
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+
+use super::client::LanguageServerRef;
+use super::log;
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Like `on_notification`, with the params deserialized into `N::Params`. Notifications
+    /// whose params do not match are logged and dropped.
+    ///
+    /// Extension methods are declared like the methods of `lsp_types`, by implementing
+    /// `Notification` or `Request` on a marker type, see `rust_analyzer` for examples. The
+    /// typed `request` and `notify` send them like any other method.
+    pub fn on_typed_notification<N, F>(&self, mut handler: F) -> usize
+    where
+        N: Notification,
+        F: 'static + Send + FnMut(N::Params),
+    {
+        self.on_notification(N::METHOD, move |params| {
+            match serde_json::from_value(params) {
+                Ok(params) => handler(params),
+                Err(err) => log::warning!("malformed {} notification: {:?}", N::METHOD, err),
+            }
+        })
+    }
+
+    /// Like `on_request`, with the params deserialized into `R::Params` and the result
+    /// serialized from `R::Result`. Requests whose params do not match are answered with an
+    /// invalid params error.
+    pub fn on_typed_request<R, F>(&self, mut handler: F)
+    where
+        R: Request,
+        F: 'static + Send + FnMut(R::Params) -> Result<R::Result, Error>,
+    {
+        self.on_request(R::METHOD, move |params| {
+            let params = serde_json::from_value(params).map_err(|_| Error::invalid_params())?;
+            Ok(json!(handler(params)?))
+        });
+    }
+}
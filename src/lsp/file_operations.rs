@@ -0,0 +1,206 @@
+use globset::GlobBuilder;
+use lsp_types::notification::{DidCreateFiles, DidDeleteFiles, DidRenameFiles};
+use lsp_types::request::{WillCreateFiles, WillDeleteFiles, WillRenameFiles};
+use lsp_types::{
+    CreateFilesParams, DeleteFilesParams, FileCreate, FileDelete, FileOperationFilter,
+    FileOperationPatternKind, FileOperationRegistrationOptions, FileRename, RenameFilesParams, Url,
+    WorkspaceEdit, WorkspaceFileOperationsServerCapabilities,
+};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::log;
+
+/// Returns whether `uri` passes one of the `filters` a server registered for a file
+/// operation. `kind` is `None` when it is not known whether `uri` names a file or a folder,
+/// e.g. for a file that does not exist yet, in which case the `matches` restriction of a
+/// filter is ignored.
+pub fn file_operation_matches(
+    filters: &[FileOperationFilter],
+    uri: &Url,
+    kind: Option<FileOperationPatternKind>,
+) -> bool {
+    let path = match uri.to_file_path() {
+        Ok(path) => path,
+        Err(_) => uri.path().into(),
+    };
+    filters.iter().any(|filter| {
+        if let Some(scheme) = &filter.scheme {
+            if scheme != uri.scheme() {
+                return false;
+            }
+        }
+        let pattern = &filter.pattern;
+        if let (Some(expected), Some(kind)) = (&pattern.matches, &kind) {
+            if expected != kind {
+                return false;
+            }
+        }
+        let ignore_case = pattern
+            .options
+            .as_ref()
+            .and_then(|o| o.ignore_case)
+            .unwrap_or(false);
+        match GlobBuilder::new(&pattern.glob)
+            .case_insensitive(ignore_case)
+            .literal_separator(true)
+            .build()
+        {
+            Ok(glob) => glob.compile_matcher().is_match(&path),
+            Err(err) => {
+                log::warning!("invalid file operation glob {:?}: {}", pattern.glob, err);
+                false
+            }
+        }
+    })
+}
+
+/// Whether `uri` currently names a file or a folder on disk, if it exists at all.
+fn file_kind(uri: &Url) -> Option<FileOperationPatternKind> {
+    let metadata = std::fs::metadata(uri.to_file_path().ok()?).ok()?;
+    if metadata.is_dir() {
+        Some(FileOperationPatternKind::Folder)
+    } else {
+        Some(FileOperationPatternKind::File)
+    }
+}
+
+type Selector =
+    fn(&WorkspaceFileOperationsServerCapabilities) -> &Option<FileOperationRegistrationOptions>;
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// The filters the server registered for one kind of file operation, or `None` if it
+    /// does not want to hear about that operation at all.
+    fn file_operation_filters(&self, select: Selector) -> Option<Vec<FileOperationFilter>> {
+        let result = self.initialize_result()?;
+        let file_operations = result.capabilities.workspace?.file_operations?;
+        select(&file_operations)
+            .as_ref()
+            .map(|options| options.filters.clone())
+    }
+
+    /// Keeps the uris the server registered interest in, given each uri's kind.
+    fn filter_file_operations<T>(
+        &self,
+        select: Selector,
+        items: Vec<T>,
+        uri: impl Fn(&T) -> &Url,
+    ) -> Vec<T> {
+        let filters = match self.file_operation_filters(select) {
+            Some(filters) => filters,
+            None => return Vec::new(),
+        };
+        items
+            .into_iter()
+            .filter(|item| {
+                let uri = uri(item);
+                file_operation_matches(&filters, uri, file_kind(uri))
+            })
+            .collect()
+    }
+
+    /// Asks the server for edits to apply before `uris` are created, e.g. to add module
+    /// declarations. Returns `None` without contacting the server if it registered interest
+    /// in none of them.
+    pub async fn will_create_files(
+        &self,
+        uris: Vec<Url>,
+    ) -> Result<Option<WorkspaceEdit>, ClientError> {
+        let uris = self.filter_file_operations(|ops| &ops.will_create, uris, |uri| uri);
+        if uris.is_empty() {
+            return Ok(None);
+        }
+        let params = CreateFilesParams {
+            files: uris
+                .into_iter()
+                .map(|uri| FileCreate { uri: uri.into() })
+                .collect(),
+        };
+        self.request::<WillCreateFiles>(params).await
+    }
+
+    /// Tells the server `uris` were created, if it registered interest in any of them.
+    pub async fn did_create_files(&self, uris: Vec<Url>) {
+        let uris = self.filter_file_operations(|ops| &ops.did_create, uris, |uri| uri);
+        if uris.is_empty() {
+            return;
+        }
+        let params = CreateFilesParams {
+            files: uris
+                .into_iter()
+                .map(|uri| FileCreate { uri: uri.into() })
+                .collect(),
+        };
+        self.notify::<DidCreateFiles>(params).await;
+    }
+
+    /// Asks the server for edits to apply before each `(old, new)` pair is renamed, e.g. to
+    /// update imports. Filters are matched against the old uri.
+    pub async fn will_rename_files(
+        &self,
+        renames: Vec<(Url, Url)>,
+    ) -> Result<Option<WorkspaceEdit>, ClientError> {
+        let renames = self.filter_file_operations(|ops| &ops.will_rename, renames, |(old, _)| old);
+        if renames.is_empty() {
+            return Ok(None);
+        }
+        let params = RenameFilesParams {
+            files: renames.into_iter().map(file_rename).collect(),
+        };
+        self.request::<WillRenameFiles>(params).await
+    }
+
+    /// Tells the server each `(old, new)` pair was renamed. Filters are matched against the
+    /// new uri, since the old one no longer exists.
+    pub async fn did_rename_files(&self, renames: Vec<(Url, Url)>) {
+        let renames = self.filter_file_operations(|ops| &ops.did_rename, renames, |(_, new)| new);
+        if renames.is_empty() {
+            return;
+        }
+        let params = RenameFilesParams {
+            files: renames.into_iter().map(file_rename).collect(),
+        };
+        self.notify::<DidRenameFiles>(params).await;
+    }
+
+    /// Asks the server for edits to apply before `uris` are deleted.
+    pub async fn will_delete_files(
+        &self,
+        uris: Vec<Url>,
+    ) -> Result<Option<WorkspaceEdit>, ClientError> {
+        let uris = self.filter_file_operations(|ops| &ops.will_delete, uris, |uri| uri);
+        if uris.is_empty() {
+            return Ok(None);
+        }
+        let params = DeleteFilesParams {
+            files: uris
+                .into_iter()
+                .map(|uri| FileDelete { uri: uri.into() })
+                .collect(),
+        };
+        self.request::<WillDeleteFiles>(params).await
+    }
+
+    /// Tells the server `uris` were deleted. As the files are gone, filters restricted to
+    /// files or folders match either.
+    pub async fn did_delete_files(&self, uris: Vec<Url>) {
+        let uris = self.filter_file_operations(|ops| &ops.did_delete, uris, |uri| uri);
+        if uris.is_empty() {
+            return;
+        }
+        let params = DeleteFilesParams {
+            files: uris
+                .into_iter()
+                .map(|uri| FileDelete { uri: uri.into() })
+                .collect(),
+        };
+        self.notify::<DidDeleteFiles>(params).await;
+    }
+}
+
+fn file_rename((old_uri, new_uri): (Url, Url)) -> FileRename {
+    FileRename {
+        old_uri: old_uri.into(),
+        new_uri: new_uri.into(),
+    }
+}
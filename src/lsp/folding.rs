@@ -0,0 +1,20 @@
+use lsp_types::request::FoldingRangeRequest;
+use lsp_types::{FoldingRange, FoldingRangeParams, TextDocumentIdentifier, Url};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Requests the foldable regions of a document, such as bodies, imports and comments.
+    pub async fn folding_ranges(&self, uri: Url) -> Result<Vec<FoldingRange>, ClientError> {
+        let params = FoldingRangeParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        Ok(self
+            .request_if_supported::<FoldingRangeRequest>(params)
+            .await?
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,68 @@
+use lsp_types::request::{Formatting, OnTypeFormatting, RangeFormatting};
+use lsp_types::{
+    DocumentFormattingParams, DocumentOnTypeFormattingParams, DocumentRangeFormattingParams,
+    FormattingOptions, Position, Range, TextDocumentIdentifier, TextEdit, Url,
+};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::navigation::text_document_position;
+
+/// The formatting helpers only compute edits; apply them with
+/// `DocumentManager::apply_text_edits`, which edits open buffers in place and writes
+/// everything else to disk.
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    pub async fn format_document(
+        &self,
+        uri: Url,
+        options: FormattingOptions,
+    ) -> Result<Vec<TextEdit>, ClientError> {
+        let params = DocumentFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            options,
+            work_done_progress_params: Default::default(),
+        };
+        Ok(self
+            .request_if_supported::<Formatting>(params)
+            .await?
+            .unwrap_or_default())
+    }
+
+    pub async fn format_range(
+        &self,
+        uri: Url,
+        range: Range,
+        options: FormattingOptions,
+    ) -> Result<Vec<TextEdit>, ClientError> {
+        let params = DocumentRangeFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            options,
+            work_done_progress_params: Default::default(),
+        };
+        Ok(self
+            .request_if_supported::<RangeFormatting>(params)
+            .await?
+            .unwrap_or_default())
+    }
+
+    /// Formats after `ch` was typed at `position`. Servers only honour the trigger characters
+    /// listed in their `documentOnTypeFormattingProvider` capability.
+    pub async fn on_type_format(
+        &self,
+        uri: Url,
+        position: Position,
+        ch: &str,
+        options: FormattingOptions,
+    ) -> Result<Vec<TextEdit>, ClientError> {
+        let params = DocumentOnTypeFormattingParams {
+            text_document_position: text_document_position(uri, position),
+            ch: ch.to_owned(),
+            options,
+        };
+        Ok(self
+            .request_if_supported::<OnTypeFormatting>(params)
+            .await?
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,79 @@
+//! A log of every message exchanged with a server, for diagnosing servers that misbehave:
+//! one line of JSON per message, written as it goes so the log survives a crash, e.g.
+//!
+//! ```json
+//! {"direction":"sent","timestamp":1760608800123,"bytes":171,"message":{"jsonrpc":"2.0","id":2,...}}
+//! ```
+//!
+//! `timestamp` counts milliseconds since the Unix epoch and `bytes` the length of the body.
+//! The CLI replays such a log with `serve-recording`. See
+//! `LanguageServerRef::log_frames`.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::client::Direction;
+use super::log;
+
+/// A line of the log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoggedFrame {
+    pub direction: Direction,
+    /// When the message was written or read, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The length of the body in bytes, as the `Content-Length` header gave it.
+    pub bytes: usize,
+    /// The body, or the body as a string when it is not JSON.
+    pub message: Value,
+}
+
+/// Writes the messages exchanged with a server, one `LoggedFrame` per line.
+pub struct FrameLog {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl FrameLog {
+    pub fn new(writer: impl Write + Send + 'static) -> FrameLog {
+        FrameLog {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// A log appended to the file at `path`.
+    pub fn create(path: &Path) -> io::Result<FrameLog> {
+        let file = File::options().create(true).append(true).open(path)?;
+        Ok(FrameLog::new(file))
+    }
+
+    /// Appends a message, flushing it for the log to be complete whenever the client stops.
+    pub fn write(&self, direction: Direction, body: &str) {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        let frame = LoggedFrame {
+            direction,
+            timestamp,
+            bytes: body.len(),
+            message: serde_json::from_str(body).unwrap_or_else(|_| json!(body)),
+        };
+        let line = serde_json::to_string(&frame).expect("frames serialize");
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(err) = writeln!(writer, "{}", line).and_then(|()| writer.flush()) {
+            log::warning!("failed to log a frame: {}", err);
+        }
+    }
+}
+
+/// The frames of a log, in the order they were written.
+pub fn parse(log: &str) -> Result<Vec<LoggedFrame>, serde_json::Error> {
+    log.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect()
+}
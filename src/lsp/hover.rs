@@ -0,0 +1,19 @@
+use lsp_types::request::HoverRequest;
+use lsp_types::{Hover, HoverParams, Position, Url};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::navigation::text_document_position;
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Requests the hover of the symbol at `position`, usually its signature and
+    /// documentation. Render the contents with `markup::hover_to_plain_text` or
+    /// `markup::hover_to_ansi`.
+    pub async fn hover(&self, uri: Url, position: Position) -> Result<Option<Hover>, ClientError> {
+        let params = HoverParams {
+            text_document_position_params: text_document_position(uri, position),
+            work_done_progress_params: Default::default(),
+        };
+        self.request_if_supported::<HoverRequest>(params).await
+    }
+}
@@ -0,0 +1,63 @@
+use lsp_types::request::{InlayHintRequest, InlayHintResolveRequest};
+use lsp_types::{InlayHint, InlayHintLabel, InlayHintParams, Range, TextDocumentIdentifier, Url};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+
+/// An inlay hint along with its label flattened to plain text.
+#[derive(Debug, Clone)]
+pub struct LabeledInlayHint {
+    pub label: String,
+    pub hint: InlayHint,
+}
+
+impl From<InlayHint> for LabeledInlayHint {
+    fn from(hint: InlayHint) -> Self {
+        LabeledInlayHint {
+            label: inlay_hint_label(&hint.label),
+            hint,
+        }
+    }
+}
+
+/// Joins the parts of a hint label, dropping their tooltips, locations and commands.
+pub fn inlay_hint_label(label: &InlayHintLabel) -> String {
+    match label {
+        InlayHintLabel::String(label) => label.clone(),
+        InlayHintLabel::LabelParts(parts) => parts.iter().map(|part| part.value.as_str()).collect(),
+    }
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Requests the inlay hints shown within `range`.
+    pub async fn inlay_hints(
+        &self,
+        uri: Url,
+        range: Range,
+    ) -> Result<Vec<LabeledInlayHint>, ClientError> {
+        let params = InlayHintParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            work_done_progress_params: Default::default(),
+        };
+        let hints = self
+            .request_if_supported::<InlayHintRequest>(params)
+            .await?;
+        Ok(hints
+            .unwrap_or_default()
+            .into_iter()
+            .map(LabeledInlayHint::from)
+            .collect())
+    }
+
+    /// Fills in the lazily computed parts of a hint, such as tooltips and label locations.
+    pub async fn resolve_inlay_hint(
+        &self,
+        hint: InlayHint,
+    ) -> Result<LabeledInlayHint, ClientError> {
+        let hint = self
+            .request_if_supported::<InlayHintResolveRequest>(hint)
+            .await?;
+        Ok(LabeledInlayHint::from(hint))
+    }
+}
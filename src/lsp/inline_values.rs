@@ -0,0 +1,37 @@
+use lsp_types::request::{InlineValueRequest, Request};
+use lsp_types::{
+    InlineValue, InlineValueContext, InlineValueParams, Range, TextDocumentIdentifier, Url,
+};
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Asks which values a debugger should display inline within `range` while stopped in
+    /// the stack frame described by `context`. Values come back either as literal text, a
+    /// variable to look up, or an expression to evaluate.
+    pub async fn inline_values(
+        &self,
+        uri: Url,
+        range: Range,
+        context: InlineValueContext,
+    ) -> Result<Vec<InlineValue>, ClientError> {
+        if !self.capabilities().supports(InlineValueRequest::METHOD) {
+            return Err(ClientError::Unsupported(InlineValueRequest::METHOD));
+        }
+        let params = InlineValueParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            context,
+            work_done_progress_params: Default::default(),
+        };
+        // lsp-types declares a single value, servers answer with a list as the specification
+        // says
+        let result = self
+            .request_value(InlineValueRequest::METHOD, json!(params))
+            .await?;
+        let values: Option<Vec<InlineValue>> = serde_json::from_value(result)?;
+        Ok(values.into_iter().flatten().collect())
+    }
+}
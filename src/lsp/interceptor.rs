@@ -0,0 +1,40 @@
+//! Middleware for the messages exchanged with a server, to observe, rewrite or answer them
+//! without changing the client, e.g. to rewrite paths between a container and the host, or
+//! to work around a server deviating from the specification. See
+//! `LanguageServerRef::add_interceptor`.
+
+use serde_json::Value;
+
+/// What happens to a message once an `Interceptor` saw it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Flow {
+    /// Hands the message, as the interceptor left it, to the next interceptor, then on.
+    Continue,
+    /// Drops the message: it is not written, or not handled. An outgoing request dropped
+    /// fails with `ClientError::Canceled`.
+    Drop,
+    /// Answers a request in place of the other side, with its `result` or its `error`
+    /// object: an outgoing request is not written and completes with it, an incoming one is
+    /// not handled and the server is sent it. Other messages are dropped.
+    Respond(Result<Value, Value>),
+}
+
+/// Sees every message before it is written to the server, and every message read from the
+/// server before it is handled, as the whole JSON-RPC message. Both do nothing by default.
+///
+/// Interceptors run in the order they were added, in both directions, outside the client
+/// lock but on the task sending or reading the message, so they should be quick.
+pub trait Interceptor: Send + Sync {
+    /// Called with a request, a notification or a response to the server about to be
+    /// written.
+    fn on_outgoing(&self, message: &mut Value) -> Flow {
+        let _ = message;
+        Flow::Continue
+    }
+
+    /// Called with a message read from the server, once it was found to be JSON.
+    fn on_incoming(&self, message: &mut Value) -> Flow {
+        let _ = message;
+        Flow::Continue
+    }
+}
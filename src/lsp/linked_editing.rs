@@ -0,0 +1,23 @@
+use lsp_types::request::LinkedEditingRange;
+use lsp_types::{LinkedEditingRangeParams, LinkedEditingRanges, Position, Url};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::navigation::text_document_position;
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Returns the ranges which must change together with the one at `position`, such as
+    /// the opening and closing name of an HTML/JSX tag, or `None` if there are none.
+    pub async fn linked_editing_ranges(
+        &self,
+        uri: Url,
+        position: Position,
+    ) -> Result<Option<LinkedEditingRanges>, ClientError> {
+        let params = LinkedEditingRangeParams {
+            text_document_position_params: text_document_position(uri, position),
+            work_done_progress_params: Default::default(),
+        };
+        self.request_if_supported::<LinkedEditingRange>(params)
+            .await
+    }
+}
@@ -0,0 +1,107 @@
+//! What the client reports while talking to the server. With the `tracing` feature every
+//! request is a `request` span with its `method` and `id`, its response an event with how
+//! long it took, and the messages read and written are events too, so embedders can
+//! filter and correlate them with their own. Without it only warnings are reported, on
+//! stderr.
+
+use std::time::Duration;
+
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+/// Reports something that went wrong and that the client carries on after.
+macro_rules! warning {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        eprintln!($($arg)+);
+    }};
+}
+
+pub(crate) use warning;
+
+/// Runs `exchange`, the sending of a request and the wait for its response, within a
+/// `request` span.
+pub(crate) async fn in_request_span<T>(
+    method: &str,
+    exchange: impl std::future::Future<Output = T>,
+) -> T {
+    #[cfg(feature = "tracing")]
+    {
+        use tracing::Instrument;
+        let span = tracing::debug_span!("request", method, id = tracing::field::Empty);
+        exchange.instrument(span).await
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = method;
+        exchange.await
+    }
+}
+
+/// Records the id the request of the current span was sent with.
+pub(crate) fn request_sent(id: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("id", id);
+    #[cfg(not(feature = "tracing"))]
+    let _ = id;
+}
+
+/// Reports the response to the request of the current span, `elapsed` after sending it.
+/// `None` when the server exited before answering.
+pub(crate) fn response_received(
+    elapsed: Duration,
+    response: Option<&Result<Box<RawValue>, Box<RawValue>>>,
+) {
+    #[cfg(feature = "tracing")]
+    {
+        let duration_ms = elapsed.as_secs_f64() * 1000.0;
+        match response {
+            Some(Ok(_)) => tracing::debug!(duration_ms, "response"),
+            Some(Err(error)) => {
+                let error: Value = serde_json::from_str(error.get()).unwrap_or_default();
+                // the macros have a `Value` of their own in scope
+                let code = error.get("code").and_then(|code| code.as_i64());
+                let error = error.get("message").and_then(|message| message.as_str());
+                tracing::debug!(duration_ms, code, error, "error response")
+            }
+            None => tracing::debug!(duration_ms, "no response"),
+        }
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (elapsed, response);
+}
+
+/// Reports a message written to the server.
+pub(crate) fn message_sent(message: &Value) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+        method = message.get("method").and_then(|method| method.as_str()),
+        id = message.get("id").map(tracing::field::display),
+        bytes = message.to_string().len(),
+        "sent"
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = message;
+}
+
+/// Reports a message read from the server, `bytes` long.
+pub(crate) fn message_received(method: Option<&str>, id: Option<&Value>, bytes: usize) {
+    #[cfg(feature = "tracing")]
+    {
+        // outside the macro, which has a `Value` of its own in scope
+        let id = id.map(|id| match id {
+            Value::String(id) => id.clone(),
+            id => id.to_string(),
+        });
+        tracing::trace!(
+            method,
+            id = id.map(tracing::field::display),
+            bytes,
+            "received"
+        );
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (method, id, bytes);
+}
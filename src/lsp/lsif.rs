@@ -0,0 +1,491 @@
+//! Builds dumps in the Language Server Index Format, LSIF: a graph of the documents of a
+//! project, the ranges within them, and what a server answers for those ranges, so that
+//! code navigation works without running the server. Dumps are read back with
+//! `LsifIndex::parse`, and SCIP indexes into the same model with `scip::parse`.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use lsp_types::request::{GotoDefinition, HoverRequest, References, Request};
+use lsp_types::{Hover, Location, Moniker, Position, PositionEncodingKind, Range, Url};
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::navigation::GotoKind;
+use super::symbols::DocumentSymbolNode;
+use super::text_utils::PositionEncoding;
+
+/// The version of LSIF dumps are written in.
+pub const LSIF_VERSION: &str = "0.4.3";
+
+/// What the server answered about one symbol, the unit an index is built from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexedSymbol {
+    /// Where the symbol is defined, usually its name in its declaration.
+    pub definitions: Vec<Location>,
+    /// Where the symbol is used, its definitions left out.
+    pub references: Vec<Location>,
+    pub hover: Option<Hover>,
+    pub monikers: Vec<Moniker>,
+}
+
+/// Why an index could not be read.
+#[derive(Debug)]
+pub enum IndexError {
+    Json(serde_json::Error),
+    /// The index is not laid out as its format says.
+    Malformed(String),
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexError::Json(err) => write!(f, "{}", err),
+            IndexError::Malformed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<serde_json::Error> for IndexError {
+    fn from(err: serde_json::Error) -> IndexError {
+        IndexError::Json(err)
+    }
+}
+
+/// The symbols of a project, written out with `elements`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LsifIndex {
+    pub project_root: Url,
+    /// How the characters of the ranges are counted, UTF-16 unless read from an index
+    /// saying otherwise.
+    pub encoding: PositionEncoding,
+    /// The documents of the project with their language ids, in the order they are dumped.
+    pub documents: Vec<(Url, String)>,
+    pub symbols: Vec<IndexedSymbol>,
+}
+
+/// Hands out the ids of the elements of a dump and collects them in order.
+struct Elements {
+    next_id: u64,
+    elements: Vec<Value>,
+}
+
+impl Elements {
+    fn vertex(&mut self, label: &str, mut properties: Value) -> u64 {
+        self.next_id += 1;
+        properties["id"] = json!(self.next_id);
+        properties["type"] = json!("vertex");
+        properties["label"] = json!(label);
+        self.elements.push(properties);
+        self.next_id
+    }
+
+    fn edge(&mut self, label: &str, out_v: u64, in_vs: &[u64], mut properties: Value) {
+        self.next_id += 1;
+        properties["id"] = json!(self.next_id);
+        properties["type"] = json!("edge");
+        properties["label"] = json!(label);
+        properties["outV"] = json!(out_v);
+        match in_vs {
+            [in_v] if label != "contains" && label != "item" => properties["inV"] = json!(in_v),
+            in_vs => properties["inVs"] = json!(in_vs),
+        }
+        self.elements.push(properties);
+    }
+}
+
+impl LsifIndex {
+    pub fn new(project_root: Url) -> LsifIndex {
+        LsifIndex {
+            project_root,
+            encoding: PositionEncoding::Utf16,
+            documents: Vec::new(),
+            symbols: Vec::new(),
+        }
+    }
+
+    /// Adds a document unless it is part of the index already.
+    pub fn add_document(&mut self, uri: Url, language_id: &str) {
+        if !self.documents.iter().any(|(known, _)| *known == uri) {
+            self.documents.push((uri, language_id.to_owned()));
+        }
+    }
+
+    /// The vertices and edges of the dump, each one line of the line-based format or one
+    /// element of the JSON array format.
+    ///
+    /// Every symbol gets a result set, which its definition and reference ranges point to
+    /// and which holds its definition, references, hover and monikers. Locations in
+    /// documents that are not part of the index, e.g. in dependencies, are left out. A
+    /// range belongs to the first symbol that mentions it.
+    pub fn elements(&self) -> Vec<Value> {
+        let mut out = Elements {
+            next_id: 0,
+            elements: Vec::new(),
+        };
+        out.vertex(
+            "metaData",
+            json!({
+                "version": LSIF_VERSION,
+                "projectRoot": self.project_root,
+                "positionEncoding": match self.encoding {
+                    PositionEncoding::Utf8 => PositionEncodingKind::UTF8,
+                    PositionEncoding::Utf16 => PositionEncodingKind::UTF16,
+                    PositionEncoding::Utf32 => PositionEncodingKind::UTF32,
+                },
+                "toolInfo": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+            }),
+        );
+        let kind = self.documents.first().map_or("", |(_, language)| language);
+        let project = out.vertex("project", json!({ "kind": kind }));
+        out.vertex(
+            "$event",
+            json!({ "kind": "begin", "scope": "project", "data": project }),
+        );
+
+        let mut documents = HashMap::new();
+        for (uri, language_id) in &self.documents {
+            let document = out.vertex("document", json!({ "uri": uri, "languageId": language_id }));
+            out.vertex(
+                "$event",
+                json!({ "kind": "begin", "scope": "document", "data": document }),
+            );
+            documents.insert(uri.clone(), document);
+        }
+        let all: Vec<u64> = self
+            .documents
+            .iter()
+            .map(|(uri, _)| documents[uri])
+            .collect();
+        if !all.is_empty() {
+            out.edge("contains", project, &all, json!({}));
+        }
+
+        // the ranges of each document, by document id, and the result set of each range
+        let mut ranges: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+        let mut range_ids: HashMap<(Url, Range), u64> = HashMap::new();
+        let mut range_id = |out: &mut Elements, location: &Location| {
+            let document = *documents.get(&location.uri)?;
+            let key = (location.uri.clone(), location.range);
+            if let Some(id) = range_ids.get(&key) {
+                return Some((document, *id, false));
+            }
+            let range = location.range;
+            let id = out.vertex("range", json!({ "start": range.start, "end": range.end }));
+            range_ids.insert(key, id);
+            ranges.entry(document).or_default().push(id);
+            Some((document, id, true))
+        };
+
+        for symbol in &self.symbols {
+            let result_set = out.vertex("resultSet", json!({}));
+            // the definition and reference ranges grouped by document, for the item edges
+            let mut definitions: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+            let mut references: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+            for (location, is_definition) in symbol
+                .definitions
+                .iter()
+                .map(|location| (location, true))
+                .chain(symbol.references.iter().map(|location| (location, false)))
+            {
+                let Some((document, range, new)) = range_id(&mut out, location) else {
+                    continue;
+                };
+                if new {
+                    out.edge("next", range, &[result_set], json!({}));
+                }
+                let group = if is_definition {
+                    &mut definitions
+                } else {
+                    &mut references
+                };
+                group.entry(document).or_default().push(range);
+            }
+
+            if !definitions.is_empty() {
+                let result = out.vertex("definitionResult", json!({}));
+                out.edge(GotoDefinition::METHOD, result_set, &[result], json!({}));
+                for (document, ranges) in &definitions {
+                    out.edge("item", result, ranges, json!({ "document": document }));
+                }
+            }
+            if !definitions.is_empty() || !references.is_empty() {
+                let result = out.vertex("referenceResult", json!({}));
+                out.edge(References::METHOD, result_set, &[result], json!({}));
+                for (property, groups) in
+                    [("definitions", &definitions), ("references", &references)]
+                {
+                    for (document, ranges) in groups {
+                        out.edge(
+                            "item",
+                            result,
+                            ranges,
+                            json!({ "document": document, "property": property }),
+                        );
+                    }
+                }
+            }
+            if let Some(hover) = &symbol.hover {
+                let result = out.vertex("hoverResult", json!({ "result": hover }));
+                out.edge(HoverRequest::METHOD, result_set, &[result], json!({}));
+            }
+            for moniker in &symbol.monikers {
+                let mut properties = json!({
+                    "scheme": moniker.scheme,
+                    "identifier": moniker.identifier,
+                    "unique": moniker.unique,
+                });
+                if let Some(kind) = &moniker.kind {
+                    properties["kind"] = json!(kind);
+                }
+                let moniker = out.vertex("moniker", properties);
+                out.edge("moniker", result_set, &[moniker], json!({}));
+            }
+        }
+
+        for (document, ranges) in &ranges {
+            out.edge("contains", *document, ranges, json!({}));
+        }
+        for (uri, _) in &self.documents {
+            out.vertex(
+                "$event",
+                json!({ "kind": "end", "scope": "document", "data": documents[uri] }),
+            );
+        }
+        out.vertex(
+            "$event",
+            json!({ "kind": "end", "scope": "project", "data": project }),
+        );
+        out.elements
+    }
+
+    /// Reads a dump in either LSIF format, a JSON array or one element per line.
+    ///
+    /// Each result set, and each range with results of its own, becomes a symbol: defined at
+    /// the ranges of its definition result, referenced at the `references` of its
+    /// reference result. Ranges are looked up in the document containing them.
+    pub fn parse(dump: &str) -> Result<LsifIndex, IndexError> {
+        let elements: Vec<Value> = if dump.trim_start().starts_with('[') {
+            serde_json::from_str(dump)?
+        } else {
+            dump.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<Result<_, _>>()?
+        };
+        // ids may be numbers or strings, both are keyed by their JSON text
+        let key = |value: &Value| value.to_string();
+        let in_vs = |edge: &Value| -> Vec<String> {
+            match edge.get("inVs").and_then(Value::as_array) {
+                Some(in_vs) => in_vs.iter().map(key).collect(),
+                None => edge.get("inV").map(key).into_iter().collect(),
+            }
+        };
+
+        let mut index = LsifIndex::new(Url::parse("file:///").expect("a valid url"));
+        let mut documents: HashMap<String, Url> = HashMap::new();
+        let mut ranges: HashMap<String, Range> = HashMap::new();
+        let mut vertices: HashMap<String, &Value> = HashMap::new();
+        // the anchors of symbols in the order they appear
+        let mut anchors: Vec<String> = Vec::new();
+        for element in &elements {
+            let id = key(&element["id"]);
+            if element["type"] != "vertex" {
+                continue;
+            }
+            match element["label"].as_str().unwrap_or_default() {
+                "metaData" => {
+                    if let Some(root) = element["projectRoot"].as_str() {
+                        index.project_root = Url::parse(root).map_err(|err| {
+                            IndexError::Malformed(format!("bad projectRoot {}: {}", root, err))
+                        })?;
+                    }
+                    let encoding = element["positionEncoding"].clone();
+                    if let Ok(kind) = serde_json::from_value::<PositionEncodingKind>(encoding) {
+                        index.encoding = PositionEncoding::from_kind(&kind);
+                    }
+                }
+                "document" => {
+                    let uri: Url = serde_json::from_value(element["uri"].clone())?;
+                    let language_id = element["languageId"].as_str().unwrap_or("plaintext");
+                    index.add_document(uri.clone(), language_id);
+                    documents.insert(id.clone(), uri);
+                }
+                "range" => {
+                    let start: Position = serde_json::from_value(element["start"].clone())?;
+                    let end: Position = serde_json::from_value(element["end"].clone())?;
+                    ranges.insert(id.clone(), Range::new(start, end));
+                }
+                "resultSet" => anchors.push(id.clone()),
+                _ => {}
+            }
+            vertices.insert(id, element);
+        }
+
+        let mut document_of: HashMap<String, Url> = HashMap::new();
+        let mut anchored = HashSet::new();
+        // the results of each anchor, by the request they answer
+        let mut results: HashMap<(String, &str), String> = HashMap::new();
+        let mut monikers: HashMap<String, Vec<String>> = HashMap::new();
+        // the item edges of each result, with the document and property they carry
+        let mut items: HashMap<String, Vec<&Value>> = HashMap::new();
+        for edge in elements.iter().filter(|element| element["type"] == "edge") {
+            let out_v = key(&edge["outV"]);
+            match edge["label"].as_str().unwrap_or_default() {
+                "contains" => {
+                    if let Some(uri) = documents.get(&out_v) {
+                        for range in in_vs(edge) {
+                            document_of.insert(range, uri.clone());
+                        }
+                    }
+                }
+                "item" => items.entry(out_v).or_default().push(edge),
+                "moniker" => monikers.entry(out_v).or_default().extend(in_vs(edge)),
+                label @ (GotoDefinition::METHOD | References::METHOD | HoverRequest::METHOD) => {
+                    if let Some(in_v) = in_vs(edge).pop() {
+                        if ranges.contains_key(&out_v) && anchored.insert(out_v.clone()) {
+                            anchors.push(out_v.clone());
+                        }
+                        results.insert((out_v, label), in_v);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let locations = |result: Option<&String>, properties: &[&str]| -> Vec<Location> {
+            let Some(edges) = result.and_then(|result| items.get(result)) else {
+                return Vec::new();
+            };
+            edges
+                .iter()
+                .filter(|edge| {
+                    let property = edge["property"].as_str().unwrap_or("references");
+                    properties.contains(&property)
+                })
+                .flat_map(|edge| {
+                    let document = edge.get("document").and_then(|id| documents.get(&key(id)));
+                    let (document_of, ranges) = (&document_of, &ranges);
+                    in_vs(edge).into_iter().filter_map(move |range| {
+                        let uri = document.or_else(|| document_of.get(&range))?;
+                        Some(Location::new(uri.clone(), *ranges.get(&range)?))
+                    })
+                })
+                .collect()
+        };
+        for anchor in anchors {
+            let result = |method: &'static str| results.get(&(anchor.clone(), method));
+            let definitions = locations(
+                result(GotoDefinition::METHOD),
+                &["definitions", "references"],
+            );
+            let references = locations(result(References::METHOD), &["references"])
+                .into_iter()
+                .filter(|location| !definitions.contains(location))
+                .collect();
+            let hover = result(HoverRequest::METHOD)
+                .and_then(|result| vertices.get(result))
+                .map(|result| serde_json::from_value(result["result"].clone()))
+                .transpose()?;
+            let monikers = monikers
+                .get(&anchor)
+                .into_iter()
+                .flatten()
+                .filter_map(|moniker| vertices.get(moniker))
+                .map(|moniker| serde_json::from_value((*moniker).clone()))
+                .collect::<Result<_, _>>()?;
+            index.symbols.push(IndexedSymbol {
+                definitions,
+                references,
+                hover,
+                monikers,
+            });
+        }
+        Ok(index)
+    }
+
+    /// Moves the documents below the project root to `root`, e.g. to query a dump made on
+    /// another machine.
+    pub fn relocate(&mut self, root: Url) {
+        let from = self.project_root.as_str().to_owned();
+        let moved = |uri: &mut Url| {
+            if let Some(path) = uri.as_str().strip_prefix(&from) {
+                if let Ok(to) = root.join(path) {
+                    *uri = to;
+                }
+            }
+        };
+        for (uri, _) in &mut self.documents {
+            moved(uri);
+        }
+        for symbol in &mut self.symbols {
+            for location in symbol.definitions.iter_mut().chain(&mut symbol.references) {
+                moved(&mut location.uri);
+            }
+        }
+        self.project_root = root;
+    }
+
+    /// The symbol defined or referenced at `position` of the document `uri`, the one with
+    /// the narrowest range when ranges nest.
+    pub fn symbol_at(&self, uri: &Url, position: Position) -> Option<&IndexedSymbol> {
+        self.symbols
+            .iter()
+            .flat_map(|symbol| {
+                symbol
+                    .definitions
+                    .iter()
+                    .chain(&symbol.references)
+                    .map(move |location| (symbol, location))
+            })
+            .filter(|(_, location)| {
+                location.uri == *uri
+                    && location.range.start <= position
+                    && position <= location.range.end
+            })
+            // nested ranges end no later and start no earlier than those around them
+            .min_by_key(|(_, location)| (location.range.end, Reverse(location.range.start)))
+            .map(|(symbol, _)| symbol)
+    }
+}
+
+/// Maps a request the server does not support to its empty answer.
+fn optional<T: Default>(result: Result<T, ClientError>) -> Result<T, ClientError> {
+    match result {
+        Err(ClientError::Unsupported(_)) => Ok(T::default()),
+        result => result,
+    }
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Asks the server about `symbol` of the document `uri`, at its name: where it is
+    /// defined and referenced, its hover and its monikers. Requests the server does not
+    /// support leave their part empty, a symbol without a definition is defined where the
+    /// document declares it.
+    pub async fn index_symbol(
+        &self,
+        uri: &Url,
+        symbol: &DocumentSymbolNode,
+    ) -> Result<IndexedSymbol, ClientError> {
+        let position = symbol.selection_range.start;
+        let mut definitions =
+            optional(self.goto(GotoKind::Definition, uri.clone(), position).await)?;
+        if definitions.is_empty() {
+            definitions.push(Location::new(uri.clone(), symbol.selection_range));
+        }
+        let references = optional(self.references(uri.clone(), position, false).await)?
+            .into_iter()
+            .filter(|location| !definitions.contains(location))
+            .collect();
+        let hover = optional(self.hover(uri.clone(), position).await)?;
+        let monikers = optional(self.moniker(uri.clone(), position).await)?;
+        Ok(IndexedSymbol {
+            definitions,
+            references,
+            hover,
+            monikers,
+        })
+    }
+}
@@ -0,0 +1,270 @@
+use lsp_types::{Documentation, HoverContents, MarkedString, MarkupContent, MarkupKind};
+
+const BOLD: &str = "\x1b[1m";
+const ITALIC: &str = "\x1b[3m";
+const UNDERLINE: &str = "\x1b[4m";
+const CODE: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// A fenced code block found in markdown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// The info string after the opening fence, e.g. `rust` or `typescript`.
+    pub language: Option<String>,
+    pub code: String,
+}
+
+/// Returns the fence a line opens or closes a code block with, if any.
+fn fence(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    ["```", "~~~"]
+        .into_iter()
+        .find(|fence| trimmed.starts_with(fence))
+}
+
+/// Extracts the fenced code blocks of a markdown document, in order. Hover responses
+/// usually put the signature of the hovered symbol in the first one.
+pub fn code_blocks(markdown: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut open: Option<(&str, CodeBlock)> = None;
+    for line in markdown.lines() {
+        let marker = fence(line);
+        let closes =
+            matches!((&open, marker), (Some((opening, _)), Some(closing)) if *opening == closing);
+        if closes {
+            let (_, mut block) = open.take().unwrap();
+            // drop the newline following the last line
+            block.code.pop();
+            blocks.push(block);
+            continue;
+        }
+        match (&mut open, marker) {
+            (Some((_, block)), _) => {
+                block.code.push_str(line);
+                block.code.push('\n');
+            }
+            (None, Some(opening)) => {
+                let info = line.trim_start()[opening.len()..].trim();
+                let language = info.split_whitespace().next().map(str::to_owned);
+                open = Some((
+                    opening,
+                    CodeBlock {
+                        language,
+                        code: String::new(),
+                    },
+                ));
+            }
+            (None, None) => {}
+        }
+    }
+    // an unterminated block runs to the end of the document
+    if let Some((_, mut block)) = open {
+        block.code.pop();
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// Strips markdown syntax, keeping the text and the contents of code blocks.
+pub fn markdown_to_plain_text(markdown: &str) -> String {
+    render_markdown(markdown, false)
+}
+
+/// Renders markdown for a terminal: headings and bold text in bold, emphasis in italics,
+/// code in cyan and link texts underlined.
+pub fn markdown_to_ansi(markdown: &str) -> String {
+    render_markdown(markdown, true)
+}
+
+fn render_markdown(markdown: &str, ansi: bool) -> String {
+    let mut lines = Vec::new();
+    let mut fenced: Option<&str> = None;
+    for line in markdown.lines() {
+        if let Some(marker) = fence(line) {
+            match fenced {
+                None => {
+                    fenced = Some(marker);
+                    continue;
+                }
+                Some(opening) if opening == marker => {
+                    fenced = None;
+                    continue;
+                }
+                Some(_) => {}
+            }
+        }
+        if fenced.is_some() {
+            lines.push(if ansi {
+                format!("{}{}{}", CODE, line, RESET)
+            } else {
+                line.to_owned()
+            });
+            continue;
+        }
+
+        let trimmed = line.trim_start();
+        let level = trimmed.chars().take_while(|c| *c == '#').count();
+        if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+            let heading = render_inline(trimmed[level..].trim(), false);
+            lines.push(if ansi {
+                format!("{}{}{}{}", BOLD, UNDERLINE, heading, RESET)
+            } else {
+                heading
+            });
+            continue;
+        }
+        lines.push(render_inline(line, ansi));
+    }
+    lines.join("\n")
+}
+
+/// Renders the inline syntax of one line: escapes, code spans, emphasis and links.
+/// Underscores are left alone, as they are far more common in identifiers than as
+/// emphasis in hover texts.
+fn render_inline(line: &str, ansi: bool) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::new();
+    let mut bold = false;
+    let mut italic = false;
+    let mut i = 0;
+    let style = |out: &mut String, code: &str| {
+        if ansi {
+            out.push_str(code);
+        }
+    };
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() && chars[i + 1].is_ascii_punctuation() => {
+                out.push(chars[i + 1]);
+                i += 2;
+            }
+            '`' => {
+                let ticks = chars[i..].iter().take_while(|c| **c == '`').count();
+                let close = (i + ticks..chars.len()).find(|&j| {
+                    chars[j..].iter().take_while(|c| **c == '`').count() == ticks
+                        && chars.get(j.wrapping_sub(1)) != Some(&'`')
+                });
+                match close {
+                    Some(close) => {
+                        let code: String = chars[i + ticks..close].iter().collect();
+                        style(&mut out, CODE);
+                        out.push_str(code.trim());
+                        style(&mut out, RESET);
+                        i = close + ticks;
+                    }
+                    None => {
+                        out.extend(&chars[i..i + ticks]);
+                        i += ticks;
+                    }
+                }
+            }
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                bold = !bold;
+                style(&mut out, if bold { BOLD } else { RESET });
+                i += 2;
+            }
+            '*' if italic || chars.get(i + 1).is_some_and(|c| !c.is_whitespace()) => {
+                italic = !italic;
+                style(&mut out, if italic { ITALIC } else { RESET });
+                i += 1;
+            }
+            '!' if chars.get(i + 1) == Some(&'[') => i += 1,
+            '[' => match link(&chars, i) {
+                Some((text, end)) => {
+                    style(&mut out, UNDERLINE);
+                    out.push_str(&render_inline(&text, false));
+                    style(&mut out, RESET);
+                    i = end;
+                }
+                None => {
+                    out.push('[');
+                    i += 1;
+                }
+            },
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    if ansi && (bold || italic) {
+        out.push_str(RESET);
+    }
+    out
+}
+
+/// Parses `[text](target)` starting at `start`, returning the text and the index after
+/// the closing parenthesis.
+fn link(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let text_end = (start + 1..chars.len()).find(|&i| chars[i] == ']')?;
+    if chars.get(text_end + 1) != Some(&'(') {
+        return None;
+    }
+    let target_end = (text_end + 2..chars.len()).find(|&i| chars[i] == ')')?;
+    Some((chars[start + 1..text_end].iter().collect(), target_end + 1))
+}
+
+fn marked_string_markdown(marked: &MarkedString) -> String {
+    match marked {
+        MarkedString::String(markdown) => markdown.clone(),
+        MarkedString::LanguageString(code) => {
+            format!("```{}\n{}\n```", code.language, code.value)
+        }
+    }
+}
+
+/// Escapes plain text so that it renders as itself.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii_punctuation() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn markup_markdown(markup: &MarkupContent) -> String {
+    match markup.kind {
+        MarkupKind::Markdown => markup.value.clone(),
+        MarkupKind::PlainText => escape_markdown(&markup.value),
+    }
+}
+
+/// Joins the contents of a hover into a single markdown document.
+pub fn hover_markdown(contents: &HoverContents) -> String {
+    match contents {
+        HoverContents::Scalar(marked) => marked_string_markdown(marked),
+        HoverContents::Array(marked) => marked
+            .iter()
+            .map(marked_string_markdown)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        HoverContents::Markup(markup) => markup_markdown(markup),
+    }
+}
+
+/// The documentation of a completion item or signature as markdown.
+pub fn documentation_markdown(documentation: &Documentation) -> String {
+    match documentation {
+        Documentation::String(text) => escape_markdown(text),
+        Documentation::MarkupContent(markup) => markup_markdown(markup),
+    }
+}
+
+pub fn hover_to_plain_text(contents: &HoverContents) -> String {
+    markdown_to_plain_text(&hover_markdown(contents))
+}
+
+pub fn hover_to_ansi(contents: &HoverContents) -> String {
+    markdown_to_ansi(&hover_markdown(contents))
+}
+
+pub fn documentation_to_plain_text(documentation: &Documentation) -> String {
+    markdown_to_plain_text(&documentation_markdown(documentation))
+}
+
+pub fn documentation_to_ansi(documentation: &Documentation) -> String {
+    markdown_to_ansi(&documentation_markdown(documentation))
+}
@@ -0,0 +1,303 @@
+//! Counts what the client asks the server and how the server answers, for embedders that
+//! watch the servers they run: per method, how many requests were sent, how long the
+//! answers took and how many were errors, and how many bytes went each way. See
+//! `LanguageServerRef::metrics`.
+//!
+//! With the `prometheus` feature the counts can be rendered in the Prometheus text format
+//! and served over HTTP, see `serve_prometheus`.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::client::Direction;
+
+/// The upper bounds of the latency buckets, in seconds, as Prometheus clients default to.
+pub const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// How long the answers to a method took.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    /// How many answers took at most each bound of `LATENCY_BUCKETS`, the last one counting
+    /// those above every bound.
+    pub buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    /// The time every answer took together, in seconds.
+    pub sum: f64,
+    pub count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    /// The time the answers took on average, `None` before the first.
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0).then(|| Duration::from_secs_f64(self.sum / self.count as f64))
+    }
+}
+
+/// The requests of one method.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MethodMetrics {
+    pub requests: u64,
+    /// The requests answered with an error, or not at all because the server exited.
+    pub errors: u64,
+    /// The answers, errors included.
+    pub latency: Histogram,
+}
+
+impl MethodMetrics {
+    /// The share of the answered requests that failed, `None` before the first answer.
+    pub fn error_rate(&self) -> Option<f64> {
+        (self.latency.count > 0).then(|| self.errors as f64 / self.latency.count as f64)
+    }
+}
+
+/// The counts at one point, see `Metrics::snapshot`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsSnapshot {
+    /// By method, in order.
+    pub methods: BTreeMap<String, MethodMetrics>,
+    /// The bodies of the messages written to the server, headers left out.
+    pub bytes_sent: u64,
+    /// The bodies of the messages read from the server.
+    pub bytes_received: u64,
+}
+
+/// The counts of a server, updated as the client talks to it.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    counts: Mutex<MetricsSnapshot>,
+}
+
+impl Metrics {
+    pub(crate) fn request_sent(&self, method: &str) {
+        let mut counts = self.counts.lock().unwrap();
+        counts
+            .methods
+            .entry(method.to_owned())
+            .or_default()
+            .requests += 1;
+    }
+
+    pub(crate) fn response_received(&self, method: &str, elapsed: Duration, failed: bool) {
+        let mut counts = self.counts.lock().unwrap();
+        let method = counts.methods.entry(method.to_owned()).or_default();
+        method.latency.observe(elapsed);
+        method.errors += u64::from(failed);
+    }
+
+    pub(crate) fn message(&self, direction: Direction, bytes: usize) {
+        let mut counts = self.counts.lock().unwrap();
+        match direction {
+            Direction::Sent => counts.bytes_sent += bytes as u64,
+            Direction::Received => counts.bytes_received += bytes as u64,
+        }
+    }
+
+    /// The counts so far.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.counts.lock().unwrap().clone()
+    }
+}
+
+#[cfg(feature = "prometheus")]
+mod prometheus {
+    use std::fmt::Write;
+    use std::io;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::time;
+
+    use super::{Metrics, MetricsSnapshot, LATENCY_BUCKETS};
+    use crate::lsp::log;
+
+    /// The longest request head read, past which the connection is dropped.
+    const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+    /// How long a client gets to send its request.
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// A label value escaped as the text format requires.
+    fn label(value: &str) -> String {
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    }
+
+    fn header(text: &mut String, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(text, "# HELP {} {}", name, help);
+        let _ = writeln!(text, "# TYPE {} {}", name, kind);
+    }
+
+    impl MetricsSnapshot {
+        /// The counts in the Prometheus text exposition format, the metrics named
+        /// `lsp_client_*` and those of requests labelled with their method.
+        pub fn to_prometheus(&self) -> String {
+            let mut text = String::new();
+            header(
+                &mut text,
+                "lsp_client_requests_total",
+                "counter",
+                "Requests sent to the server.",
+            );
+            for (method, metrics) in &self.methods {
+                let _ = writeln!(
+                    text,
+                    "lsp_client_requests_total{{method=\"{}\"}} {}",
+                    label(method),
+                    metrics.requests
+                );
+            }
+            header(
+                &mut text,
+                "lsp_client_request_errors_total",
+                "counter",
+                "Requests answered with an error, or not at all because the server exited.",
+            );
+            for (method, metrics) in &self.methods {
+                let _ = writeln!(
+                    text,
+                    "lsp_client_request_errors_total{{method=\"{}\"}} {}",
+                    label(method),
+                    metrics.errors
+                );
+            }
+            header(
+                &mut text,
+                "lsp_client_request_duration_seconds",
+                "histogram",
+                "How long the server took to answer.",
+            );
+            for (method, metrics) in &self.methods {
+                let method = label(method);
+                let histogram = &metrics.latency;
+                let mut cumulative = 0;
+                for (bound, count) in LATENCY_BUCKETS.iter().zip(&histogram.buckets) {
+                    cumulative += count;
+                    let _ = writeln!(
+                        text,
+                        "lsp_client_request_duration_seconds_bucket{{method=\"{}\",le=\"{}\"}} {}",
+                        method, bound, cumulative
+                    );
+                }
+                let _ = writeln!(
+                    text,
+                    "lsp_client_request_duration_seconds_bucket{{method=\"{}\",le=\"+Inf\"}} {}",
+                    method, histogram.count
+                );
+                let _ = writeln!(
+                    text,
+                    "lsp_client_request_duration_seconds_sum{{method=\"{}\"}} {}",
+                    method, histogram.sum
+                );
+                let _ = writeln!(
+                    text,
+                    "lsp_client_request_duration_seconds_count{{method=\"{}\"}} {}",
+                    method, histogram.count
+                );
+            }
+            for (name, help, bytes) in [
+                (
+                    "lsp_client_sent_bytes_total",
+                    "Bytes of the messages written to the server.",
+                    self.bytes_sent,
+                ),
+                (
+                    "lsp_client_received_bytes_total",
+                    "Bytes of the messages read from the server.",
+                    self.bytes_received,
+                ),
+            ] {
+                header(&mut text, name, "counter", help);
+                let _ = writeln!(text, "{} {}", name, bytes);
+            }
+            text
+        }
+    }
+
+    /// Answers every HTTP request to `address` with the counts of `metrics` in the
+    /// Prometheus text format, until the returned future is dropped. The path is not
+    /// looked at, so `/metrics` does as well as any.
+    pub async fn serve_prometheus(address: SocketAddr, metrics: Arc<Metrics>) -> io::Result<()> {
+        let listener = TcpListener::bind(address).await?;
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                if let Err(err) = reply(stream, &metrics).await {
+                    log::warning!("failed to serve the metrics: {}", err);
+                }
+            });
+        }
+    }
+
+    async fn reply(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+        // the request itself does not matter, only that it was sent in full
+        match time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+            Ok(Ok(true)) => {}
+            Ok(Ok(false)) => return Ok(()),
+            Ok(Err(err)) => return Err(err),
+            Err(_) => {
+                let message = "the request was not sent in time";
+                return Err(io::Error::new(io::ErrorKind::TimedOut, message));
+            }
+        }
+        let body = metrics.snapshot().to_prometheus();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+
+    /// Reads the head of a request, up to the empty line ending it, returning false when the
+    /// client closes the connection first.
+    async fn read_request(stream: &mut TcpStream) -> io::Result<bool> {
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        loop {
+            let read = stream.read(&mut buffer).await?;
+            if read == 0 {
+                return Ok(false);
+            }
+            // the end may start in the bytes read before
+            let start = request.len().saturating_sub(3);
+            request.extend_from_slice(&buffer[..read]);
+            if request[start..]
+                .windows(4)
+                .any(|window| window == b"\r\n\r\n")
+            {
+                return Ok(true);
+            }
+            if request.len() > MAX_REQUEST_HEAD {
+                let message = "the request is too large";
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "prometheus")]
+pub use prometheus::serve_prometheus;
@@ -1,2 +1,53 @@
+#[cfg(feature = "bench_support")]
+pub mod bench_support;
+pub mod call_hierarchy;
+pub mod capabilities;
+#[cfg(feature = "clangd")]
+pub mod clangd;
 pub mod client;
+pub mod code_actions;
+pub mod code_lens;
+pub mod colors;
+pub mod commands;
+pub mod completion;
+pub mod diagnostics;
+pub mod document_links;
+pub mod documents;
+pub mod edits;
+pub mod extensions;
+pub mod file_operations;
+pub mod folding;
+pub mod formatting;
+pub mod frame_log;
+pub mod hover;
+pub mod inlay_hints;
+pub mod inline_values;
+pub mod interceptor;
+pub mod linked_editing;
+mod log;
+pub mod lsif;
+pub mod markup;
+pub mod metrics;
+pub mod moniker;
+pub mod navigation;
 pub mod parsing;
+pub mod readiness;
+pub mod rename;
+pub mod replay;
+pub mod response_cache;
+pub mod rust_analyzer;
+pub mod scip;
+pub mod selection;
+pub mod semantic_tokens;
+pub mod server_capabilities;
+pub mod settings;
+pub mod signature_help;
+pub mod snippet;
+pub mod symbols;
+pub mod testing;
+pub mod text_utils;
+pub mod tsserver;
+pub mod validation;
+#[cfg(feature = "watcher")]
+pub mod watcher;
+pub mod workspace_folders;
@@ -0,0 +1,22 @@
+use lsp_types::request::MonikerRequest;
+use lsp_types::{Moniker, MonikerParams, Position, Url};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::navigation::text_document_position;
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Returns the monikers of the symbol at `position`: identifiers, scoped by scheme and
+    /// uniqueness level, which stay stable across indexes of different repositories.
+    pub async fn moniker(&self, uri: Url, position: Position) -> Result<Vec<Moniker>, ClientError> {
+        let params = MonikerParams {
+            text_document_position_params: text_document_position(uri, position),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        Ok(self
+            .request_if_supported::<MonikerRequest>(params)
+            .await?
+            .unwrap_or_default())
+    }
+}
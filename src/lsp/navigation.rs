@@ -0,0 +1,153 @@
+use lsp_types::request::{
+    DocumentHighlightRequest, GotoDeclaration, GotoDefinition, GotoImplementation,
+    GotoTypeDefinition, References,
+};
+use lsp_types::{
+    DocumentHighlight, DocumentHighlightParams, GotoDefinitionParams, GotoDefinitionResponse,
+    Location, PartialResultParams, Position, ReferenceContext, ReferenceParams,
+    TextDocumentIdentifier, TextDocumentPositionParams, Url,
+};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::log;
+
+/// The family of goto requests which all share the same params and response shapes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GotoKind {
+    Definition,
+    Declaration,
+    TypeDefinition,
+    Implementation,
+}
+
+/// Flattens the `Location | Location[] | LocationLink[]` variants into plain locations.
+///
+/// Location links are reduced to their target uri and target selection range, which is
+/// the range pointing at the symbol's name rather than its whole body.
+pub fn normalize_goto_response(response: GotoDefinitionResponse) -> Vec<Location> {
+    match response {
+        GotoDefinitionResponse::Scalar(location) => vec![location],
+        GotoDefinitionResponse::Array(locations) => locations,
+        GotoDefinitionResponse::Link(links) => links
+            .into_iter()
+            .map(|link| Location {
+                uri: link.target_uri,
+                range: link.target_selection_range,
+            })
+            .collect(),
+    }
+}
+
+pub(crate) fn text_document_position(uri: Url, position: Position) -> TextDocumentPositionParams {
+    TextDocumentPositionParams {
+        text_document: TextDocumentIdentifier { uri },
+        position,
+    }
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Issues the goto request of the given `kind` and returns the normalized locations.
+    /// A `null` response from the server is reported as an empty list.
+    pub async fn goto(
+        &self,
+        kind: GotoKind,
+        uri: Url,
+        position: Position,
+    ) -> Result<Vec<Location>, ClientError> {
+        let params = GotoDefinitionParams {
+            text_document_position_params: text_document_position(uri, position),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let response = match kind {
+            GotoKind::Definition => self.request_if_supported::<GotoDefinition>(params).await?,
+            GotoKind::Declaration => self.request_if_supported::<GotoDeclaration>(params).await?,
+            GotoKind::TypeDefinition => {
+                self.request_if_supported::<GotoTypeDefinition>(params)
+                    .await?
+            }
+            GotoKind::Implementation => {
+                self.request_if_supported::<GotoImplementation>(params)
+                    .await?
+            }
+        };
+        Ok(response.map(normalize_goto_response).unwrap_or_default())
+    }
+
+    /// Finds all references to the symbol at `position`.
+    pub async fn references(
+        &self,
+        uri: Url,
+        position: Position,
+        include_declaration: bool,
+    ) -> Result<Vec<Location>, ClientError> {
+        let params = reference_params(uri, position, include_declaration, Default::default());
+        Ok(self
+            .request_if_supported::<References>(params)
+            .await?
+            .unwrap_or_default())
+    }
+
+    /// Like `references`, but asks the server to stream partial results, handing each batch
+    /// to `on_partial` as it arrives. The returned list only holds the locations delivered
+    /// in the final response, which is usually empty when the server streamed everything.
+    pub async fn references_streaming<F>(
+        &self,
+        uri: Url,
+        position: Position,
+        include_declaration: bool,
+        mut on_partial: F,
+    ) -> Result<Vec<Location>, ClientError>
+    where
+        F: 'static + Send + FnMut(Vec<Location>),
+    {
+        let token = self.register_progress_listener(move |value| {
+            match serde_json::from_value::<Vec<Location>>(value) {
+                Ok(locations) => on_partial(locations),
+                Err(err) => log::warning!("malformed partial references result: {:?}", err),
+            }
+        });
+        let partial_result_params = PartialResultParams {
+            partial_result_token: Some(token.clone()),
+        };
+        let params = reference_params(uri, position, include_declaration, partial_result_params);
+        let response = self.request_if_supported::<References>(params).await;
+        self.remove_progress_listener(&token);
+        Ok(response?.unwrap_or_default())
+    }
+
+    /// Finds the occurrences of the symbol at `position` within the same document, marked
+    /// as reads, writes or plain text matches when the server can tell them apart.
+    pub async fn document_highlights(
+        &self,
+        uri: Url,
+        position: Position,
+    ) -> Result<Vec<DocumentHighlight>, ClientError> {
+        let params = DocumentHighlightParams {
+            text_document_position_params: text_document_position(uri, position),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        Ok(self
+            .request_if_supported::<DocumentHighlightRequest>(params)
+            .await?
+            .unwrap_or_default())
+    }
+}
+
+fn reference_params(
+    uri: Url,
+    position: Position,
+    include_declaration: bool,
+    partial_result_params: PartialResultParams,
+) -> ReferenceParams {
+    ReferenceParams {
+        text_document_position: text_document_position(uri, position),
+        work_done_progress_params: Default::default(),
+        partial_result_params,
+        context: ReferenceContext {
+            include_declaration,
+        },
+    }
+}
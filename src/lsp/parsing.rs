@@ -1,7 +1,9 @@
 use tokio::io::AsyncBufReadExt;
 use tokio::io::AsyncReadExt;
-use tokio::io::Error;
+use tokio::io::AsyncWriteExt;
+use tokio::io::{Error, ErrorKind};
 
+use serde::de::IgnoredAny;
 use serde_json;
 
 #[derive(Debug)]
@@ -10,6 +12,19 @@ pub enum ParseError {
     ParseInt(std::num::ParseIntError),
     Utf8(std::string::FromUtf8Error),
     Json(serde_json::Error),
+    /// The body is not as long as the JSON value it holds, going by its `Content-Length`
+    /// header: `json` is the length of the value, `None` when the body cuts it short. See
+    /// `check_length`.
+    LengthMismatch {
+        declared: usize,
+        json: Option<usize>,
+    },
+    /// The `Content-Length` header declares a body longer than the maximum the reader was
+    /// given, see `MessageReader::set_max_length`. The body was skipped.
+    TooLarge {
+        declared: usize,
+        max: usize,
+    },
     Unknown(String),
 }
 
@@ -48,6 +63,20 @@ impl From<String> for ParseError {
 enum LspHeader {
     ContentType,
     ContentLength(usize),
+    /// A header the lenient mode skips.
+    Other,
+}
+
+/// How strictly `read_message_with` reads the headers of a message.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Only `Content-Length` and `Content-Type`, each name followed by `: `. A header
+    /// line may end with `\n` alone rather than `\r\n` in either mode.
+    #[default]
+    Strict,
+    /// Also headers it does not know, which are skipped, and names followed by a colon
+    /// with or without spaces around it, for servers and proxies sloppy about framing.
+    Lenient,
 }
 
 /// Given a reference to a reader, attempts to read a Language Server Protocol message,
@@ -55,47 +84,252 @@ enum LspHeader {
 pub async fn read_message<B: AsyncBufReadExt + Unpin>(
     reader: &mut B,
 ) -> Result<String, ParseError> {
-    let mut buffer = String::new();
-    let mut content_length: Option<usize> = None;
-
-    // read in headers.
-    loop {
-        buffer.clear();
-        reader.read_line(&mut buffer).await?;
-        match &buffer {
-            s if s.trim().len() == 0 => break, // empty line is end of headers
-            s => {
-                match parse_header(s)? {
-                    LspHeader::ContentLength(len) => content_length = Some(len),
-                    LspHeader::ContentType => (), // utf-8 only currently allowed value
-                };
+    read_message_with(reader, ParseMode::Strict).await
+}
+
+/// `read_message`, with the headers read as `mode` says.
+pub async fn read_message_with<B: AsyncBufReadExt + Unpin>(
+    reader: &mut B,
+    mode: ParseMode,
+) -> Result<String, ParseError> {
+    let mut messages = MessageReader::new(reader, mode);
+    messages.read_next().await?;
+    Ok(messages.body)
+}
+
+/// Where `resync` got to after a message could not be read.
+#[derive(Debug)]
+pub struct Resynced {
+    /// The bytes skipped before the `Content-Length` header of the next message.
+    pub skipped: usize,
+    /// That message.
+    pub message: Result<String, ParseError>,
+}
+
+/// Skips to the next message once reading one failed partway, e.g. on a malformed header
+/// or a body shorter than its length, leaving the reader wherever that was. Scans for the
+/// next `Content-Length:` header, in any case, and reads the message it starts. Fails only
+/// when the stream ends first.
+pub async fn resync<B: AsyncBufReadExt + Unpin>(
+    reader: &mut B,
+    mode: ParseMode,
+) -> Result<Resynced, Error> {
+    let mut messages = MessageReader::new(reader, mode);
+    let skipped = messages.resync().await?;
+    let message = match messages.read_next().await {
+        Ok(()) => Ok(messages.body),
+        Err(err) => Err(err),
+    };
+    Ok(Resynced { skipped, message })
+}
+
+/// Reads messages like `read_message_with`, into buffers kept from one message to the next
+/// rather than allocated for each, for servers sending many messages.
+pub struct MessageReader<B> {
+    reader: B,
+    mode: ParseMode,
+    /// The header line being read.
+    line: String,
+    /// The body of the message last read.
+    body: String,
+    /// Whether `resync` stopped after the name of a `Content-Length` header.
+    at_length: bool,
+    /// The longest body read, see `set_max_length`.
+    max_length: Option<usize>,
+}
+
+impl<B: AsyncBufReadExt + Unpin> MessageReader<B> {
+    pub fn new(reader: B, mode: ParseMode) -> MessageReader<B> {
+        MessageReader {
+            reader,
+            mode,
+            line: String::new(),
+            body: String::new(),
+            at_length: false,
+            max_length: None,
+        }
+    }
+
+    /// Reads the headers of the messages from now on as `mode` says.
+    pub fn set_mode(&mut self, mode: ParseMode) {
+        self.mode = mode;
+    }
+
+    /// Skips the bodies longer than `max` bytes from now on, failing with
+    /// `ParseError::TooLarge` rather than allocating them. Any length is read by default.
+    pub fn set_max_length(&mut self, max: Option<usize>) {
+        self.max_length = max;
+    }
+
+    /// The reader the messages are read from.
+    pub fn get_mut(&mut self) -> &mut B {
+        &mut self.reader
+    }
+
+    /// Reads the next message, its body borrowed until the next call.
+    pub async fn read(&mut self) -> Result<&str, ParseError> {
+        self.read_next().await?;
+        Ok(&self.body)
+    }
+
+    /// The body of the message the last successful `read` returned.
+    pub fn message(&self) -> &str {
+        &self.body
+    }
+
+    /// Skips to the next message like `resync`, returning the bytes skipped. The next
+    /// `read` reads the message.
+    pub async fn resync(&mut self) -> Result<usize, Error> {
+        let pattern = b"content-length:";
+        let mut matched = 0;
+        let mut skipped = 0;
+        while matched < pattern.len() {
+            let buffer = self.reader.fill_buf().await?;
+            if buffer.is_empty() {
+                return Err(Error::from(ErrorKind::UnexpectedEof));
+            }
+            let mut consumed = 0;
+            for &byte in buffer {
+                consumed += 1;
+                if byte.to_ascii_lowercase() == pattern[matched] {
+                    matched += 1;
+                    if matched == pattern.len() {
+                        break;
+                    }
+                } else {
+                    // no prefix of the pattern recurs in it, so a mismatch starts over
+                    skipped += matched + 1;
+                    matched = 0;
+                    if byte.to_ascii_lowercase() == pattern[0] {
+                        skipped -= 1;
+                        matched = 1;
+                    }
+                }
+            }
+            self.reader.consume(consumed);
+        }
+        self.at_length = true;
+        Ok(skipped)
+    }
+
+    /// Reads a line into `line`, returning its length.
+    async fn read_line(&mut self) -> Result<usize, ParseError> {
+        self.line.clear();
+        self.reader.read_line(&mut self.line).await.map_err(|err| {
+            if err.kind() == ErrorKind::InvalidData {
+                // garbage rather than the end of the stream
+                ParseError::Unknown("header is not UTF-8".to_owned())
+            } else {
+                ParseError::Io(err)
+            }
+        })
+    }
+
+    /// Reads the headers of a message and its body into `body`.
+    async fn read_next(&mut self) -> Result<(), ParseError> {
+        let mut content_length: Option<usize> = None;
+        if std::mem::take(&mut self.at_length) {
+            // the value of the header `resync` found
+            self.read_line().await?;
+            content_length = Some(self.line.trim().parse()?);
+        }
+
+        // read in headers.
+        loop {
+            if self.read_line().await? == 0 {
+                return Err(ParseError::Io(Error::from(ErrorKind::UnexpectedEof)));
             }
-        };
+            match &self.line {
+                s if s.trim().is_empty() => break, // empty line is end of headers
+                s => {
+                    match parse_header(s, self.mode)? {
+                        LspHeader::ContentLength(len) => content_length = Some(len),
+                        LspHeader::ContentType => (), // utf-8 only currently allowed value
+                        LspHeader::Other => (),
+                    };
+                }
+            };
+        }
+
+        let content_length =
+            content_length.ok_or(format!("missing content-length header: {}", self.line))?;
+        if let Some(max) = self.max_length.filter(|&max| content_length > max) {
+            // skipped in place, so the next message is read as usual
+            let mut body = (&mut self.reader).take(content_length as u64);
+            tokio::io::copy(&mut body, &mut tokio::io::sink()).await?;
+            return Err(ParseError::TooLarge {
+                declared: content_length,
+                max,
+            });
+        }
+        // message body isn't newline terminated, so we read content_length bytes
+        let mut body = std::mem::take(&mut self.body).into_bytes();
+        body.clear();
+        body.resize(content_length, 0);
+        self.reader.read_exact(&mut body).await?;
+        self.body = String::from_utf8(body)?;
+        Ok(())
     }
+}
 
-    let content_length =
-        content_length.ok_or(format!("missing content-length header: {}", buffer))?;
-    // message body isn't newline terminated, so we read content_length bytes
-    let mut body_buffer = vec![0; content_length];
-    reader.read_exact(&mut body_buffer).await?;
-    let body = String::from_utf8(body_buffer)?;
-    Ok(body)
+/// Fails when `body` holds a JSON value cut short, or one followed by more than whitespace,
+/// either of which is the mark of a wrong `Content-Length` that left the reader in the
+/// middle of a message. A body that is not JSON at all is left to the caller to reject.
+///
+/// Messages are read without it, as it parses the body: it is meant for a body that did
+/// not parse, to tell a wrong length from a malformed message.
+pub fn check_length(body: &str) -> Result<(), ParseError> {
+    let mut values = serde_json::Deserializer::from_str(body).into_iter::<IgnoredAny>();
+    let json = match values.next() {
+        Some(Ok(_)) => values.byte_offset(),
+        Some(Err(err)) if err.is_eof() => {
+            return Err(ParseError::LengthMismatch {
+                declared: body.len(),
+                json: None,
+            })
+        }
+        Some(Err(_)) | None => return Ok(()),
+    };
+    if body[json..].trim().is_empty() {
+        Ok(())
+    } else {
+        Err(ParseError::LengthMismatch {
+            declared: body.len(),
+            json: Some(json),
+        })
+    }
 }
 
-const HEADER_CONTENT_LENGTH: &'static str = "content-length";
-const HEADER_CONTENT_TYPE: &'static str = "content-type";
+const HEADER_CONTENT_LENGTH: &str = "content-length";
+const HEADER_CONTENT_TYPE: &str = "content-type";
 
 /// Given a header string, attempts to extract and validate the name and value parts.
-fn parse_header(s: &str) -> Result<LspHeader, ParseError> {
-    let split: Vec<String> = s.split(": ").map(|s| s.trim().to_lowercase()).collect();
+fn parse_header(s: &str, mode: ParseMode) -> Result<LspHeader, ParseError> {
+    let split: Vec<String> = match mode {
+        ParseMode::Strict => s.split(": ").map(|s| s.trim().to_lowercase()).collect(),
+        ParseMode::Lenient => match s.split_once(':') {
+            Some((name, value)) => vec![name.trim().to_lowercase(), value.trim().to_owned()],
+            None => vec![s.to_owned()],
+        },
+    };
     if split.len() != 2 {
         return Err(ParseError::Unknown(format!("malformed header: {}", s)));
     }
-    match split[0].as_ref() {
-        HEADER_CONTENT_TYPE => Ok(LspHeader::ContentType),
-        HEADER_CONTENT_LENGTH => Ok(LspHeader::ContentLength(usize::from_str_radix(
-            &split[1], 10,
-        )?)),
-        _ => Err(ParseError::Unknown(format!("Unknown header: {}", s))),
+    match (split[0].as_ref(), mode) {
+        (HEADER_CONTENT_TYPE, _) => Ok(LspHeader::ContentType),
+        (HEADER_CONTENT_LENGTH, _) => Ok(LspHeader::ContentLength(split[1].parse()?)),
+        (_, ParseMode::Lenient) => Ok(LspHeader::Other),
+        (_, ParseMode::Strict) => Err(ParseError::Unknown(format!("Unknown header: {}", s))),
     }
 }
+
+/// Writes `body` to `writer` as a Language Server Protocol message, the counterpart of
+/// `read_message`.
+pub async fn write_message<W: AsyncWriteExt + Unpin>(
+    writer: &mut W,
+    body: &str,
+) -> Result<(), Error> {
+    let message = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+    writer.write_all(message.as_bytes()).await?;
+    writer.flush().await
+}
@@ -0,0 +1,111 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::oneshot;
+use tokio::time::{self, Instant};
+
+use super::client::{ClientError, LanguageServerRef};
+
+/// How `wait_until_ready` decides that the server can answer meaningful queries. Servers
+/// commonly answer requests sent right after `initialize` with empty results while they are
+/// still loading the project.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Readiness {
+    /// The work done progress the server started on its own has ended, and no new one began
+    /// for `settle`. rust-analyzer reports indexing this way, typescript-language-server
+    /// the loading of the project.
+    Progress { settle: Duration },
+    /// The server sent the notification `method`, e.g. a server specific "project loaded"
+    /// notification. Only notifications sent after `wait_until_ready` was called count.
+    Notification(String),
+    /// A request for `method` returned a result other than `null` or an empty list. The
+    /// request is retried every `interval`, also when it fails.
+    FirstQuery {
+        method: String,
+        params: Value,
+        interval: Duration,
+    },
+}
+
+/// Returns true for the results servers give while they are still loading.
+fn is_empty_result(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Array(items) => items.is_empty(),
+        _ => false,
+    }
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Waits until the server is ready according to `readiness`, failing with
+    /// `ClientError::Timeout` once `timeout` elapsed.
+    pub async fn wait_until_ready(
+        &self,
+        readiness: Readiness,
+        timeout: Duration,
+    ) -> Result<(), ClientError> {
+        let deadline = Instant::now() + timeout;
+        match readiness {
+            Readiness::Progress { settle } => {
+                time::timeout_at(deadline, self.progress_settled(settle))
+                    .await
+                    .map_err(|_| ClientError::Timeout)
+            }
+            Readiness::Notification(method) => {
+                let (tx, rx) = oneshot::channel();
+                let tx = Mutex::new(Some(tx));
+                let id = self.on_notification(&method, move |_| {
+                    if let Some(tx) = tx.lock().unwrap().take() {
+                        let _ = tx.send(());
+                    }
+                });
+                let received = time::timeout_at(deadline, rx).await;
+                self.remove_notification_handler(id);
+                match received {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err(ClientError::Timeout),
+                }
+            }
+            Readiness::FirstQuery {
+                method,
+                params,
+                interval,
+            } => time::timeout_at(deadline, self.first_answer(&method, &params, interval))
+                .await
+                .map_err(|_| ClientError::Timeout)?,
+        }
+    }
+
+    async fn progress_settled(&self, settle: Duration) {
+        let mut progress = self.server_progress();
+        loop {
+            let idle = progress.borrow_and_update().is_empty();
+            if idle {
+                // returns once nothing began for `settle`, or when the server is gone
+                match time::timeout(settle, progress.changed()).await {
+                    Ok(Ok(())) => {}
+                    Err(_) | Ok(Err(_)) => return,
+                }
+            } else if progress.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    async fn first_answer(
+        &self,
+        method: &str,
+        params: &Value,
+        interval: Duration,
+    ) -> Result<(), ClientError> {
+        loop {
+            match self.request_value(method, params.clone()).await {
+                Ok(value) if !is_empty_result(&value) => return Ok(()),
+                Err(err @ (ClientError::Canceled | ClientError::ClientClosed)) => return Err(err),
+                Ok(_) | Err(_) => time::sleep(interval).await,
+            }
+        }
+    }
+}
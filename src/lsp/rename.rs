@@ -0,0 +1,53 @@
+use lsp_types::request::{PrepareRenameRequest, Rename};
+use lsp_types::{Position, PrepareRenameResponse, RenameParams, Url, WorkspaceEdit};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::navigation::text_document_position;
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Asks the server whether the symbol at `position` can be renamed, returning the range
+    /// (and optionally the placeholder) to rename, or `None` if renaming is not possible.
+    pub async fn prepare_rename(
+        &self,
+        uri: Url,
+        position: Position,
+    ) -> Result<Option<PrepareRenameResponse>, ClientError> {
+        self.request_if_supported::<PrepareRenameRequest>(text_document_position(uri, position))
+            .await
+    }
+
+    /// Renames the symbol at `position` to `new_name`, returning the edit the server computed.
+    /// Nothing is applied; hand the edit to `edits::apply_workspace_edit_to_disk` or
+    /// `DocumentManager::apply_workspace_edit` to perform the rename.
+    ///
+    /// When the server supports it, the position is first validated with
+    /// `textDocument/prepareRename`, and `None` is returned when the server rejects it. An
+    /// error from prepareRename, which is how many servers reject a position, is returned
+    /// without attempting the rename.
+    pub async fn rename(
+        &self,
+        uri: Url,
+        position: Position,
+        new_name: &str,
+    ) -> Result<Option<WorkspaceEdit>, ClientError> {
+        if self.capabilities().supports_prepare_rename() {
+            match self.prepare_rename(uri.clone(), position).await? {
+                None => return Ok(None),
+                Some(PrepareRenameResponse::DefaultBehavior { default_behavior })
+                    if !default_behavior =>
+                {
+                    return Ok(None)
+                }
+                Some(_) => {}
+            }
+        }
+
+        let params = RenameParams {
+            text_document_position: text_document_position(uri, position),
+            new_name: new_name.to_owned(),
+            work_done_progress_params: Default::default(),
+        };
+        self.request_if_supported::<Rename>(params).await
+    }
+}
@@ -0,0 +1,230 @@
+//! Captures the messages exchanged with a server and plays the server's side back later,
+//! so code built on the client can be tested against a real server's answers without
+//! starting it.
+//!
+//! A capture is a frame log, see `frame_log`, and `Replay` serves one: it sends what the
+//! server sent, in order, waiting for each message the client sent first. Requests are
+//! matched by method and answered with the recorded response under their new id.
+//!
+//! ```no_run
+//! # async fn example(server: lsp_client::lsp::client::LanguageServerRef<tokio::process::ChildStdin>) -> std::io::Result<()> {
+//! use lsp_client::lsp::replay::{Capture, Replay};
+//!
+//! // once, against the real server
+//! let capture = Capture::start(&server);
+//! // ... initialize, query, shut down ...
+//! capture.save("tests/hover.jsonl".as_ref())?;
+//!
+//! // in the tests, without it
+//! let (server, replaying) = Replay::load("tests/hover.jsonl".as_ref())?.connect();
+//! // ... the same queries ...
+//! assert!(replaying.await.unwrap()?.is_complete());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncWrite, BufReader, DuplexStream, WriteHalf};
+use tokio::task::JoinHandle;
+
+use super::client::{Direction, LanguageServerRef};
+use super::frame_log::{self, FrameLog, LoggedFrame};
+use super::log;
+use super::parsing::{read_message, write_message, ParseError};
+use super::testing;
+
+/// The messages exchanged with a server from `Capture::start` on, kept in memory.
+#[derive(Clone, Default)]
+pub struct Capture {
+    log: Arc<Mutex<Vec<u8>>>,
+}
+
+/// The writer of the frame log a `Capture` keeps.
+struct CaptureWriter(Arc<Mutex<Vec<u8>>>);
+
+impl Write for CaptureWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Capture {
+    /// Captures the traffic of `server` from now on, in place of any frame log it writes.
+    pub fn start<W>(server: &LanguageServerRef<W>) -> Capture
+    where
+        W: tokio::io::AsyncWriteExt + Unpin,
+    {
+        let capture = Capture::default();
+        server.log_frames(FrameLog::new(CaptureWriter(capture.log.clone())));
+        capture
+    }
+
+    /// The messages so far.
+    pub fn frames(&self) -> Vec<LoggedFrame> {
+        let log = self.log.lock().unwrap();
+        frame_log::parse(&String::from_utf8_lossy(&log)).expect("the capture is a frame log")
+    }
+
+    /// Writes the messages so far to `path` as a frame log, for `Replay::load`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, &*self.log.lock().unwrap())
+    }
+}
+
+/// What a replay did not play as recorded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    /// The messages of the client the recording did not expect where they came, in order.
+    pub unexpected: Vec<Value>,
+    /// The frames left when the client exited or went away.
+    pub remaining: Vec<LoggedFrame>,
+}
+
+impl Report {
+    /// Whether the client sent what the recording expected and received all of it.
+    pub fn is_complete(&self) -> bool {
+        self.unexpected.is_empty() && self.remaining.is_empty()
+    }
+}
+
+/// An error answering a request the recording does not expect.
+fn diverged(id: &Value, message: String) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": -32803, "message": message },
+    })
+}
+
+/// Plays the server's side of a recording back.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    frames: Vec<LoggedFrame>,
+    paced: bool,
+}
+
+impl Replay {
+    pub fn new(frames: Vec<LoggedFrame>) -> Replay {
+        Replay {
+            frames,
+            paced: false,
+        }
+    }
+
+    /// Loads the frame log at `path`, as `--log-frames` and `Capture::save` write it.
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        let log = std::fs::read_to_string(path)?;
+        let frames = frame_log::parse(&log)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(Replay::new(frames))
+    }
+
+    /// Waits before each message of the server as long as the server did after the
+    /// message before it. By default messages are sent as soon as they are due.
+    pub fn paced(self) -> Replay {
+        Replay {
+            paced: true,
+            ..self
+        }
+    }
+
+    /// A client of the replay, over an in-memory pipe, and the replay serving it until the
+    /// client sends `exit`.
+    pub fn connect(
+        self,
+    ) -> (
+        LanguageServerRef<WriteHalf<DuplexStream>>,
+        JoinHandle<io::Result<Report>>,
+    ) {
+        let (server, end) = testing::pipe();
+        let replaying = tokio::spawn(async move { self.serve(end.reader, end.writer).await });
+        (server, replaying)
+    }
+
+    /// Acts as the server on `reader` and `writer` until the client sends `exit` or closes
+    /// `reader`.
+    ///
+    /// A client message the recording does not expect next is skipped, requests being
+    /// answered with an error naming the expected method, so the replay catches up once the
+    /// client does.
+    pub async fn serve<R, W>(&self, reader: R, mut writer: W) -> io::Result<Report>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut reader = BufReader::new(reader);
+        let mut report = Report::default();
+        // the recorded ids of client requests, mapped to the ids they have now
+        let mut ids: HashMap<String, Value> = HashMap::new();
+        let mut frames = self.frames.iter().peekable();
+        let mut previous: Option<&LoggedFrame> = None;
+        loop {
+            if let Some(frame) = frames.next_if(|frame| frame.direction == Direction::Received) {
+                if let (true, Some(previous)) = (self.paced, previous) {
+                    let delay = frame.timestamp.saturating_sub(previous.timestamp);
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                }
+                previous = Some(frame);
+                let mut message = frame.message.clone();
+                if message.get("method").is_none() {
+                    if let Some(id) = message.get("id").and_then(|id| ids.get(&id.to_string())) {
+                        message["id"] = id.clone();
+                    }
+                }
+                write_message(&mut writer, &message.to_string()).await?;
+                continue;
+            }
+
+            let body = match read_message(&mut reader).await {
+                Ok(body) => body,
+                Err(ParseError::Io(_)) => break,
+                Err(err) => {
+                    log::warning!("malformed message from the client: {:?}", err);
+                    continue;
+                }
+            };
+            let message: Value = serde_json::from_str(&body).unwrap_or(Value::Null);
+            let method = message.get("method").and_then(Value::as_str);
+            let expected = frames.peek().map(|frame| &frame.message);
+            let expected_method = expected.and_then(|expected| expected.get("method")?.as_str());
+            if expected.is_some() && method == expected_method {
+                let frame = frames.next().expect("peeked");
+                previous = Some(frame);
+                if let (Some(recorded), Some(id)) = (frame.message.get("id"), message.get("id")) {
+                    ids.insert(recorded.to_string(), id.clone());
+                }
+                if method == Some("exit") {
+                    break;
+                }
+                continue;
+            }
+            if method == Some("exit") {
+                break;
+            }
+            // a message the recording does not expect here
+            if let (Some(method), Some(id)) = (method, message.get("id")) {
+                let error = match expected_method {
+                    Some(expected) => {
+                        format!("the recording expects {} here, not {}", expected, method)
+                    }
+                    None => format!("the recording has no {} here", method),
+                };
+                write_message(&mut writer, &diverged(id, error).to_string()).await?;
+            }
+            report.unexpected.push(message);
+        }
+        report.remaining = frames.cloned().collect();
+        Ok(report)
+    }
+}
@@ -0,0 +1,166 @@
+//! Answers repeated queries about a document from the responses the server already gave,
+//! until the document changes, e.g. for batch analysis querying the same positions again
+//! and again. See `DocumentManager::cache_responses`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use lsp_types::notification::{Cancel, Notification};
+use lsp_types::request::{DocumentSymbolRequest, GotoDefinition, HoverRequest, Request};
+use lsp_types::Url;
+use serde_json::Value;
+
+use super::interceptor::{Flow, Interceptor};
+
+/// The methods `ResponseCache::new` caches.
+pub const CACHED_METHODS: &[&str] = &[
+    HoverRequest::METHOD,
+    GotoDefinition::METHOD,
+    DocumentSymbolRequest::METHOD,
+];
+
+/// A request, as its method and params.
+type Query = (String, String);
+
+#[derive(Default)]
+struct Cache {
+    methods: Vec<String>,
+    /// The version of the documents open in the manager.
+    versions: HashMap<Url, i32>,
+    /// The results by document, for the version it has in `versions`.
+    results: HashMap<Url, HashMap<Query, Value>>,
+    /// The requests sent for results not cached yet, by id, with the document and version
+    /// they were sent for.
+    misses: HashMap<u64, (Url, Option<i32>, Query)>,
+}
+
+/// An interceptor answering the requests for its methods it has seen answered before for
+/// the same document version, with the same params. Errors are not cached. The document
+/// manager drops the results of a document when it changes or closes, along with the results
+/// pointing into it, e.g. definitions; changes to files not open in the manager go
+/// unnoticed.
+#[derive(Clone)]
+pub struct ResponseCache(Arc<Mutex<Cache>>);
+
+impl ResponseCache {
+    /// A cache for `methods`, whose params must have a `textDocument`, such as
+    /// `CACHED_METHODS`.
+    pub fn new(methods: &[&str]) -> ResponseCache {
+        ResponseCache(Arc::new(Mutex::new(Cache {
+            methods: methods.iter().map(|&method| method.to_owned()).collect(),
+            ..Cache::default()
+        })))
+    }
+
+    /// Drops the results for `uri`, which is now at `version`, or closed when `None`, and the
+    /// results of other documents with a location in it.
+    pub fn document_changed(&self, uri: &Url, version: Option<i32>) {
+        let mut cache = self.0.lock().unwrap();
+        match version {
+            Some(version) => cache.versions.insert(uri.clone(), version),
+            None => cache.versions.remove(uri),
+        };
+        cache.results.remove(uri);
+        for results in cache.results.values_mut() {
+            results.retain(|_, result| !refers_to(result, uri.as_str()));
+        }
+        cache.results.retain(|_, results| !results.is_empty());
+        // their responses would be about the text before
+        cache.misses.retain(|_, (missed, ..)| missed != uri);
+    }
+
+    /// Drops every result.
+    pub fn clear(&self) {
+        self.0.lock().unwrap().results.clear();
+    }
+
+    /// The number of results cached.
+    pub fn len(&self) -> usize {
+        let cache = self.0.lock().unwrap();
+        cache.results.values().map(HashMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Whether `result` has a location in `uri`, as the `uri` or `targetUri` of a `Location` or a
+/// `LocationLink`.
+fn refers_to(result: &Value, uri: &str) -> bool {
+    match result {
+        Value::Object(fields) => fields.iter().any(|(name, field)| match field {
+            Value::String(field) => (name == "uri" || name == "targetUri") && field == uri,
+            field => refers_to(field, uri),
+        }),
+        Value::Array(items) => items.iter().any(|item| refers_to(item, uri)),
+        _ => false,
+    }
+}
+
+/// The document a request is about, with the request.
+fn query(message: &Value) -> Option<(Url, Query)> {
+    let method = message.get("method")?.as_str()?;
+    let params = message.get("params")?;
+    let uri = params.get("textDocument")?.get("uri")?.as_str()?;
+    Some((
+        Url::parse(uri).ok()?,
+        (method.to_owned(), params.to_string()),
+    ))
+}
+
+impl Interceptor for ResponseCache {
+    fn on_outgoing(&self, message: &mut Value) -> Flow {
+        if message.get("method").and_then(Value::as_str) == Some(Cancel::METHOD) {
+            // a request timed out or given up, whose response may never come
+            if let Some(id) = message["params"]["id"].as_u64() {
+                self.0.lock().unwrap().misses.remove(&id);
+            }
+            return Flow::Continue;
+        }
+        let Some(id) = message.get("id").and_then(Value::as_u64) else {
+            return Flow::Continue;
+        };
+        let mut cache = self.0.lock().unwrap();
+        let Some((uri, query)) = query(message) else {
+            return Flow::Continue;
+        };
+        if !cache.methods.contains(&query.0) {
+            return Flow::Continue;
+        }
+        if let Some(result) = cache
+            .results
+            .get(&uri)
+            .and_then(|results| results.get(&query))
+        {
+            return Flow::Respond(Ok(result.clone()));
+        }
+        let version = cache.versions.get(&uri).copied();
+        cache.misses.insert(id, (uri, version, query));
+        Flow::Continue
+    }
+
+    fn on_incoming(&self, message: &mut Value) -> Flow {
+        // responses have no method
+        if message.get("method").is_some() {
+            return Flow::Continue;
+        }
+        let Some(id) = message.get("id").and_then(Value::as_u64) else {
+            return Flow::Continue;
+        };
+        let mut cache = self.0.lock().unwrap();
+        let Some((uri, version, query)) = cache.misses.remove(&id) else {
+            return Flow::Continue;
+        };
+        // the document changed while the request was in flight
+        if cache.versions.get(&uri).copied() != version {
+            return Flow::Continue;
+        }
+        let error = message.get("error").is_some_and(|error| !error.is_null());
+        if let (Some(result), false) = (message.get("result"), error) {
+            let result = result.clone();
+            cache.results.entry(uri).or_default().insert(query, result);
+        }
+        Flow::Continue
+    }
+}
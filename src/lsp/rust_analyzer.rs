@@ -0,0 +1,68 @@
+use lsp_types::request::Request;
+use lsp_types::{Position, TextDocumentIdentifier, Url};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpandMacroParams {
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpandedMacro {
+    pub name: String,
+    pub expansion: String,
+}
+
+/// `rust-analyzer/expandMacro`: the recursive expansion of the macro call at a position.
+/// Like the other rust-analyzer extensions it is described in the server's
+/// `lsp-extensions.md`.
+pub enum ExpandMacro {}
+
+impl Request for ExpandMacro {
+    type Params = ExpandMacroParams;
+    type Result = Option<ExpandedMacro>;
+    const METHOD: &'static str = "rust-analyzer/expandMacro";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewSyntaxTreeParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+/// `rust-analyzer/viewSyntaxTree`: a textual dump of a document's syntax tree.
+pub enum ViewSyntaxTree {}
+
+impl Request for ViewSyntaxTree {
+    type Params = ViewSyntaxTreeParams;
+    type Result = String;
+    const METHOD: &'static str = "rust-analyzer/viewSyntaxTree";
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Expands the macro call at `position`, or returns `None` if there is none.
+    pub async fn expand_macro(
+        &self,
+        uri: Url,
+        position: Position,
+    ) -> Result<Option<ExpandedMacro>, ClientError> {
+        let params = ExpandMacroParams {
+            text_document: TextDocumentIdentifier { uri },
+            position,
+        };
+        self.request::<ExpandMacro>(params).await
+    }
+
+    /// Dumps the syntax tree of a document.
+    pub async fn view_syntax_tree(&self, uri: Url) -> Result<String, ClientError> {
+        let params = ViewSyntaxTreeParams {
+            text_document: TextDocumentIdentifier { uri },
+        };
+        self.request::<ViewSyntaxTree>(params).await
+    }
+}
@@ -0,0 +1,320 @@
+//! Reads SCIP indexes, the protobuf successor of LSIF, into the model of `lsif`. Only the
+//! parts code navigation needs are decoded: the documents, the occurrences of symbols in
+//! them and the documentation of the symbols.
+
+use std::collections::HashMap;
+
+use lsp_types::{
+    Hover, HoverContents, Location, MarkupContent, MarkupKind, Moniker, Position, Range,
+    UniquenessLevel, Url,
+};
+
+use super::lsif::{IndexError, IndexedSymbol, LsifIndex};
+use super::text_utils::PositionEncoding;
+
+/// The `symbol_roles` bit marking the occurrence defining its symbol.
+const DEFINITION_ROLE: u64 = 0x1;
+
+/// A field of a protobuf message: its number and its value, still encoded when it is
+/// length-delimited.
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    /// A fixed-width value, which no field read here has.
+    Fixed,
+}
+
+/// Iterates over the fields of an encoded protobuf message.
+struct Fields<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    fn varint(&mut self) -> Result<u64, IndexError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.bytes.split_first().ok_or_else(truncated)?;
+            self.bytes = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(IndexError::Malformed("a varint is too long".to_owned()))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], IndexError> {
+        if self.bytes.len() < len {
+            return Err(truncated());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u64, Field<'a>), IndexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+        let field = self.varint().and_then(|key| {
+            let value = match key & 0x7 {
+                0 => Field::Varint(self.varint()?),
+                1 => self.take(8).map(|_| Field::Fixed)?,
+                2 => {
+                    let len = self.varint()? as usize;
+                    Field::Bytes(self.take(len)?)
+                }
+                5 => self.take(4).map(|_| Field::Fixed)?,
+                wire_type => {
+                    return Err(IndexError::Malformed(format!(
+                        "unknown wire type {}",
+                        wire_type
+                    )))
+                }
+            };
+            Ok((key >> 3, value))
+        });
+        if field.is_err() {
+            // nothing after a malformed field can be read
+            self.bytes = &[];
+        }
+        Some(field)
+    }
+}
+
+fn fields(bytes: &[u8]) -> Fields<'_> {
+    Fields { bytes }
+}
+
+fn truncated() -> IndexError {
+    IndexError::Malformed("the index is truncated".to_owned())
+}
+
+fn string(bytes: &[u8]) -> Result<String, IndexError> {
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| IndexError::Malformed("a string is not UTF-8".to_owned()))
+}
+
+/// The numbers of a repeated integer field, packed or not.
+fn integers(field: Field<'_>, out: &mut Vec<u32>) -> Result<(), IndexError> {
+    match field {
+        Field::Varint(value) => out.push(value as u32),
+        Field::Bytes(bytes) => {
+            let mut packed = fields(bytes);
+            while !packed.bytes.is_empty() {
+                out.push(packed.varint()? as u32);
+            }
+        }
+        Field::Fixed => {}
+    }
+    Ok(())
+}
+
+/// A symbol occurring in a document.
+struct Occurrence {
+    range: Range,
+    symbol: String,
+    roles: u64,
+}
+
+fn occurrence(bytes: &[u8]) -> Result<Occurrence, IndexError> {
+    let mut range = Vec::new();
+    let mut symbol = String::new();
+    let mut roles = 0;
+    for field in fields(bytes) {
+        match field? {
+            (1, field) => integers(field, &mut range)?,
+            (2, Field::Bytes(bytes)) => symbol = string(bytes)?,
+            (3, Field::Varint(value)) => roles = value,
+            _ => {}
+        }
+    }
+    // [line, character, character] on one line, [line, character, line, character] otherwise
+    let range = match range[..] {
+        [line, start, end] => Range::new(Position::new(line, start), Position::new(line, end)),
+        [start_line, start, end_line, end] => Range::new(
+            Position::new(start_line, start),
+            Position::new(end_line, end),
+        ),
+        _ => {
+            return Err(IndexError::Malformed(format!(
+                "the occurrence of {} has {} range numbers",
+                symbol,
+                range.len()
+            )))
+        }
+    };
+    Ok(Occurrence {
+        range,
+        symbol,
+        roles,
+    })
+}
+
+/// The documentation of a symbol, as markdown paragraphs, its signature first.
+fn symbol_information(bytes: &[u8]) -> Result<(String, Vec<String>), IndexError> {
+    let mut symbol = String::new();
+    let mut documentation = Vec::new();
+    let mut signature = None;
+    for field in fields(bytes) {
+        match field? {
+            (1, Field::Bytes(bytes)) => symbol = string(bytes)?,
+            (3, Field::Bytes(bytes)) => documentation.push(string(bytes)?),
+            (7, Field::Bytes(document)) => {
+                // a Document with the language in field 4 and the text in field 5
+                let mut language = String::new();
+                let mut text = String::new();
+                for field in fields(document) {
+                    match field? {
+                        (4, Field::Bytes(bytes)) => language = string(bytes)?.to_lowercase(),
+                        (5, Field::Bytes(bytes)) => text = string(bytes)?,
+                        _ => {}
+                    }
+                }
+                if !text.is_empty() {
+                    signature = Some(format!("```{}\n{}\n```", language, text));
+                }
+            }
+            _ => {}
+        }
+    }
+    documentation.splice(0..0, signature);
+    Ok((symbol, documentation))
+}
+
+/// The position encoding of a `PositionEncoding` enum value, unspecified read as UTF-16
+/// like LSP.
+fn position_encoding(value: u64) -> PositionEncoding {
+    match value {
+        1 => PositionEncoding::Utf8,
+        3 => PositionEncoding::Utf32,
+        _ => PositionEncoding::Utf16,
+    }
+}
+
+/// The key of a symbol of the document at `path`, which includes the path for symbols
+/// local to the document.
+fn symbol_key(path: &str, symbol: &str) -> String {
+    if symbol.starts_with("local ") {
+        format!("{} {}", path, symbol)
+    } else {
+        symbol.to_owned()
+    }
+}
+
+/// Reads an encoded SCIP `Index`.
+///
+/// Every symbol becomes an `IndexedSymbol`, defined by its occurrences with the definition
+/// role and referenced by the others, with its documentation as hover and its SCIP symbol
+/// as moniker. Symbols local to a document are kept apart per document. The index has
+/// the position encoding of its first document saying.
+pub fn parse(bytes: &[u8]) -> Result<LsifIndex, IndexError> {
+    let mut project_root = None;
+    let mut documents = Vec::new();
+    let mut documentation: HashMap<String, Vec<String>> = HashMap::new();
+    for field in fields(bytes) {
+        match field? {
+            (1, Field::Bytes(metadata)) => {
+                for field in fields(metadata) {
+                    if let (3, Field::Bytes(root)) = field? {
+                        project_root = Some(string(root)?);
+                    }
+                }
+            }
+            (2, Field::Bytes(document)) => documents.push(document),
+            (3, Field::Bytes(symbol)) => {
+                let (symbol, docs) = symbol_information(symbol)?;
+                documentation.insert(symbol, docs);
+            }
+            _ => {}
+        }
+    }
+    let project_root = project_root
+        .ok_or_else(|| IndexError::Malformed("the index has no project root".to_owned()))?;
+    let mut project_root = Url::parse(&project_root).map_err(|err| {
+        IndexError::Malformed(format!("bad project root {}: {}", project_root, err))
+    })?;
+    if !project_root.path().ends_with('/') {
+        let path = format!("{}/", project_root.path());
+        project_root.set_path(&path);
+    }
+
+    let mut index = LsifIndex::new(project_root.clone());
+    let mut encoding = None;
+    // the symbols by their SCIP symbol, local ones prefixed with their document
+    let mut symbols: HashMap<String, IndexedSymbol> = HashMap::new();
+    let mut order = Vec::new();
+    for document in documents {
+        let mut path = String::new();
+        let mut language = String::new();
+        let mut occurrences = Vec::new();
+        for field in fields(document) {
+            match field? {
+                (1, Field::Bytes(bytes)) => path = string(bytes)?,
+                (2, Field::Bytes(bytes)) => occurrences.push(occurrence(bytes)?),
+                (3, Field::Bytes(bytes)) => {
+                    let (symbol, docs) = symbol_information(bytes)?;
+                    documentation.insert(symbol_key(&path, &symbol), docs);
+                }
+                (4, Field::Bytes(bytes)) => language = string(bytes)?.to_lowercase(),
+                (6, Field::Varint(value)) if value != 0 => {
+                    encoding.get_or_insert(position_encoding(value));
+                }
+                _ => {}
+            }
+        }
+        let uri = project_root
+            .join(&path)
+            .map_err(|err| IndexError::Malformed(format!("bad document path {}: {}", path, err)))?;
+        index.add_document(uri.clone(), &language);
+        for occurrence in occurrences {
+            if occurrence.symbol.is_empty() {
+                continue;
+            }
+            let key = symbol_key(&path, &occurrence.symbol);
+            let unique = if key == occurrence.symbol {
+                UniquenessLevel::Global
+            } else {
+                UniquenessLevel::Document
+            };
+            let symbol = symbols.entry(key.clone()).or_insert_with(|| {
+                order.push(key.clone());
+                IndexedSymbol {
+                    monikers: vec![Moniker {
+                        scheme: "scip".to_owned(),
+                        identifier: occurrence.symbol.clone(),
+                        unique,
+                        kind: None,
+                    }],
+                    ..IndexedSymbol::default()
+                }
+            });
+            let location = Location::new(uri.clone(), occurrence.range);
+            if occurrence.roles & DEFINITION_ROLE != 0 {
+                symbol.definitions.push(location);
+            } else {
+                symbol.references.push(location);
+            }
+        }
+    }
+    index.encoding = encoding.unwrap_or(PositionEncoding::Utf16);
+    for key in order {
+        let mut symbol = symbols.remove(&key).expect("ordered symbols are known");
+        symbol.hover = documentation
+            .get(&key)
+            .filter(|docs| !docs.is_empty())
+            .map(|docs| Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: docs.join("\n\n"),
+                }),
+                range: None,
+            });
+        index.symbols.push(symbol);
+    }
+    Ok(index)
+}
@@ -0,0 +1,43 @@
+use lsp_types::request::SelectionRangeRequest;
+use lsp_types::{
+    Position, Range, SelectionRange, SelectionRangeParams, TextDocumentIdentifier, Url,
+};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+
+/// Unrolls the linked list of parents into the ranges it covers, innermost first.
+pub fn selection_range_chain(selection: &SelectionRange) -> Vec<Range> {
+    let mut chain = vec![selection.range];
+    let mut parent = selection.parent.as_deref();
+    while let Some(selection) = parent {
+        chain.push(selection.range);
+        parent = selection.parent.as_deref();
+    }
+    chain
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Requests the expansion chain of each position, e.g. identifier, expression, statement,
+    /// block, function. The result holds one chain per position, innermost range first.
+    pub async fn selection_ranges(
+        &self,
+        uri: Url,
+        positions: Vec<Position>,
+    ) -> Result<Vec<Vec<Range>>, ClientError> {
+        let params = SelectionRangeParams {
+            text_document: TextDocumentIdentifier { uri },
+            positions,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let selections = self
+            .request_if_supported::<SelectionRangeRequest>(params)
+            .await?;
+        Ok(selections
+            .unwrap_or_default()
+            .iter()
+            .map(selection_range_chain)
+            .collect())
+    }
+}
@@ -0,0 +1,135 @@
+use lsp_types::request::{SemanticTokensFullRequest, SemanticTokensRangeRequest};
+use lsp_types::{
+    InitializeResult, Position, Range, SemanticToken, SemanticTokenModifier, SemanticTokenType,
+    SemanticTokensLegend, SemanticTokensParams, SemanticTokensRangeParams,
+    SemanticTokensRangeResult, SemanticTokensResult, SemanticTokensServerCapabilities,
+    TextDocumentIdentifier, Url,
+};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+
+/// A semantic token with its position made absolute and its type and modifiers looked up
+/// in the server's legend.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedSemanticToken {
+    pub range: Range,
+    pub token_type: SemanticTokenType,
+    pub modifiers: Vec<SemanticTokenModifier>,
+}
+
+/// Extracts the token legend the server advertised in its `InitializeResult`.
+pub fn semantic_tokens_legend(result: &InitializeResult) -> Option<SemanticTokensLegend> {
+    match result.capabilities.semantic_tokens_provider.as_ref()? {
+        SemanticTokensServerCapabilities::SemanticTokensOptions(options) => {
+            Some(options.legend.clone())
+        }
+        SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(options) => {
+            Some(options.semantic_tokens_options.legend.clone())
+        }
+    }
+}
+
+/// Decodes the relative, legend-indexed token stream of the specification.
+///
+/// Each token's line is relative to the previous token, and so is its start character when
+/// both are on the same line. Tokens whose type index is missing from the legend are
+/// dropped, as are modifier bits without a legend entry.
+pub fn decode_semantic_tokens(
+    tokens: &[SemanticToken],
+    legend: &SemanticTokensLegend,
+) -> Vec<DecodedSemanticToken> {
+    let mut decoded = Vec::with_capacity(tokens.len());
+    let mut line = 0;
+    let mut start = 0;
+    for token in tokens {
+        if token.delta_line == 0 {
+            start += token.delta_start;
+        } else {
+            line += token.delta_line;
+            start = token.delta_start;
+        }
+        let token_type = match legend.token_types.get(token.token_type as usize) {
+            Some(token_type) => token_type.clone(),
+            None => continue,
+        };
+        let modifiers = legend
+            .token_modifiers
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| *bit < 32 && token.token_modifiers_bitset & (1 << bit) != 0)
+            .map(|(_, modifier)| modifier.clone())
+            .collect();
+        decoded.push(DecodedSemanticToken {
+            range: Range {
+                start: Position {
+                    line,
+                    character: start,
+                },
+                end: Position {
+                    line,
+                    character: start + token.length,
+                },
+            },
+            token_type,
+            modifiers,
+        });
+    }
+    decoded
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Fetches and decodes the semantic tokens of a whole document.
+    pub async fn semantic_tokens_full(
+        &self,
+        uri: Url,
+    ) -> Result<Vec<DecodedSemanticToken>, ClientError> {
+        let legend = self.semantic_tokens_legend()?;
+        let params = SemanticTokensParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let data = match self
+            .request_if_supported::<SemanticTokensFullRequest>(params)
+            .await?
+        {
+            Some(SemanticTokensResult::Tokens(tokens)) => tokens.data,
+            Some(SemanticTokensResult::Partial(partial)) => partial.data,
+            None => Vec::new(),
+        };
+        Ok(decode_semantic_tokens(&data, &legend))
+    }
+
+    /// Fetches and decodes the semantic tokens within `range`.
+    pub async fn semantic_tokens_range(
+        &self,
+        uri: Url,
+        range: Range,
+    ) -> Result<Vec<DecodedSemanticToken>, ClientError> {
+        let legend = self.semantic_tokens_legend()?;
+        let params = SemanticTokensRangeParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let data = match self
+            .request_if_supported::<SemanticTokensRangeRequest>(params)
+            .await?
+        {
+            Some(SemanticTokensRangeResult::Tokens(tokens)) => tokens.data,
+            Some(SemanticTokensRangeResult::Partial(partial)) => partial.data,
+            None => Vec::new(),
+        };
+        Ok(decode_semantic_tokens(&data, &legend))
+    }
+
+    fn semantic_tokens_legend(&self) -> Result<SemanticTokensLegend, ClientError> {
+        self.capabilities()
+            .semantic_tokens_legend()
+            .ok_or(ClientError::Unsupported("textDocument/semanticTokens"))
+    }
+}
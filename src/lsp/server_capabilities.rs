@@ -0,0 +1,423 @@
+use lsp_types::request::{
+    CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare,
+    CodeActionRequest, CodeActionResolveRequest, CodeLensRequest, CodeLensResolve,
+    ColorPresentationRequest, Completion, DocumentColor, DocumentDiagnosticRequest,
+    DocumentHighlightRequest, DocumentLinkRequest, DocumentLinkResolve, DocumentSymbolRequest,
+    ExecuteCommand, FoldingRangeRequest, Formatting, GotoDeclaration, GotoDefinition,
+    GotoImplementation, GotoTypeDefinition, HoverRequest, InlayHintRequest,
+    InlayHintResolveRequest, InlineValueRequest, LinkedEditingRange, MonikerRequest,
+    OnTypeFormatting, PrepareRenameRequest, RangeFormatting, References, Rename, Request,
+    ResolveCompletionItem, SelectionRangeRequest, SemanticTokensFullRequest,
+    SemanticTokensRangeRequest, SignatureHelpRequest, WillCreateFiles, WillDeleteFiles,
+    WillRenameFiles, WorkspaceDiagnosticRequest, WorkspaceSymbolRequest, WorkspaceSymbolResolve,
+};
+use lsp_types::{
+    CallHierarchyServerCapability, CodeActionProviderCapability, ColorProviderCapability,
+    DeclarationCapability, DiagnosticServerCapabilities, FoldingRangeProviderCapability,
+    HoverProviderCapability, ImplementationProviderCapability, InlayHintServerCapabilities,
+    LinkedEditingRangeServerCapabilities, OneOf, Registration, SelectionRangeProviderCapability,
+    SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions,
+    SemanticTokensServerCapabilities, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TypeDefinitionProviderCapability,
+};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::text_utils::PositionEncoding;
+
+/// Requests whose support the server announces through a boolean option of another method,
+/// e.g. `codeLens/resolve` through the `resolveProvider` of `textDocument/codeLens`. Used
+/// for dynamic registrations, which carry those options in their `registerOptions`.
+const OPTION_METHODS: &[(&str, &str, &str)] = &[
+    (
+        ResolveCompletionItem::METHOD,
+        Completion::METHOD,
+        "resolveProvider",
+    ),
+    (
+        CodeActionResolveRequest::METHOD,
+        CodeActionRequest::METHOD,
+        "resolveProvider",
+    ),
+    (
+        CodeLensResolve::METHOD,
+        CodeLensRequest::METHOD,
+        "resolveProvider",
+    ),
+    (
+        DocumentLinkResolve::METHOD,
+        DocumentLinkRequest::METHOD,
+        "resolveProvider",
+    ),
+    (
+        InlayHintResolveRequest::METHOD,
+        InlayHintRequest::METHOD,
+        "resolveProvider",
+    ),
+    (
+        WorkspaceSymbolResolve::METHOD,
+        WorkspaceSymbolRequest::METHOD,
+        "resolveProvider",
+    ),
+    (
+        PrepareRenameRequest::METHOD,
+        Rename::METHOD,
+        "prepareProvider",
+    ),
+];
+
+/// The requests a server capability covers, in the order `Capabilities::supported_methods`
+/// lists them.
+const CAPABILITY_METHODS: &[&str] = &[
+    Completion::METHOD,
+    ResolveCompletionItem::METHOD,
+    HoverRequest::METHOD,
+    SignatureHelpRequest::METHOD,
+    GotoDefinition::METHOD,
+    GotoDeclaration::METHOD,
+    GotoTypeDefinition::METHOD,
+    GotoImplementation::METHOD,
+    References::METHOD,
+    DocumentHighlightRequest::METHOD,
+    DocumentSymbolRequest::METHOD,
+    WorkspaceSymbolRequest::METHOD,
+    WorkspaceSymbolResolve::METHOD,
+    CodeActionRequest::METHOD,
+    CodeActionResolveRequest::METHOD,
+    CodeLensRequest::METHOD,
+    CodeLensResolve::METHOD,
+    Formatting::METHOD,
+    RangeFormatting::METHOD,
+    OnTypeFormatting::METHOD,
+    Rename::METHOD,
+    PrepareRenameRequest::METHOD,
+    DocumentLinkRequest::METHOD,
+    DocumentLinkResolve::METHOD,
+    DocumentColor::METHOD,
+    ColorPresentationRequest::METHOD,
+    FoldingRangeRequest::METHOD,
+    SelectionRangeRequest::METHOD,
+    CallHierarchyPrepare::METHOD,
+    CallHierarchyIncomingCalls::METHOD,
+    CallHierarchyOutgoingCalls::METHOD,
+    SemanticTokensFullRequest::METHOD,
+    SemanticTokensRangeRequest::METHOD,
+    MonikerRequest::METHOD,
+    LinkedEditingRange::METHOD,
+    InlineValueRequest::METHOD,
+    InlayHintRequest::METHOD,
+    InlayHintResolveRequest::METHOD,
+    DocumentDiagnosticRequest::METHOD,
+    WorkspaceDiagnosticRequest::METHOD,
+    ExecuteCommand::METHOD,
+    WillCreateFiles::METHOD,
+    WillRenameFiles::METHOD,
+    WillDeleteFiles::METHOD,
+];
+
+/// The method semantic tokens are registered under dynamically, covering all its requests.
+const SEMANTIC_TOKENS: &str = "textDocument/semanticTokens";
+
+/// The capabilities the server announced in its `InitializeResult`, together with those it
+/// registered dynamically since, see `LanguageServerRef::capabilities`.
+#[derive(Debug, Clone, Default)]
+pub struct Capabilities {
+    announced: ServerCapabilities,
+    registrations: Vec<Registration>,
+}
+
+fn one_of<T>(provider: &Option<OneOf<bool, T>>) -> bool {
+    !matches!(provider, None | Some(OneOf::Left(false)))
+}
+
+impl Capabilities {
+    pub fn new(announced: ServerCapabilities, registrations: Vec<Registration>) -> Self {
+        Capabilities {
+            announced,
+            registrations,
+        }
+    }
+
+    /// The capabilities from the `InitializeResult`.
+    pub fn announced(&self) -> &ServerCapabilities {
+        &self.announced
+    }
+
+    /// The capabilities registered through `client/registerCapability` and not unregistered
+    /// since.
+    pub fn registrations(&self) -> &[Registration] {
+        &self.registrations
+    }
+
+    fn registration(&self, method: &str) -> Option<&Registration> {
+        self.registrations
+            .iter()
+            .find(|registration| registration.method == method)
+    }
+
+    /// Whether the server supports the request or notification `method`, either from the
+    /// start or through a dynamic registration. Methods without a server capability, such
+    /// as `shutdown` or extensions, are assumed to be supported.
+    pub fn supports(&self, method: &str) -> bool {
+        self.registered(method) || self.announces(method).unwrap_or(true)
+    }
+
+    fn registered(&self, method: &str) -> bool {
+        if self.registration(method).is_some() {
+            return true;
+        }
+        if method == SemanticTokensFullRequest::METHOD
+            || method == SemanticTokensRangeRequest::METHOD
+        {
+            return self.registration(SEMANTIC_TOKENS).is_some();
+        }
+        OPTION_METHODS
+            .iter()
+            .filter(|(option_method, _, _)| *option_method == method)
+            .filter_map(|(_, parent, option)| {
+                self.registration(parent)?
+                    .register_options
+                    .as_ref()?
+                    .get(option)?
+                    .as_bool()
+            })
+            .any(|enabled| enabled)
+    }
+
+    /// Whether the `InitializeResult` announced `method`, or `None` if no capability covers
+    /// it.
+    fn announces(&self, method: &str) -> Option<bool> {
+        let caps = &self.announced;
+        let file_operations = caps
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.file_operations.as_ref());
+        let supported = match method {
+            Completion::METHOD => caps.completion_provider.is_some(),
+            ResolveCompletionItem::METHOD => caps
+                .completion_provider
+                .as_ref()
+                .and_then(|options| options.resolve_provider)
+                .unwrap_or(false),
+            HoverRequest::METHOD => !matches!(
+                caps.hover_provider,
+                None | Some(HoverProviderCapability::Simple(false))
+            ),
+            SignatureHelpRequest::METHOD => caps.signature_help_provider.is_some(),
+            GotoDefinition::METHOD => one_of(&caps.definition_provider),
+            GotoDeclaration::METHOD => !matches!(
+                caps.declaration_provider,
+                None | Some(DeclarationCapability::Simple(false))
+            ),
+            GotoTypeDefinition::METHOD => !matches!(
+                caps.type_definition_provider,
+                None | Some(TypeDefinitionProviderCapability::Simple(false))
+            ),
+            GotoImplementation::METHOD => !matches!(
+                caps.implementation_provider,
+                None | Some(ImplementationProviderCapability::Simple(false))
+            ),
+            References::METHOD => one_of(&caps.references_provider),
+            DocumentHighlightRequest::METHOD => one_of(&caps.document_highlight_provider),
+            DocumentSymbolRequest::METHOD => one_of(&caps.document_symbol_provider),
+            WorkspaceSymbolRequest::METHOD => one_of(&caps.workspace_symbol_provider),
+            WorkspaceSymbolResolve::METHOD => matches!(
+                &caps.workspace_symbol_provider,
+                Some(OneOf::Right(options)) if options.resolve_provider == Some(true)
+            ),
+            CodeActionRequest::METHOD => !matches!(
+                caps.code_action_provider,
+                None | Some(CodeActionProviderCapability::Simple(false))
+            ),
+            CodeActionResolveRequest::METHOD => matches!(
+                &caps.code_action_provider,
+                Some(CodeActionProviderCapability::Options(options))
+                    if options.resolve_provider == Some(true)
+            ),
+            CodeLensRequest::METHOD => caps.code_lens_provider.is_some(),
+            CodeLensResolve::METHOD => caps
+                .code_lens_provider
+                .as_ref()
+                .and_then(|options| options.resolve_provider)
+                .unwrap_or(false),
+            Formatting::METHOD => one_of(&caps.document_formatting_provider),
+            RangeFormatting::METHOD => one_of(&caps.document_range_formatting_provider),
+            OnTypeFormatting::METHOD => caps.document_on_type_formatting_provider.is_some(),
+            Rename::METHOD => one_of(&caps.rename_provider),
+            PrepareRenameRequest::METHOD => matches!(
+                &caps.rename_provider,
+                Some(OneOf::Right(options)) if options.prepare_provider == Some(true)
+            ),
+            DocumentLinkRequest::METHOD => caps.document_link_provider.is_some(),
+            DocumentLinkResolve::METHOD => caps
+                .document_link_provider
+                .as_ref()
+                .and_then(|options| options.resolve_provider)
+                .unwrap_or(false),
+            DocumentColor::METHOD | ColorPresentationRequest::METHOD => !matches!(
+                caps.color_provider,
+                None | Some(ColorProviderCapability::Simple(false))
+            ),
+            FoldingRangeRequest::METHOD => !matches!(
+                caps.folding_range_provider,
+                None | Some(FoldingRangeProviderCapability::Simple(false))
+            ),
+            SelectionRangeRequest::METHOD => !matches!(
+                caps.selection_range_provider,
+                None | Some(SelectionRangeProviderCapability::Simple(false))
+            ),
+            CallHierarchyPrepare::METHOD
+            | CallHierarchyIncomingCalls::METHOD
+            | CallHierarchyOutgoingCalls::METHOD => !matches!(
+                caps.call_hierarchy_provider,
+                None | Some(CallHierarchyServerCapability::Simple(false))
+            ),
+            SemanticTokensFullRequest::METHOD => matches!(
+                self.semantic_tokens_options()
+                    .and_then(|options| options.full.as_ref()),
+                Some(
+                    SemanticTokensFullOptions::Bool(true) | SemanticTokensFullOptions::Delta { .. }
+                )
+            ),
+            SemanticTokensRangeRequest::METHOD => self
+                .semantic_tokens_options()
+                .and_then(|options| options.range)
+                .unwrap_or(false),
+            MonikerRequest::METHOD => one_of(&caps.moniker_provider),
+            LinkedEditingRange::METHOD => !matches!(
+                caps.linked_editing_range_provider,
+                None | Some(LinkedEditingRangeServerCapabilities::Simple(false))
+            ),
+            InlineValueRequest::METHOD => one_of(&caps.inline_value_provider),
+            InlayHintRequest::METHOD => one_of(&caps.inlay_hint_provider),
+            InlayHintResolveRequest::METHOD => {
+                let options = match &caps.inlay_hint_provider {
+                    Some(OneOf::Right(InlayHintServerCapabilities::Options(options))) => options,
+                    Some(OneOf::Right(InlayHintServerCapabilities::RegistrationOptions(
+                        options,
+                    ))) => &options.inlay_hint_options,
+                    _ => return Some(false),
+                };
+                options.resolve_provider == Some(true)
+            }
+            DocumentDiagnosticRequest::METHOD => caps.diagnostic_provider.is_some(),
+            WorkspaceDiagnosticRequest::METHOD => {
+                let options = match &caps.diagnostic_provider {
+                    Some(DiagnosticServerCapabilities::Options(options)) => options,
+                    Some(DiagnosticServerCapabilities::RegistrationOptions(options)) => {
+                        &options.diagnostic_options
+                    }
+                    None => return Some(false),
+                };
+                options.workspace_diagnostics
+            }
+            ExecuteCommand::METHOD => caps.execute_command_provider.is_some(),
+            WillCreateFiles::METHOD => file_operations.is_some_and(|ops| ops.will_create.is_some()),
+            WillRenameFiles::METHOD => file_operations.is_some_and(|ops| ops.will_rename.is_some()),
+            WillDeleteFiles::METHOD => file_operations.is_some_and(|ops| ops.will_delete.is_some()),
+            _ => return None,
+        };
+        Some(supported)
+    }
+
+    fn semantic_tokens_options(&self) -> Option<&SemanticTokensOptions> {
+        match self.announced.semantic_tokens_provider.as_ref()? {
+            SemanticTokensServerCapabilities::SemanticTokensOptions(options) => Some(options),
+            SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(options) => {
+                Some(&options.semantic_tokens_options)
+            }
+        }
+    }
+
+    /// The requests covered by a server capability that the server supports.
+    pub fn supported_methods(&self) -> Vec<&'static str> {
+        CAPABILITY_METHODS
+            .iter()
+            .copied()
+            .filter(|method| self.supports(method))
+            .collect()
+    }
+
+    pub fn supports_hover(&self) -> bool {
+        self.supports(HoverRequest::METHOD)
+    }
+
+    pub fn supports_completion(&self) -> bool {
+        self.supports(Completion::METHOD)
+    }
+
+    pub fn supports_rename(&self) -> bool {
+        self.supports(Rename::METHOD)
+    }
+
+    pub fn supports_prepare_rename(&self) -> bool {
+        self.supports(PrepareRenameRequest::METHOD)
+    }
+
+    /// The token legend of semantic tokens, from the `InitializeResult` or a dynamic
+    /// registration.
+    pub fn semantic_tokens_legend(&self) -> Option<SemanticTokensLegend> {
+        if let Some(options) = self.semantic_tokens_options() {
+            return Some(options.legend.clone());
+        }
+        let options = self
+            .registration(SEMANTIC_TOKENS)?
+            .register_options
+            .clone()?;
+        let options: SemanticTokensOptions = serde_json::from_value(options).ok()?;
+        Some(options.legend)
+    }
+
+    /// How the server wants `textDocument/didChange` to describe changes. Servers that do
+    /// not say are not synchronized at all.
+    pub fn text_document_sync_kind(&self) -> TextDocumentSyncKind {
+        match &self.announced.text_document_sync {
+            Some(TextDocumentSyncCapability::Kind(kind)) => *kind,
+            Some(TextDocumentSyncCapability::Options(options)) => {
+                options.change.unwrap_or(TextDocumentSyncKind::NONE)
+            }
+            None => TextDocumentSyncKind::NONE,
+        }
+    }
+
+    /// The encoding of the `character` of positions.
+    pub fn position_encoding(&self) -> PositionEncoding {
+        self.announced
+            .position_encoding
+            .as_ref()
+            .map(PositionEncoding::from_kind)
+            .unwrap_or_default()
+    }
+
+    /// The commands `workspace/executeCommand` accepts.
+    pub fn commands(&self) -> Vec<String> {
+        let announced = self
+            .announced
+            .execute_command_provider
+            .iter()
+            .flat_map(|options| options.commands.iter().cloned());
+        let registered = self
+            .registrations
+            .iter()
+            .filter(|registration| registration.method == ExecuteCommand::METHOD)
+            .filter_map(|registration| registration.register_options.as_ref()?.get("commands"))
+            .filter_map(|commands| commands.as_array())
+            .flatten()
+            .filter_map(|command| command.as_str().map(str::to_owned));
+        announced.chain(registered).collect()
+    }
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Like `request`, but fails with `ClientError::Unsupported` without contacting the
+    /// server when it did not announce or register support for `R`.
+    pub async fn request_if_supported<R>(&self, params: R::Params) -> Result<R::Result, ClientError>
+    where
+        R: Request,
+    {
+        if !self.capabilities().supports(R::METHOD) {
+            return Err(ClientError::Unsupported(R::METHOD));
+        }
+        self.request::<R>(params).await
+    }
+}
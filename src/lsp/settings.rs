@@ -0,0 +1,112 @@
+use std::sync::{Arc, Mutex};
+
+use jsonrpc_lite::Error;
+use lsp_types::notification::DidChangeConfiguration;
+use lsp_types::request::{Request, WorkspaceConfiguration};
+use lsp_types::{ConfigurationParams, DidChangeConfigurationParams};
+use serde_json::{Map, Value};
+use tokio::io::AsyncWriteExt;
+
+use super::client::LanguageServerRef;
+
+/// Looks up a dotted `section` such as `"rust-analyzer.cargo"` in a settings tree.
+/// An empty section names the whole tree.
+pub fn settings_section<'a>(tree: &'a Value, section: &str) -> Option<&'a Value> {
+    section
+        .split('.')
+        .filter(|key| !key.is_empty())
+        .try_fold(tree, |value, key| value.get(key))
+}
+
+/// Stores `value` at the dotted `section` of a settings tree, creating intermediate objects
+/// and replacing non-object values in the way.
+fn set_settings_section(tree: &mut Value, section: &str, value: Value) {
+    let mut slot = tree;
+    for key in section.split('.').filter(|key| !key.is_empty()) {
+        if !slot.is_object() {
+            *slot = Value::Object(Map::new());
+        }
+        slot = slot
+            .as_object_mut()
+            .unwrap()
+            .entry(key)
+            .or_insert(Value::Null);
+    }
+    *slot = value;
+}
+
+/// The client's settings, kept as a single JSON tree.
+///
+/// The tree is pushed to the server with `workspace/didChangeConfiguration` whenever it
+/// changes, and the same tree answers the server's `workspace/configuration` requests, so
+/// both mechanisms always agree. Sections are dotted paths into the tree.
+pub struct Settings<W: AsyncWriteExt> {
+    server: LanguageServerRef<W>,
+    tree: Arc<Mutex<Value>>,
+}
+
+impl<W: AsyncWriteExt + Unpin> Settings<W> {
+    pub fn new(server: LanguageServerRef<W>, tree: Value) -> Self {
+        let tree = Arc::new(Mutex::new(tree));
+        {
+            let tree = tree.clone();
+            server.on_request(WorkspaceConfiguration::METHOD, move |params| {
+                let params: ConfigurationParams =
+                    serde_json::from_value(params).map_err(|_| Error::invalid_params())?;
+                let tree = tree.lock().unwrap();
+                let values = params
+                    .items
+                    .iter()
+                    .map(|item| {
+                        let section = item.section.as_deref().unwrap_or_default();
+                        settings_section(&tree, section)
+                            .cloned()
+                            .unwrap_or(Value::Null)
+                    })
+                    .collect();
+                Ok(Value::Array(values))
+            });
+        }
+        Settings { server, tree }
+    }
+
+    /// Returns a copy of the whole settings tree.
+    pub fn tree(&self) -> Value {
+        self.tree.lock().unwrap().clone()
+    }
+
+    /// Returns a copy of one section of the settings tree.
+    pub fn get(&self, section: &str) -> Option<Value> {
+        settings_section(&self.tree.lock().unwrap(), section).cloned()
+    }
+
+    /// Replaces the whole settings tree and notifies the server.
+    pub async fn set(&self, tree: Value) {
+        *self.tree.lock().unwrap() = tree;
+        self.push().await;
+    }
+
+    /// Replaces one section of the settings tree and notifies the server.
+    pub async fn update(&self, section: &str, value: Value) {
+        set_settings_section(&mut self.tree.lock().unwrap(), section, value);
+        self.push().await;
+    }
+
+    /// Sends the current tree through `workspace/didChangeConfiguration`. Servers that
+    /// pull their configuration treat this as a cue to send `workspace/configuration`.
+    pub async fn push(&self) {
+        let settings = self.tree();
+        self.server
+            .notify::<DidChangeConfiguration>(DidChangeConfigurationParams { settings })
+            .await;
+    }
+}
+
+impl<W: AsyncWriteExt> Clone for Settings<W> {
+    fn clone(&self) -> Self {
+        Settings {
+            server: self.server.clone(),
+            tree: self.tree.clone(),
+        }
+    }
+}
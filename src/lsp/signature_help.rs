@@ -0,0 +1,47 @@
+use lsp_types::request::SignatureHelpRequest;
+use lsp_types::{
+    Position, SignatureHelp, SignatureHelpContext, SignatureHelpParams, SignatureHelpTriggerKind,
+    Url,
+};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::navigation::text_document_position;
+
+/// Builds the context for asking again while signature help is already showing, e.g.
+/// after the user typed a `,`. Passing the previous answer lets the server keep the
+/// signature the user selected active.
+pub fn retrigger_context(
+    active_signature_help: SignatureHelp,
+    trigger_character: Option<String>,
+) -> SignatureHelpContext {
+    let trigger_kind = match trigger_character {
+        Some(_) => SignatureHelpTriggerKind::TRIGGER_CHARACTER,
+        None => SignatureHelpTriggerKind::CONTENT_CHANGE,
+    };
+    SignatureHelpContext {
+        trigger_kind,
+        trigger_character,
+        is_retrigger: true,
+        active_signature_help: Some(active_signature_help),
+    }
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Requests the signatures of the call surrounding `position`. `context` may be left
+    /// out for a one-off invocation.
+    pub async fn signature_help(
+        &self,
+        uri: Url,
+        position: Position,
+        context: Option<SignatureHelpContext>,
+    ) -> Result<Option<SignatureHelp>, ClientError> {
+        let params = SignatureHelpParams {
+            context,
+            text_document_position_params: text_document_position(uri, position),
+            work_done_progress_params: Default::default(),
+        };
+        self.request_if_supported::<SignatureHelpRequest>(params)
+            .await
+    }
+}
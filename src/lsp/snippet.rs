@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use lsp_types::{InsertTextFormat, TextEdit};
+
+/// A piece of a parsed snippet, see the "Snippet Syntax" section of the specification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnippetElement {
+    Text(String),
+    /// `$1`, `${1}` or `${1:placeholder}`. `$0` marks the final cursor position.
+    Tabstop {
+        index: u32,
+        placeholder: Vec<SnippetElement>,
+    },
+    /// `${1|one,two,three|}`
+    Choice {
+        index: u32,
+        options: Vec<String>,
+    },
+    /// `$TM_FILENAME`, `${TM_FILENAME}` or `${TM_FILENAME:default}`. Transforms are parsed
+    /// but not applied.
+    Variable {
+        name: String,
+        default: Vec<SnippetElement>,
+    },
+}
+
+/// A snippet as sent in completion items and edits with `InsertTextFormat::SNIPPET`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Snippet {
+    pub elements: Vec<SnippetElement>,
+}
+
+/// The places the cursor visits for one tabstop index, as byte ranges into the rendered
+/// text. Tabstops sharing an index are mirrors and are edited together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnippetTabstop {
+    pub index: u32,
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// A snippet rendered to plain text.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RenderedSnippet {
+    pub text: String,
+    /// In visiting order: ascending index, with `$0` last.
+    pub tabstops: Vec<SnippetTabstop>,
+}
+
+impl RenderedSnippet {
+    /// Where the cursor ends up after the snippet is inserted: at `$0`, or at the end of
+    /// the text if the snippet has none.
+    pub fn final_offset(&self) -> usize {
+        self.tabstops
+            .iter()
+            .find(|tabstop| tabstop.index == 0)
+            .and_then(|tabstop| tabstop.ranges.first())
+            .map(|range| range.start)
+            .unwrap_or(self.text.len())
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parses elements until the end of input or, when `nested`, an unescaped `}`.
+    fn elements(&mut self, nested: bool) -> Vec<SnippetElement> {
+        let mut elements = Vec::new();
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '}' if nested => break,
+                '\\' if matches!(self.chars.get(self.pos + 1), Some('$' | '}' | '\\')) => {
+                    text.push(self.chars[self.pos + 1]);
+                    self.pos += 2;
+                }
+                '$' => {
+                    let start = self.pos;
+                    match self.dollar() {
+                        Some(element) => {
+                            if !text.is_empty() {
+                                elements.push(SnippetElement::Text(std::mem::take(&mut text)));
+                            }
+                            elements.push(element);
+                        }
+                        // not a valid construct, keep the `$` as text
+                        None => {
+                            self.pos = start + 1;
+                            text.push('$');
+                        }
+                    }
+                }
+                _ => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        if !text.is_empty() {
+            elements.push(SnippetElement::Text(text));
+        }
+        elements
+    }
+
+    fn int(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    fn var(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(c) if c == '_' || c.is_ascii_alphabetic() => {}
+            _ => return None,
+        }
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c == '_' || c.is_ascii_alphanumeric())
+        {
+            self.pos += 1;
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    /// Parses the construct starting at a `$`, returning `None` if it is malformed.
+    fn dollar(&mut self) -> Option<SnippetElement> {
+        self.pos += 1;
+        if !self.eat('{') {
+            if let Some(index) = self.int() {
+                return Some(SnippetElement::Tabstop {
+                    index,
+                    placeholder: Vec::new(),
+                });
+            }
+            let name = self.var()?;
+            return Some(SnippetElement::Variable {
+                name,
+                default: Vec::new(),
+            });
+        }
+
+        if let Some(index) = self.int() {
+            let placeholder = match self.peek()? {
+                '}' => Vec::new(),
+                ':' => {
+                    self.pos += 1;
+                    self.elements(true)
+                }
+                '|' => {
+                    let options = self.choice()?;
+                    return Some(SnippetElement::Choice { index, options });
+                }
+                '/' => {
+                    self.transform()?;
+                    Vec::new()
+                }
+                _ => return None,
+            };
+            if !self.eat('}') {
+                return None;
+            }
+            return Some(SnippetElement::Tabstop { index, placeholder });
+        }
+
+        let name = self.var()?;
+        let default = match self.peek()? {
+            '}' => Vec::new(),
+            ':' => {
+                self.pos += 1;
+                self.elements(true)
+            }
+            '/' => {
+                self.transform()?;
+                Vec::new()
+            }
+            _ => return None,
+        };
+        if !self.eat('}') {
+            return None;
+        }
+        Some(SnippetElement::Variable { name, default })
+    }
+
+    /// Parses `|one,two|}` after a choice's index, consuming the closing brace.
+    fn choice(&mut self) -> Option<Vec<String>> {
+        self.pos += 1;
+        let mut options = Vec::new();
+        let mut option = String::new();
+        loop {
+            match self.peek()? {
+                '\\' if matches!(self.chars.get(self.pos + 1), Some(',' | '|' | '\\')) => {
+                    option.push(self.chars[self.pos + 1]);
+                    self.pos += 2;
+                }
+                ',' => {
+                    options.push(std::mem::take(&mut option));
+                    self.pos += 1;
+                }
+                '|' => {
+                    self.pos += 1;
+                    if !self.eat('}') {
+                        return None;
+                    }
+                    options.push(option);
+                    return Some(options);
+                }
+                c => {
+                    option.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    /// Skips a `/regex/format/options` transform, leaving the closing brace.
+    fn transform(&mut self) -> Option<()> {
+        self.pos += 1;
+        for _ in 0..2 {
+            loop {
+                match self.peek()? {
+                    '\\' => self.pos += 2,
+                    '/' => break,
+                    _ => self.pos += 1,
+                }
+            }
+            self.pos += 1;
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.pos += 1;
+        }
+        Some(())
+    }
+}
+
+impl Snippet {
+    /// Parses snippet syntax. Parsing never fails: malformed constructs are kept as text.
+    pub fn parse(snippet: &str) -> Snippet {
+        let mut parser = Parser {
+            chars: snippet.chars().collect(),
+            pos: 0,
+        };
+        Snippet {
+            elements: parser.elements(false),
+        }
+    }
+
+    /// Renders the snippet to plain text. `variable` resolves variables such as
+    /// `TM_FILENAME`; unknown variables render as their default, or their name if they
+    /// have none. Choices render as their first option, and tabstops without a placeholder
+    /// mirror the placeholder given elsewhere for the same index.
+    pub fn render(&self, variable: impl Fn(&str) -> Option<String>) -> RenderedSnippet {
+        let mut placeholders = HashMap::new();
+        collect_placeholders(&self.elements, &mut placeholders);
+        let mut rendered = RenderedSnippet::default();
+        let mut tabstops: HashMap<u32, Vec<Range<usize>>> = HashMap::new();
+        render_elements(
+            &self.elements,
+            &variable,
+            &placeholders,
+            &mut rendered.text,
+            &mut tabstops,
+        );
+        rendered.tabstops = tabstops
+            .into_iter()
+            .map(|(index, ranges)| SnippetTabstop { index, ranges })
+            .collect();
+        rendered
+            .tabstops
+            .sort_by_key(|tabstop| (tabstop.index == 0, tabstop.index));
+        rendered
+    }
+
+    /// Renders the snippet to plain text, without resolving any variables.
+    pub fn to_plain_text(&self) -> String {
+        self.render(|_| None).text
+    }
+}
+
+fn collect_placeholders<'a>(
+    elements: &'a [SnippetElement],
+    placeholders: &mut HashMap<u32, &'a [SnippetElement]>,
+) {
+    for element in elements {
+        match element {
+            SnippetElement::Tabstop { index, placeholder } if !placeholder.is_empty() => {
+                placeholders.entry(*index).or_insert(placeholder);
+                collect_placeholders(placeholder, placeholders);
+            }
+            SnippetElement::Variable { default, .. } => collect_placeholders(default, placeholders),
+            _ => {}
+        }
+    }
+}
+
+fn render_elements(
+    elements: &[SnippetElement],
+    variable: &dyn Fn(&str) -> Option<String>,
+    placeholders: &HashMap<u32, &[SnippetElement]>,
+    text: &mut String,
+    tabstops: &mut HashMap<u32, Vec<Range<usize>>>,
+) {
+    for element in elements {
+        match element {
+            SnippetElement::Text(value) => text.push_str(value),
+            SnippetElement::Tabstop { index, placeholder } => {
+                let start = text.len();
+                if placeholder.is_empty() {
+                    // mirrors do not mirror further, placeholders may contain their own index
+                    let mirrored = placeholders.get(index).copied().unwrap_or_default();
+                    render_elements(mirrored, variable, &HashMap::new(), text, tabstops);
+                } else {
+                    render_elements(placeholder, variable, placeholders, text, tabstops);
+                }
+                tabstops.entry(*index).or_default().push(start..text.len());
+            }
+            SnippetElement::Choice { index, options } => {
+                let start = text.len();
+                text.push_str(options.first().map(String::as_str).unwrap_or_default());
+                tabstops.entry(*index).or_default().push(start..text.len());
+            }
+            SnippetElement::Variable { name, default } => match variable(name) {
+                Some(value) => text.push_str(&value),
+                None if !default.is_empty() => {
+                    render_elements(default, variable, placeholders, text, tabstops)
+                }
+                None => text.push_str(name),
+            },
+        }
+    }
+}
+
+/// Returns `edit` with its new text rendered to plain text if `format` says it is a
+/// snippet, for clients that insert text without interactive tabstops.
+pub fn plain_text_edit(edit: &TextEdit, format: Option<InsertTextFormat>) -> TextEdit {
+    match format {
+        Some(InsertTextFormat::SNIPPET) => TextEdit {
+            range: edit.range,
+            new_text: Snippet::parse(&edit.new_text).to_plain_text(),
+        },
+        _ => edit.clone(),
+    }
+}
@@ -0,0 +1,317 @@
+use futures::future::join_all;
+use lsp_types::request::{
+    DocumentSymbolRequest, Request, WorkspaceSymbolRequest, WorkspaceSymbolResolve,
+};
+use lsp_types::{
+    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, OneOf, Position, Range,
+    SymbolInformation, SymbolKind, SymbolTag, TextDocumentIdentifier, Url, WorkspaceSymbol,
+    WorkspaceSymbolParams, WorkspaceSymbolResponse,
+};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::log;
+
+/// A document symbol together with the symbols nested inside it.
+///
+/// Servers answer `textDocument/documentSymbol` either with a nested `DocumentSymbol[]`
+/// or a flat `SymbolInformation[]`; both are normalized into this tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentSymbolNode {
+    pub name: String,
+    pub detail: Option<String>,
+    pub kind: SymbolKind,
+    pub tags: Vec<SymbolTag>,
+    /// The full extent of the symbol, including its body.
+    pub range: Range,
+    /// The range that should be selected when navigating to the symbol, e.g. its name.
+    pub selection_range: Range,
+    pub children: Vec<DocumentSymbolNode>,
+}
+
+impl DocumentSymbolNode {
+    /// Returns this node and all of its descendants in depth-first, pre-order.
+    pub fn descendants(&self) -> Vec<&DocumentSymbolNode> {
+        let mut nodes = vec![self];
+        for child in &self.children {
+            nodes.extend(child.descendants());
+        }
+        nodes
+    }
+}
+
+impl From<DocumentSymbol> for DocumentSymbolNode {
+    fn from(symbol: DocumentSymbol) -> Self {
+        DocumentSymbolNode {
+            name: symbol.name,
+            detail: symbol.detail,
+            kind: symbol.kind,
+            tags: symbol.tags.unwrap_or_default(),
+            range: symbol.range,
+            selection_range: symbol.selection_range,
+            children: symbol
+                .children
+                .unwrap_or_default()
+                .into_iter()
+                .map(DocumentSymbolNode::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<SymbolInformation> for DocumentSymbolNode {
+    fn from(symbol: SymbolInformation) -> Self {
+        DocumentSymbolNode {
+            name: symbol.name,
+            detail: None,
+            kind: symbol.kind,
+            tags: symbol.tags.unwrap_or_default(),
+            range: symbol.location.range,
+            selection_range: symbol.location.range,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// The innermost symbol enclosing a position, see `LanguageServerRef::symbol_at`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolAtPoint {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The names of the symbols containing this one, outermost first.
+    pub container_path: Vec<String>,
+    pub range: Range,
+    pub selection_range: Range,
+}
+
+/// Returns the symbols whose range contains `position`, outermost first. Siblings do not
+/// overlap, so at most one symbol per level matches.
+pub fn enclosing_symbols(
+    roots: &[DocumentSymbolNode],
+    position: Position,
+) -> Vec<&DocumentSymbolNode> {
+    let point = Range::new(position, position);
+    let mut chain = Vec::new();
+    let mut level = roots;
+    while let Some(node) = level
+        .iter()
+        .find(|node| range_contains(&node.range, &point))
+    {
+        chain.push(node);
+        level = &node.children;
+    }
+    chain
+}
+
+/// Describes the innermost symbol enclosing `position`, if any.
+pub fn symbol_at_point(roots: &[DocumentSymbolNode], position: Position) -> Option<SymbolAtPoint> {
+    let mut chain = enclosing_symbols(roots, position);
+    let symbol = chain.pop()?;
+    Some(SymbolAtPoint {
+        name: symbol.name.clone(),
+        kind: symbol.kind,
+        container_path: chain.iter().map(|node| node.name.clone()).collect(),
+        range: symbol.range,
+        selection_range: symbol.selection_range,
+    })
+}
+
+/// Returns true if a symbol called `name` is what a user means by `segment` of a path like
+/// `Shape.area`. Servers decorate names, e.g. `impl Display for Circle`, `area(int)` or
+/// `Vec<T>`, so only the last word of the name up to any parameters is compared.
+pub fn symbol_name_matches(name: &str, segment: &str) -> bool {
+    let word = name.split_whitespace().last().unwrap_or_default();
+    let bare = word.split(['(', '<']).next().unwrap_or_default();
+    name == segment || bare == segment
+}
+
+/// Returns the symbols named by `path`, the names of a symbol and its innermost containers
+/// such as `["Shape", "area"]`, in document order. The path may leave out outer containers,
+/// so `["area"]` finds every `area`.
+pub fn symbols_at_path<'a>(
+    roots: &'a [DocumentSymbolNode],
+    path: &[&str],
+) -> Vec<&'a DocumentSymbolNode> {
+    fn visit<'a>(
+        nodes: &'a [DocumentSymbolNode],
+        chain: &mut Vec<&'a str>,
+        path: &[&str],
+        found: &mut Vec<&'a DocumentSymbolNode>,
+    ) {
+        for node in nodes {
+            chain.push(&node.name);
+            if chain.len() >= path.len()
+                && chain[chain.len() - path.len()..]
+                    .iter()
+                    .zip(path)
+                    .all(|(name, segment)| symbol_name_matches(name, segment))
+            {
+                found.push(node);
+            }
+            visit(&node.children, chain, path, found);
+            chain.pop();
+        }
+    }
+
+    let mut found = Vec::new();
+    if !path.is_empty() {
+        visit(roots, &mut Vec::new(), path, &mut found);
+    }
+    found
+}
+
+/// Returns true if `inner` lies entirely within `outer`.
+pub(crate) fn range_contains(outer: &Range, inner: &Range) -> bool {
+    let start = |r: &Range| (r.start.line, r.start.character);
+    let end = |r: &Range| (r.end.line, r.end.character);
+    start(outer) <= start(inner) && end(inner) <= end(outer)
+}
+
+/// Converts a document symbol response of either shape into a symbol tree.
+pub fn normalize_document_symbols(response: DocumentSymbolResponse) -> Vec<DocumentSymbolNode> {
+    match response {
+        DocumentSymbolResponse::Nested(symbols) => {
+            symbols.into_iter().map(DocumentSymbolNode::from).collect()
+        }
+        DocumentSymbolResponse::Flat(symbols) => nest_flat_symbols(symbols),
+    }
+}
+
+/// Rebuilds the hierarchy of a flat symbol list from range containment. `containerName`
+/// is only a display hint and is ambiguous for overloads, so it is not relied upon.
+fn nest_flat_symbols(mut symbols: Vec<SymbolInformation>) -> Vec<DocumentSymbolNode> {
+    // outer symbols sort before the symbols they contain
+    symbols.sort_by(|a, b| {
+        let (a, b) = (a.location.range, b.location.range);
+        (a.start.line, a.start.character)
+            .cmp(&(b.start.line, b.start.character))
+            .then((b.end.line, b.end.character).cmp(&(a.end.line, a.end.character)))
+    });
+
+    let mut roots = Vec::new();
+    let mut stack: Vec<DocumentSymbolNode> = Vec::new();
+    for symbol in symbols {
+        let node = DocumentSymbolNode::from(symbol);
+        while let Some(top) = stack.last() {
+            if range_contains(&top.range, &node.range) {
+                break;
+            }
+            let finished = stack.pop().expect("stack to be non-empty");
+            attach_symbol(&mut stack, &mut roots, finished);
+        }
+        stack.push(node);
+    }
+    while let Some(finished) = stack.pop() {
+        attach_symbol(&mut stack, &mut roots, finished);
+    }
+    roots
+}
+
+fn attach_symbol(
+    stack: &mut [DocumentSymbolNode],
+    roots: &mut Vec<DocumentSymbolNode>,
+    node: DocumentSymbolNode,
+) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(node),
+        None => roots.push(node),
+    }
+}
+
+/// Converts a workspace symbol response of either shape into `WorkspaceSymbol`s.
+pub fn normalize_workspace_symbols(response: WorkspaceSymbolResponse) -> Vec<WorkspaceSymbol> {
+    match response {
+        WorkspaceSymbolResponse::Nested(symbols) => symbols,
+        WorkspaceSymbolResponse::Flat(symbols) => symbols
+            .into_iter()
+            .map(|symbol| WorkspaceSymbol {
+                name: symbol.name,
+                kind: symbol.kind,
+                tags: symbol.tags,
+                container_name: symbol.container_name,
+                location: OneOf::Left(symbol.location),
+                data: None,
+            })
+            .collect(),
+    }
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Requests the symbols of a document as a tree, whichever shape the server answers in.
+    pub async fn document_symbols(&self, uri: Url) -> Result<Vec<DocumentSymbolNode>, ClientError> {
+        let params = DocumentSymbolParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let response = self
+            .request_if_supported::<DocumentSymbolRequest>(params)
+            .await?;
+        Ok(response.map(normalize_document_symbols).unwrap_or_default())
+    }
+
+    /// Finds the innermost document symbol enclosing `position`, e.g. the method a line
+    /// belongs to, together with the names of the symbols containing it.
+    pub async fn symbol_at(
+        &self,
+        uri: Url,
+        position: Position,
+    ) -> Result<Option<SymbolAtPoint>, ClientError> {
+        let roots = self.document_symbols(uri).await?;
+        Ok(symbol_at_point(&roots, position))
+    }
+
+    /// Searches the workspace for symbols matching `query`.
+    ///
+    /// Servers may defer computing `location.range` and only return a uri; such symbols are
+    /// resolved through `workspaceSymbol/resolve`, all at once, if the server supports it.
+    /// Symbols the server fails to resolve are returned as they were received.
+    pub async fn workspace_symbols(
+        &self,
+        query: &str,
+    ) -> Result<Vec<WorkspaceSymbol>, ClientError> {
+        let params = WorkspaceSymbolParams {
+            query: query.to_owned(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let response = self
+            .request_if_supported::<WorkspaceSymbolRequest>(params)
+            .await?;
+        let symbols = response
+            .map(normalize_workspace_symbols)
+            .unwrap_or_default();
+        if !self.capabilities().supports(WorkspaceSymbolResolve::METHOD) {
+            return Ok(symbols);
+        }
+
+        let resolved = symbols.into_iter().map(|symbol| async move {
+            if let OneOf::Left(_) = symbol.location {
+                return symbol;
+            }
+            match self.resolve_workspace_symbol(symbol.clone()).await {
+                Ok(symbol) => symbol,
+                Err(err) => {
+                    log::warning!(
+                        "failed to resolve workspace symbol {}: {:?}",
+                        symbol.name,
+                        err
+                    );
+                    symbol
+                }
+            }
+        });
+        Ok(join_all(resolved).await)
+    }
+
+    /// Fills in the deferred parts of a workspace symbol, such as `location.range`.
+    pub async fn resolve_workspace_symbol(
+        &self,
+        symbol: WorkspaceSymbol,
+    ) -> Result<WorkspaceSymbol, ClientError> {
+        self.request_if_supported::<WorkspaceSymbolResolve>(symbol)
+            .await
+    }
+}
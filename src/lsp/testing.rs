@@ -0,0 +1,345 @@
+//! A scripted server for testing code built on `LanguageServerRef` without a server binary.
+//!
+//! A `MockServer` is a script: the requests and notifications it expects from the client,
+//! in order, with the answer to each request, and the notifications it sends in between.
+//! `connect` starts it behind a client over an in-memory pipe, which `pipe` opens for tests
+//! acting as the server themselves. `shutdown` and `exit` are always answered, and a message
+//! the script does not expect next is recorded as unmatched, requests being answered with
+//! an error. Once the client has shut the server down, `MockHandle::verify` fails the test
+//! unless every message matched and the whole script ran.
+//!
+//! ```no_run
+//! # async fn example() {
+//! use lsp_client::lsp::testing::MockServer;
+//! use lsp_types::request::HoverRequest;
+//! use lsp_types::{Hover, HoverContents, HoverProviderCapability, MarkedString};
+//! use lsp_types::ServerCapabilities;
+//!
+//! let capabilities = ServerCapabilities {
+//!     hover_provider: Some(HoverProviderCapability::Simple(true)),
+//!     ..ServerCapabilities::default()
+//! };
+//! let hover = Hover {
+//!     contents: HoverContents::Scalar(MarkedString::String("fn main()".to_owned())),
+//!     range: None,
+//! };
+//! let (server, mock) = MockServer::new()
+//!     .initialize(capabilities)
+//!     .request::<HoverRequest>(Some(hover))
+//!     .connect();
+//! // ... initialize, hover, shut down ...
+//! mock.verify().await;
+//! # }
+//! ```
+
+use std::collections::HashSet;
+
+use lsp_types::notification::{Initialized, Notification};
+use lsp_types::request::{Initialize, Request};
+use lsp_types::{InitializeResult, ServerCapabilities, ServerInfo};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncWrite, BufReader, DuplexStream, ReadHalf, WriteHalf};
+use tokio::task::JoinHandle;
+
+use super::client::{self, LanguageServerRef};
+use super::log;
+use super::parsing::{read_message, write_message, ParseError};
+
+/// The error code of the answer to a request the script does not expect.
+const UNEXPECTED: i64 = -32601;
+
+/// A step of the script.
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    /// A request from the client, answered with the result or error.
+    Request {
+        method: String,
+        params: Option<Value>,
+        answer: Result<Value, Value>,
+    },
+    /// A notification from the client.
+    Notification {
+        method: String,
+        params: Option<Value>,
+    },
+    /// A notification to the client, sent as soon as the steps before it ran.
+    Send { method: String, params: Value },
+}
+
+impl Step {
+    fn expects(&self, method: &str, params: Option<&Value>) -> bool {
+        let (expected, expected_params) = match self {
+            Step::Request { method, params, .. } | Step::Notification { method, params } => {
+                (method, params)
+            }
+            Step::Send { .. } => return false,
+        };
+        expected == method
+            && expected_params
+                .as_ref()
+                .is_none_or(|expected| Some(expected) == params)
+    }
+}
+
+/// The server's end of an in-memory pipe to a client, see `pipe`.
+pub struct PipeEnd {
+    pub reader: BufReader<ReadHalf<DuplexStream>>,
+    pub writer: WriteHalf<DuplexStream>,
+}
+
+impl PipeEnd {
+    /// The next message of the client.
+    ///
+    /// # Panics
+    ///
+    /// When the client closed the pipe or sent a message that is not JSON.
+    pub async fn receive(&mut self) -> Value {
+        let body = read_message(&mut self.reader)
+            .await
+            .expect("the client sends a message");
+        serde_json::from_str(&body).expect("the client sends JSON")
+    }
+
+    /// Sends `message` to the client.
+    ///
+    /// # Panics
+    ///
+    /// When the client closed the pipe.
+    pub async fn send(&mut self, message: &Value) {
+        write_message(&mut self.writer, &message.to_string())
+            .await
+            .expect("the client reads the pipe");
+    }
+}
+
+/// A client over an in-memory pipe, and the server's end of the pipe, to act as the server
+/// message by message where a script does not fit.
+pub fn pipe() -> (LanguageServerRef<WriteHalf<DuplexStream>>, PipeEnd) {
+    let (client_end, server_end) = tokio::io::duplex(64 * 1024);
+    let (client_reader, client_writer) = tokio::io::split(client_end);
+    let (server_reader, server_writer) = tokio::io::split(server_end);
+    let server = client::connect(client_reader, client_writer);
+    let end = PipeEnd {
+        reader: BufReader::new(server_reader),
+        writer: server_writer,
+    };
+    (server, end)
+}
+
+/// A script of what the server expects and answers, see the module documentation.
+#[derive(Debug, Clone, Default)]
+pub struct MockServer {
+    steps: Vec<Step>,
+    ignored: HashSet<String>,
+}
+
+fn to_value(value: impl Serialize) -> Value {
+    serde_json::to_value(value).expect("protocol types are JSON")
+}
+
+impl MockServer {
+    pub fn new() -> MockServer {
+        MockServer::default()
+    }
+
+    /// Expects `initialize`, answered with `capabilities`, then `initialized`.
+    pub fn initialize(self, capabilities: ServerCapabilities) -> MockServer {
+        let result = InitializeResult {
+            capabilities,
+            server_info: Some(ServerInfo {
+                name: "mock".to_owned(),
+                version: None,
+            }),
+        };
+        self.request::<Initialize>(result)
+            .notification::<Initialized>()
+    }
+
+    /// Expects an `R` request, answered with `result`.
+    pub fn request<R: Request>(mut self, result: R::Result) -> MockServer {
+        self.steps.push(Step::Request {
+            method: R::METHOD.to_owned(),
+            params: None,
+            answer: Ok(to_value(result)),
+        });
+        self
+    }
+
+    /// Expects an `R` request, answered with an error.
+    pub fn request_failing<R: Request>(mut self, code: i64, message: &str) -> MockServer {
+        self.steps.push(Step::Request {
+            method: R::METHOD.to_owned(),
+            params: None,
+            answer: Err(json!({ "code": code, "message": message })),
+        });
+        self
+    }
+
+    /// Expects an `N` notification from the client.
+    pub fn notification<N: Notification>(mut self) -> MockServer {
+        self.steps.push(Step::Notification {
+            method: N::METHOD.to_owned(),
+            params: None,
+        });
+        self
+    }
+
+    /// Requires the request or notification expected last to come with `params`, rather
+    /// than with any.
+    ///
+    /// # Panics
+    ///
+    /// When the last step sends a notification instead.
+    pub fn with_params(mut self, expected: impl Serialize) -> MockServer {
+        match self.steps.last_mut() {
+            Some(Step::Request { params, .. } | Step::Notification { params, .. }) => {
+                *params = Some(to_value(expected))
+            }
+            _ => panic!("with_params follows a request or notification the client sends"),
+        }
+        self
+    }
+
+    /// Sends an `N` notification to the client once the steps before ran.
+    pub fn send<N: Notification>(mut self, params: N::Params) -> MockServer {
+        self.steps.push(Step::Send {
+            method: N::METHOD.to_owned(),
+            params: to_value(params),
+        });
+        self
+    }
+
+    /// Lets the client send `N` notifications anywhere, e.g. `textDocument/didOpen`,
+    /// without them being matched against the script.
+    pub fn ignore<N: Notification>(mut self) -> MockServer {
+        self.ignored.insert(N::METHOD.to_owned());
+        self
+    }
+
+    /// A client of the mock, over an in-memory pipe, and the mock serving it until the
+    /// client sends `exit`.
+    pub fn connect(self) -> (LanguageServerRef<WriteHalf<DuplexStream>>, MockHandle) {
+        let (server, end) = pipe();
+        let serving = tokio::spawn(async move { self.serve(end.reader, end.writer).await });
+        (server, MockHandle { serving })
+    }
+
+    /// Acts as the server on `reader` and `writer` until the client sends `exit` or closes
+    /// `reader`.
+    pub async fn serve<R, W>(&self, reader: R, mut writer: W) -> std::io::Result<Unmatched>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut reader = BufReader::new(reader);
+        let mut unmatched = Unmatched::default();
+        let mut steps = self.steps.iter().peekable();
+        loop {
+            if let Some(Step::Send { method, params }) =
+                steps.next_if(|step| matches!(step, Step::Send { .. }))
+            {
+                let notification = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+                write_message(&mut writer, &notification.to_string()).await?;
+                continue;
+            }
+
+            let body = match read_message(&mut reader).await {
+                Ok(body) => body,
+                Err(ParseError::Io(_)) => break,
+                Err(err) => {
+                    log::warning!("malformed message from the client: {:?}", err);
+                    continue;
+                }
+            };
+            let message: Value = serde_json::from_str(&body).unwrap_or(Value::Null);
+            let Some(method) = message.get("method").and_then(Value::as_str) else {
+                // the client answering a request, which the mock never sends
+                unmatched.messages.push(message);
+                continue;
+            };
+            let id = message.get("id");
+            if let Some(step) = steps.next_if(|step| step.expects(method, message.get("params"))) {
+                if let (Step::Request { answer, .. }, Some(id)) = (step, id) {
+                    let response = match answer {
+                        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+                    };
+                    write_message(&mut writer, &response.to_string()).await?;
+                }
+                continue;
+            }
+            match (method, id) {
+                ("exit", _) => break,
+                ("shutdown", Some(id)) => {
+                    let response = json!({ "jsonrpc": "2.0", "id": id, "result": null });
+                    write_message(&mut writer, &response.to_string()).await?;
+                }
+                (method, _) if self.ignored.contains(method) => {}
+                (method, id) => {
+                    if let Some(id) = id {
+                        let error = json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": {
+                                "code": UNEXPECTED,
+                                "message": format!("the mock server does not expect {} here", method),
+                            },
+                        });
+                        write_message(&mut writer, &error.to_string()).await?;
+                    }
+                    unmatched.messages.push(message);
+                }
+            }
+        }
+        unmatched.steps = steps.map(|step| format!("{:?}", step)).collect();
+        Ok(unmatched)
+    }
+}
+
+/// What did not go as the script says.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Unmatched {
+    /// The messages of the client the script did not expect where they came, in order.
+    pub messages: Vec<Value>,
+    /// The steps that never ran, described.
+    pub steps: Vec<String>,
+}
+
+impl Unmatched {
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty() && self.steps.is_empty()
+    }
+}
+
+/// The mock serving a client, see `MockServer::connect`.
+pub struct MockHandle {
+    serving: JoinHandle<std::io::Result<Unmatched>>,
+}
+
+impl MockHandle {
+    /// Waits for the client to send `exit`, e.g. through `LanguageServerRef::shutdown`,
+    /// and returns what did not go as scripted.
+    pub async fn finish(self) -> Unmatched {
+        self.serving
+            .await
+            .expect("the mock server panicked")
+            .expect("the in-memory pipe does not fail")
+    }
+
+    /// Like `finish`, failing unless the client sent what the script expects and nothing
+    /// else.
+    ///
+    /// # Panics
+    ///
+    /// With the unmatched messages and the steps left.
+    pub async fn verify(self) {
+        let unmatched = self.finish().await;
+        assert!(
+            unmatched.is_empty(),
+            "the client did not follow the script:\nunexpected messages: {:#?}\nsteps left: {:#?}",
+            unmatched.messages,
+            unmatched.steps
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::ops;
+
+use lsp_types::{InitializeResult, Position, PositionEncodingKind, Range};
+use serde::{Deserialize, Serialize};
+
+/// What the `character` of a `Position` counts, as negotiated through the server's
+/// `positionEncoding` capability. Servers that do not announce one use UTF-16.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PositionEncoding {
+    /// Bytes.
+    Utf8,
+    /// UTF-16 code units, the encoding every server supports.
+    #[default]
+    Utf16,
+    /// Unicode code points, i.e. `char`s.
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// Maps an encoding kind to a `PositionEncoding`, falling back to UTF-16 for kinds the
+    /// specification does not define.
+    pub fn from_kind(kind: &PositionEncodingKind) -> PositionEncoding {
+        if *kind == PositionEncodingKind::UTF8 {
+            PositionEncoding::Utf8
+        } else if *kind == PositionEncodingKind::UTF32 {
+            PositionEncoding::Utf32
+        } else {
+            PositionEncoding::Utf16
+        }
+    }
+
+    /// The encoding the server picked in its `InitializeResult`.
+    pub fn negotiated(result: &InitializeResult) -> PositionEncoding {
+        result
+            .capabilities
+            .position_encoding
+            .as_ref()
+            .map(PositionEncoding::from_kind)
+            .unwrap_or_default()
+    }
+
+    fn len(self, ch: char) -> usize {
+        match self {
+            PositionEncoding::Utf8 => ch.len_utf8(),
+            PositionEncoding::Utf16 => ch.len_utf16(),
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+}
+
+/// Returns the byte offset at which line `line` starts, or `None` if `text` has fewer
+/// lines.
+fn line_start(text: &str, line: u32) -> Option<usize> {
+    let mut start = 0;
+    for _ in 0..line {
+        start += text[start..].find('\n')? + 1;
+    }
+    Some(start)
+}
+
+/// Converts a position into a byte offset into `text`, clamping positions past the end of
+/// a line or of the document. Positions inside a multi-unit character resolve to its start.
+pub fn position_to_offset(text: &str, position: Position, encoding: PositionEncoding) -> usize {
+    let line_start = match line_start(text, position.line) {
+        Some(start) => start,
+        None => return text.len(),
+    };
+    let line = match text[line_start..].find('\n') {
+        Some(idx) => &text[line_start..line_start + idx],
+        None => &text[line_start..],
+    };
+    let line = line.strip_suffix('\r').unwrap_or(line);
+
+    let mut units = 0;
+    for (idx, ch) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + idx;
+        }
+        units += encoding.len(ch);
+    }
+    line_start + line.len()
+}
+
+/// Converts a byte offset into `text` into a position. Offsets past the end of the text
+/// clamp to its end, offsets inside a character to the character's start.
+pub fn offset_to_position(text: &str, offset: usize, encoding: PositionEncoding) -> Position {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..]
+            .chars()
+            .map(|ch| encoding.len(ch))
+            .sum::<usize>() as u32,
+    }
+}
+
+/// Converts a character offset (counting `char`s) into a byte offset, clamping to the end
+/// of the text.
+pub fn char_offset_to_offset(text: &str, char_offset: usize) -> usize {
+    text.char_indices()
+        .nth(char_offset)
+        .map_or(text.len(), |(idx, _)| idx)
+}
+
+/// Converts a byte offset into a character offset, counting the characters before it.
+pub fn offset_to_char_offset(text: &str, offset: usize) -> usize {
+    text.char_indices()
+        .take_while(|(idx, _)| *idx < offset)
+        .count()
+}
+
+/// Converts a range into byte offsets into `text`. Inverted ranges are collapsed to their
+/// start.
+pub fn range_to_offsets(text: &str, range: Range, encoding: PositionEncoding) -> ops::Range<usize> {
+    let start = position_to_offset(text, range.start, encoding);
+    let end = position_to_offset(text, range.end, encoding).max(start);
+    start..end
+}
+
+/// Converts byte offsets into `text` into a range.
+pub fn offsets_to_range(
+    text: &str,
+    offsets: ops::Range<usize>,
+    encoding: PositionEncoding,
+) -> Range {
+    Range {
+        start: offset_to_position(text, offsets.start, encoding),
+        end: offset_to_position(text, offsets.end, encoding),
+    }
+}
+
+/// Returns the part of `text` covered by `range`.
+pub fn slice_range(text: &str, range: Range, encoding: PositionEncoding) -> &str {
+    &text[range_to_offsets(text, range, encoding)]
+}
@@ -0,0 +1,110 @@
+use lsp_types::notification::Notification;
+use lsp_types::{CodeActionContext, CodeActionKind, CodeActionOrCommand, Range, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+
+use super::client::{ClientError, LanguageServerRef};
+use super::documents::DocumentManager;
+use super::edits::EditError;
+
+/// The command typescript-language-server sorts and prunes the imports of a file with.
+pub const ORGANIZE_IMPORTS: &str = "_typescript.organizeImports";
+
+/// The command typescript-language-server applies a quick fix throughout a file with, as
+/// the commands of its "fix all" quick fixes run it.
+pub const APPLY_FIX_ALL_CODE_ACTION: &str = "_typescript.applyFixAllCodeAction";
+
+/// The code action kind under which typescript-language-server offers to apply every
+/// automatic fix of a file.
+pub const SOURCE_FIX_ALL: &str = "source.fixAll.ts";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypescriptVersion {
+    pub version: String,
+    /// Where the server found TypeScript, e.g. `bundled` or a workspace `node_modules`.
+    pub source: String,
+}
+
+/// `$/typescriptVersion`: the TypeScript version serving the workspace, sent once after
+/// `initialize`.
+pub enum TypescriptVersionNotification {}
+
+impl Notification for TypescriptVersionNotification {
+    type Params = TypescriptVersion;
+    const METHOD: &'static str = "$/typescriptVersion";
+}
+
+impl<W: AsyncWriteExt + Unpin> LanguageServerRef<W> {
+    /// Organizes the imports of a file, applying the edits the server sends back through
+    /// `documents`.
+    pub async fn organize_imports(
+        &self,
+        documents: &DocumentManager<W>,
+        uri: &Url,
+    ) -> Result<(), ClientError> {
+        let path = uri
+            .to_file_path()
+            .map_err(|_| EditError::NotAFile(uri.clone()))?;
+        let arguments = vec![json!(path)];
+        self.execute_command_with(ORGANIZE_IMPORTS, arguments, move |edit| {
+            documents.apply_command_edit(edit)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Runs `_typescript.applyFixAllCodeAction` with the `arguments` of a command the server
+    /// offered it in, applying the edits the server sends back through `documents`.
+    pub async fn apply_fix_all_code_action(
+        &self,
+        documents: &DocumentManager<W>,
+        arguments: Vec<Value>,
+    ) -> Result<(), ClientError> {
+        self.execute_command_with(APPLY_FIX_ALL_CODE_ACTION, arguments, move |edit| {
+            documents.apply_command_edit(edit)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Applies every automatic fix the server offers for a file, such as adding missing
+    /// `await`s or removing unreachable code. Returns false if there was nothing to fix.
+    pub async fn fix_all(
+        &self,
+        documents: &DocumentManager<W>,
+        uri: &Url,
+    ) -> Result<bool, ClientError> {
+        let context = CodeActionContext {
+            diagnostics: Vec::new(),
+            only: Some(vec![CodeActionKind::new(SOURCE_FIX_ALL)]),
+            trigger_kind: None,
+        };
+        // the fixes cover the whole file whatever the range
+        let actions = self
+            .code_actions(uri.clone(), Range::default(), context)
+            .await?;
+        let action = actions.into_iter().find(|action| match action {
+            CodeActionOrCommand::CodeAction(action) => {
+                action.kind.as_ref().map(CodeActionKind::as_str) == Some(SOURCE_FIX_ALL)
+            }
+            CodeActionOrCommand::Command(_) => false,
+        });
+        match action {
+            Some(action) => {
+                self.apply_code_action(documents, action).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Registers `handler` to receive the TypeScript version the server reports. Returns an
+    /// id for `remove_notification_handler`.
+    pub fn on_typescript_version<F>(&self, handler: F) -> usize
+    where
+        F: 'static + Send + FnMut(TypescriptVersion),
+    {
+        self.on_typed_notification::<TypescriptVersionNotification, _>(handler)
+    }
+}
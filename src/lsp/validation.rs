@@ -0,0 +1,223 @@
+use std::ops::RangeInclusive;
+
+use lsp_types::notification::{self, Notification};
+use lsp_types::request::{self, Request};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+/// The member of a message to check, see `validate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Member {
+    /// The `params` of a request or a notification.
+    Params,
+    /// The `result` of a response.
+    Result,
+}
+
+/// Numeric enums lsp_types takes any number for, as the method and member they are in, the
+/// name of the members holding them anywhere under it and their values in the specification.
+const ENUMS: &[(&str, Member, &str, RangeInclusive<i64>)] = &[
+    (
+        notification::PublishDiagnostics::METHOD,
+        Member::Params,
+        "severity",
+        1..=4,
+    ),
+    (
+        request::DocumentSymbolRequest::METHOD,
+        Member::Result,
+        "kind",
+        1..=26,
+    ),
+    (
+        request::WorkspaceSymbolRequest::METHOD,
+        Member::Result,
+        "kind",
+        1..=26,
+    ),
+    (request::Completion::METHOD, Member::Result, "kind", 1..=25),
+    (
+        request::Completion::METHOD,
+        Member::Result,
+        "insertTextFormat",
+        1..=2,
+    ),
+    (
+        notification::ShowMessage::METHOD,
+        Member::Params,
+        "type",
+        1..=4,
+    ),
+    (
+        notification::LogMessage::METHOD,
+        Member::Params,
+        "type",
+        1..=5,
+    ),
+];
+
+/// Checks `value`, a member of a `method` message, against its lsp_types structure,
+/// returning how it deviates: fields lsp_types does not know, fields missing or of the wrong
+/// type, and enum values out of the specification. Methods lsp_types does not know pass.
+pub fn validate(method: &str, member: Member, value: &Value) -> Vec<String> {
+    let mut errors = check_method(method, member, value).unwrap_or_default();
+    for (_, _, name, range) in ENUMS
+        .iter()
+        .filter(|(enum_method, enum_member, ..)| *enum_method == method && *enum_member == member)
+    {
+        check_enum(value, "", name, range, &mut errors);
+    }
+    errors
+}
+
+/// Checks the `params` and `result` of the methods listed, `None` for other methods.
+macro_rules! methods {
+    (requests: [$($request:ident),* $(,)?], notifications: [$($notification:ident),* $(,)?]) => {
+        fn check_method(method: &str, member: Member, value: &Value) -> Option<Vec<String>> {
+            $(
+                if method == <request::$request as Request>::METHOD {
+                    return Some(match member {
+                        Member::Params => check::<<request::$request as Request>::Params>(value),
+                        Member::Result => check::<<request::$request as Request>::Result>(value),
+                    });
+                }
+            )*
+            $(
+                if method == <notification::$notification as Notification>::METHOD {
+                    return match member {
+                        Member::Params => Some(check::<
+                            <notification::$notification as Notification>::Params,
+                        >(value)),
+                        // notifications have no response
+                        Member::Result => None,
+                    };
+                }
+            )*
+            None
+        }
+    };
+}
+
+methods! {
+    requests: [
+        Initialize,
+        Shutdown,
+        RegisterCapability,
+        UnregisterCapability,
+        WorkDoneProgressCreate,
+        ApplyWorkspaceEdit,
+        WorkspaceConfiguration,
+        ShowMessageRequest,
+        HoverRequest,
+        GotoDefinition,
+        GotoDeclaration,
+        GotoTypeDefinition,
+        GotoImplementation,
+        References,
+        DocumentHighlightRequest,
+        DocumentSymbolRequest,
+        WorkspaceSymbolRequest,
+        Completion,
+        ResolveCompletionItem,
+        SignatureHelpRequest,
+        CodeActionRequest,
+        CodeLensRequest,
+        DocumentLinkRequest,
+        Formatting,
+        RangeFormatting,
+        Rename,
+        PrepareRenameRequest,
+        FoldingRangeRequest,
+        SelectionRangeRequest,
+        SemanticTokensFullRequest,
+        SemanticTokensRangeRequest,
+        InlayHintRequest,
+        CallHierarchyPrepare,
+        CallHierarchyIncomingCalls,
+        CallHierarchyOutgoingCalls,
+        ExecuteCommand,
+    ],
+    notifications: [
+        Initialized,
+        Exit,
+        DidOpenTextDocument,
+        DidChangeTextDocument,
+        DidSaveTextDocument,
+        DidCloseTextDocument,
+        DidChangeConfiguration,
+        DidChangeWatchedFiles,
+        DidChangeWorkspaceFolders,
+        PublishDiagnostics,
+        ShowMessage,
+        LogMessage,
+        Cancel,
+    ],
+}
+
+/// Deserializes `value` as `T`, then compares it with `T` serialized back to find the
+/// fields `T` dropped.
+fn check<T: DeserializeOwned + Serialize>(value: &Value) -> Vec<String> {
+    let parsed: T = match serde_json::from_value(value.clone()) {
+        Ok(parsed) => parsed,
+        Err(err) => return vec![err.to_string()],
+    };
+    let mut errors = Vec::new();
+    match serde_json::to_value(parsed) {
+        Ok(known) => unknown_fields(value, &known, "", &mut errors),
+        Err(err) => errors.push(err.to_string()),
+    }
+    errors
+}
+
+/// Reports the fields of `value` missing from `known`, its known fields. A `null` field is
+/// taken for a field left out.
+fn unknown_fields(value: &Value, known: &Value, path: &str, errors: &mut Vec<String>) {
+    match (value, known) {
+        (Value::Object(fields), Value::Object(known)) => {
+            for (name, field) in fields {
+                let path = format!("{}/{}", path, name);
+                match known.get(name) {
+                    Some(known) => unknown_fields(field, known, &path, errors),
+                    None if field.is_null() => {}
+                    None => errors.push(format!("unknown field `{}`", path)),
+                }
+            }
+        }
+        (Value::Array(items), Value::Array(known)) if items.len() == known.len() => {
+            for (index, (item, known)) in items.iter().zip(known).enumerate() {
+                unknown_fields(item, known, &format!("{}/{}", path, index), errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Reports the numeric `name` fields anywhere under `value` outside of `range`.
+fn check_enum(
+    value: &Value,
+    path: &str,
+    name: &str,
+    range: &RangeInclusive<i64>,
+    errors: &mut Vec<String>,
+) {
+    match value {
+        Value::Object(fields) => {
+            for (field_name, field) in fields {
+                let path = format!("{}/{}", path, field_name);
+                match field.as_i64() {
+                    Some(number) if field_name == name && !range.contains(&number) => {
+                        errors.push(format!("invalid value {} for `{}`", number, path))
+                    }
+                    _ => check_enum(field, &path, name, range, errors),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                check_enum(item, &format!("{}/{}", path, index), name, range, errors);
+            }
+        }
+        _ => {}
+    }
+}
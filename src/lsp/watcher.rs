@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use globset::{GlobBuilder, GlobMatcher};
+use jsonrpc_lite::Error;
+use lsp_types::notification::{DidChangeWatchedFiles, Notification};
+use lsp_types::{
+    DidChangeWatchedFilesParams, DidChangeWatchedFilesRegistrationOptions, FileChangeType,
+    FileEvent, FileSystemWatcher, GlobPattern, OneOf, Registration, Url, WatchKind,
+};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+use super::client::{CapabilityChange, LanguageServerRef};
+use super::log;
+
+/// One `FileSystemWatcher` of a registration, with its glob compiled.
+struct CompiledWatcher {
+    /// Folder relative patterns are matched against, `None` for absolute patterns.
+    base: Option<PathBuf>,
+    glob: GlobMatcher,
+    kind: WatchKind,
+}
+
+impl CompiledWatcher {
+    fn compile(watcher: &FileSystemWatcher) -> Option<CompiledWatcher> {
+        let (base, pattern) = match &watcher.glob_pattern {
+            GlobPattern::String(pattern) => (None, pattern),
+            GlobPattern::Relative(relative) => {
+                let base_uri = match &relative.base_uri {
+                    OneOf::Left(folder) => &folder.uri,
+                    OneOf::Right(uri) => uri,
+                };
+                (Some(base_uri.to_file_path().ok()?), &relative.pattern)
+            }
+        };
+        let glob = match GlobBuilder::new(pattern).literal_separator(true).build() {
+            Ok(glob) => glob.compile_matcher(),
+            Err(err) => {
+                log::warning!("invalid watcher glob {:?}: {}", pattern, err);
+                return None;
+            }
+        };
+        Some(CompiledWatcher {
+            base,
+            glob,
+            kind: watcher.kind.unwrap_or(WatchKind::all()),
+        })
+    }
+
+    fn matches(&self, path: &Path, change: FileChangeType) -> bool {
+        let kind = match change {
+            FileChangeType::CREATED => WatchKind::Create,
+            FileChangeType::DELETED => WatchKind::Delete,
+            _ => WatchKind::Change,
+        };
+        if !self.kind.contains(kind) {
+            return false;
+        }
+        match &self.base {
+            Some(base) => match path.strip_prefix(base) {
+                Ok(relative) => self.glob.is_match(relative),
+                Err(_) => false,
+            },
+            None => self.glob.is_match(path),
+        }
+    }
+}
+
+/// The watchers of a `workspace/didChangeWatchedFiles` registration.
+fn compile_registration(registration: &Registration) -> Result<Vec<CompiledWatcher>, Error> {
+    let options: DidChangeWatchedFilesRegistrationOptions = registration
+        .register_options
+        .clone()
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(|_| Error::invalid_params())?
+        .ok_or_else(Error::invalid_params)?;
+    Ok(options
+        .watchers
+        .iter()
+        .filter_map(CompiledWatcher::compile)
+        .collect())
+}
+
+/// The file changes described by a `notify` event.
+fn file_changes(event: Event) -> Vec<(PathBuf, FileChangeType)> {
+    let change = match event.kind {
+        EventKind::Create(_) => FileChangeType::CREATED,
+        EventKind::Remove(_) => FileChangeType::DELETED,
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => FileChangeType::DELETED,
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => FileChangeType::CREATED,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            let mut paths = event.paths.into_iter();
+            return paths
+                .next()
+                .map(|from| (from, FileChangeType::DELETED))
+                .into_iter()
+                .chain(paths.next().map(|to| (to, FileChangeType::CREATED)))
+                .collect();
+        }
+        EventKind::Modify(_) => FileChangeType::CHANGED,
+        _ => return Vec::new(),
+    };
+    event.paths.into_iter().map(|path| (path, change)).collect()
+}
+
+/// Watches folders on disk and reports changes to the server through
+/// `workspace/didChangeWatchedFiles`.
+///
+/// Only changes matching the watchers the server registered dynamically through
+/// `client/registerCapability` are reported, the way servers expect: those registered before
+/// the `FileWatcher` was created, then those received through
+/// `LanguageServerRef::on_registration`, next to whatever else listens for registrations.
+/// Watching stops when the `FileWatcher` is dropped.
+pub struct FileWatcher {
+    _watcher: RecommendedWatcher,
+    registrations: Arc<Mutex<HashMap<String, Vec<CompiledWatcher>>>>,
+    /// Removes the registration handler.
+    stop: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl FileWatcher {
+    /// Starts watching `roots` recursively on behalf of `server`.
+    pub fn new<W>(server: LanguageServerRef<W>, roots: &[PathBuf]) -> notify::Result<FileWatcher>
+    where
+        W: 'static + AsyncWriteExt + Unpin + Send,
+    {
+        // watching first, so that failing leaves no registration handler behind
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+                Ok(event) => {
+                    let _ = tx.send(event);
+                }
+                Err(err) => log::warning!("file watcher error: {:?}", err),
+            })?;
+        for root in roots {
+            watcher.watch(root, RecursiveMode::Recursive)?;
+        }
+
+        let registrations: Arc<Mutex<HashMap<String, Vec<CompiledWatcher>>>> =
+            Arc::new(Mutex::new(HashMap::new()));
+        let handler = {
+            let registrations = registrations.clone();
+            server.on_registration(DidChangeWatchedFiles::METHOD, move |change| {
+                let mut registrations = registrations.lock().unwrap();
+                match change {
+                    CapabilityChange::Register(registration) => {
+                        let watchers = compile_registration(&registration)?;
+                        registrations.insert(registration.id, watchers);
+                    }
+                    CapabilityChange::Unregister(unregistration) => {
+                        registrations.remove(&unregistration.id);
+                    }
+                }
+                Ok(())
+            })
+        };
+        // the watchers registered before, once listening so that none is missed
+        for registration in server.capabilities().registrations() {
+            if registration.method != DidChangeWatchedFiles::METHOD {
+                continue;
+            }
+            if let Ok(watchers) = compile_registration(registration) {
+                registrations
+                    .lock()
+                    .unwrap()
+                    .entry(registration.id.clone())
+                    .or_insert(watchers);
+            }
+        }
+        let stop: Box<dyn FnOnce() + Send> = {
+            let server = server.clone();
+            Box::new(move || server.remove_registration_handler(handler))
+        };
+
+        {
+            let registrations = registrations.clone();
+            tokio::task::spawn(async move {
+                while let Some(event) = rx.recv().await {
+                    let changes: Vec<FileEvent> = {
+                        let registrations = registrations.lock().unwrap();
+                        file_changes(event)
+                            .into_iter()
+                            .filter(|(path, change)| {
+                                registrations
+                                    .values()
+                                    .flatten()
+                                    .any(|watcher| watcher.matches(path, *change))
+                            })
+                            .filter_map(|(path, change)| {
+                                Url::from_file_path(path)
+                                    .ok()
+                                    .map(|uri| FileEvent { uri, typ: change })
+                            })
+                            .collect()
+                    };
+                    if !changes.is_empty() {
+                        let params = DidChangeWatchedFilesParams { changes };
+                        server.notify::<DidChangeWatchedFiles>(params).await;
+                    }
+                }
+            });
+        }
+
+        Ok(FileWatcher {
+            _watcher: watcher,
+            registrations,
+            stop: Some(stop),
+        })
+    }
+
+    /// Returns whether the server registered any watchers yet.
+    pub fn has_registrations(&self) -> bool {
+        !self.registrations.lock().unwrap().is_empty()
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop();
+        }
+    }
+}
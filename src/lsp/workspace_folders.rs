@@ -0,0 +1,81 @@
+use std::sync::{Arc, Mutex};
+
+use lsp_types::notification::DidChangeWorkspaceFolders;
+use lsp_types::request::{Request, WorkspaceFoldersRequest};
+use lsp_types::{
+    DidChangeWorkspaceFoldersParams, Url, WorkspaceFolder, WorkspaceFoldersChangeEvent,
+};
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+
+use super::client::LanguageServerRef;
+
+/// The workspace folders open on a language server.
+///
+/// Pass `folders()` as `InitializeParams::workspace_folders`; afterwards `add` and `remove`
+/// keep the server informed through `workspace/didChangeWorkspaceFolders`. The server's
+/// `workspace/workspaceFolders` requests are answered from the same list.
+pub struct WorkspaceFolders<W: AsyncWriteExt> {
+    server: LanguageServerRef<W>,
+    folders: Arc<Mutex<Vec<WorkspaceFolder>>>,
+}
+
+impl<W: AsyncWriteExt + Unpin> WorkspaceFolders<W> {
+    pub fn new(server: LanguageServerRef<W>, folders: Vec<WorkspaceFolder>) -> Self {
+        let folders = Arc::new(Mutex::new(folders));
+        {
+            let folders = folders.clone();
+            server.on_request(WorkspaceFoldersRequest::METHOD, move |_| {
+                Ok(json!(*folders.lock().unwrap()))
+            });
+        }
+        WorkspaceFolders { server, folders }
+    }
+
+    /// Returns the current folders.
+    pub fn folders(&self) -> Vec<WorkspaceFolder> {
+        self.folders.lock().unwrap().clone()
+    }
+
+    /// Adds a folder, doing nothing if a folder with the same uri is already open.
+    pub async fn add(&self, folder: WorkspaceFolder) {
+        {
+            let mut folders = self.folders.lock().unwrap();
+            if folders.iter().any(|open| open.uri == folder.uri) {
+                return;
+            }
+            folders.push(folder.clone());
+        }
+        self.notify_change(vec![folder], Vec::new()).await;
+    }
+
+    /// Removes the folder with the given uri, doing nothing if it is not open.
+    pub async fn remove(&self, uri: &Url) {
+        let removed = {
+            let mut folders = self.folders.lock().unwrap();
+            match folders.iter().position(|open| &open.uri == uri) {
+                Some(index) => folders.remove(index),
+                None => return,
+            }
+        };
+        self.notify_change(Vec::new(), vec![removed]).await;
+    }
+
+    async fn notify_change(&self, added: Vec<WorkspaceFolder>, removed: Vec<WorkspaceFolder>) {
+        let params = DidChangeWorkspaceFoldersParams {
+            event: WorkspaceFoldersChangeEvent { added, removed },
+        };
+        self.server
+            .notify::<DidChangeWorkspaceFolders>(params)
+            .await;
+    }
+}
+
+impl<W: AsyncWriteExt> Clone for WorkspaceFolders<W> {
+    fn clone(&self) -> Self {
+        WorkspaceFolders {
+            server: self.server.clone(),
+            folders: self.folders.clone(),
+        }
+    }
+}
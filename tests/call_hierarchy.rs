@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use lsp_client::lsp::call_hierarchy::{CallDirection, CallEdge, CallGraph};
+use lsp_types::{CallHierarchyItem, Position, Range, SymbolKind, Url};
+use serde_json::json;
+
+fn range(line: u32) -> Range {
+    Range::new(Position::new(line, 3), Position::new(line, 8))
+}
+
+/// A function declared on `line`, with a fresh `data` payload as servers hand out.
+fn item(name: &str, line: u32) -> CallHierarchyItem {
+    CallHierarchyItem {
+        name: name.to_owned(),
+        kind: SymbolKind::FUNCTION,
+        tags: None,
+        detail: None,
+        uri: Url::parse("file:///src/main.rs").unwrap(),
+        range: range(line),
+        selection_range: range(line),
+        data: Some(json!({ "nonce": nonce() })),
+    }
+}
+
+fn nonce() -> u64 {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static NONCE: AtomicU64 = AtomicU64::new(0);
+    NONCE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// `main` calls `parse` twice and `print` once, `parse` calls itself and `print`.
+fn outgoing() -> HashMap<&'static str, Vec<(CallHierarchyItem, Vec<Range>)>> {
+    HashMap::from([
+        (
+            "main",
+            vec![
+                (item("parse", 10), vec![range(2), range(3)]),
+                (item("print", 20), vec![range(4)]),
+            ],
+        ),
+        (
+            "parse",
+            vec![
+                (item("parse", 10), vec![range(12)]),
+                (item("print", 20), vec![range(13)]),
+            ],
+        ),
+        ("print", vec![]),
+    ])
+}
+
+async fn expand(max_depth: usize) -> (CallGraph, Vec<String>) {
+    let calls = outgoing();
+    let mut expanded = Vec::new();
+    let graph = CallGraph::expand(
+        vec![item("main", 1)],
+        CallDirection::Outgoing,
+        max_depth,
+        |item| {
+            expanded.push(item.name.clone());
+            let neighbours = calls[item.name.as_str()].clone();
+            async move { Ok(neighbours) }
+        },
+    )
+    .await
+    .unwrap();
+    (graph, expanded)
+}
+
+#[tokio::test]
+async fn items_are_expanded_once_and_recursion_terminates() {
+    let (graph, expanded) = expand(10).await;
+    assert_eq!(expanded, ["main", "parse", "print"]);
+    let names: Vec<_> = graph.nodes.iter().map(|node| node.name.as_str()).collect();
+    assert_eq!(names, ["main", "parse", "print"]);
+    assert_eq!(graph.roots, [0]);
+    assert_eq!(
+        graph.edges,
+        [
+            CallEdge {
+                caller: 0,
+                callee: 1,
+                from_ranges: vec![range(2), range(3)],
+            },
+            CallEdge {
+                caller: 0,
+                callee: 2,
+                from_ranges: vec![range(4)],
+            },
+            CallEdge {
+                caller: 1,
+                callee: 1,
+                from_ranges: vec![range(12)],
+            },
+            CallEdge {
+                caller: 1,
+                callee: 2,
+                from_ranges: vec![range(13)],
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn expansion_stops_at_the_maximum_depth() {
+    let (graph, expanded) = expand(1).await;
+    assert_eq!(expanded, ["main"]);
+    assert_eq!(graph.nodes.len(), 3);
+    assert_eq!(graph.edges.len(), 2);
+}
+
+#[tokio::test]
+async fn incoming_edges_point_at_the_expanded_item() {
+    let callers = vec![(item("main", 1), vec![range(2)])];
+    let graph = CallGraph::expand(vec![item("parse", 10)], CallDirection::Incoming, 1, |_| {
+        let callers = callers.clone();
+        async move { Ok(callers) }
+    })
+    .await
+    .unwrap();
+    assert_eq!(
+        graph.edges,
+        [CallEdge {
+            caller: 1,
+            callee: 0,
+            from_ranges: vec![range(2)],
+        }]
+    );
+}
@@ -0,0 +1,2098 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use lsp_client::cli::actions;
+use lsp_client::cli::args::{
+    self, ActionId, Command, LineColumn, LineColumnRange, Options, Query, Target,
+};
+use lsp_client::cli::bench::{BenchMethod, Latencies};
+use lsp_client::cli::cache::{CachedFile, SymbolCache, CACHE_FILE};
+use lsp_client::cli::changes::{self, Changes};
+use lsp_client::cli::completions::{self, Shell};
+use lsp_client::cli::config::{Config, Settings, CONFIG_FILE};
+use lsp_client::cli::daemon;
+use lsp_client::cli::dead_code;
+use lsp_client::cli::diff;
+use lsp_client::cli::doctor::{self, Check};
+use lsp_client::cli::explain::Explanation;
+use lsp_client::cli::format::{self, Reformatted};
+use lsp_client::cli::highlight::{self, Highlighted};
+use lsp_client::cli::inventory;
+use lsp_client::cli::output::{Outcome, OutputFormat, Printer, Style};
+use lsp_client::cli::progress;
+use lsp_client::cli::proxy::ProxyOptions;
+use lsp_client::cli::record::{Frame, Record, Recording, Sender};
+use lsp_client::cli::repl::{self, ReplCommand};
+use lsp_client::cli::report::FileAnalysis;
+use lsp_client::cli::results::{self, ResultCache, MAX_AGE, MAX_SIZE, RESULTS_DIR};
+use lsp_client::cli::schema;
+use lsp_client::cli::script;
+use lsp_client::cli::session::{Session, Timeouts, Trace};
+use lsp_client::cli::CliError;
+use lsp_client::lsp::call_hierarchy::{CallDirection, CallGraph};
+use lsp_client::lsp::client::{Direction, ServerProgress};
+use lsp_client::lsp::frame_log;
+use lsp_client::lsp::navigation::GotoKind;
+use lsp_client::lsp::semantic_tokens::DecodedSemanticToken;
+use lsp_client::lsp::server_capabilities::Capabilities;
+use lsp_client::lsp::symbols::{self, SymbolAtPoint};
+use lsp_client::lsp::text_utils::PositionEncoding;
+use lsp_types::{
+    CallHierarchyItem, CodeActionOrCommand, CompletionList, Diagnostic, DiagnosticSeverity,
+    DocumentSymbolResponse, FoldingRange, HoverContents, Location, MarkedString, Position, Range,
+    SemanticTokenModifier, SemanticTokenType, ServerInfo, SymbolKind, Url, WorkspaceEdit,
+};
+use serde_json::{json, Value};
+
+fn parse(line: &str) -> Result<args::Invocation, CliError> {
+    let args: Vec<String> = line.split(' ').map(str::to_owned).collect();
+    args::parse(&args)
+}
+
+fn range(start_line: u32, start: u32, end_line: u32, end: u32) -> serde_json::Value {
+    json!({
+        "start": { "line": start_line, "character": start },
+        "end": { "line": end_line, "character": end },
+    })
+}
+
+#[test]
+fn options_may_follow_the_command() {
+    let invocation =
+        parse("rename src/main.rs 3:7 renamed --write --server rust-analyzer").unwrap();
+    assert_eq!(
+        invocation.command,
+        Command::Query(Query::Rename {
+            file: PathBuf::from("src/main.rs"),
+            target: Target::At(LineColumn { line: 3, column: 7 }),
+            new_name: "renamed".to_owned(),
+            apply: true,
+        })
+    );
+    assert_eq!(
+        invocation.options.server,
+        Some(vec!["rust-analyzer".to_owned()])
+    );
+    assert_eq!(invocation.options.root, None);
+}
+
+#[test]
+fn invalid_arguments_are_usage_errors() {
+    for line in [
+        "hover src/main.rs",
+        "hover src/main.rs 0:1",
+        "hover src/main.rs 1",
+        "symbols src/main.rs --verbose",
+        "frobnicate src/main.rs",
+    ] {
+        let err = parse(line).unwrap_err();
+        assert!(matches!(err, CliError::Usage(_)), "{}", line);
+        assert_eq!(err.exit_code(), 2);
+    }
+}
+
+#[test]
+fn positions_from_json_count_from_1() {
+    let at: LineColumn = serde_json::from_value(json!({ "line": 1, "column": 4 })).unwrap();
+    assert_eq!(at, LineColumn { line: 1, column: 4 });
+    // as sent to the daemon, which would underflow on 0
+    for at in [
+        json!({ "line": 0, "column": 4 }),
+        json!({ "line": 1, "column": 0 }),
+    ] {
+        assert!(serde_json::from_value::<LineColumn>(at).is_err());
+    }
+}
+
+#[test]
+fn symbols_may_stand_in_for_positions() {
+    let invocation = parse("rename src/app.ts --symbol App.render draw").unwrap();
+    assert_eq!(
+        invocation.command,
+        Command::Query(Query::Rename {
+            file: PathBuf::from("src/app.ts"),
+            target: Target::Symbol("App.render".to_owned()),
+            new_name: "draw".to_owned(),
+            apply: false,
+        })
+    );
+    assert!(parse("hover src/app.ts 3:4 --symbol App").is_err());
+
+    assert_eq!(
+        repl::parse_line("refs src/app.ts App.render").unwrap(),
+        ReplCommand::Query(Query::References {
+            file: PathBuf::from("src/app.ts"),
+            target: Target::Symbol("App.render".to_owned()),
+            include_declaration: true,
+        })
+    );
+
+    let steps = script::parse_script(
+        r#"{"command": "definition", "file": "src/app.ts", "symbol": "App.render"}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        steps[0].command,
+        ReplCommand::Query(Query::Definition {
+            file: PathBuf::from("src/app.ts"),
+            target: Target::Symbol("App.render".to_owned()),
+        })
+    );
+    let err = script::parse_script(
+        r#"{"command": "hover", "file": "a.ts", "line": 1, "column": 1, "symbol": "App"}"#,
+    )
+    .unwrap_err();
+    assert!(err.message().starts_with("line 1: expected either"));
+}
+
+#[test]
+fn command_line_options_override_the_config_file() {
+    let dir = std::env::temp_dir().join("lsp_client_cli_config");
+    let nested = dir.join("src").join("deep");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::write(
+        dir.join(CONFIG_FILE),
+        r#"{
+            "server": "typescript-language-server --stdio",
+            "rootUri": "..",
+            "initializationOptions": { "hostInfo": "cli" },
+            "output": "text"
+        }"#,
+    )
+    .unwrap();
+
+    // found from a nested directory, paths are relative to the file
+    let config = Config::find(&nested).unwrap().unwrap();
+    assert_eq!(config.root().unwrap(), Some(dir.join("..")));
+
+    let options = parse("symbols a.ts --root project").unwrap().options;
+    let settings = Settings::new(options, Some(config)).unwrap();
+    assert_eq!(settings.root, Some(PathBuf::from("project")));
+    assert_eq!(
+        settings.server,
+        Some(vec![
+            "typescript-language-server".to_owned(),
+            "--stdio".to_owned()
+        ])
+    );
+    assert_eq!(
+        settings.initialization_options,
+        Some(json!({ "hostInfo": "cli" }))
+    );
+    assert_eq!(settings.output, OutputFormat::Text);
+
+    std::fs::write(dir.join(CONFIG_FILE), r#"{ "serverCommand": "clangd" }"#).unwrap();
+    let err = Config::find(&nested).unwrap_err();
+    assert!(matches!(err, CliError::Config { .. }));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn configs_are_checked_against_their_schema() {
+    let shipped: serde_json::Value =
+        serde_json::from_str(include_str!("../lsp.schema.json")).unwrap();
+    assert_eq!(
+        shipped,
+        schema::config_schema(),
+        "run lsp-client config-schema"
+    );
+
+    let dir = std::env::temp_dir().join(format!("lsp-client-schema-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(CONFIG_FILE);
+    let message = |config: &str| {
+        std::fs::write(&path, config).unwrap();
+        match Config::load(&path) {
+            Err(CliError::Config { message, .. }) => message,
+            other => panic!("{:?}", other),
+        }
+    };
+    assert_eq!(
+        message(r#"{ "sever": "clangd" }"#),
+        "at /sever: unknown field \"sever\", did you mean \"server\"?"
+    );
+    assert_eq!(
+        message(r#"{ "server": ["clangd", 1] }"#),
+        "at /server/1: expected a string, found 1"
+    );
+    assert_eq!(
+        message(r#"{ "server": 1, "extensions": "rs" }"#),
+        "at /extensions: expected an array or null, found \"rs\"\n\
+         at /server: expected a string, an array or null, found 1"
+    );
+
+    // the initializationOptions follow the schema the config names
+    std::fs::write(
+        dir.join("options.schema.json"),
+        r##"{
+            "type": "object",
+            "properties": {
+                "checkOnSave": { "type": "boolean" },
+                "cargo": { "$ref": "#/$defs/cargo" }
+            },
+            "additionalProperties": false,
+            "$defs": {
+                "cargo": {
+                    "type": "object",
+                    "properties": { "features": { "enum": ["all", "none"] } }
+                }
+            }
+        }"##,
+    )
+    .unwrap();
+    assert_eq!(
+        message(
+            r#"{
+                "initializationOptionsSchema": "options.schema.json",
+                "initializationOptions": { "checkOnSve": true, "cargo": { "features": "some" } }
+            }"#
+        ),
+        "at /initializationOptions/cargo/features: expected \"all\" or \"none\", found \"some\"\n\
+         at /initializationOptions/checkOnSve: unknown field \"checkOnSve\", did you mean \"checkOnSave\"?"
+    );
+    std::fs::write(
+        &path,
+        r#"{
+            "$schema": "lsp.schema.json",
+            "initializationOptionsSchema": "options.schema.json",
+            "initializationOptions": { "checkOnSave": true, "cargo": { "features": "all" } }
+        }"#,
+    )
+    .unwrap();
+    let config = Config::load(&path).unwrap();
+    assert_eq!(
+        config.initialization_options,
+        Some(json!({ "checkOnSave": true, "cargo": { "features": "all" } }))
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn schemas_referring_to_themselves_are_checked_without_looping() {
+    // a recursive schema checks every level of the value
+    let tree = json!({
+        "$ref": "#/$defs/node",
+        "$defs": {
+            "node": {
+                "type": "object",
+                "properties": { "child": { "$ref": "#/$defs/node" } },
+                "additionalProperties": false
+            }
+        }
+    });
+    let violations = schema::validate(&tree, &json!({ "child": { "child": { "chlid": {} } } }));
+    let messages: Vec<String> = violations.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        messages,
+        ["at /child/child/chlid: unknown field \"chlid\", did you mean \"child\"?"]
+    );
+
+    // one following itself for the same value is reported
+    let looping = json!({
+        "anyOf": [{ "$ref": "#" }, { "type": "string" }]
+    });
+    assert!(schema::validate(&looping, &json!("text")).is_empty());
+    let violations = schema::validate(&looping, &json!(1));
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].pointer, "");
+}
+
+#[test]
+fn json_output_counts_columns_in_characters_from_one() {
+    let dir = std::env::temp_dir().join("lsp_client_cli_json");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.rs");
+    std::fs::write(&path, "let s = \"😀\"; s.len()\n").unwrap();
+    let uri = Url::from_file_path(&path).unwrap();
+
+    // the `s` of `s.len()`, past an emoji taking two utf-16 code units
+    let location = Location {
+        uri: uri.clone(),
+        range: Range::new(Position::new(0, 14), Position::new(0, 15)),
+    };
+    let printer = Printer::new(PositionEncoding::Utf16);
+    let value = printer.json(&Outcome::Locations(vec![location]));
+    assert_eq!(
+        value,
+        json!({
+            "locations": [{
+                "uri": uri,
+                "path": path,
+                "range": {
+                    "start": { "line": 1, "column": 14 },
+                    "end": { "line": 1, "column": 15 },
+                },
+            }]
+        })
+    );
+    assert_eq!(
+        printer
+            .render(OutputFormat::Text, &Outcome::Locations(Vec::new()))
+            .unwrap(),
+        ""
+    );
+    assert!(printer
+        .render(OutputFormat::Sarif, &Outcome::Locations(Vec::new()))
+        .is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn diffs_keep_three_lines_of_context() {
+    let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+    let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\nn";
+    assert_eq!(
+        diff::unified("old", "new", old, new),
+        "--- old\n+++ new\n\
+         @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+         @@ -11,3 +11,4 @@\n k\n l\n m\n+n\n\\ No newline at end of file\n"
+    );
+    assert_eq!(
+        diff::unified("/dev/null", "new", "", "x\n"),
+        "--- /dev/null\n+++ new\n@@ -0,0 +1 @@\n+x\n"
+    );
+    assert_eq!(diff::unified("old", "new", old, old), "");
+}
+
+#[test]
+fn renames_render_as_a_diff() {
+    let dir = std::env::temp_dir().join("lsp_client_cli_rename");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("shapes.ts");
+    std::fs::write(&path, "class Shape {}\nconst s = new Shape();\n").unwrap();
+    let uri = Url::from_file_path(&path).unwrap();
+    let renamed = Url::from_file_path(dir.join("figures.ts")).unwrap();
+
+    let edit: WorkspaceEdit = serde_json::from_value(json!({
+        "documentChanges": [
+            {
+                "textDocument": { "uri": uri, "version": null },
+                "edits": [
+                    { "range": range(0, 6, 0, 11), "newText": "Figure" },
+                    { "range": range(1, 14, 1, 19), "newText": "Figure" },
+                ],
+            },
+            { "kind": "rename", "oldUri": uri, "newUri": renamed },
+        ]
+    }))
+    .unwrap();
+    let mut printer = Printer::new(PositionEncoding::Utf16);
+    printer.base = Some(dir.clone());
+    assert_eq!(
+        printer.text(&Outcome::Edit(edit)),
+        "rename shapes.ts -> figures.ts\n\
+         --- shapes.ts\n+++ figures.ts\n@@ -1,2 +1,2 @@\n\
+         -class Shape {}\n-const s = new Shape();\n\
+         +class Figure {}\n+const s = new Figure();\n"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn format_checks_print_a_diff_and_fail() {
+    assert_eq!(
+        parse("format --check a.ts b.ts").unwrap().command,
+        Command::Format {
+            files: vec![PathBuf::from("a.ts"), PathBuf::from("b.ts")],
+            check: true,
+        }
+    );
+    assert!(matches!(parse("format"), Err(CliError::Usage(_))));
+    assert!(!format::formatting_options("fn main() {\n\tlet x = 1;\n}\n").insert_spaces);
+    assert!(format::formatting_options("fn main() {}\n").insert_spaces);
+
+    let file = Reformatted {
+        uri: Url::parse("file:///work/main.ts").unwrap(),
+        before: "let a=1\nlet b = 2\n".to_owned(),
+        after: "let a = 1;\nlet b = 2;\n".to_owned(),
+    };
+    let mut printer = Printer::new(PositionEncoding::Utf16);
+    printer.base = Some(PathBuf::from("/work"));
+    let checked = Outcome::Formatted {
+        files: vec![file.clone()],
+        written: false,
+    };
+    assert_eq!(
+        printer.text(&checked),
+        "--- main.ts\n+++ main.ts\n@@ -1,2 +1,2 @@\n\
+         -let a=1\n-let b = 2\n+let a = 1;\n+let b = 2;\n"
+    );
+    assert_eq!(printer.plain(&checked), "main.ts:1:1: not formatted\n");
+    assert!(matches!(format::check(&checked), Err(CliError::Failed(_))));
+
+    let written = Outcome::Formatted {
+        files: vec![file],
+        written: true,
+    };
+    assert_eq!(printer.text(&written), "formatted main.ts\n");
+    assert!(format::check(&written).is_ok());
+}
+
+#[test]
+fn code_actions_are_picked_by_number_or_kind() {
+    let at = |line, column| LineColumn { line, column };
+    assert_eq!(
+        parse("apply-action src/app.ts 3:5-4:1 source.organizeImports")
+            .unwrap()
+            .command,
+        Command::ApplyAction {
+            file: PathBuf::from("src/app.ts"),
+            range: LineColumnRange {
+                start: at(3, 5),
+                end: at(4, 1),
+            },
+            id: ActionId::Kind("source.organizeImports".to_owned()),
+        }
+    );
+    assert_eq!(
+        parse("actions src/app.ts 3:5").unwrap().command,
+        Command::Query(Query::Actions {
+            file: PathBuf::from("src/app.ts"),
+            range: LineColumnRange {
+                start: at(3, 5),
+                end: at(3, 5),
+            },
+        })
+    );
+    for line in [
+        "actions src/app.ts 3:5-2:1",
+        "apply-action src/app.ts 3:5 0",
+        "apply-action src/app.ts 3:5",
+    ] {
+        assert!(matches!(parse(line), Err(CliError::Usage(_))), "{}", line);
+    }
+
+    let actions: Vec<CodeActionOrCommand> = serde_json::from_value(json!([
+        { "title": "Add import", "kind": "quickfix", "isPreferred": true },
+        { "title": "Extract function", "kind": "refactor.extract.function" },
+        { "title": "Extract constant", "kind": "refactor.extract.constant",
+          "disabled": { "reason": "no expression selected" } },
+        { "title": "Organize imports", "command": "organize" },
+    ]))
+    .unwrap();
+    let title = |id: ActionId| actions::pick(&actions, &id).map(actions::action_title);
+    assert_eq!(title(ActionId::Index(2)).unwrap(), "Extract function");
+    assert_eq!(
+        title(ActionId::Kind("quickfix".to_owned())).unwrap(),
+        "Add import"
+    );
+    assert_eq!(
+        title(ActionId::Kind("command".to_owned())).unwrap(),
+        "Organize imports"
+    );
+    assert!(title(ActionId::Kind("refactor".to_owned())).is_err());
+    assert!(title(ActionId::Kind("refactor.ex".to_owned())).is_err());
+    assert!(title(ActionId::Index(5)).is_err());
+
+    let outcome = Outcome::Actions {
+        uri: Url::parse("file:///work/app.ts").unwrap(),
+        range: Range::default(),
+        actions,
+    };
+    assert_eq!(
+        Printer::new(PositionEncoding::Utf16).text(&outcome),
+        "1. Add import [quickfix, preferred]\n\
+         2. Extract function [refactor.extract.function]\n\
+         3. Extract constant [refactor.extract.constant, disabled: no expression selected]\n\
+         4. Organize imports [command]\n"
+    );
+}
+
+#[test]
+fn completions_show_their_kind_and_detail() {
+    assert_eq!(
+        parse("complete src/app.ts 4:9 --resolve").unwrap().command,
+        Command::Query(Query::Complete {
+            file: PathBuf::from("src/app.ts"),
+            at: LineColumn { line: 4, column: 9 },
+            resolve: true,
+        })
+    );
+
+    let list: CompletionList = serde_json::from_value(json!({
+        "isIncomplete": false,
+        "items": [
+            { "label": "push", "kind": 2, "detail": "fn(&mut self, T)",
+              "documentation": { "kind": "markdown", "value": "Appends an element." } },
+            { "label": "len", "kind": 2, "textEdit": { "range": range(3, 8, 3, 8), "newText": "len()" } },
+        ],
+    }))
+    .unwrap();
+    let outcome = Outcome::Completions {
+        uri: Url::parse("file:///work/app.ts").unwrap(),
+        position: Position::new(3, 8),
+        list,
+    };
+    let printer = Printer::new(PositionEncoding::Utf16);
+    assert_eq!(
+        printer.text(&outcome),
+        "push [method] fn(&mut self, T)\n    Appends an element.\nlen [method]\n"
+    );
+    let items = &printer.json(&outcome)["completions"]["items"];
+    assert_eq!(items[1]["insertText"], "len()");
+    assert_eq!(items[0]["documentation"], "Appends an element.");
+}
+
+#[test]
+fn sarif_output_maps_codes_to_rules() {
+    let base = PathBuf::from("/work");
+    let uri = Url::parse("file:///work/src/main.ts").unwrap();
+    let other = Url::parse("file:///work/src/other.ts").unwrap();
+    let diagnostic: Diagnostic = serde_json::from_value(json!({
+        "range": { "start": { "line": 2, "character": 4 }, "end": { "line": 2, "character": 9 } },
+        "severity": 4,
+        "code": 6133,
+        "message": "'value' is declared but its value is never read.",
+        "relatedInformation": [{
+            "location": {
+                "uri": other,
+                "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 1 } },
+            },
+            "message": "imported here",
+        }],
+    }))
+    .unwrap();
+
+    let mut printer = Printer::new(PositionEncoding::Utf16);
+    printer.base = Some(base);
+    printer.tool = Some("typescript".to_owned());
+    let log = printer.sarif(&uri, &[diagnostic.clone(), diagnostic]);
+
+    let run = &log["runs"][0];
+    assert_eq!(log["version"], "2.1.0");
+    assert_eq!(run["tool"]["driver"]["name"], "typescript");
+    assert_eq!(run["tool"]["driver"]["rules"], json!([{ "id": "6133" }]));
+    let result = &run["results"][1];
+    assert_eq!(result["ruleId"], "6133");
+    assert_eq!(result["ruleIndex"], 0);
+    assert_eq!(result["level"], "note");
+    assert_eq!(
+        result["locations"][0]["physicalLocation"],
+        json!({
+            "artifactLocation": { "uri": "src/main.ts", "uriBaseId": "%SRCROOT%" },
+            "region": { "startLine": 3, "startColumn": 5, "endLine": 3, "endColumn": 10 },
+        })
+    );
+    assert_eq!(
+        result["relatedLocations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+        "src/other.ts"
+    );
+    assert_eq!(
+        result["relatedLocations"][0]["message"]["text"],
+        "imported here"
+    );
+}
+
+#[test]
+fn plain_output_prints_one_line_per_diagnostic() {
+    let mut printer = Printer::new(PositionEncoding::Utf16);
+    printer.base = Some(PathBuf::from("/work"));
+    let uri = Url::parse("file:///work/src/lib.rs").unwrap();
+    let diagnostics: Vec<Diagnostic> = serde_json::from_value(json!([
+        {
+            "range": { "start": { "line": 9, "character": 0 }, "end": { "line": 9, "character": 1 } },
+            "severity": 2,
+            "message": "unused variable",
+        },
+        {
+            "range": { "start": { "line": 3, "character": 7 }, "end": { "line": 3, "character": 9 } },
+            "severity": 1,
+            "code": "E0308",
+            "message": "mismatched types\nexpected `u32`, found `&str`",
+        },
+    ]))
+    .unwrap();
+
+    let output = printer
+        .render(
+            OutputFormat::Plain,
+            &Outcome::Diagnostics { uri, diagnostics },
+        )
+        .unwrap();
+    assert_eq!(
+        output,
+        "src/lib.rs:4:8: error[E0308]: mismatched types expected `u32`, found `&str`\n\
+         src/lib.rs:10:1: warning: unused variable\n"
+    );
+}
+
+#[test]
+fn editor_output_counts_columns_the_way_the_editor_does() {
+    let dir = std::env::temp_dir().join(format!("lsp-client-editor-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("lib.rs");
+    std::fs::write(&file, "fn main() {\n\tlet é = 1;\n}\n").unwrap();
+    let uri = Url::from_file_path(&file).unwrap();
+    let mut printer = Printer::new(PositionEncoding::Utf16);
+    printer.base = Some(dir.clone());
+    // `1` on the second line, after a tab and a two byte character
+    let diagnostics: Vec<Diagnostic> = serde_json::from_value(json!([
+        {
+            "range": { "start": { "line": 1, "character": 9 }, "end": { "line": 1, "character": 10 } },
+            "severity": 4,
+            "code": "unused",
+            "message": "literal\nout of place",
+        },
+    ]))
+    .unwrap();
+    let outcome = Outcome::Diagnostics {
+        uri: uri.clone(),
+        diagnostics,
+    };
+
+    assert_eq!(
+        printer.render(OutputFormat::VimQuickfix, &outcome).unwrap(),
+        "lib.rs:2:11: note[unused]: literal out of place\n"
+    );
+    assert_eq!(
+        printer.render(OutputFormat::Emacs, &outcome).unwrap(),
+        "lib.rs:2.17-17: note[unused]: literal out of place\n"
+    );
+    let locations = Outcome::Locations(vec![Location::new(
+        uri,
+        Range::new(Position::new(0, 3), Position::new(1, 1)),
+    )]);
+    assert_eq!(
+        printer.render(OutputFormat::Emacs, &locations).unwrap(),
+        "lib.rs:1.4-2.8: fn main() {\n"
+    );
+    assert!(matches!(
+        printer.render(OutputFormat::VimQuickfix, &Outcome::Checks(Vec::new())),
+        Err(CliError::Usage(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn context_shows_the_source_with_the_range_underlined() {
+    let dir = std::env::temp_dir().join(format!("lsp-client-context-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("lib.rs");
+    std::fs::write(&file, "fn main() {\n\tlet x = parse(\n\t\t1);\n}\n").unwrap();
+    let uri = Url::from_file_path(&file).unwrap();
+    let mut printer = Printer::new(PositionEncoding::Utf16);
+    printer.base = Some(dir.clone());
+    printer.style = Style {
+        color: false,
+        context: Some(1),
+    };
+    let diagnostics: Vec<Diagnostic> = serde_json::from_value(json!([
+        {
+            "range": { "start": { "line": 1, "character": 9 }, "end": { "line": 2, "character": 4 } },
+            "severity": 1,
+            "message": "mismatched types",
+        },
+    ]))
+    .unwrap();
+    let outcome = Outcome::Diagnostics {
+        uri: uri.clone(),
+        diagnostics,
+    };
+    assert_eq!(
+        printer.render(OutputFormat::Text, &outcome).unwrap(),
+        "error: mismatched types\n  --> lib.rs:2:10\n  \
+         |\n\
+         1 | fn main() {\n\
+         2 |     let x = parse(\n  \
+         |             ^^^^^^\n\
+         3 |         1);\n  \
+         |         ^^\n\
+         4 | }\n"
+    );
+
+    printer.style.color = true;
+    let hover = Outcome::Hover {
+        uri,
+        position: Position::new(0, 3),
+        hover: serde_json::from_value(json!({ "contents": "fn main()" })).unwrap(),
+    };
+    let output = printer.render(OutputFormat::Text, &hover).unwrap();
+    assert!(output.contains("\x1b[1;32m^\x1b[0m"), "{:?}", output);
+    assert!(output.ends_with("fn main()\n"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn repl_lines_parse_into_commands() {
+    assert_eq!(
+        repl::parse_line("def src/app.ts 10 4").unwrap(),
+        ReplCommand::Query(Query::Definition {
+            file: PathBuf::from("src/app.ts"),
+            target: Target::At(LineColumn {
+                line: 10,
+                column: 4
+            }),
+        })
+    );
+    assert_eq!(
+        repl::parse_line(r#"raw {"jsonrpc": "2.0", "id": 1, "method": "workspace/symbol", "params": {"query": "App"}}"#)
+            .unwrap(),
+        ReplCommand::Raw {
+            method: "workspace/symbol".to_owned(),
+            params: json!({ "query": "App" }),
+            request: true,
+        }
+    );
+    assert_eq!(repl::parse_line("  ").unwrap(), ReplCommand::Empty);
+    assert_eq!(repl::parse_line("!3").unwrap(), ReplCommand::Rerun(3));
+    assert!(repl::parse_line("def src/app.ts 10").is_err());
+}
+
+#[test]
+fn scripts_parse_commands_and_raw_messages() {
+    let steps = script::parse_script(
+        r#"// find the definition, then ask tsserver directly
+{"command": "definition", "file": "src/app.ts", "line": 10, "column": 4}
+
+{"command": "references", "file": "src/app.ts", "line": 10, "column": 4, "includeDeclaration": false}
+{"jsonrpc": "2.0", "method": "workspace/didChangeConfiguration", "params": {"settings": {}}}"#,
+    )
+    .unwrap();
+    assert_eq!(steps.len(), 3);
+    assert_eq!(steps[0].line, 2);
+    assert_eq!(steps[0].file(), Some(std::path::Path::new("src/app.ts")));
+    assert_eq!(
+        steps[1].command,
+        ReplCommand::Query(Query::References {
+            file: PathBuf::from("src/app.ts"),
+            target: Target::At(LineColumn {
+                line: 10,
+                column: 4
+            }),
+            include_declaration: false,
+        })
+    );
+    assert!(matches!(
+        steps[2].command,
+        ReplCommand::Raw { request: false, .. }
+    ));
+
+    let err = script::parse_script(
+        "{\"command\": \"hover\", \"file\": \"a.ts\", \"line\": 0, \"column\": 1}",
+    )
+    .unwrap_err();
+    assert!(err.message().starts_with("line 1: invalid position"));
+}
+
+#[test]
+fn proxy_options_collect_dropped_methods() {
+    let invocation =
+        parse("proxy --drop textDocument/hover --log traffic.jsonl --drop $/progress").unwrap();
+    assert_eq!(
+        invocation.command,
+        Command::Proxy(ProxyOptions {
+            log: Some(PathBuf::from("traffic.jsonl")),
+            drop: vec!["textDocument/hover".to_owned(), "$/progress".to_owned()],
+            inject: None,
+            ..ProxyOptions::default()
+        })
+    );
+    assert_eq!(invocation.command.file(), None);
+}
+
+#[test]
+fn bridges_rewrite_the_traffic_both_ways() {
+    let invocation =
+        parse("bridge --min-severity warning --hide-code E0001 --map-path /home/me/app=/work")
+            .unwrap();
+    let Command::Bridge(ProxyOptions { mut transforms, .. }) = invocation.command else {
+        panic!("expected a bridge, got {:?}", invocation.command);
+    };
+    assert!(parse("bridge --map-path app=/work").is_err());
+    transforms.initialization_options = Some(json!({ "cargo": { "features": "all" } }));
+
+    let mut initialize = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "rootUri": "file:///home/me/app",
+            "initializationOptions": { "cargo": { "target": "x86" }, "checkOnSave": true },
+            "workspaceFolders": [{ "uri": "file:///home/me/app2", "name": "app2" }],
+        },
+    });
+    transforms.from_editor(&mut initialize);
+    assert_eq!(initialize["params"]["rootUri"], "file:///work");
+    // a sibling folder is left alone
+    assert_eq!(
+        initialize["params"]["workspaceFolders"][0]["uri"],
+        "file:///home/me/app2"
+    );
+    assert_eq!(
+        initialize["params"]["initializationOptions"],
+        json!({ "cargo": { "target": "x86", "features": "all" }, "checkOnSave": true })
+    );
+
+    let diagnostic = |severity: u8, code: &str| {
+        json!({
+            "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 1 } },
+            "severity": severity,
+            "code": code,
+            "message": code,
+        })
+    };
+    let mut published = json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {
+            "uri": "file:///work/src/main.rs",
+            "diagnostics": [diagnostic(1, "E0308"), diagnostic(1, "E0001"), diagnostic(3, "I1"), diagnostic(2, "W1")],
+        },
+    });
+    transforms.from_server(&mut published);
+    assert_eq!(
+        published["params"]["uri"],
+        "file:///home/me/app/src/main.rs"
+    );
+    let codes: Vec<&Value> = published["params"]["diagnostics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|diagnostic| &diagnostic["code"])
+        .collect();
+    assert_eq!(codes, ["E0308", "W1"]);
+}
+
+#[test]
+fn diagnostics_can_be_watched() {
+    let invocation = parse("diagnostics src/lib.rs --watch").unwrap();
+    assert_eq!(
+        invocation.command,
+        Command::Query(Query::Diagnostics {
+            file: PathBuf::from("src/lib.rs"),
+            watch: true,
+            fail_on: None,
+            changed_since: None,
+        })
+    );
+}
+
+#[test]
+fn diagnostics_fail_at_the_given_severity() {
+    let Command::Query(query) = parse("diagnostics src/lib.rs --fail-on warning")
+        .unwrap()
+        .command
+    else {
+        panic!("expected a query");
+    };
+    assert!(parse("diagnostics src/lib.rs --fail-on severe").is_err());
+    assert!(parse("diagnostics src/lib.rs --fail-on error --watch").is_err());
+
+    let diagnostic = |severity| Diagnostic {
+        severity,
+        ..Diagnostic::new_simple(Range::default(), "found".to_owned())
+    };
+    let outcome = |diagnostics| Outcome::Diagnostics {
+        uri: Url::parse("file:///src/lib.rs").unwrap(),
+        diagnostics,
+    };
+    let hints = outcome(vec![diagnostic(Some(DiagnosticSeverity::HINT))]);
+    assert!(lsp_client::cli::check_severity(&query, &hints).is_ok());
+
+    // without a severity a diagnostic counts as an error
+    let failing = outcome(vec![
+        diagnostic(Some(DiagnosticSeverity::WARNING)),
+        diagnostic(None),
+        diagnostic(Some(DiagnosticSeverity::INFORMATION)),
+    ]);
+    let err = lsp_client::cli::check_severity(&query, &failing).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "2 diagnostics of severity warning or worse"
+    );
+    assert_eq!(err.exit_code(), 1);
+}
+
+#[test]
+fn several_configs_route_files_to_their_servers() {
+    let invocation =
+        parse("diagnostics src/lib.rs web/app.ts --config rust.json,web.json --fail-on error")
+            .unwrap();
+    assert_eq!(
+        invocation.options.config,
+        [PathBuf::from("rust.json"), PathBuf::from("web.json")]
+    );
+    assert_eq!(
+        invocation.command,
+        Command::DiagnosticsReport {
+            files: vec![PathBuf::from("src/lib.rs"), PathBuf::from("web/app.ts")],
+            fail_on: Some(DiagnosticSeverity::ERROR),
+            changed_since: None,
+        }
+    );
+    assert!(parse("diagnostics a.rs b.rs --watch").is_err());
+
+    let config = |json| serde_json::from_value::<Config>(json).unwrap();
+    let rust = config(json!({ "server": "rust-analyzer" }));
+    assert!(rust.handles(&PathBuf::from("src/lib.rs")));
+    assert!(!rust.handles(&PathBuf::from("web/app.ts")));
+    let web = config(json!({ "server": "my-server", "extensions": ["ts", "tsx"] }));
+    assert!(web.handles(&PathBuf::from("web/app.ts")));
+    assert!(!web.handles(&PathBuf::from("src/lib.rs")));
+    // without a server, the server is picked by the file
+    assert!(config(json!({})).handles(&PathBuf::from("main.py")));
+
+    let diagnostic = |code: &str| Diagnostic {
+        code: Some(lsp_types::NumberOrString::String(code.to_owned())),
+        ..Diagnostic::new_simple(Range::default(), "found".to_owned())
+    };
+    let printer = Printer::new(PositionEncoding::Utf16);
+    let log = printer.sarif_report(&[
+        (
+            Url::parse("file:///work/src/lib.rs").unwrap(),
+            vec![diagnostic("E0308")],
+        ),
+        (
+            Url::parse("file:///work/web/app.ts").unwrap(),
+            vec![diagnostic("2322"), diagnostic("E0308")],
+        ),
+    ]);
+    let run = &log["runs"][0];
+    assert_eq!(log["runs"].as_array().unwrap().len(), 1);
+    assert_eq!(
+        run["tool"]["driver"]["rules"],
+        json!([{ "id": "E0308" }, { "id": "2322" }])
+    );
+    assert_eq!(run["results"][2]["ruleIndex"], 0);
+}
+
+#[test]
+fn capabilities_list_the_supported_requests() {
+    assert_eq!(
+        parse("capabilities").unwrap().command,
+        Command::Capabilities { file: None }
+    );
+    assert!(parse("capabilities a.rs b.rs").is_err());
+
+    let announced = serde_json::from_value(json!({
+        "textDocumentSync": 2,
+        "hoverProvider": true,
+        "completionProvider": { "triggerCharacters": [".", ":"] },
+        "renameProvider": { "prepareProvider": true },
+        "executeCommandProvider": { "commands": ["fix.all"] },
+    }))
+    .unwrap();
+    let outcome = Outcome::Capabilities {
+        server_info: Some(ServerInfo {
+            name: "my-server".to_owned(),
+            version: Some("1.2.0".to_owned()),
+        }),
+        capabilities: Box::new(Capabilities::new(announced, Vec::new())),
+    };
+    let printer = Printer::new(PositionEncoding::Utf16);
+    assert_eq!(
+        printer.text(&outcome),
+        "server: my-server 1.2.0\n\
+         position encoding: utf-16\n\
+         document sync: incremental\n\
+         completion triggers: . :\n\
+         requests:\n    \
+         textDocument/completion\n    \
+         textDocument/hover\n    \
+         textDocument/rename\n    \
+         textDocument/prepareRename\n    \
+         workspace/executeCommand\n\
+         commands:\n    \
+         fix.all\n"
+    );
+    let json = printer.json(&outcome);
+    assert_eq!(json["capabilities"]["serverInfo"]["version"], "1.2.0");
+    assert_eq!(json["capabilities"]["announced"]["hoverProvider"], true);
+    assert_eq!(json["capabilities"]["supported"][1], "textDocument/hover");
+}
+
+#[tokio::test]
+async fn doctor_stops_at_the_first_failing_check() {
+    assert!(doctor::find_program("sh").is_some());
+    assert!(doctor::find_program("./no/such/server").is_none());
+
+    let run = |server: &str, root: &str| {
+        let server = vec![server.to_owned()];
+        let root = PathBuf::from(root);
+        async move { doctor::run(&server, &root, None, Timeouts::default(), None).await }
+    };
+    let outcome = run("gopls-missing", "/no/such/root").await;
+    let Outcome::Checks(checks) = &outcome else {
+        panic!("expected checks");
+    };
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0].name, "root");
+    assert!(doctor::check(&outcome).is_err());
+
+    let root = std::env::temp_dir();
+    let outcome = run("rust-analyzer-missing", root.to_str().unwrap()).await;
+    let Outcome::Checks(checks) = &outcome else {
+        panic!("expected checks");
+    };
+    assert!(checks[0].passed);
+    assert_eq!(
+        checks[1],
+        Check {
+            name: "server",
+            passed: false,
+            detail: "rust-analyzer-missing is not found".to_owned(),
+            hint: Some(
+                "install it, or point --server or the server of the config at it".to_owned()
+            ),
+        }
+    );
+    assert_eq!(doctor::check(&outcome).unwrap_err().exit_code(), 1);
+}
+
+#[test]
+fn recordings_keep_the_command_line_without_record() {
+    let invocation = parse("hover src/main.rs 3:5 --record session.json --output json").unwrap();
+    assert_eq!(
+        invocation.options.record,
+        Some(Record {
+            path: PathBuf::from("session.json"),
+            args: ["hover", "src/main.rs", "3:5", "--output", "json"]
+                .map(str::to_owned)
+                .to_vec(),
+        })
+    );
+    assert_eq!(
+        parse("replay session.json").unwrap().command,
+        Command::Replay {
+            recording: PathBuf::from("session.json")
+        }
+    );
+}
+
+#[tokio::test]
+async fn recordings_are_served_in_place_of_the_server() {
+    let dir = std::env::temp_dir().join("lsp_client_cli_replay");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("session.json");
+    let frame = |from: Sender, message| Frame { from, message };
+    let recording = Recording {
+        args: Vec::new(),
+        frames: vec![
+            frame(
+                Sender::Client,
+                json!({ "jsonrpc": "2.0", "id": 41, "method": "initialize" }),
+            ),
+            frame(
+                Sender::Server,
+                json!({ "jsonrpc": "2.0", "id": 41, "result": {
+                    "capabilities": { "hoverProvider": true, "definitionProvider": true },
+                    "serverInfo": { "name": "recorded" },
+                } }),
+            ),
+            frame(
+                Sender::Client,
+                json!({ "jsonrpc": "2.0", "method": "initialized" }),
+            ),
+            frame(
+                Sender::Client,
+                json!({ "jsonrpc": "2.0", "id": 42, "method": "textDocument/hover" }),
+            ),
+            frame(
+                Sender::Server,
+                json!({ "jsonrpc": "2.0", "id": 42, "result": { "contents": "recorded hover" } }),
+            ),
+        ],
+    };
+    std::fs::write(&path, serde_json::to_string(&recording).unwrap()).unwrap();
+
+    let server = vec![
+        env!("CARGO_BIN_EXE_lsp-client").to_owned(),
+        "serve-recording".to_owned(),
+        path.to_string_lossy().into_owned(),
+    ];
+    let session = Session::start(&server, &dir, None, Timeouts::default(), None, None, false)
+        .await
+        .unwrap();
+    let info = session.server.initialize_result().unwrap().server_info;
+    assert_eq!(info.unwrap().name, "recorded");
+
+    let uri = Url::from_file_path(dir.join("main.rs")).unwrap();
+    let position = Position::new(0, 0);
+    // a request the recording does not expect is refused, the replay waits for the hover
+    let err = session
+        .server
+        .goto(GotoKind::Definition, uri.clone(), position)
+        .await
+        .unwrap_err();
+    assert!(format!("{:?}", err).contains("expects textDocument/hover"));
+    let hover = session.server.hover(uri, position).await.unwrap().unwrap();
+    assert_eq!(
+        hover.contents,
+        HoverContents::Scalar(MarkedString::String("recorded hover".to_owned()))
+    );
+    session.finish().await;
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn frame_logs_record_the_traffic_and_are_served_like_recordings() {
+    let dir = std::env::temp_dir().join(format!("lsp-client-frames-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let recording = Recording {
+        args: Vec::new(),
+        frames: vec![
+            Frame {
+                from: Sender::Client,
+                message: json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize" }),
+            },
+            Frame {
+                from: Sender::Server,
+                message: json!({ "jsonrpc": "2.0", "id": 1, "result": {
+                    "capabilities": { "hoverProvider": true }
+                } }),
+            },
+            Frame {
+                from: Sender::Client,
+                message: json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/hover" }),
+            },
+            Frame {
+                from: Sender::Server,
+                message: json!({ "jsonrpc": "2.0", "id": 2, "result": { "contents": "logged" } }),
+            },
+        ],
+    };
+    let recording_path = dir.join("session.json");
+    std::fs::write(&recording_path, serde_json::to_string(&recording).unwrap()).unwrap();
+    let log_path = dir.join("frames.jsonl");
+    let serve = |path: &Path| {
+        vec![
+            env!("CARGO_BIN_EXE_lsp-client").to_owned(),
+            "serve-recording".to_owned(),
+            path.to_string_lossy().into_owned(),
+        ]
+    };
+    let uri = Url::from_file_path(dir.join("main.rs")).unwrap();
+    let position = Position::new(0, 0);
+
+    let trace = Trace::Frames(log_path.clone());
+    let session = Session::start(
+        &serve(&recording_path),
+        &dir,
+        None,
+        Timeouts::default(),
+        Some(&trace),
+        None,
+        false,
+    )
+    .await
+    .unwrap();
+    session.server.hover(uri.clone(), position).await.unwrap();
+    session.finish().await;
+
+    let frames = frame_log::parse(&std::fs::read_to_string(&log_path).unwrap()).unwrap();
+    let hover = frames
+        .iter()
+        .find(|frame| frame.message["method"] == "textDocument/hover")
+        .unwrap();
+    assert_eq!(hover.direction, Direction::Sent);
+    assert_eq!(hover.bytes, hover.message.to_string().len());
+    assert!(hover.timestamp > 0);
+    assert!(frames
+        .iter()
+        .any(|frame| frame.direction == Direction::Received
+            && frame.message["result"]["contents"] == "logged"));
+
+    // the log plays the server back
+    let session = Session::start(
+        &serve(&log_path),
+        &dir,
+        None,
+        Timeouts::default(),
+        None,
+        None,
+        false,
+    )
+    .await
+    .unwrap();
+    let hover = session.server.hover(uri, position).await.unwrap().unwrap();
+    assert_eq!(
+        hover.contents,
+        HoverContents::Scalar(MarkedString::String("logged".to_owned()))
+    );
+    session.finish().await;
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn the_daemon_answers_queries_with_the_server_it_keeps_running() {
+    let dir = std::env::temp_dir().join(format!("lsp-client-daemon-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+    let hover = |id: u64| {
+        [
+            Frame {
+                from: Sender::Client,
+                message: json!({ "jsonrpc": "2.0", "id": id, "method": "textDocument/hover" }),
+            },
+            Frame {
+                from: Sender::Server,
+                message: json!({ "jsonrpc": "2.0", "id": id, "result": {
+                    "contents": format!("hover {}", id)
+                } }),
+            },
+        ]
+    };
+    // a second initialize would be refused, the server is only started once
+    let mut frames = vec![
+        Frame {
+            from: Sender::Client,
+            message: json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize" }),
+        },
+        Frame {
+            from: Sender::Server,
+            message: json!({ "jsonrpc": "2.0", "id": 1, "result": {
+                "capabilities": { "hoverProvider": true }
+            } }),
+        },
+    ];
+    frames.extend(hover(2));
+    frames.extend(hover(3));
+    let recording = Recording {
+        args: Vec::new(),
+        frames,
+    };
+    let path = dir.join("session.json");
+    std::fs::write(&path, serde_json::to_string(&recording).unwrap()).unwrap();
+
+    let socket = dir.join("daemon.sock");
+    let timeouts = Timeouts {
+        index: Duration::ZERO,
+        ..Timeouts::default()
+    };
+    let serving = tokio::spawn({
+        let socket = socket.clone();
+        async move { daemon::serve(&socket, timeouts, None).await }
+    });
+    let request = daemon::Request {
+        server: vec![
+            env!("CARGO_BIN_EXE_lsp-client").to_owned(),
+            "serve-recording".to_owned(),
+            path.to_string_lossy().into_owned(),
+        ],
+        root: dir.clone(),
+        initialization_options: None,
+        query: Query::Hover {
+            file: PathBuf::from("main.rs"),
+            target: Target::At(LineColumn { line: 1, column: 4 }),
+        }
+        .rebased(&dir),
+        output: OutputFormat::Json,
+        style: Style::default(),
+        base: dir.clone(),
+    };
+    let first = loop {
+        match daemon::ask(&socket, &request).await {
+            Some(response) => break response.unwrap(),
+            None => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    };
+    assert_eq!(first.error, None);
+    let output: Value = serde_json::from_str(&first.output).unwrap();
+    assert_eq!(output["hover"]["plainText"], "hover 2");
+    let second = daemon::ask(&socket, &request).await.unwrap().unwrap();
+    assert!(second.result().is_ok());
+    let output: Value = serde_json::from_str(&second.output).unwrap();
+    assert_eq!(output["hover"]["plainText"], "hover 3");
+
+    // without a daemon the query is left to a server of its own
+    assert!(daemon::ask(&dir.join("none.sock"), &request)
+        .await
+        .is_none());
+    serving.abort();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn timeouts_are_given_in_seconds() {
+    let invocation = parse("hover src/lib.rs 1:1 --timeout 2.5 --wait-for-index 0").unwrap();
+    let timeouts = invocation.options.timeouts;
+    assert_eq!(timeouts.request, Duration::from_millis(2500));
+    assert_eq!(timeouts.index, Duration::ZERO);
+    assert_eq!(timeouts.startup, Timeouts::default().startup);
+
+    for line in [
+        "hover src/lib.rs 1:1 --timeout soon",
+        "hover src/lib.rs 1:1 --startup-timeout -1",
+    ] {
+        let err = parse(line).unwrap_err();
+        assert!(
+            err.message().contains("expects a number of seconds"),
+            "{}",
+            line
+        );
+    }
+}
+
+#[test]
+fn traffic_is_traced_to_stderr_or_a_file() {
+    let invocation = parse("symbols src/lib.rs --trace-lsp").unwrap();
+    assert_eq!(invocation.options.trace, Some(Trace::Stderr));
+    let invocation = parse("--trace-lsp=lsp.log symbols src/lib.rs").unwrap();
+    assert_eq!(
+        invocation.options.trace,
+        Some(Trace::File(PathBuf::from("lsp.log")))
+    );
+    assert!(parse("symbols src/lib.rs --trace-lsp=").is_err());
+}
+
+#[test]
+fn symbol_inventories_render_as_csv() {
+    let mut printer = Printer::new(PositionEncoding::Utf16);
+    printer.base = Some(PathBuf::from("/work"));
+    let uri = Url::parse("file:///work/src/shapes.rs").unwrap();
+    let response: DocumentSymbolResponse = serde_json::from_value(json!([{
+        "name": "Shape",
+        "kind": 11,
+        "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 4, "character": 1 } },
+        "selectionRange": { "start": { "line": 0, "character": 10 }, "end": { "line": 0, "character": 15 } },
+        "children": [{
+            "name": "scale(x, y)",
+            "kind": 6,
+            "range": { "start": { "line": 2, "character": 4 }, "end": { "line": 2, "character": 30 } },
+            "selectionRange": { "start": { "line": 2, "character": 7 }, "end": { "line": 2, "character": 12 } },
+        }],
+    }]))
+    .unwrap();
+    let outcome = Outcome::Inventory(vec![(uri, symbols::normalize_document_symbols(response))]);
+
+    assert_eq!(
+        printer.render(OutputFormat::Csv, &outcome).unwrap(),
+        "path,name,kind,container,line,column,end_line\n\
+         src/shapes.rs,Shape,interface,,1,11,5\n\
+         src/shapes.rs,\"scale(x, y)\",method,Shape,3,8,3\n"
+    );
+    let json = printer.json(&outcome);
+    assert_eq!(json["inventory"][1]["container"], json!(["Shape"]));
+    assert!(printer
+        .render(OutputFormat::Csv, &Outcome::Locations(Vec::new()))
+        .is_err());
+
+    let invocation = parse("dump-symbols src/a.rs src/b.rs --output csv").unwrap();
+    assert_eq!(
+        invocation.command,
+        Command::DumpSymbols {
+            files: vec![PathBuf::from("src/a.rs"), PathBuf::from("src/b.rs")]
+        }
+    );
+}
+
+#[test]
+fn the_workspace_is_crawled_for_the_files_of_the_server() {
+    let dir = std::env::temp_dir().join("lsp_client_cli_crawl");
+    let _ = std::fs::remove_dir_all(&dir);
+    for file in [
+        "src/main.rs",
+        "src/util/mod.rs",
+        "target/debug/build.rs",
+        ".git/hooks.rs",
+        "README.md",
+    ] {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+
+    let files = inventory::workspace_files(&dir, &["rust-analyzer".to_owned()], None).unwrap();
+    assert_eq!(
+        files,
+        [dir.join("src/main.rs"), dir.join("src/util/mod.rs")]
+    );
+    let err = inventory::workspace_files(&dir, &["my-server".to_owned()], None).unwrap_err();
+    assert!(matches!(err, CliError::Usage(_)));
+    let extensions = ["md".to_owned()];
+    let files =
+        inventory::workspace_files(&dir, &["my-server".to_owned()], Some(&extensions)).unwrap();
+    assert_eq!(files, [dir.join("README.md")]);
+}
+
+#[tokio::test]
+async fn call_graphs_render_as_dot() {
+    let item = |name: &str, line: u32| CallHierarchyItem {
+        name: name.to_owned(),
+        kind: SymbolKind::FUNCTION,
+        tags: None,
+        detail: None,
+        uri: Url::parse("file:///work/src/main.rs").unwrap(),
+        range: Range::new(Position::new(line, 0), Position::new(line + 2, 1)),
+        selection_range: Range::new(Position::new(line, 3), Position::new(line, 7)),
+        data: None,
+    };
+    let call = |line| Range::new(Position::new(line, 4), Position::new(line, 9));
+    let graph = CallGraph::expand(
+        vec![item("main", 0)],
+        CallDirection::Outgoing,
+        3,
+        |caller| {
+            let callees = match caller.name.as_str() {
+                "main" => vec![(item("run", 5), vec![call(1), call(2)])],
+                _ => Vec::new(),
+            };
+            async move { Ok(callees) }
+        },
+    )
+    .await
+    .unwrap();
+    let mut printer = Printer::new(PositionEncoding::Utf16);
+    printer.base = Some(PathBuf::from("/work"));
+
+    assert_eq!(
+        printer
+            .render(OutputFormat::Dot, &Outcome::CallGraph(graph.clone()))
+            .unwrap(),
+        "digraph calls {\n    rankdir=LR;\n    node [shape=box];\n    \
+         n0 [label=\"main\\nsrc/main.rs:1:4\", style=bold];\n    \
+         n1 [label=\"run\\nsrc/main.rs:6:4\"];\n    \
+         n0 -> n1 [label=\"2\"];\n}\n"
+    );
+    assert_eq!(
+        printer
+            .render(OutputFormat::Plain, &Outcome::CallGraph(graph))
+            .unwrap(),
+        "src/main.rs:2:5: main -> run\nsrc/main.rs:3:5: main -> run\n"
+    );
+
+    let invocation = parse("callgraph src/main.rs --symbol main --incoming --depth 1").unwrap();
+    assert_eq!(
+        invocation.command,
+        Command::Query(Query::CallGraph {
+            file: PathBuf::from("src/main.rs"),
+            target: Target::Symbol("main".to_owned()),
+            direction: CallDirection::Incoming,
+            depth: 1,
+        })
+    );
+}
+
+#[test]
+fn only_public_symbols_are_checked_for_use() {
+    assert!(dead_code::is_public(
+        "rust",
+        "pub(crate) fn ",
+        "parse",
+        false
+    ));
+    assert!(!dead_code::is_public(
+        "rust",
+        "/// public API\nfn ",
+        "parse",
+        false
+    ));
+    assert!(dead_code::is_public(
+        "typescript",
+        "export function ",
+        "render",
+        false
+    ));
+    assert!(!dead_code::is_public(
+        "typescript",
+        "private ",
+        "render",
+        true
+    ));
+    assert!(dead_code::is_public("go", "func ", "Render", false));
+    assert!(!dead_code::is_public("python", "def ", "_render", false));
+
+    let text = "pub struct Shape;\n\
+                impl Shape {\n    pub fn area() {}\n    fn scale() {}\n}\n\
+                impl Display for Shape {\n    fn fmt() {}\n}\n\
+                pub fn main() {}\n";
+    let response: DocumentSymbolResponse = serde_json::from_value(json!([
+        { "name": "Shape", "kind": 23, "range": range(0, 0, 0, 17), "selectionRange": range(0, 11, 0, 16) },
+        { "name": "impl Shape", "kind": 19, "range": range(1, 0, 4, 1), "selectionRange": range(1, 5, 1, 10), "children": [
+            { "name": "area", "kind": 6, "range": range(2, 4, 2, 20), "selectionRange": range(2, 11, 2, 15) },
+            { "name": "scale", "kind": 6, "range": range(3, 4, 3, 17), "selectionRange": range(3, 7, 3, 12) },
+        ] },
+        { "name": "impl Display for Shape", "kind": 19, "range": range(5, 0, 7, 1), "selectionRange": range(5, 17, 5, 22), "children": [
+            { "name": "fmt", "kind": 6, "range": range(6, 4, 6, 15), "selectionRange": range(6, 7, 6, 10) },
+        ] },
+        { "name": "main", "kind": 12, "range": range(8, 0, 8, 16), "selectionRange": range(8, 7, 8, 11) },
+    ]))
+    .unwrap();
+    let symbols = symbols::normalize_document_symbols(response);
+    let names: Vec<&str> = dead_code::candidates("rust", text, PositionEncoding::Utf16, &symbols)
+        .into_iter()
+        .map(|symbol| symbol.name.as_str())
+        .collect();
+    assert_eq!(names, ["Shape", "area"]);
+}
+
+#[test]
+fn benchmarks_report_latency_percentiles_per_method() {
+    let invocation =
+        parse("bench src/main.rs 3:7 5:1 --methods hover,definition --iterations 4").unwrap();
+    assert_eq!(
+        invocation.command,
+        Command::Bench {
+            file: PathBuf::from("src/main.rs"),
+            positions: vec![
+                LineColumn { line: 3, column: 7 },
+                LineColumn { line: 5, column: 1 }
+            ],
+            methods: vec![BenchMethod::Hover, BenchMethod::Definition],
+            iterations: 4,
+        }
+    );
+    assert!(parse("bench src/main.rs").is_err());
+    assert!(parse("bench src/main.rs 1:1 --methods rename").is_err());
+
+    let latencies = Latencies {
+        method: "textDocument/hover",
+        samples: (1..=10).map(Duration::from_millis).collect(),
+        failed: 2,
+    };
+    assert_eq!(latencies.percentile(50.0), Some(Duration::from_millis(5)));
+    assert_eq!(latencies.percentile(90.0), Some(Duration::from_millis(9)));
+    assert_eq!(latencies.percentile(99.0), Some(Duration::from_millis(10)));
+    let printer = Printer::new(PositionEncoding::Utf16);
+    let outcome = Outcome::Bench(vec![
+        latencies,
+        Latencies {
+            method: "textDocument/completion",
+            samples: Vec::new(),
+            failed: 3,
+        },
+    ]);
+    let text = printer.text(&outcome);
+    let lines: Vec<Vec<&str>> = text
+        .lines()
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    assert_eq!(
+        lines[0],
+        ["method", "requests", "failed", "min", "p50", "p90", "p99", "max"]
+    );
+    assert_eq!(
+        lines[1],
+        [
+            "textDocument/hover",
+            "12",
+            "2",
+            "1.0ms",
+            "5.0ms",
+            "9.0ms",
+            "10.0ms",
+            "10.0ms"
+        ]
+    );
+    assert_eq!(
+        lines[2],
+        ["textDocument/completion", "3", "3", "-", "-", "-", "-", "-"]
+    );
+    let json = printer.json(&outcome);
+    assert_eq!(json["bench"][0]["milliseconds"]["mean"], json!(5.5));
+    assert_eq!(json["bench"][1]["milliseconds"]["p50"], Value::Null);
+}
+
+#[test]
+fn cached_symbols_are_found_until_their_file_changes() {
+    let dir = std::env::temp_dir().join(format!("lsp-client-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let cached = |name: &str| {
+        let path = dir.join(name);
+        std::fs::write(&path, "struct Shape;\nimpl Shape { fn scale() {} }\n").unwrap();
+        let response: DocumentSymbolResponse = serde_json::from_value(json!([{
+            "name": "Shape",
+            "kind": 23,
+            "range": range(0, 0, 1, 27),
+            "selectionRange": range(0, 7, 0, 12),
+            "children": [{
+                "name": "scale",
+                "kind": 6,
+                "range": range(1, 13, 1, 25),
+                "selectionRange": range(1, 16, 1, 21),
+            }],
+        }]))
+        .unwrap();
+        CachedFile {
+            uri: Url::from_file_path(&path).unwrap(),
+            modified: std::fs::metadata(&path).unwrap().modified().unwrap(),
+            encoding: PositionEncoding::Utf16,
+            symbols: symbols::normalize_document_symbols(response),
+        }
+    };
+
+    let mut cache = SymbolCache::default();
+    cache.update(vec![cached("a.rs"), cached("b.rs")]);
+    cache.save(&dir).unwrap();
+    let mut cache = SymbolCache::load(&dir);
+    assert!(dir.join(CACHE_FILE).is_file());
+    let found = cache.find(&["Shape", "scale"]);
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].1.selection_range.start, Position::new(1, 16));
+
+    // symbols of a changed file are stale, those of a deleted one dropped
+    std::fs::write(dir.join("a.rs"), "").unwrap();
+    let modified = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+    std::fs::File::options()
+        .write(true)
+        .open(dir.join("a.rs"))
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    let found = cache.find(&["scale"]);
+    assert_eq!(found.len(), 1);
+    assert!(found[0].0.uri.path().ends_with("/b.rs"));
+    std::fs::remove_file(dir.join("b.rs")).unwrap();
+    cache.update(Vec::new());
+    assert_eq!(cache.files.len(), 1);
+    assert!(SymbolCache::load(&dir.join("missing")).files.is_empty());
+}
+
+#[test]
+fn cached_results_are_kept_until_the_workspace_changes() {
+    let dir = std::env::temp_dir().join(format!("lsp-client-results-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+    let extensions = ["rs".to_owned()];
+    let cache = ResultCache::new(&dir, &["rust-analyzer".to_owned()], Some(&extensions));
+    let params = json!({ "query": "main" });
+
+    let contents = cache.workspace_contents().unwrap();
+    assert_eq!(
+        cache.get::<Value>(&contents, "workspace/symbol", &params),
+        None
+    );
+    cache.put(&contents, "workspace/symbol", &params, &json!(["main"]));
+    assert!(dir.join(RESULTS_DIR).is_dir());
+    assert_eq!(
+        cache.get::<Value>(&contents, "workspace/symbol", &params),
+        Some(json!(["main"]))
+    );
+    // another query, another server or other files miss
+    assert_eq!(
+        cache.get::<Value>(&contents, "workspace/symbol", &json!({ "query": "other" })),
+        None
+    );
+    let other = ResultCache::new(&dir, &["other-server".to_owned()], Some(&extensions));
+    assert_eq!(
+        other.get::<Value>(&contents, "workspace/symbol", &params),
+        None
+    );
+    // files the server does not handle leave the workspace unchanged
+    std::fs::write(dir.join("notes.txt"), "").unwrap();
+    assert_eq!(cache.workspace_contents(), Some(contents.clone()));
+    std::fs::write(dir.join("main.rs"), "fn main() { run() }\n").unwrap();
+    assert_ne!(cache.workspace_contents(), Some(contents));
+    assert_ne!(results::contents("a"), results::contents("b"));
+    assert!(
+        parse("--no-cache highlight main.rs")
+            .unwrap()
+            .options
+            .no_cache
+    );
+}
+
+#[test]
+fn cached_results_are_checked_and_pruned() {
+    let dir = std::env::temp_dir().join(format!("lsp-client-pruned-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let cache = ResultCache::new(&dir, &["rust-analyzer".to_owned()], None);
+    let params = json!({ "query": "main" });
+    let contents = results::contents("fn main() {}");
+
+    cache.put(&contents, "workspace/symbol", &params, &json!(["main"]));
+    let entries = || -> Vec<_> {
+        std::fs::read_dir(dir.join(RESULTS_DIR))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect()
+    };
+    let listed = entries();
+    let [entry] = &listed[..] else {
+        panic!("expected one entry");
+    };
+    // an entry for another key, e.g. written by another version, is a miss
+    let mut written: Value =
+        serde_json::from_str(&std::fs::read_to_string(entry).unwrap()).unwrap();
+    assert_eq!(written["key"]["method"], json!("workspace/symbol"));
+    written["key"]["params"] = json!({ "query": "other" });
+    std::fs::write(entry, written.to_string()).unwrap();
+    assert_eq!(
+        cache.get::<Value>(&contents, "workspace/symbol", &params),
+        None
+    );
+
+    // entries past their age are removed as others are written
+    let expiring = cache.clone().with_limits(MAX_SIZE, Duration::ZERO);
+    expiring.put(
+        &contents,
+        "workspace/symbol",
+        &json!({ "query": "run" }),
+        &json!([]),
+    );
+    assert_eq!(entries().len(), 1);
+    // and the least recently used past the size
+    let small = cache.with_limits(1, MAX_AGE);
+    small.put(&contents, "workspace/symbol", &params, &json!(["main"]));
+    assert_eq!(entries().len(), 1);
+    assert_eq!(
+        small.get::<Value>(&contents, "workspace/symbol", &params),
+        Some(json!(["main"]))
+    );
+}
+
+#[test]
+fn only_diagnostics_on_changed_lines_are_kept() {
+    let invocation = parse("diagnostics src/lib.rs --changed-only=origin/main").unwrap();
+    let Command::Query(Query::Diagnostics { changed_since, .. }) = invocation.command else {
+        panic!("expected a diagnostics query");
+    };
+    assert_eq!(changed_since.as_deref(), Some("origin/main"));
+    assert!(parse("diagnostics src/lib.rs --changed-only --watch").is_err());
+
+    let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -3 +3 @@ fn parse() {
+-    let x = 1;
++    let x = 2;
+@@ -10,2 +9,0 @@ fn parse() {
+-    a();
+-    b();
+@@ -20,0 +20,3 @@ fn main() {
++    c();
++    d();
++    e();
+";
+    let lines = changes::changed_lines(diff);
+    assert_eq!(lines, [2..3, 19..22]);
+
+    let diagnostic = |start_line, end_line| {
+        Diagnostic::new_simple(
+            Range::new(Position::new(start_line, 0), Position::new(end_line, 4)),
+            "found".to_owned(),
+        )
+    };
+    let mut diagnostics = vec![
+        diagnostic(0, 1),
+        diagnostic(1, 2),
+        diagnostic(8, 8),
+        diagnostic(21, 21),
+        diagnostic(22, 30),
+    ];
+    Changes::Lines(lines).retain(&mut diagnostics);
+    assert_eq!(diagnostics, [diagnostic(1, 2), diagnostic(21, 21)]);
+    assert!(Changes::Whole.touches(&diagnostic(8, 8).range));
+}
+
+#[test]
+fn reports_render_as_a_self_contained_page() {
+    let invocation = parse("report src/a.rs --output html --threshold 1").unwrap();
+    assert_eq!(
+        invocation.command,
+        Command::Report {
+            files: vec![PathBuf::from("src/a.rs")],
+            threshold: 1,
+        }
+    );
+    assert_eq!(invocation.options.output, Some(OutputFormat::Html));
+
+    let mut printer = Printer::new(PositionEncoding::Utf16);
+    printer.base = Some(PathBuf::from("/work"));
+    let uri = Url::parse("file:///work/src/<a>.rs").unwrap();
+    let response: DocumentSymbolResponse = serde_json::from_value(json!([
+        { "name": "Shape", "kind": 23, "range": range(0, 0, 0, 17), "selectionRange": range(0, 11, 0, 16) },
+        { "name": "area", "kind": 12, "range": range(1, 0, 1, 16), "selectionRange": range(1, 7, 1, 11) },
+        { "name": "scale", "kind": 12, "range": range(2, 0, 2, 16), "selectionRange": range(2, 7, 2, 12) },
+    ]))
+    .unwrap();
+    let outcome = Outcome::Analysis(vec![FileAnalysis {
+        uri: uri.clone(),
+        diagnostics: vec![Diagnostic {
+            severity: Some(DiagnosticSeverity::WARNING),
+            ..Diagnostic::new_simple(
+                serde_json::from_value(range(1, 7, 1, 11)).unwrap(),
+                "unused <T> & more".to_owned(),
+            )
+        }],
+        symbols: symbols::normalize_document_symbols(response),
+        unreferenced: Vec::new(),
+    }]);
+
+    let html = printer.render(OutputFormat::Html, &outcome).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(!html.contains("<script") && !html.contains("<link"));
+    assert!(html.contains("<a href=\"#file-1\">src/&lt;a&gt;.rs</a>"));
+    assert!(html.contains("<span class=\"message\">unused &lt;T&gt; &amp; more</span>"));
+    assert!(
+        html.contains("<tr><td>function</td><td class=\"count\">2</td></tr>\n<tr><td>struct</td>")
+    );
+    assert_eq!(
+        printer.text(&outcome),
+        "src/<a>.rs: 1 diagnostic, 3 symbols, 0 unused\n"
+    );
+    assert!(printer
+        .render(OutputFormat::Html, &Outcome::Locations(Vec::new()))
+        .is_err());
+}
+
+#[test]
+fn environment_variables_override_the_config_file_but_not_the_command_line() {
+    let env = |vars: &[(&str, &str)]| {
+        Options::from_env(
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        )
+    };
+    let defaults = env(&[
+        ("LSP_CLIENT_SERVER", "pyright-langserver --stdio"),
+        ("LSP_CLIENT_OUTPUT", "json"),
+        ("LSP_CLIENT_TIMEOUT", "5"),
+        ("LSP_CLIENT_WAIT_FOR_INDEX", "0"),
+        ("PATH", "/usr/bin"),
+    ])
+    .unwrap();
+    let args: Vec<String> = ["symbols", "a.py", "--timeout", "1"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    let options = args::parse_over(defaults, &args).unwrap().options;
+    assert_eq!(options.timeouts.request, Duration::from_secs(1));
+    assert_eq!(options.timeouts.index, Duration::ZERO);
+
+    let config: Config =
+        serde_json::from_value(json!({ "server": "pylsp", "output": "plain" })).unwrap();
+    let settings = Settings::new(options, Some(config)).unwrap();
+    assert_eq!(
+        settings.server,
+        Some(vec!["pyright-langserver".to_owned(), "--stdio".to_owned()])
+    );
+    assert_eq!(settings.output, OutputFormat::Json);
+
+    for vars in [
+        [("LSP_CLIENT_TIMEOUT", "soon")],
+        [("LSP_CLIENT_OUTPUT", "yaml")],
+        [("LSP_CLIENT_SERVR", "pylsp")],
+    ] {
+        let err = env(&vars).unwrap_err();
+        assert!(err.message().contains(vars[0].0), "{}", err.message());
+    }
+}
+
+#[test]
+fn completions_and_the_man_page_follow_the_usage() {
+    let sections = completions::sections();
+    let titles: Vec<_> = sections.iter().map(|section| section.title).collect();
+    assert_eq!(titles, ["commands", "options", "environment"]);
+    for entry in sections.iter().flat_map(|section| &section.entries) {
+        assert!(!entry.term.is_empty(), "{:?}", entry);
+        assert!(!entry.description.is_empty(), "{:?}", entry);
+    }
+    let options = &sections[1].entries;
+    let fail_on = options
+        .iter()
+        .find(|entry| entry.term == "--fail-on <severity>")
+        .unwrap();
+    assert_eq!(
+        fail_on.description,
+        "diagnostics: exits with 1 when there are diagnostics of the severity or worse, \
+         one of error, warning, info, hint"
+    );
+
+    let bash = completions::script(Shell::Bash);
+    assert!(bash.contains("compgen -W \"text plain json"), "{}", bash);
+    assert!(bash.contains("--root) COMPREPLY=($(compgen -d"), "{}", bash);
+    let zsh = completions::script(Shell::Zsh);
+    assert!(zsh.starts_with("#compdef lsp-client\n"));
+    assert!(zsh.contains("'*--color[auto (the default)"), "{}", zsh);
+    let fish = completions::script(Shell::Fish);
+    assert!(
+        fish.contains("-l changed-only -d 'diagnostics: only those"),
+        "{}",
+        fish
+    );
+    for script in [&bash, &zsh, &fish] {
+        for entry in &sections[0].entries {
+            let name = entry.term.split(' ').next().unwrap();
+            assert!(script.contains(name), "{} in {}", name, script);
+        }
+    }
+
+    let page = completions::man_page();
+    assert!(page.starts_with(".TH LSP\\-CLIENT 1 "));
+    assert!(page.contains(".SH OPTIONS\n.TP\n.B \\-\\-config <file>[,...]\n"));
+    assert!(page.contains(".SH NOTES\nlines and columns count from 1"));
+
+    let parse = |args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        args::parse(&args).map(|invocation| invocation.command)
+    };
+    assert_eq!(
+        parse(&["completions", "zsh"]).unwrap(),
+        Command::Completions { shell: Shell::Zsh }
+    );
+    assert_eq!(parse(&["--man"]).unwrap(), Command::Man);
+    assert_eq!(parse(&["hover", "--man"]).unwrap(), Command::Man);
+    assert!(parse(&["completions", "tcsh"]).is_err());
+}
+
+#[test]
+fn progress_lines_show_how_much_is_done() {
+    let mut progress = ServerProgress {
+        title: "Indexing".to_owned(),
+        message: Some("3/12 crates".to_owned()),
+        percentage: Some(25),
+    };
+    assert_eq!(
+        progress::line(&progress),
+        "Indexing [#####               ]  25% 3/12 crates"
+    );
+    progress.percentage = None;
+    assert_eq!(progress::line(&progress), "Indexing 3/12 crates");
+    progress.message = Some("x".repeat(100));
+    assert_eq!(progress::line(&progress).chars().count(), 79);
+
+    let args: Vec<String> = ["--quiet", "symbols", "a.rs"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    assert!(args::parse(&args).unwrap().options.quiet);
+}
+
+#[test]
+fn highlighted_pages_follow_the_semantic_tokens_and_folds() {
+    assert_eq!(
+        parse("highlight src/a.rs --output html").unwrap().command,
+        Command::Highlight {
+            file: PathBuf::from("src/a.rs")
+        }
+    );
+
+    let token =
+        |line, start, end, token_type, modifiers: &[SemanticTokenModifier]| DecodedSemanticToken {
+            range: serde_json::from_value(range(line, start, line, end)).unwrap(),
+            token_type,
+            modifiers: modifiers.to_vec(),
+        };
+    let folds: Vec<FoldingRange> = serde_json::from_value(json!([
+        { "startLine": 1, "endLine": 3 },
+        { "startLine": 1, "endLine": 2 },
+        { "startLine": 2, "endLine": 9 },
+        { "startLine": 3, "endLine": 3 },
+    ]))
+    .unwrap();
+    assert_eq!(highlight::nested_folds(&folds, 4), [(1, 3), (2, 3)]);
+    assert_eq!(highlight::nested_folds(&folds, 5), [(1, 3)]);
+
+    let file = Highlighted {
+        uri: Url::parse("file:///work/a.rs").unwrap(),
+        text: "let s = \"😀<\";\nfn f() {\n    g();\n}\n".to_owned(),
+        tokens: vec![
+            token(0, 0, 3, SemanticTokenType::KEYWORD, &[]),
+            // utf-16, the emoji counts twice
+            token(0, 8, 13, SemanticTokenType::STRING, &[]),
+            token(0, 10, 12, SemanticTokenType::OPERATOR, &[]),
+            token(
+                1,
+                3,
+                4,
+                SemanticTokenType::FUNCTION,
+                &[SemanticTokenModifier::DECLARATION],
+            ),
+        ],
+        folds: serde_json::from_value(json!([{ "startLine": 1, "endLine": 3 }])).unwrap(),
+    };
+    let mut printer = Printer::new(PositionEncoding::Utf16);
+    printer.base = Some(PathBuf::from("/work"));
+    let page = printer.highlighted_html(&file);
+    assert!(page.contains("<title>a.rs</title>"), "{}", page);
+    assert!(
+        page.contains(
+            "<span class=\"line\" id=\"L1\"><span class=\"number\">1</span>\
+             <span class=\"t-keyword\">let</span> s = \
+             <span class=\"t-string\">&quot;😀&lt;&quot;</span>;</span>\n"
+        ),
+        "{}",
+        page
+    );
+    assert!(
+        page.contains(
+            "<details open>\n<summary><span class=\"line\" id=\"L2\">\
+             <span class=\"number\">2</span>fn \
+             <span class=\"t-function m-declaration\">f</span>() {</span>\n</summary>\n"
+        ),
+        "{}",
+        page
+    );
+    assert!(page.contains("}</span>\n</details>\n</div>"), "{}", page);
+
+    let json = printer.json(&Outcome::Highlighted(file));
+    assert_eq!(json["highlighted"]["tokens"][3]["type"], "function");
+    assert_eq!(
+        json["highlighted"]["tokens"][3]["modifiers"],
+        json!(["declaration"])
+    );
+    assert_eq!(json["highlighted"]["folds"][0]["startLine"], 2);
+}
+
+#[test]
+fn explanations_combine_every_answer_about_a_symbol() {
+    assert_eq!(
+        parse("explain src/shape.rs --symbol Shape.area")
+            .unwrap()
+            .command,
+        Command::Query(Query::Explain {
+            file: PathBuf::from("src/shape.rs"),
+            target: Target::Symbol("Shape.area".to_owned()),
+        })
+    );
+
+    let dir = std::env::temp_dir().join(format!("lsp-client-explain-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("shape.rs");
+    std::fs::write(
+        &file,
+        "struct Shape;\nimpl Shape {\n    fn area(&self) -> f64 {\n        self.area()\n    }\n}\n",
+    )
+    .unwrap();
+    let uri = Url::from_file_path(&file).unwrap();
+    let location = |line, start, end| Location {
+        uri: uri.clone(),
+        range: serde_json::from_value(range(line, start, line, end)).unwrap(),
+    };
+    let mut explanation = Explanation {
+        uri: uri.clone(),
+        position: Position::new(3, 13),
+        hover: serde_json::from_value(json!({ "contents": "fn area(&self) -> f64" })).unwrap(),
+        definitions: vec![location(2, 7, 11)],
+        type_definitions: Vec::new(),
+        references: Some(1),
+        enclosing: Some(SymbolAtPoint {
+            name: "area".to_owned(),
+            kind: SymbolKind::METHOD,
+            container_path: vec!["Shape".to_owned()],
+            range: serde_json::from_value(range(2, 4, 4, 5)).unwrap(),
+            selection_range: serde_json::from_value(range(2, 7, 2, 11)).unwrap(),
+        }),
+    };
+    let mut printer = Printer::new(PositionEncoding::Utf16);
+    printer.base = Some(dir.clone());
+    assert_eq!(
+        printer.text(&Outcome::Explanation(explanation.clone())),
+        "shape.rs:4:14, in method Shape.area\n\
+         hover:\n    fn area(&self) -> f64\n\
+         definition:\n    shape.rs:3:8: fn area(&self) -> f64 {\n\
+         type definition:\n    none\n\
+         references: 1\n"
+    );
+
+    explanation.references = None;
+    explanation.enclosing = None;
+    let json = printer.json(&Outcome::Explanation(explanation));
+    assert_eq!(
+        json["explanation"]["position"],
+        json!({ "line": 4, "column": 14 })
+    );
+    assert_eq!(
+        json["explanation"]["definitions"][0]["range"]["start"],
+        json!({ "line": 3, "column": 8 })
+    );
+    assert_eq!(json["explanation"]["typeDefinitions"], json!([]));
+    assert_eq!(json["explanation"]["references"], Value::Null);
+    assert_eq!(json["explanation"]["enclosingSymbol"], Value::Null);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,621 @@
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use lsp_client::lsp::client::{
+    self, ClientError, Direction, ProtocolViolation, RequestTimeouts, UnknownResponsePolicy,
+};
+use lsp_client::lsp::interceptor::{Flow, Interceptor};
+use lsp_client::lsp::parsing::{read_message, write_message};
+use lsp_client::lsp::testing;
+use lsp_types::InitializeParams;
+use serde_json::{json, Value};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+#[tokio::test]
+async fn protocol_violations_are_reported_and_skipped() {
+    let (server, mut end) = testing::pipe();
+    let mut violations = server.protocol_violations();
+
+    let answered = tokio::spawn({
+        let server = server.clone();
+        async move { server.request_value("custom/echo", json!({})).await }
+    });
+    let request = end.receive().await;
+
+    write_message(&mut end.writer, "not json").await.unwrap();
+    let stale = json!({ "jsonrpc": "2.0", "id": 99, "result": "late" });
+    end.send(&stale).await;
+    end.writer
+        .write_all(b"Content-Length: nope\r\n\r\n")
+        .await
+        .unwrap();
+    // the read loop carries on and still answers the request
+    let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": "echo" });
+    end.send(&response).await;
+    assert_eq!(answered.await.unwrap().unwrap(), json!("echo"));
+
+    assert!(matches!(
+        violations.recv().await.unwrap(),
+        ProtocolViolation::Malformed(_)
+    ));
+    assert_eq!(
+        violations.recv().await.unwrap(),
+        ProtocolViolation::UnknownId {
+            id: json!(99),
+            response: json!("late")
+        }
+    );
+    assert!(matches!(
+        violations.recv().await.unwrap(),
+        ProtocolViolation::Malformed(_)
+    ));
+    // the empty line ending the headers of the malformed message
+    assert_eq!(
+        violations.recv().await.unwrap(),
+        ProtocolViolation::Skipped(2)
+    );
+}
+
+#[tokio::test]
+async fn wrong_lengths_and_oversized_messages_are_reported_and_skipped() {
+    let (server, mut end) = testing::pipe();
+    server.set_max_message_size(Some(64));
+    let mut violations = server.protocol_violations();
+    let (tx, mut notified) = tokio::sync::mpsc::unbounded_channel();
+    server.on_notification("custom/note", move |params| {
+        let _ = tx.send(params);
+    });
+
+    let note = json!({ "jsonrpc": "2.0", "method": "custom/note", "params": {} }).to_string();
+    let frame = |body: &str, length: usize| format!("Content-Length: {}\r\n\r\n{}", length, body);
+    let stream = [
+        // runs into the next message, which is lost
+        frame("{\"a\": 1}", 12),
+        frame(&note, note.len()),
+        frame(&note, note.len()),
+        frame(&" ".repeat(100), 100),
+        frame(&note, note.len()),
+    ]
+    .concat();
+    end.writer.write_all(stream.as_bytes()).await.unwrap();
+    notified.recv().await.unwrap();
+    notified.recv().await.unwrap();
+
+    assert_eq!(
+        violations.recv().await.unwrap(),
+        ProtocolViolation::LengthMismatch {
+            declared: 12,
+            json: Some(8)
+        }
+    );
+    assert!(matches!(
+        violations.recv().await.unwrap(),
+        ProtocolViolation::Skipped(_)
+    ));
+    assert_eq!(
+        violations.recv().await.unwrap(),
+        ProtocolViolation::TooLarge {
+            declared: 100,
+            max: 64
+        }
+    );
+    assert!(violations.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn responses_to_no_request_follow_the_policy() {
+    let (server, mut end) = testing::pipe();
+    server.set_unknown_response_policy(UnknownResponsePolicy::Ignore);
+    let mut violations = server.protocol_violations();
+
+    let stale =
+        json!({ "jsonrpc": "2.0", "id": 7, "error": { "code": -32800, "message": "canceled" } });
+    end.send(&stale).await;
+    write_message(&mut end.writer, "not json").await.unwrap();
+    // only the malformed message is reported
+    assert!(matches!(
+        violations.recv().await.unwrap(),
+        ProtocolViolation::Malformed(_)
+    ));
+}
+
+#[tokio::test]
+async fn responses_with_a_result_and_an_error_fail_with_the_error() {
+    let (server, mut end) = testing::pipe();
+    let mut violations = server.protocol_violations();
+
+    for (response, expected) in [
+        (
+            json!({ "result": "ignored", "error": { "code": -32603, "message": "failed" } }),
+            Err(-32603),
+        ),
+        (
+            json!({ "result": "kept", "error": null }),
+            Ok(json!("kept")),
+        ),
+    ] {
+        let answered = tokio::spawn({
+            let server = server.clone();
+            async move { server.request_value("custom/both", json!({})).await }
+        });
+        let request = end.receive().await;
+        let mut response = response;
+        response["jsonrpc"] = json!("2.0");
+        response["id"] = request["id"].clone();
+        end.send(&response).await;
+        match (answered.await.unwrap(), expected) {
+            (Err(ClientError::Server(error)), Err(code)) => {
+                assert_eq!(error.code, code);
+                assert_eq!(
+                    violations.recv().await.unwrap(),
+                    ProtocolViolation::ResultAndError {
+                        id: request["id"].clone()
+                    }
+                );
+            }
+            (Ok(result), Ok(expected)) => assert_eq!(result, expected),
+            (answer, expected) => panic!("{:?} instead of {:?}", answer, expected),
+        }
+    }
+    assert!(violations.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn closing_fails_pending_requests() {
+    let (server, mut end) = testing::pipe();
+
+    let answered = tokio::spawn({
+        let server = server.clone();
+        async move { server.request_value("custom/slow", json!({})).await }
+    });
+    end.receive().await;
+    server.close().await;
+    assert!(server.is_closed());
+    assert!(matches!(
+        answered.await.unwrap(),
+        Err(ClientError::ClientClosed)
+    ));
+    // nothing is sent once closed, and the writer was shut down
+    assert!(matches!(
+        server.request_value("custom/late", json!({})).await,
+        Err(ClientError::ClientClosed)
+    ));
+    assert!(read_message(&mut end.reader).await.is_err());
+}
+
+#[tokio::test]
+async fn dropping_the_client_stops_its_read_loop() {
+    let (server, mut end) = testing::pipe();
+    drop(server);
+    // both halves of the client end are gone
+    assert!(read_message(&mut end.reader).await.is_err());
+}
+
+/// The input of a server that stopped reading it.
+struct Closed;
+
+impl AsyncWrite for Closed {
+    fn poll_write(self: Pin<&mut Self>, _: &mut Context, _: &[u8]) -> Poll<io::Result<usize>> {
+        Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn requests_fail_once_the_server_stops_reading() {
+    let (client_end, mut server_end) = tokio::io::duplex(64 * 1024);
+    let server = client::connect(client_end, Closed);
+    server.on_unhandled_request(|_, _| Err(jsonrpc_lite::Error::method_not_found()));
+    let (tx, mut notified) = tokio::sync::mpsc::unbounded_channel();
+    server.on_notification("custom/note", move |params| {
+        let _ = tx.send(params);
+    });
+
+    assert!(matches!(
+        server.request_value("custom/lost", json!({})).await,
+        Err(ClientError::Canceled)
+    ));
+    assert!(server.pending_requests().is_empty());
+    // the read loop carries on after failing to answer the server
+    for message in [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "custom/ask", "params": {} }),
+        json!({ "jsonrpc": "2.0", "method": "custom/note", "params": {} }),
+    ] {
+        write_message(&mut server_end, &message.to_string())
+            .await
+            .unwrap();
+    }
+    assert_eq!(notified.recv().await.unwrap(), json!({}));
+}
+
+#[tokio::test]
+async fn pending_requests_are_listed_until_answered() {
+    let (server, mut end) = testing::pipe();
+    assert!(server.pending_requests().is_empty());
+
+    for method in ["custom/first", "custom/second"] {
+        tokio::spawn({
+            let server = server.clone();
+            async move { server.request_value(method, json!({})).await }
+        });
+        end.receive().await;
+    }
+    let pending = server.pending_requests();
+    let methods: Vec<_> = pending
+        .iter()
+        .map(|request| request.method.as_str())
+        .collect();
+    assert_eq!(methods, ["custom/first", "custom/second"]);
+    assert!(pending[0].elapsed >= pending[1].elapsed);
+
+    let response = json!({ "jsonrpc": "2.0", "id": pending[0].id, "result": null });
+    end.send(&response).await;
+    while server.pending_requests().len() > 1 {
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(server.pending_requests()[0].method, "custom/second");
+}
+
+#[tokio::test]
+async fn requests_time_out_by_method() {
+    let (server, mut end) = testing::pipe();
+    server.set_request_timeouts(
+        RequestTimeouts::new(Duration::from_secs(60))
+            .with("custom/quick", Duration::from_millis(10)),
+    );
+    let mut violations = server.protocol_violations();
+
+    assert!(matches!(
+        server.request_value("custom/quick", json!({})).await,
+        Err(ClientError::Timeout)
+    ));
+    // the request is forgotten, and the server told so
+    assert!(server.pending_requests().is_empty());
+    let timed_out = end.receive().await;
+    let cancel = end.receive().await;
+    assert_eq!(cancel["method"], json!("$/cancelRequest"));
+    assert_eq!(cancel["params"], json!({ "id": timed_out["id"] }));
+    // its answer, coming all the same, is no violation
+    let canceled = json!({
+        "jsonrpc": "2.0",
+        "id": timed_out["id"],
+        "error": { "code": -32800, "message": "canceled" },
+    });
+    end.send(&canceled).await;
+
+    // an explicit timeout wins over the method's
+    let answered = tokio::spawn({
+        let server = server.clone();
+        async move {
+            server
+                .request_value_within("custom/quick", json!({}), Duration::from_secs(60))
+                .await
+        }
+    });
+    let request = end.receive().await;
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": "late" });
+    end.send(&response).await;
+    assert_eq!(answered.await.unwrap().unwrap(), json!("late"));
+    assert!(violations.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn bodies_are_decoded_lossily_when_asked() {
+    let (server, mut end) = testing::pipe();
+    server.set_lossy_utf8(true);
+    let mut violations = server.protocol_violations();
+    let (tx, mut paths) = tokio::sync::mpsc::unbounded_channel();
+    server.on_notification("custom/path", move |params: Value| {
+        let _ = tx.send(params["path"].clone());
+    });
+
+    let mut body = br#"{"jsonrpc":"2.0","method":"custom/path","params":{"path":"/tmp/"#.to_vec();
+    body.extend_from_slice(b"\xff\"}}");
+    let mut message = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    message.extend_from_slice(&body);
+    end.writer.write_all(&message).await.unwrap();
+
+    assert_eq!(paths.recv().await.unwrap(), json!("/tmp/\u{FFFD}"));
+    assert_eq!(
+        violations.recv().await.unwrap(),
+        ProtocolViolation::InvalidUtf8 { bytes: body.len() }
+    );
+}
+
+#[tokio::test]
+async fn null_results_are_results_and_shapeless_messages_are_malformed() {
+    let (server, mut end) = testing::pipe();
+    let mut violations = server.protocol_violations();
+
+    let answered = tokio::spawn({
+        let server = server.clone();
+        async move { server.request::<lsp_types::request::Shutdown>(()).await }
+    });
+    let request = end.receive().await;
+    let shapeless = json!({ "jsonrpc": "2.0", "id": request["id"] });
+    end.send(&shapeless).await;
+    let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": null });
+    end.send(&response).await;
+    answered.await.unwrap().unwrap();
+    assert!(matches!(
+        violations.recv().await.unwrap(),
+        ProtocolViolation::Malformed(_)
+    ));
+}
+
+#[tokio::test]
+async fn unhandled_messages_go_to_the_fallback_handlers() {
+    let (server, mut end) = testing::pipe();
+    server.on_unhandled_request(|_, _| Err(jsonrpc_lite::Error::method_not_found()));
+    let (tx, mut unhandled) = tokio::sync::mpsc::unbounded_channel();
+    server.on_unhandled_notification(move |method, _| {
+        let _ = tx.send(method.to_owned());
+    });
+    server.on_notification("custom/claimed", |_| {});
+
+    for message in [
+        json!({ "jsonrpc": "2.0", "method": "custom/claimed", "params": {} }),
+        json!({ "jsonrpc": "2.0", "method": "custom/unclaimed", "params": {} }),
+        json!({ "jsonrpc": "2.0", "id": "s1", "method": "custom/ask", "params": {} }),
+    ] {
+        end.send(&message).await;
+    }
+    assert_eq!(unhandled.recv().await.unwrap(), "custom/unclaimed");
+    let response = end.receive().await;
+    assert_eq!(response["id"], json!("s1"));
+    assert_eq!(response["error"]["code"], json!(-32601));
+    assert!(unhandled.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn strict_validation_reports_deviations_both_ways() {
+    let (server, mut end) = testing::pipe();
+    server.set_strict_validation(true);
+    let mut violations = server.protocol_violations();
+
+    server
+        .send_notification("textDocument/didClose", &json!({}))
+        .await;
+    assert_eq!(
+        violations.recv().await.unwrap(),
+        ProtocolViolation::Invalid {
+            direction: Direction::Sent,
+            method: "textDocument/didClose".to_owned(),
+            error: "missing field `textDocument`".to_owned(),
+        }
+    );
+
+    let diagnostics = json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {
+            "uri": "file:///main.rs",
+            "diagnostics": [{
+                "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": { "line": 0, "character": 1 }
+                },
+                "message": "unused",
+                "severity": 7
+            }],
+            "extra": true
+        }
+    });
+    end.send(&diagnostics).await;
+    let mut errors = Vec::new();
+    for _ in 0..2 {
+        match violations.recv().await.unwrap() {
+            ProtocolViolation::Invalid {
+                direction: Direction::Received,
+                error,
+                ..
+            } => errors.push(error),
+            violation => panic!("unexpected violation: {}", violation),
+        }
+    }
+    assert_eq!(
+        errors,
+        [
+            "unknown field `/extra`",
+            "invalid value 7 for `/diagnostics/0/severity`"
+        ]
+    );
+}
+
+struct Shim;
+
+impl Interceptor for Shim {
+    fn on_outgoing(&self, message: &mut Value) -> Flow {
+        match message["method"].as_str() {
+            Some("custom/cached") => Flow::Respond(Ok(json!("from the cache"))),
+            Some("custom/dropped") => Flow::Drop,
+            _ => {
+                message["params"]["rewritten"] = json!(true);
+                Flow::Continue
+            }
+        }
+    }
+
+    fn on_incoming(&self, message: &mut Value) -> Flow {
+        if message["method"] == "custom/ask" {
+            return Flow::Respond(Err(json!({ "code": -32601, "message": "no" })));
+        }
+        message["params"]["seen"] = json!(true);
+        Flow::Continue
+    }
+}
+
+#[tokio::test]
+async fn interceptors_rewrite_answer_and_drop_messages() {
+    let (server, mut end) = testing::pipe();
+    server.add_interceptor(Shim);
+    let (tx, mut notified) = tokio::sync::mpsc::unbounded_channel();
+    server.on_notification("custom/note", move |params| {
+        let _ = tx.send(params);
+    });
+
+    assert_eq!(
+        server
+            .request_value("custom/cached", json!({}))
+            .await
+            .unwrap(),
+        json!("from the cache")
+    );
+    assert!(matches!(
+        server.request_value("custom/dropped", json!({})).await,
+        Err(ClientError::Canceled)
+    ));
+    server.send_notification("custom/sent", &json!({})).await;
+    // only the notification was written, rewritten
+    let sent = end.receive().await;
+    assert_eq!(sent["method"], json!("custom/sent"));
+    assert_eq!(sent["params"], json!({ "rewritten": true }));
+
+    for message in [
+        json!({ "jsonrpc": "2.0", "id": 7, "method": "custom/ask", "params": {} }),
+        json!({ "jsonrpc": "2.0", "method": "custom/note", "params": {} }),
+    ] {
+        end.send(&message).await;
+    }
+    let answer = end.receive().await;
+    assert_eq!(answer["id"], json!(7));
+    assert_eq!(answer["error"]["code"], json!(-32601));
+    // answers to the server are intercepted too
+    assert_eq!(answer["params"], json!({ "rewritten": true }));
+    assert_eq!(notified.recv().await.unwrap(), json!({ "seen": true }));
+}
+
+#[tokio::test]
+async fn identical_requests_in_flight_share_one_response() {
+    let (server, mut end) = testing::pipe();
+    server.coalesce_requests(&["custom/hover"]);
+    let hover = |params: Value| {
+        let server = server.clone();
+        tokio::spawn(async move { server.request_value("custom/hover", params).await })
+    };
+
+    let first = hover(json!({ "line": 1 }));
+    let request = end.receive().await;
+    let second = hover(json!({ "line": 1 }));
+    let other = hover(json!({ "line": 2 }));
+    // only the request with other params was sent
+    let other_request = end.receive().await;
+    assert_eq!(other_request["params"], json!({ "line": 2 }));
+
+    for (request, result) in [(&request, "one"), (&other_request, "two")] {
+        let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": result });
+        end.send(&response).await;
+    }
+    assert_eq!(first.await.unwrap().unwrap(), json!("one"));
+    assert_eq!(second.await.unwrap().unwrap(), json!("one"));
+    assert_eq!(other.await.unwrap().unwrap(), json!("two"));
+}
+
+#[tokio::test]
+async fn shared_requests_outlive_the_callers_giving_up() {
+    let (server, mut end) = testing::pipe();
+    server.coalesce_requests(&["custom/hover"]);
+    let params = json!({ "line": 1 });
+
+    let first = tokio::spawn({
+        let server = server.clone();
+        let params = params.clone();
+        async move {
+            let timeout = Duration::from_millis(100);
+            server
+                .request_value_within("custom/hover", params, timeout)
+                .await
+        }
+    });
+    let request = end.receive().await;
+    let second = tokio::spawn({
+        let server = server.clone();
+        let params = params.clone();
+        async move { server.request_value("custom/hover", params).await }
+    });
+    assert!(matches!(first.await.unwrap(), Err(ClientError::Timeout)));
+    // still waited for, so not canceled
+    let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": "one" });
+    end.send(&response).await;
+    assert_eq!(second.await.unwrap().unwrap(), json!("one"));
+
+    // canceled once the last caller gives up
+    let timeout = Duration::from_millis(10);
+    let last = server.request_value_within("custom/hover", params, timeout);
+    assert!(matches!(last.await, Err(ClientError::Timeout)));
+    let request = end.receive().await;
+    let cancel = end.receive().await;
+    assert_eq!(cancel["method"], json!("$/cancelRequest"));
+    assert_eq!(cancel["params"]["id"], request["id"]);
+    assert!(server.pending_requests().is_empty());
+}
+
+#[tokio::test]
+async fn edits_sent_while_a_command_runs_are_applied_before_they_are_answered() {
+    let (server, mut end) = testing::pipe();
+
+    let initializing = tokio::spawn({
+        let server = server.clone();
+        async move { server.initialize(InitializeParams::default()).await }
+    });
+    let request = end.receive().await;
+    let capabilities = json!({ "executeCommandProvider": { "commands": ["custom.fix"] } });
+    let response = json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "result": { "capabilities": capabilities },
+    });
+    end.send(&response).await;
+    initializing.await.unwrap().unwrap();
+    // initialized
+    end.receive().await;
+
+    let applied = Arc::new(Mutex::new(Vec::new()));
+    let running = tokio::spawn({
+        let server = server.clone();
+        let applied = applied.clone();
+        async move {
+            let apply = move |edit| {
+                let applied = applied.clone();
+                async move {
+                    applied.lock().unwrap().push(edit);
+                    Err("read-only".to_owned())
+                }
+            };
+            server
+                .execute_command_with("custom.fix", Vec::new(), apply)
+                .await
+        }
+    });
+    let execute = end.receive().await;
+    assert_eq!(execute["method"], "workspace/executeCommand");
+    let apply_edit = json!({
+        "jsonrpc": "2.0",
+        "id": "edit",
+        "method": "workspace/applyEdit",
+        "params": { "edit": { "changes": {} } },
+    });
+    end.send(&apply_edit).await;
+    // the server hears how applying went, not that the edit was left to the caller
+    let answer = end.receive().await;
+    assert_eq!(answer["id"], "edit");
+    assert_eq!(answer["result"]["applied"], false);
+    assert_eq!(answer["result"]["failureReason"], "read-only");
+    assert_eq!(applied.lock().unwrap().len(), 1);
+
+    let response = json!({ "jsonrpc": "2.0", "id": execute["id"], "result": null });
+    end.send(&response).await;
+    let outcome = running.await.unwrap().unwrap();
+    assert_eq!(outcome.edits.len(), 1);
+}
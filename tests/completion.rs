@@ -0,0 +1,20 @@
+use lsp_client::lsp::completion;
+use lsp_types::{CompletionItem, CompletionResponse};
+use serde_json::json;
+
+#[test]
+fn completions_are_sorted_by_sort_text_then_label() {
+    let items: Vec<CompletionItem> = serde_json::from_value(json!([
+        { "label": "zeta", "sortText": "1" },
+        { "label": "beta" },
+        { "label": "alpha", "sortText": "2" },
+        { "label": "gamma", "sortText": "1" },
+    ]))
+    .unwrap();
+    let mut list = completion::normalize_completion_response(CompletionResponse::Array(items));
+    assert!(!list.is_incomplete);
+
+    completion::sort_completion_items(&mut list.items);
+    let labels: Vec<&str> = list.items.iter().map(|item| item.label.as_str()).collect();
+    assert_eq!(labels, ["zeta", "gamma", "alpha", "beta"]);
+}
@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use lsp_client::lsp::documents::DocumentManager;
+use lsp_client::lsp::testing;
+use lsp_types::Url;
+use serde_json::json;
+
+#[tokio::test]
+async fn changes_within_the_debounce_window_are_sent_as_one() {
+    let (server, mut end) = testing::pipe();
+    let documents = DocumentManager::new(server);
+    documents.set_change_debounce(Some(Duration::from_millis(50)));
+
+    let uri = Url::parse("file:///main.rs").unwrap();
+    documents.open(uri.clone(), "rust", "fn".to_owned()).await;
+    assert_eq!(end.receive().await["method"], json!("textDocument/didOpen"));
+    for text in ["fn m", "fn ma", "fn main"] {
+        documents.change(&uri, text.to_owned()).await;
+    }
+    let change = end.receive().await;
+    assert_eq!(change["method"], json!("textDocument/didChange"));
+    assert_eq!(change["params"]["textDocument"]["version"], json!(4));
+    assert_eq!(
+        change["params"]["contentChanges"],
+        json!([{ "text": "fn main" }])
+    );
+
+    // flushed at once rather than when the window is over
+    documents.change(&uri, "fn main()".to_owned()).await;
+    documents.close(&uri).await;
+    let change = end.receive().await;
+    assert_eq!(change["params"]["textDocument"]["version"], json!(5));
+    assert_eq!(
+        end.receive().await["method"],
+        json!("textDocument/didClose")
+    );
+}
+
+#[tokio::test]
+async fn requests_within_the_debounce_window_see_the_latest_text() {
+    let (server, mut end) = testing::pipe();
+    let documents = DocumentManager::new(server.clone());
+    documents.set_change_debounce(Some(Duration::from_secs(60)));
+
+    let uri = Url::parse("file:///main.rs").unwrap();
+    documents.open(uri.clone(), "rust", "fn".to_owned()).await;
+    end.receive().await;
+    documents.change(&uri, "fn main".to_owned()).await;
+    let hover = tokio::spawn({
+        let server = server.clone();
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": 0, "character": 4 },
+        });
+        async move { server.request_value("textDocument/hover", params).await }
+    });
+
+    let change = end.receive().await;
+    assert_eq!(change["method"], json!("textDocument/didChange"));
+    assert_eq!(
+        change["params"]["contentChanges"],
+        json!([{ "text": "fn main" }])
+    );
+    let request = end.receive().await;
+    assert_eq!(request["method"], json!("textDocument/hover"));
+    end.send(&json!({ "jsonrpc": "2.0", "id": request["id"], "result": null }))
+        .await;
+    assert_eq!(hover.await.unwrap().unwrap(), json!(null));
+}
+
+#[tokio::test]
+async fn cached_responses_last_until_the_document_changes() {
+    let (server, mut end) = testing::pipe();
+    let documents = DocumentManager::new(server.clone());
+    let cache = documents.cache_responses();
+
+    let uri = Url::parse("file:///main.rs").unwrap();
+    documents
+        .open(uri.clone(), "rust", "fn main".to_owned())
+        .await;
+    end.receive().await;
+    let params = json!({
+        "textDocument": { "uri": uri },
+        "position": { "line": 0, "character": 4 },
+    });
+    for hover in ["main", "main()"] {
+        let answered = tokio::spawn({
+            let server = server.clone();
+            let params = params.clone();
+            async move { server.request_value("textDocument/hover", params).await }
+        });
+        let request = end.receive().await;
+        assert_eq!(request["method"], json!("textDocument/hover"));
+        let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": hover });
+        end.send(&response).await;
+        assert_eq!(answered.await.unwrap().unwrap(), json!(hover));
+        // asked again, answered without the server
+        let cached = server.request_value("textDocument/hover", params.clone());
+        assert_eq!(cached.await.unwrap(), json!(hover));
+        assert_eq!(cache.len(), 1);
+
+        documents.change(&uri, "fn main()".to_owned()).await;
+        assert!(cache.is_empty());
+        end.receive().await;
+    }
+}
+
+#[tokio::test]
+async fn cached_responses_are_not_used_for_changes_held_back() {
+    let (server, mut end) = testing::pipe();
+    let documents = DocumentManager::new(server.clone());
+    let cache = documents.cache_responses();
+    documents.set_change_debounce(Some(Duration::from_secs(60)));
+
+    let uri = Url::parse("file:///main.rs").unwrap();
+    documents
+        .open(uri.clone(), "rust", "fn main".to_owned())
+        .await;
+    end.receive().await;
+    let params = json!({
+        "textDocument": { "uri": uri },
+        "position": { "line": 0, "character": 4 },
+    });
+    for hover in ["main", "main()"] {
+        let answered = tokio::spawn({
+            let server = server.clone();
+            let params = params.clone();
+            async move { server.request_value("textDocument/hover", params).await }
+        });
+        let mut request = end.receive().await;
+        if hover == "main()" {
+            // the change held back is sent first, and the old hover is not used
+            assert_eq!(request["method"], json!("textDocument/didChange"));
+            request = end.receive().await;
+        }
+        assert_eq!(request["method"], json!("textDocument/hover"));
+        let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": hover });
+        end.send(&response).await;
+        assert_eq!(answered.await.unwrap().unwrap(), json!(hover));
+        assert_eq!(cache.len(), 1);
+
+        documents.change(&uri, "fn main()".to_owned()).await;
+    }
+}
+
+#[tokio::test]
+async fn cached_definitions_are_dropped_when_their_target_changes() {
+    let (server, mut end) = testing::pipe();
+    let documents = DocumentManager::new(server.clone());
+    let cache = documents.cache_responses();
+
+    let main = Url::parse("file:///main.rs").unwrap();
+    let lib = Url::parse("file:///lib.rs").unwrap();
+    for (uri, text) in [(&main, "lib::run()"), (&lib, "pub fn run() {}")] {
+        documents.open(uri.clone(), "rust", text.to_owned()).await;
+        end.receive().await;
+    }
+    let answered = tokio::spawn({
+        let server = server.clone();
+        let params = json!({
+            "textDocument": { "uri": main },
+            "position": { "line": 0, "character": 5 },
+        });
+        async move {
+            server
+                .request_value("textDocument/definition", params)
+                .await
+        }
+    });
+    let request = end.receive().await;
+    let range = json!({
+        "start": { "line": 0, "character": 7 },
+        "end": { "line": 0, "character": 10 },
+    });
+    let location = json!([{ "uri": lib, "range": range }]);
+    let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": location });
+    end.send(&response).await;
+    answered.await.unwrap().unwrap();
+    assert_eq!(cache.len(), 1);
+
+    documents.change(&lib, "\npub fn run() {}".to_owned()).await;
+    assert!(cache.is_empty());
+}
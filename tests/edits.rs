@@ -0,0 +1,32 @@
+use lsp_client::lsp::edits;
+use lsp_types::{Position, Range, TextEdit};
+
+fn edit(start: (u32, u32), end: (u32, u32), new_text: &str) -> TextEdit {
+    TextEdit {
+        range: Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+        new_text: new_text.to_owned(),
+    }
+}
+
+#[test]
+fn ranges_refer_to_the_original_text() {
+    let text = "let a = 1;\nlet b = 2;\n";
+    let edits = [
+        edit((1, 4), (1, 5), "second"),
+        edit((0, 4), (0, 5), "first"),
+    ];
+    assert_eq!(
+        edits::apply_text_edits(text, &edits),
+        "let first = 1;\nlet second = 2;\n"
+    );
+}
+
+#[test]
+fn inserts_at_the_same_position_keep_their_order() {
+    let edits = [
+        edit((0, 0), (0, 0), "a"),
+        edit((0, 0), (0, 0), "b"),
+        edit((0, 0), (0, 0), "c"),
+    ];
+    assert_eq!(edits::apply_text_edits("!", &edits), "abc!");
+}
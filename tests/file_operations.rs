@@ -0,0 +1,80 @@
+use lsp_client::lsp::file_operations::file_operation_matches;
+use lsp_types::{
+    FileOperationFilter, FileOperationPattern, FileOperationPatternKind,
+    FileOperationPatternOptions, Url,
+};
+
+fn filter(
+    scheme: Option<&str>,
+    glob: &str,
+    matches: Option<FileOperationPatternKind>,
+) -> FileOperationFilter {
+    FileOperationFilter {
+        scheme: scheme.map(str::to_owned),
+        pattern: FileOperationPattern {
+            glob: glob.to_owned(),
+            matches,
+            options: None,
+        },
+    }
+}
+
+#[test]
+fn file_operations_match_by_glob() {
+    let filters = [filter(None, "**/*.rs", None)];
+    let source = Url::parse("file:///work/src/main.rs").unwrap();
+    let readme = Url::parse("file:///work/README.md").unwrap();
+    assert!(file_operation_matches(&filters, &source, None));
+    assert!(!file_operation_matches(&filters, &readme, None));
+    // a single star stops at separators
+    let filters = [filter(None, "/work/*.rs", None)];
+    assert!(!file_operation_matches(&filters, &source, None));
+
+    let mut filter = filter(None, "**/*.RS", None);
+    assert!(!file_operation_matches(&[filter.clone()], &source, None));
+    filter.pattern.options = Some(FileOperationPatternOptions {
+        ignore_case: Some(true),
+    });
+    assert!(file_operation_matches(&[filter], &source, None));
+}
+
+#[test]
+fn file_operations_match_by_scheme() {
+    let filters = [filter(Some("file"), "**/*.rs", None)];
+    let file = Url::parse("file:///work/main.rs").unwrap();
+    let untitled = Url::parse("untitled:///work/main.rs").unwrap();
+    assert!(file_operation_matches(&filters, &file, None));
+    assert!(!file_operation_matches(&filters, &untitled, None));
+    // without a scheme any does
+    let filters = [filter(None, "**/*.rs", None)];
+    assert!(file_operation_matches(&filters, &untitled, None));
+}
+
+#[test]
+fn file_operations_match_by_kind_when_known() {
+    let filters = [filter(
+        None,
+        "**/src",
+        Some(FileOperationPatternKind::Folder),
+    )];
+    let uri = Url::parse("file:///work/src").unwrap();
+    assert!(file_operation_matches(
+        &filters,
+        &uri,
+        Some(FileOperationPatternKind::Folder)
+    ));
+    assert!(!file_operation_matches(
+        &filters,
+        &uri,
+        Some(FileOperationPatternKind::File)
+    ));
+    // a path that does not exist may be either
+    assert!(file_operation_matches(&filters, &uri, None));
+    // and a filter without `matches` takes both
+    let filters = [filter(None, "**/src", None)];
+    assert!(file_operation_matches(
+        &filters,
+        &uri,
+        Some(FileOperationPatternKind::File)
+    ));
+}
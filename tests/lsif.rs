@@ -0,0 +1,186 @@
+use lsp_client::lsp::lsif::{IndexedSymbol, LsifIndex};
+use lsp_client::lsp::scip;
+use lsp_client::lsp::text_utils::PositionEncoding;
+use lsp_types::{Hover, HoverContents, Location, Position, Range, Url};
+use serde_json::{json, Value};
+
+fn location(uri: &Url, line: u32, start: u32, end: u32) -> Location {
+    Location::new(
+        uri.clone(),
+        Range::new(Position::new(line, start), Position::new(line, end)),
+    )
+}
+
+fn labelled<'a>(elements: &'a [Value], label: &str) -> Vec<&'a Value> {
+    elements
+        .iter()
+        .filter(|element| element["label"] == label)
+        .collect()
+}
+
+#[test]
+fn symbols_link_their_ranges_to_their_results() {
+    let lib = Url::parse("file:///project/src/lib.rs").unwrap();
+    let main = Url::parse("file:///project/src/main.rs").unwrap();
+    let dependency = Url::parse("file:///registry/serde/src/lib.rs").unwrap();
+    let mut index = LsifIndex::new(Url::parse("file:///project/").unwrap());
+    index.add_document(lib.clone(), "rust");
+    index.add_document(main.clone(), "rust");
+    index.add_document(lib.clone(), "rust");
+    index.symbols.push(IndexedSymbol {
+        definitions: vec![location(&lib, 0, 7, 12)],
+        references: vec![location(&main, 3, 4, 9), location(&dependency, 1, 0, 5)],
+        ..IndexedSymbol::default()
+    });
+    // mentions the definition of the first symbol again, which keeps its range
+    index.symbols.push(IndexedSymbol {
+        definitions: vec![location(&main, 3, 4, 9)],
+        ..IndexedSymbol::default()
+    });
+
+    let elements = index.elements();
+    assert_eq!(elements[0]["label"], "metaData");
+    assert_eq!(labelled(&elements, "document").len(), 2);
+    // the reference into a dependency is left out
+    let ranges = labelled(&elements, "range");
+    assert_eq!(ranges.len(), 2);
+    let result_sets = labelled(&elements, "resultSet");
+    let next = labelled(&elements, "next");
+    assert_eq!(next.len(), 2);
+    assert!(next.iter().all(|edge| edge["inV"] == result_sets[0]["id"]));
+
+    let items = labelled(&elements, "item");
+    let references: Vec<_> = items
+        .iter()
+        .filter(|edge| edge["property"] == "references")
+        .collect();
+    assert_eq!(references.len(), 1);
+    assert_eq!(references[0]["inVs"], json!([ranges[1]["id"]]));
+    // the second symbol's definition result points at the shared range
+    let definitions = labelled(&elements, "definitionResult");
+    assert!(items
+        .iter()
+        .any(|edge| edge["outV"] == definitions[1]["id"] && edge["inVs"][0] == ranges[1]["id"]));
+
+    let ids: Vec<_> = elements.iter().map(|element| &element["id"]).collect();
+    assert!(ids
+        .windows(2)
+        .all(|pair| pair[0].as_u64() < pair[1].as_u64()));
+    assert_eq!(elements.last().unwrap()["kind"], "end");
+}
+
+#[test]
+fn dumps_read_back_into_the_symbols_they_were_written_from() {
+    let lib = Url::parse("file:///project/src/lib.rs").unwrap();
+    let mut index = LsifIndex::new(Url::parse("file:///project/").unwrap());
+    index.add_document(lib.clone(), "rust");
+    let symbol = IndexedSymbol {
+        definitions: vec![location(&lib, 0, 7, 12)],
+        references: vec![location(&lib, 4, 2, 7)],
+        hover: Some(serde_json::from_value(json!({ "contents": "fn parse()" })).unwrap()),
+        ..IndexedSymbol::default()
+    };
+    index.symbols.push(symbol.clone());
+
+    let lines: Vec<String> = index.elements().iter().map(Value::to_string).collect();
+    let read = LsifIndex::parse(&lines.join("\n")).unwrap();
+    assert_eq!(read, index);
+    let array = LsifIndex::parse(&json!(index.elements()).to_string()).unwrap();
+    assert_eq!(array, index);
+
+    assert_eq!(read.symbol_at(&lib, Position::new(4, 7)), Some(&symbol));
+    assert_eq!(read.symbol_at(&lib, Position::new(4, 8)), None);
+}
+
+/// Encodes a protobuf field holding a varint, or bytes when `value` is `Err`.
+fn field(number: u64, value: Result<u64, &[u8]>) -> Vec<u8> {
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+    let mut out = Vec::new();
+    match value {
+        Ok(value) => {
+            varint(number << 3, &mut out);
+            varint(value, &mut out);
+        }
+        Err(bytes) => {
+            varint(number << 3 | 2, &mut out);
+            varint(bytes.len() as u64, &mut out);
+            out.extend(bytes);
+        }
+    }
+    out
+}
+
+fn occurrence(range: &[u8], symbol: &str, roles: u64) -> Vec<u8> {
+    [
+        field(1, Err(range)),
+        field(2, Err(symbol.as_bytes())),
+        field(3, Ok(roles)),
+    ]
+    .concat()
+}
+
+#[test]
+fn scip_occurrences_become_definitions_and_references() {
+    let parse = "rust-analyzer cargo demo 0.1.0 parse().";
+    let metadata = field(3, Err(b"file:///project"));
+    let document = [
+        field(1, Err(b"src/lib.rs")),
+        field(4, Err(b"Rust")),
+        field(2, Err(&occurrence(&[0, 7, 12], parse, 1))),
+        field(2, Err(&occurrence(&[4, 2, 4, 7], parse, 0))),
+        // locals of different documents are different symbols
+        field(2, Err(&occurrence(&[5, 8, 9], "local 0", 1))),
+        field(
+            3,
+            Err(&[field(1, Err(parse.as_bytes())), field(3, Err(b"Parses."))].concat()),
+        ),
+        field(6, Ok(1)),
+    ]
+    .concat();
+    let other = [
+        field(1, Err(b"src/main.rs")),
+        field(2, Err(&occurrence(&[1, 0, 1], "local 0", 1))),
+    ]
+    .concat();
+    let bytes = [
+        field(1, Err(&metadata)),
+        field(2, Err(&document)),
+        field(2, Err(&other)),
+    ]
+    .concat();
+
+    let index = scip::parse(&bytes).unwrap();
+    let lib = Url::parse("file:///project/src/lib.rs").unwrap();
+    assert_eq!(index.encoding, PositionEncoding::Utf8);
+    assert_eq!(
+        index.documents,
+        [
+            (lib.clone(), "rust".to_owned()),
+            (
+                Url::parse("file:///project/src/main.rs").unwrap(),
+                String::new()
+            ),
+        ]
+    );
+    assert_eq!(index.symbols.len(), 3);
+    let symbol = index.symbol_at(&lib, Position::new(4, 3)).unwrap();
+    assert_eq!(symbol.definitions, [location(&lib, 0, 7, 12)]);
+    assert_eq!(symbol.references, [location(&lib, 4, 2, 7)]);
+    assert_eq!(symbol.monikers[0].identifier, parse);
+    let Some(Hover {
+        contents: HoverContents::Markup(markup),
+        ..
+    }) = &symbol.hover
+    else {
+        panic!("expected a markdown hover, got {:?}", symbol.hover);
+    };
+    assert_eq!(markup.value, "Parses.");
+
+    assert!(scip::parse(&bytes[..bytes.len() - 1]).is_err());
+}
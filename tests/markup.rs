@@ -0,0 +1,30 @@
+use lsp_client::lsp::markup::{self, CodeBlock};
+
+#[test]
+fn extracts_fenced_code_blocks() {
+    let markdown = "```rust\nfn f() {}\n```\ntext\n~~~\nlet a = 1;\n```\nstill code";
+    assert_eq!(
+        markup::code_blocks(markdown),
+        vec![
+            CodeBlock {
+                language: Some("rust".to_owned()),
+                code: "fn f() {}".to_owned(),
+            },
+            // a fence only closes blocks opened with the same one, and the last runs on
+            CodeBlock {
+                language: None,
+                code: "let a = 1;\n```\nstill code".to_owned(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn strips_markdown_syntax() {
+    let markdown =
+        "# Title\n**bold** and `code` [link](https://example.com)\n```rust\nfn f() {}\n```";
+    assert_eq!(
+        markup::markdown_to_plain_text(markdown),
+        "Title\nbold and code link\nfn f() {}"
+    );
+}
@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use lsp_client::cli::record::{Frame, Recording, Sender};
+use lsp_client::cli::session::{Session, Timeouts};
+use lsp_client::lsp::metrics::MetricsSnapshot;
+use lsp_types::{Position, Url};
+use serde_json::json;
+
+/// Starts a server answering `initialize`, then a hover and then a hover with an error,
+/// asks for both hovers and returns the metrics.
+async fn hover_twice(dir: &Path) -> MetricsSnapshot {
+    std::fs::create_dir_all(dir).unwrap();
+    let frame = |from: Sender, message| Frame { from, message };
+    let recording = Recording {
+        args: Vec::new(),
+        frames: vec![
+            frame(
+                Sender::Client,
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize" }),
+            ),
+            frame(
+                Sender::Server,
+                json!({ "jsonrpc": "2.0", "id": 1, "result": {
+                    "capabilities": { "hoverProvider": true }
+                } }),
+            ),
+            frame(
+                Sender::Client,
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/hover" }),
+            ),
+            frame(
+                Sender::Server,
+                json!({ "jsonrpc": "2.0", "id": 2, "result": { "contents": "hover" } }),
+            ),
+            frame(
+                Sender::Client,
+                json!({ "jsonrpc": "2.0", "id": 3, "method": "textDocument/hover" }),
+            ),
+            frame(
+                Sender::Server,
+                json!({ "jsonrpc": "2.0", "id": 3, "error": {
+                    "code": -32801, "message": "content modified"
+                } }),
+            ),
+        ],
+    };
+    let path = dir.join("session.json");
+    std::fs::write(&path, serde_json::to_string(&recording).unwrap()).unwrap();
+    let server = vec![
+        env!("CARGO_BIN_EXE_lsp-client").to_owned(),
+        "serve-recording".to_owned(),
+        path.to_string_lossy().into_owned(),
+    ];
+    let session = Session::start(&server, dir, None, Timeouts::default(), None, None, false)
+        .await
+        .unwrap();
+    let uri = Url::from_file_path(dir.join("main.rs")).unwrap();
+    let position = Position::new(0, 0);
+    session.server.hover(uri.clone(), position).await.unwrap();
+    session.server.hover(uri, position).await.unwrap_err();
+    let metrics = session.server.metrics().snapshot();
+    session.finish().await;
+    std::fs::remove_dir_all(dir).unwrap();
+    metrics
+}
+
+#[tokio::test]
+async fn requests_are_counted_by_method_with_their_latency_and_errors() {
+    let dir = std::env::temp_dir().join(format!("lsp-client-metrics-{}", std::process::id()));
+    let metrics = hover_twice(&dir).await;
+
+    let initialize = &metrics.methods["initialize"];
+    assert_eq!((initialize.requests, initialize.errors), (1, 0));
+    let hover = &metrics.methods["textDocument/hover"];
+    assert_eq!((hover.requests, hover.errors), (2, 1));
+    assert_eq!(hover.error_rate(), Some(0.5));
+    assert_eq!(hover.latency.count, 2);
+    assert_eq!(hover.latency.buckets.iter().sum::<u64>(), 2);
+    assert!(hover.latency.mean().is_some());
+    // notifications are not requests, but their bytes count
+    assert!(!metrics.methods.contains_key("initialized"));
+    assert!(metrics.bytes_sent > 0);
+    assert!(metrics.bytes_received > 0);
+}
+
+#[cfg(feature = "prometheus")]
+#[tokio::test]
+async fn metrics_are_served_in_the_prometheus_text_format() {
+    use std::sync::Arc;
+
+    use lsp_client::lsp::metrics::{self, Metrics};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    let dir = std::env::temp_dir().join(format!("lsp-client-prometheus-{}", std::process::id()));
+    let text = hover_twice(&dir).await.to_prometheus();
+    assert!(text.contains("# TYPE lsp_client_request_duration_seconds histogram\n"));
+    assert!(text.contains("lsp_client_requests_total{method=\"textDocument/hover\"} 2\n"));
+    assert!(text.contains("lsp_client_request_errors_total{method=\"textDocument/hover\"} 1\n"));
+    assert!(text.contains(
+        "lsp_client_request_duration_seconds_bucket{method=\"textDocument/hover\",le=\"+Inf\"} 2\n"
+    ));
+    assert!(text.contains("lsp_client_request_duration_seconds_count{method=\"initialize\"} 1\n"));
+
+    // a port nobody listens on
+    let address = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let serving = tokio::spawn(metrics::serve_prometheus(
+        address,
+        Arc::new(Metrics::default()),
+    ));
+    let mut stream = loop {
+        match TcpStream::connect(address).await {
+            Ok(stream) => break stream,
+            Err(_) => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+        }
+    };
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+    assert!(response.ends_with("lsp_client_received_bytes_total 0\n"));
+
+    // the end of the request split across reads
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\n\r").await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    stream.write_all(b"\n").await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+
+    // requests with no end are dropped once too large
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let endless = format!("GET / HTTP/1.1\r\nX-Padding: {}\r\n", "a".repeat(16 * 1024));
+    let _ = stream.write_all(endless.as_bytes()).await;
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response).await;
+    assert!(response.is_empty());
+    serving.abort();
+}
@@ -0,0 +1,34 @@
+use lsp_client::lsp::navigation;
+use lsp_types::{GotoDefinitionResponse, Location, LocationLink, Position, Range, Url};
+
+fn range(line: u32, start: u32, end: u32) -> Range {
+    Range::new(Position::new(line, start), Position::new(line, end))
+}
+
+#[test]
+fn goto_responses_normalize_to_locations() {
+    let uri = Url::parse("file:///src/main.rs").unwrap();
+    let location = Location::new(uri.clone(), range(3, 4, 8));
+
+    let array = GotoDefinitionResponse::Array(vec![location.clone(), location.clone()]);
+    assert_eq!(navigation::normalize_goto_response(array).len(), 2);
+    let scalar = GotoDefinitionResponse::Scalar(location.clone());
+    assert_eq!(navigation::normalize_goto_response(scalar), [location]);
+}
+
+#[test]
+fn location_links_point_at_the_target_name() {
+    let uri = Url::parse("file:///src/lib.rs").unwrap();
+    let link = LocationLink {
+        origin_selection_range: Some(range(0, 0, 3)),
+        target_uri: uri.clone(),
+        // the whole function
+        target_range: Range::new(Position::new(10, 0), Position::new(14, 1)),
+        // just its name
+        target_selection_range: range(10, 3, 7),
+    };
+    assert_eq!(
+        navigation::normalize_goto_response(GotoDefinitionResponse::Link(vec![link])),
+        [Location::new(uri, range(10, 3, 7))]
+    );
+}
@@ -0,0 +1,119 @@
+use lsp_client::lsp::parsing::{
+    check_length, read_message, read_message_with, resync, MessageReader, ParseError, ParseMode,
+};
+use tokio::io::BufReader;
+
+async fn read(message: &str, mode: ParseMode) -> Result<String, ParseError> {
+    read_message_with(&mut BufReader::new(message.as_bytes()), mode).await
+}
+
+#[tokio::test]
+async fn messages_are_read_by_their_content_length() {
+    let mut reader = BufReader::new(
+        "Content-Length: 2\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n{}\
+         Content-Length: 4\r\n\r\nnull"
+            .as_bytes(),
+    );
+    assert_eq!(read_message(&mut reader).await.unwrap(), "{}");
+    assert_eq!(read_message(&mut reader).await.unwrap(), "null");
+    assert!(matches!(
+        read_message(&mut reader).await,
+        Err(ParseError::Io(_))
+    ));
+}
+
+#[tokio::test]
+async fn sloppy_headers_are_only_read_in_lenient_mode() {
+    let sloppy = [
+        "content-length:2\n\n{}",
+        "CONTENT-LENGTH :2\r\n\r\n{}",
+        "Content-Length: 2\nX-Request-Id: 7\n\n{}",
+    ];
+    for message in sloppy {
+        assert!(
+            read(message, ParseMode::Strict).await.is_err(),
+            "{:?}",
+            message
+        );
+        assert_eq!(read(message, ParseMode::Lenient).await.unwrap(), "{}");
+    }
+    // a length is still required
+    assert!(read("X-Request-Id: 7\r\n\r\n{}", ParseMode::Lenient)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn resync_skips_to_the_next_message() {
+    let mut reader = BufReader::new(
+        "Content-Length: x\r\n\r\ngarbage CONTENT-content-length: 2\r\n\r\n{}".as_bytes(),
+    );
+    assert!(matches!(
+        read_message(&mut reader).await,
+        Err(ParseError::ParseInt(_))
+    ));
+    let resynced = resync(&mut reader, ParseMode::Strict).await.unwrap();
+    assert_eq!(resynced.skipped, "\r\ngarbage CONTENT-".len());
+    assert_eq!(resynced.message.unwrap(), "{}");
+    // nothing left to skip to
+    assert!(resync(&mut reader, ParseMode::Strict).await.is_err());
+}
+
+#[tokio::test]
+async fn lengths_not_matching_the_body_are_caught() {
+    let message = "Content-Length: 12\r\n\r\n{\"a\": 1}\r\nContent-Length: 2\r\n\r\n{}\
+                   Content-Length: 4\r\n\r\nnull";
+    let mut reader = BufReader::new(message.as_bytes());
+    let body = read_message(&mut reader).await.unwrap();
+    assert!(matches!(
+        check_length(&body),
+        Err(ParseError::LengthMismatch {
+            declared: 12,
+            json: Some(8)
+        })
+    ));
+    assert!(matches!(
+        check_length("{\"a\""),
+        Err(ParseError::LengthMismatch {
+            declared: 4,
+            json: None
+        })
+    ));
+    // whitespace after the value, and bodies that are not JSON, are let through
+    assert!(check_length("{}\r\n").is_ok());
+    assert!(check_length("not json").is_ok());
+
+    // one too long took the start of the next, the one after is found again
+    let resynced = resync(&mut reader, ParseMode::Strict).await.unwrap();
+    assert_eq!(resynced.message.unwrap(), "null");
+}
+
+#[tokio::test]
+async fn bodies_over_the_maximum_are_skipped() {
+    let stream = "Content-Length: 10\r\n\r\n[1, 2, 3]\nContent-Length: 2\r\n\r\n{}";
+    let mut messages = MessageReader::new(BufReader::new(stream.as_bytes()), ParseMode::Strict);
+    messages.set_max_length(Some(8));
+    assert!(matches!(
+        messages.read().await,
+        Err(ParseError::TooLarge {
+            declared: 10,
+            max: 8
+        })
+    ));
+    assert_eq!(messages.read().await.unwrap(), "{}");
+}
+
+#[tokio::test]
+async fn message_readers_read_message_after_message() {
+    let stream = "Content-Length: 2\r\n\r\n{}X-Request-Id: 7\r\ncontent-length: 4\r\n\r\nnull\
+                  X-Request-Id: 8\r\nContent-Length: 6\r\n\r\n[1, 2]";
+    let mut messages = MessageReader::new(BufReader::new(stream.as_bytes()), ParseMode::Strict);
+    assert_eq!(messages.read().await.unwrap(), "{}");
+    // the unknown header is only skipped in lenient mode, or by resyncing
+    assert!(messages.read().await.is_err());
+    assert_eq!(messages.resync().await.unwrap(), 0);
+    assert_eq!(messages.read().await.unwrap(), "null");
+    messages.set_mode(ParseMode::Lenient);
+    assert_eq!(messages.read().await.unwrap(), "[1, 2]");
+    assert!(matches!(messages.read().await, Err(ParseError::Io(_))));
+}
@@ -0,0 +1,111 @@
+use std::process::Stdio;
+
+use lsp_client::cli::record::{Frame, Recording, Sender};
+use lsp_client::lsp::client::{start_language_server, ClientError, LanguageServerRef};
+use lsp_client::lsp::navigation::GotoKind;
+use lsp_client::lsp::replay::{Capture, Replay};
+use lsp_types::notification::Exit;
+use lsp_types::{ClientCapabilities, HoverContents, InitializeParams, MarkedString, Position, Url};
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+async fn hover<W: AsyncWriteExt + Unpin>(server: &LanguageServerRef<W>, uri: &Url) -> String {
+    let hover = server.hover(uri.clone(), Position::new(0, 0)).await;
+    match hover.unwrap().unwrap().contents {
+        HoverContents::Scalar(MarkedString::String(text)) => text,
+        contents => panic!("unexpected hover {:?}", contents),
+    }
+}
+
+#[tokio::test]
+async fn captured_sessions_are_replayed_without_the_server() {
+    let dir = std::env::temp_dir().join(format!("lsp-client-replay-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let frame = |from: Sender, message| Frame { from, message };
+    let recording = Recording {
+        args: Vec::new(),
+        frames: vec![
+            frame(
+                Sender::Client,
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize" }),
+            ),
+            frame(
+                Sender::Server,
+                json!({ "jsonrpc": "2.0", "id": 1, "result": {
+                    "capabilities": { "hoverProvider": true, "definitionProvider": true }
+                } }),
+            ),
+            frame(
+                Sender::Client,
+                json!({ "jsonrpc": "2.0", "method": "initialized" }),
+            ),
+            frame(
+                Sender::Client,
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/hover" }),
+            ),
+            frame(
+                Sender::Server,
+                json!({ "jsonrpc": "2.0", "id": 2, "result": { "contents": "captured" } }),
+            ),
+            frame(
+                Sender::Client,
+                json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+            ),
+            frame(
+                Sender::Server,
+                json!({ "jsonrpc": "2.0", "id": 3, "result": null }),
+            ),
+        ],
+    };
+    let path = dir.join("session.json");
+    std::fs::write(&path, serde_json::to_string(&recording).unwrap()).unwrap();
+    let uri = Url::from_file_path(dir.join("main.rs")).unwrap();
+    #[allow(deprecated)]
+    let params = InitializeParams {
+        root_uri: Some(Url::from_directory_path(&dir).unwrap()),
+        capabilities: ClientCapabilities::default(),
+        ..InitializeParams::default()
+    };
+
+    let child = Command::new(env!("CARGO_BIN_EXE_lsp-client"))
+        .arg("serve-recording")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap();
+    let (mut child, server) = start_language_server(child).await;
+    let capture = Capture::start(&server);
+    server.initialize(params.clone()).await.unwrap();
+    assert_eq!(hover(&server, &uri).await, "captured");
+    server.shutdown().await.unwrap();
+    child.wait().await.unwrap();
+    let captured = dir.join("captured.jsonl");
+    capture.save(&captured).unwrap();
+    assert_eq!(capture.frames().len(), 8);
+
+    let (server, replaying) = Replay::load(&captured).unwrap().connect();
+    server.initialize(params.clone()).await.unwrap();
+    assert_eq!(hover(&server, &uri).await, "captured");
+    server.shutdown().await.unwrap();
+    assert!(replaying.await.unwrap().unwrap().is_complete());
+
+    // a request the capture does not have is refused and reported
+    let (server, replaying) = Replay::load(&captured).unwrap().connect();
+    server.initialize(params).await.unwrap();
+    let err = server
+        .goto(GotoKind::Definition, uri.clone(), Position::new(0, 0))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ClientError::Server(_)));
+    server.notify::<Exit>(()).await;
+    let report = replaying.await.unwrap().unwrap();
+    assert_eq!(
+        report.unexpected[0]["method"],
+        json!("textDocument/definition")
+    );
+    assert_eq!(report.remaining.len(), 5);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,92 @@
+use lsp_client::lsp::semantic_tokens::{self, DecodedSemanticToken};
+use lsp_types::{
+    Position, Range, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensLegend,
+};
+
+fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: vec![SemanticTokenType::FUNCTION, SemanticTokenType::VARIABLE],
+        token_modifiers: vec![
+            SemanticTokenModifier::DECLARATION,
+            SemanticTokenModifier::READONLY,
+        ],
+    }
+}
+
+fn token(
+    delta_line: u32,
+    delta_start: u32,
+    length: u32,
+    token_type: u32,
+    bits: u32,
+) -> SemanticToken {
+    SemanticToken {
+        delta_line,
+        delta_start,
+        length,
+        token_type,
+        token_modifiers_bitset: bits,
+    }
+}
+
+fn decoded(
+    line: u32,
+    start: u32,
+    end: u32,
+    token_type: SemanticTokenType,
+    modifiers: Vec<SemanticTokenModifier>,
+) -> DecodedSemanticToken {
+    DecodedSemanticToken {
+        range: Range::new(Position::new(line, start), Position::new(line, end)),
+        token_type,
+        modifiers,
+    }
+}
+
+#[test]
+fn positions_are_relative_to_the_previous_token() {
+    let tokens = [
+        // `fn main` at 2:3
+        token(2, 3, 4, 0, 0b01),
+        // 6 characters further on the same line
+        token(0, 6, 1, 1, 0b11),
+        // two lines down, the start character is absolute again
+        token(2, 4, 5, 1, 0),
+    ];
+    assert_eq!(
+        semantic_tokens::decode_semantic_tokens(&tokens, &legend()),
+        [
+            decoded(
+                2,
+                3,
+                7,
+                SemanticTokenType::FUNCTION,
+                vec![SemanticTokenModifier::DECLARATION]
+            ),
+            decoded(
+                2,
+                9,
+                10,
+                SemanticTokenType::VARIABLE,
+                vec![
+                    SemanticTokenModifier::DECLARATION,
+                    SemanticTokenModifier::READONLY
+                ]
+            ),
+            decoded(4, 4, 9, SemanticTokenType::VARIABLE, vec![]),
+        ]
+    );
+}
+
+#[test]
+fn tokens_outside_the_legend_are_dropped_but_still_move_the_position() {
+    let tokens = [
+        token(1, 2, 3, 7, 0),
+        // an unknown modifier bit is ignored
+        token(0, 4, 2, 0, 0b100),
+    ];
+    assert_eq!(
+        semantic_tokens::decode_semantic_tokens(&tokens, &legend()),
+        [decoded(1, 6, 8, SemanticTokenType::FUNCTION, vec![])]
+    );
+}
@@ -0,0 +1,55 @@
+use lsp_client::lsp::server_capabilities::Capabilities;
+use lsp_types::{Registration, ServerCapabilities, TextDocumentSyncKind};
+use serde_json::json;
+
+fn announced(capabilities: serde_json::Value) -> ServerCapabilities {
+    serde_json::from_value(capabilities).unwrap()
+}
+
+#[test]
+fn announced_providers_are_supported() {
+    let capabilities = Capabilities::new(
+        announced(json!({
+            "hoverProvider": false,
+            "renameProvider": { "prepareProvider": true },
+            "codeLensProvider": {},
+            "textDocumentSync": { "openClose": true, "change": 2 },
+        })),
+        Vec::new(),
+    );
+    assert!(!capabilities.supports_hover());
+    assert!(capabilities.supports_rename());
+    assert!(capabilities.supports_prepare_rename());
+    assert!(capabilities.supports("textDocument/codeLens"));
+    assert!(!capabilities.supports("codeLens/resolve"));
+    assert!(!capabilities.supports("textDocument/definition"));
+    // nothing announces support for methods outside the specification
+    assert!(capabilities.supports("rust-analyzer/expandMacro"));
+    assert_eq!(
+        capabilities.text_document_sync_kind(),
+        TextDocumentSyncKind::INCREMENTAL
+    );
+}
+
+#[test]
+fn dynamic_registrations_add_support() {
+    let registrations = vec![
+        Registration {
+            id: "1".to_owned(),
+            method: "textDocument/codeLens".to_owned(),
+            register_options: Some(json!({ "resolveProvider": true })),
+        },
+        Registration {
+            id: "2".to_owned(),
+            method: "workspace/executeCommand".to_owned(),
+            register_options: Some(json!({ "commands": ["registered"] })),
+        },
+    ];
+    let capabilities = Capabilities::new(
+        announced(json!({ "executeCommandProvider": { "commands": ["announced"] } })),
+        registrations,
+    );
+    assert!(capabilities.supports("textDocument/codeLens"));
+    assert!(capabilities.supports("codeLens/resolve"));
+    assert_eq!(capabilities.commands(), ["announced", "registered"]);
+}
@@ -0,0 +1,31 @@
+use lsp_client::lsp::settings::settings_section;
+use serde_json::json;
+
+#[test]
+fn settings_sections_are_looked_up_by_dotted_path() {
+    let tree = json!({
+        "rust-analyzer": { "cargo": { "features": ["all"] }, "checkOnSave": true },
+    });
+    assert_eq!(
+        settings_section(&tree, "rust-analyzer.cargo.features"),
+        Some(&json!(["all"]))
+    );
+    assert_eq!(
+        settings_section(&tree, "rust-analyzer.checkOnSave"),
+        Some(&json!(true))
+    );
+    // an empty section is the whole tree
+    assert_eq!(settings_section(&tree, ""), Some(&tree));
+}
+
+#[test]
+fn missing_settings_sections_are_none() {
+    let tree = json!({ "rust-analyzer": { "checkOnSave": true } });
+    assert_eq!(settings_section(&tree, "pyright"), None);
+    assert_eq!(settings_section(&tree, "rust-analyzer.cargo"), None);
+    // below a value that is not an object
+    assert_eq!(
+        settings_section(&tree, "rust-analyzer.checkOnSave.command"),
+        None
+    );
+}
@@ -0,0 +1,66 @@
+use lsp_client::lsp::snippet::{Snippet, SnippetElement};
+
+#[test]
+fn parses_tabstops_and_placeholders() {
+    let snippet = Snippet::parse("fn ${1:name}($2) {\n\t$0\n}");
+    assert_eq!(
+        snippet.elements,
+        vec![
+            SnippetElement::Text("fn ".to_owned()),
+            SnippetElement::Tabstop {
+                index: 1,
+                placeholder: vec![SnippetElement::Text("name".to_owned())],
+            },
+            SnippetElement::Text("(".to_owned()),
+            SnippetElement::Tabstop {
+                index: 2,
+                placeholder: Vec::new(),
+            },
+            SnippetElement::Text(") {\n\t".to_owned()),
+            SnippetElement::Tabstop {
+                index: 0,
+                placeholder: Vec::new(),
+            },
+            SnippetElement::Text("\n}".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn renders_tabstops_in_visiting_order() {
+    let rendered = Snippet::parse("fn ${1:name}($2) {\n\t$0\n}").render(|_| None);
+    assert_eq!(rendered.text, "fn name() {\n\t\n}");
+    let tabstops: Vec<_> = rendered
+        .tabstops
+        .iter()
+        .flat_map(|tabstop| {
+            tabstop
+                .ranges
+                .iter()
+                .map(|range| (tabstop.index, range.clone()))
+        })
+        .collect();
+    assert_eq!(tabstops, [(1, 3..7), (2, 8..8), (0, 13..13)]);
+    assert_eq!(rendered.final_offset(), 13);
+}
+
+#[test]
+fn mirrors_render_the_placeholder_of_their_index() {
+    let rendered = Snippet::parse("${1:a} $1").render(|_| None);
+    assert_eq!(rendered.text, "a a");
+    assert_eq!(rendered.tabstops[0].ranges, vec![0..1, 2..3]);
+}
+
+#[test]
+fn renders_choices_and_variables() {
+    let snippet = Snippet::parse("${1|one,two|} ${TM_FILENAME:x} $TM_SELECTED_TEXT");
+    let rendered = snippet.render(|name| (name == "TM_FILENAME").then(|| "a.rs".to_owned()));
+    assert_eq!(rendered.text, "one a.rs TM_SELECTED_TEXT");
+    assert_eq!(snippet.to_plain_text(), "one x TM_SELECTED_TEXT");
+}
+
+#[test]
+fn keeps_malformed_and_escaped_syntax_as_text() {
+    assert_eq!(Snippet::parse("${1:abc").to_plain_text(), "${1:abc");
+    assert_eq!(Snippet::parse("\\$1 $$").to_plain_text(), "$1 $$");
+}
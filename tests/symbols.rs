@@ -0,0 +1,110 @@
+use lsp_client::lsp::symbols::{self, DocumentSymbolNode};
+use lsp_types::{DocumentSymbolResponse, Position, SymbolKind};
+use serde_json::json;
+
+/// A flat `SymbolInformation` spanning the given lines.
+fn flat(name: &str, kind: SymbolKind, start: u32, end: u32) -> serde_json::Value {
+    json!({
+        "name": name,
+        "kind": kind,
+        "location": {
+            "uri": "file:///src/shapes.rs",
+            "range": {
+                "start": { "line": start, "character": 0 },
+                "end": { "line": end, "character": 1 },
+            },
+        },
+    })
+}
+
+fn names(nodes: &[DocumentSymbolNode]) -> Vec<&str> {
+    nodes.iter().map(|node| node.name.as_str()).collect()
+}
+
+#[test]
+fn flat_symbols_nest_by_range_containment() {
+    // out of order, and with a container name that would point at the wrong parent
+    let mut area = flat("area", SymbolKind::METHOD, 6, 8);
+    area["containerName"] = json!("Square");
+    let response: DocumentSymbolResponse = serde_json::from_value(json!([
+        flat("Circle", SymbolKind::STRUCT, 10, 12),
+        area,
+        flat("main", SymbolKind::FUNCTION, 14, 16),
+        flat("Shape", SymbolKind::INTERFACE, 0, 9),
+        flat("radius", SymbolKind::FIELD, 11, 11),
+    ]))
+    .unwrap();
+
+    let roots = symbols::normalize_document_symbols(response);
+    assert_eq!(names(&roots), ["Shape", "Circle", "main"]);
+    assert_eq!(names(&roots[0].children), ["area"]);
+    assert_eq!(names(&roots[1].children), ["radius"]);
+    assert!(roots[2].children.is_empty());
+}
+
+#[test]
+fn descendants_are_listed_in_pre_order() {
+    let response: DocumentSymbolResponse = serde_json::from_value(json!([
+        flat("outer", SymbolKind::MODULE, 0, 20),
+        flat("first", SymbolKind::FUNCTION, 1, 5),
+        flat("nested", SymbolKind::VARIABLE, 2, 2),
+        flat("second", SymbolKind::FUNCTION, 6, 9),
+    ]))
+    .unwrap();
+    let roots = symbols::normalize_document_symbols(response);
+    let order: Vec<_> = roots[0]
+        .descendants()
+        .into_iter()
+        .map(|node| node.name.as_str())
+        .collect();
+    assert_eq!(order, ["outer", "first", "nested", "second"]);
+}
+
+#[test]
+fn symbol_at_point_reports_the_innermost_symbol_and_its_containers() {
+    let response: DocumentSymbolResponse = serde_json::from_value(json!([
+        flat("Shape", SymbolKind::INTERFACE, 0, 9),
+        flat("area", SymbolKind::METHOD, 2, 5),
+        flat("main", SymbolKind::FUNCTION, 14, 16),
+    ]))
+    .unwrap();
+    let roots = symbols::normalize_document_symbols(response);
+
+    let symbol = symbols::symbol_at_point(&roots, Position::new(3, 4)).unwrap();
+    assert_eq!(symbol.name, "area");
+    assert_eq!(symbol.kind, SymbolKind::METHOD);
+    assert_eq!(symbol.container_path, ["Shape"]);
+
+    let symbol = symbols::symbol_at_point(&roots, Position::new(7, 0)).unwrap();
+    assert_eq!(symbol.name, "Shape");
+    assert!(symbol.container_path.is_empty());
+
+    assert_eq!(symbols::symbol_at_point(&roots, Position::new(12, 0)), None);
+}
+
+#[test]
+fn symbols_are_found_by_their_path() {
+    let response: DocumentSymbolResponse = serde_json::from_value(json!([
+        flat("Shape", SymbolKind::INTERFACE, 0, 9),
+        flat("area", SymbolKind::METHOD, 2, 5),
+        flat("impl Display for Circle", SymbolKind::OBJECT, 10, 20),
+        flat("area(int)", SymbolKind::METHOD, 12, 14),
+        flat("area", SymbolKind::FUNCTION, 22, 24),
+    ]))
+    .unwrap();
+    let roots = symbols::normalize_document_symbols(response);
+    let lines = |path: &[&str]| -> Vec<u32> {
+        symbols::symbols_at_path(&roots, path)
+            .into_iter()
+            .map(|node| node.range.start.line)
+            .collect()
+    };
+
+    assert_eq!(lines(&["Shape", "area"]), [2]);
+    assert_eq!(lines(&["Circle", "area"]), [12]);
+    assert_eq!(lines(&["area"]), [2, 12, 22]);
+    assert_eq!(lines(&["Circle"]), [10]);
+    assert!(lines(&["Square", "area"]).is_empty());
+    assert!(lines(&["Shape", "area", "x"]).is_empty());
+    assert!(lines(&[]).is_empty());
+}
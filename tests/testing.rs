@@ -0,0 +1,110 @@
+use std::sync::{Arc, Mutex};
+
+use lsp_client::lsp::client::ClientError;
+use lsp_client::lsp::testing::MockServer;
+use lsp_types::notification::{DidOpenTextDocument, LogMessage, Notification};
+use lsp_types::request::{GotoDefinition, HoverRequest};
+use lsp_types::{
+    ClientCapabilities, Hover, HoverContents, HoverProviderCapability, InitializeParams,
+    LogMessageParams, MarkedString, MessageType, OneOf, Position, ServerCapabilities,
+    TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams, Url,
+};
+use serde_json::{json, Value};
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    }
+}
+
+fn initialize_params() -> InitializeParams {
+    InitializeParams {
+        capabilities: ClientCapabilities::default(),
+        ..InitializeParams::default()
+    }
+}
+
+fn hover(text: &str) -> Hover {
+    Hover {
+        contents: HoverContents::Scalar(MarkedString::String(text.to_owned())),
+        range: None,
+    }
+}
+
+#[tokio::test]
+async fn scripted_answers_and_notifications_reach_the_client() {
+    let uri = Url::parse("file:///project/main.rs").unwrap();
+    let (server, mock) = MockServer::new()
+        .initialize(capabilities())
+        .send::<LogMessage>(LogMessageParams {
+            typ: MessageType::INFO,
+            message: "indexing".to_owned(),
+        })
+        .request::<HoverRequest>(Some(hover("fn main()")))
+        .with_params(json!({
+            "textDocument": TextDocumentIdentifier::new(uri.clone()),
+            "position": Position::new(0, 3),
+        }))
+        .request_failing::<HoverRequest>(-32801, "content modified")
+        .ignore::<DidOpenTextDocument>()
+        .connect();
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    server.on_notification(LogMessage::METHOD, {
+        let logged = logged.clone();
+        move |params: Value| logged.lock().unwrap().push(params["message"].clone())
+    });
+
+    let result = server.initialize(initialize_params()).await.unwrap();
+    assert_eq!(result.server_info.unwrap().name, "mock");
+    server
+        .notify::<DidOpenTextDocument>(lsp_types::DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "rust".to_owned(), 1, String::new()),
+        })
+        .await;
+    let first = server
+        .hover(uri.clone(), Position::new(0, 3))
+        .await
+        .unwrap();
+    assert_eq!(first, Some(hover("fn main()")));
+    let err = server.hover(uri, Position::new(0, 3)).await.unwrap_err();
+    assert!(matches!(err, ClientError::Server(error) if error.message == "content modified"));
+    assert_eq!(*logged.lock().unwrap(), [json!("indexing")]);
+
+    server.shutdown().await.unwrap();
+    mock.verify().await;
+}
+
+#[tokio::test]
+async fn unmatched_traffic_is_reported() {
+    let uri = Url::parse("file:///project/main.rs").unwrap();
+    let (server, mock) = MockServer::new()
+        .initialize(capabilities())
+        .request::<HoverRequest>(None)
+        .with_params(TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(uri.clone()),
+            Position::new(1, 1),
+        ))
+        .request::<GotoDefinition>(None)
+        .connect();
+    server.initialize(initialize_params()).await.unwrap();
+
+    // the wrong position does not match, and is refused
+    let err = server
+        .hover(uri.clone(), Position::new(0, 0))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ClientError::Server(_)));
+    assert_eq!(server.hover(uri, Position::new(1, 1)).await.unwrap(), None);
+    server.shutdown().await.unwrap();
+
+    let unmatched = mock.finish().await;
+    assert_eq!(unmatched.messages.len(), 1);
+    assert_eq!(
+        unmatched.messages[0]["params"]["position"],
+        json!({ "line": 0, "character": 0 })
+    );
+    assert_eq!(unmatched.steps.len(), 1);
+    assert!(unmatched.steps[0].contains("textDocument/definition"));
+}
@@ -0,0 +1,60 @@
+use lsp_client::lsp::text_utils::{self, PositionEncoding};
+use lsp_types::{Position, Range};
+
+const TEXT: &str = "fn main() {\r\n    let s = \"h\u{e9}\u{1f600}!\";\r\n}\n";
+
+#[test]
+fn positions_count_units_of_the_encoding() {
+    // the `!` follows `é` (2 bytes, 1 utf-16 unit) and an emoji (4 bytes, 2 utf-16 units)
+    let offset = TEXT.find('!').unwrap();
+    for (encoding, character) in [
+        (PositionEncoding::Utf8, 20),
+        (PositionEncoding::Utf16, 17),
+        (PositionEncoding::Utf32, 16),
+    ] {
+        let position = Position::new(1, character);
+        assert_eq!(
+            text_utils::position_to_offset(TEXT, position, encoding),
+            offset
+        );
+        assert_eq!(
+            text_utils::offset_to_position(TEXT, offset, encoding),
+            position
+        );
+    }
+}
+
+#[test]
+fn positions_past_the_end_are_clamped() {
+    let encoding = PositionEncoding::Utf16;
+    assert_eq!(
+        text_utils::position_to_offset(TEXT, Position::new(0, 100), encoding),
+        TEXT.find('\r').unwrap()
+    );
+    assert_eq!(
+        text_utils::position_to_offset(TEXT, Position::new(10, 0), encoding),
+        TEXT.len()
+    );
+}
+
+#[test]
+fn char_offsets_round_trip() {
+    let offset = TEXT.find('!').unwrap();
+    let char_offset = text_utils::offset_to_char_offset(TEXT, offset);
+    assert_eq!(TEXT.chars().nth(char_offset), Some('!'));
+    assert_eq!(text_utils::char_offset_to_offset(TEXT, char_offset), offset);
+}
+
+#[test]
+fn slices_text_by_range() {
+    let range = Range::new(Position::new(1, 8), Position::new(1, 9));
+    assert_eq!(
+        text_utils::slice_range(TEXT, range, PositionEncoding::Utf16),
+        "s"
+    );
+    let offsets = text_utils::range_to_offsets(TEXT, range, PositionEncoding::Utf16);
+    assert_eq!(
+        text_utils::offsets_to_range(TEXT, offsets, PositionEncoding::Utf16),
+        range
+    );
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "tracing")]
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use lsp_client::cli::record::{Frame, Recording, Sender};
+use lsp_client::cli::session::{Session, Timeouts};
+use lsp_client::lsp::client::ClientError;
+use lsp_types::{Position, Url};
+use serde_json::json;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// The lines the subscriber writes, kept for the test to read.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Captured {
+    type Writer = Captured;
+
+    fn make_writer(&'a self) -> Captured {
+        self.clone()
+    }
+}
+
+#[tokio::test]
+async fn requests_are_spans_with_their_method_id_and_duration() {
+    let dir = std::env::temp_dir().join(format!("lsp-client-tracing-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let frame = |from: Sender, message| Frame { from, message };
+    let recording = Recording {
+        args: Vec::new(),
+        frames: vec![
+            frame(
+                Sender::Client,
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize" }),
+            ),
+            frame(
+                Sender::Server,
+                json!({ "jsonrpc": "2.0", "id": 1, "result": {
+                    "capabilities": { "hoverProvider": true }
+                } }),
+            ),
+            frame(
+                Sender::Client,
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/hover" }),
+            ),
+            frame(
+                Sender::Server,
+                json!({ "jsonrpc": "2.0", "id": 2, "error": {
+                    "code": -32801, "message": "content modified"
+                } }),
+            ),
+        ],
+    };
+    let path = dir.join("session.json");
+    std::fs::write(&path, serde_json::to_string(&recording).unwrap()).unwrap();
+
+    let captured = Captured::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .with_ansi(false)
+        .with_writer(captured.clone())
+        .finish();
+    let _default = tracing::subscriber::set_default(subscriber);
+
+    let server = vec![
+        env!("CARGO_BIN_EXE_lsp-client").to_owned(),
+        "serve-recording".to_owned(),
+        path.to_string_lossy().into_owned(),
+    ];
+    let session = Session::start(&server, &dir, None, Timeouts::default(), None, None, false)
+        .await
+        .unwrap();
+    let uri = Url::from_file_path(dir.join("main.rs")).unwrap();
+    let err = session
+        .server
+        .hover(uri, Position::new(0, 0))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ClientError::Server(_)));
+    session.finish().await;
+
+    let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    let line = |needle: &str| {
+        logs.lines()
+            .find(|line| line.contains(needle))
+            .unwrap_or_else(|| panic!("no {:?} in\n{}", needle, logs))
+            .to_owned()
+    };
+    assert!(line(r#"request{method="initialize" id=1}"#).contains("response duration_ms="));
+    let hover = line(r#"request{method="textDocument/hover" id=2}: lsp_client::lsp::log: error"#);
+    assert!(hover.contains(r#"code=-32801 error="content modified""#));
+    assert!(line("sent").contains(r#"method="initialize" id=1 bytes="#));
+    assert!(line("received").contains("id=1 bytes="));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,47 @@
+use lsp_client::lsp::validation::{validate, Member};
+use serde_json::json;
+
+#[test]
+fn messages_matching_the_specification_pass() {
+    let params = json!({
+        "textDocument": { "uri": "file:///main.rs" },
+        "position": { "line": 3, "character": 7 },
+    });
+    assert!(validate("textDocument/hover", Member::Params, &params).is_empty());
+    // a `null` optional field is as good as one left out
+    let result = json!({ "contents": "docs", "range": null });
+    assert!(validate("textDocument/hover", Member::Result, &result).is_empty());
+    assert!(validate("textDocument/hover", Member::Result, &json!(null)).is_empty());
+    // nothing is known of methods outside the specification
+    assert!(validate("custom/anything", Member::Params, &json!({ "a": 1 })).is_empty());
+}
+
+#[test]
+fn deviations_are_described() {
+    let params = json!({ "textDocument": { "uri": "file:///main.rs" } });
+    assert_eq!(
+        validate("textDocument/hover", Member::Params, &params),
+        ["missing field `position`"]
+    );
+    let symbols = json!([{
+        "name": "main",
+        "kind": 40,
+        "range": {
+            "start": { "line": 0, "character": 0 },
+            "end": { "line": 2, "character": 1 }
+        },
+        "selectionRange": {
+            "start": { "line": 0, "character": 3 },
+            "end": { "line": 0, "character": 7 }
+        },
+        "children": [],
+        "visibility": "public"
+    }]);
+    assert_eq!(
+        validate("textDocument/documentSymbol", Member::Result, &symbols),
+        [
+            "unknown field `/0/visibility`",
+            "invalid value 40 for `/0/kind`"
+        ]
+    );
+}